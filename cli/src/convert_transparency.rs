@@ -0,0 +1,40 @@
+/*
+ * Color key conversion.
+ *
+ *     kanayacl convert-transparency theme.ini --antialias
+ *
+ * Turns the color keyed images of a theme (Transparent and TransparentColor) into images with an
+ * alpha channel, and saves the theme in place.
+ */
+
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use kanaya_common::formats;
+use kanaya_common::image::transparency::{self, TransparencyOptions};
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut options = TransparencyOptions::default();
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--antialias" => options.antialias = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path] = paths[..] else {
+        return Err(anyhow!("Usage: kanayacl convert-transparency <theme> [--antialias]"));
+    };
+
+    let theme_path = Path::new(theme_path);
+    let mut theme = formats::read_theme(theme_path)?;
+    let report = transparency::convert_color_keys(&mut theme, &options);
+    for path in &report.converted {
+        println!("Converted \"{}\".", path);
+    }
+    for (path, reason) in &report.skipped {
+        eprintln!("kanayacl: warning: skipped \"{}\": {}", path, reason);
+    }
+    formats::write_theme(&theme, theme_path, theme_path)
+}
@@ -0,0 +1,48 @@
+/*
+ * Image deduplication.
+ *
+ *     kanayacl dedupe-images theme.ini [--dry-run]
+ *
+ * Makes properties that refer to pixel-identical images share one of them, removes images that
+ * nothing refers to, and saves the theme in place. For a Restyle project the duplicate image
+ * files are deleted; files in the project directory that it does not refer to are not looked at.
+ */
+
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use kanaya_common::formats;
+use kanaya_common::image::dedup;
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut dry_run = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path] = paths[..] else {
+        return Err(anyhow!("Usage: kanayacl dedupe-images <theme> [--dry-run]"));
+    };
+
+    let theme_path = Path::new(theme_path);
+    let mut theme = formats::read_theme(theme_path)?;
+    let analysis = dedup::analyze(&theme);
+    for group in &analysis.duplicates {
+        let kind = if group.identical_bytes { "identical" } else { "pixel-identical" };
+        println!("\"{}\" has {} {} copies: {}", group.keep, group.duplicates.len(), kind, group.duplicates.join(", "));
+    }
+    for (path, _) in &analysis.unreferenced {
+        println!("\"{}\" is not used.", path);
+    }
+    println!("{} bytes {}.", analysis.saved(), if dry_run { "can be saved" } else { "saved" });
+
+    if !dry_run {
+        dedup::deduplicate(&mut theme, &analysis);
+        formats::write_theme(&theme, theme_path, theme_path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+/*
+ * Sizing margin detection.
+ *
+ *     kanayacl detect-margins theme.ini [--apply] [--min-confidence 0.5]
+ *
+ * Proposes SizingMargins and ContentMargins for every state with a stretched image, and with
+ * --apply sets the proposals that are confident enough and saves the theme in place.
+ */
+
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use kanaya_common::formats::{self, restyle_ini};
+use kanaya_common::image::margins::{self, MarginOptions};
+use kanaya_common::theme::Margins;
+
+const USAGE: &str = "Usage: kanayacl detect-margins <theme> [--apply] [--min-confidence CONFIDENCE]";
+
+fn describe(margins: Option<Margins>) -> String {
+    margins.map_or_else(|| "none".to_owned(), |margins| margins.to_string())
+}
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut apply = false;
+    let mut min_confidence = 0.5;
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--apply" => apply = true,
+            "--min-confidence" => {
+                let value = args.next().ok_or_else(|| anyhow!(USAGE))?;
+                min_confidence = value.parse().with_context(|| anyhow!("Invalid confidence \"{}\"", value))?;
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path] = paths[..] else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let theme_path = Path::new(theme_path);
+    let mut theme = formats::read_theme(theme_path)?;
+    let report = margins::propose_theme_margins(&theme, &MarginOptions::default());
+
+    let mut applied = 0;
+    for state in &report.states {
+        let image_ref = &state.image_ref;
+        let section = restyle_ini::format_section_name(&image_ref.class, image_ref.part.as_deref(), image_ref.state.as_deref());
+        let proposal = &state.proposal;
+        println!(
+            "[{}] {}: SizingMargins {} (now {}), ContentMargins {} (now {}), confidence {:.2}",
+            section,
+            image_ref.path,
+            proposal.sizing,
+            describe(state.sizing),
+            proposal.content,
+            describe(state.content),
+            proposal.confidence,
+        );
+        if apply && proposal.confidence >= min_confidence && !state.is_unchanged() {
+            margins::apply_margins(&mut theme, &image_ref.class, image_ref.part.as_deref(), image_ref.state.as_deref(), proposal);
+            applied += 1;
+        }
+    }
+
+    for (image_ref, reason) in &report.failed {
+        let section = restyle_ini::format_section_name(&image_ref.class, image_ref.part.as_deref(), image_ref.state.as_deref());
+        eprintln!("kanayacl: warning: skipped [{}] {}: {}", section, image_ref.path, reason);
+    }
+
+    if apply {
+        println!("Applied {} proposals.", applied);
+        formats::write_theme(&theme, theme_path, theme_path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+/*
+ * DPI variant generation.
+ *
+ *     kanayacl dpi-variants theme.ini --dpi 120,144,192
+ *
+ * Generates the ImageFile1..7 variants that a theme is missing from the highest resolution image
+ * of each state, and saves the theme in place.
+ */
+
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use kanaya_common::formats;
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::dpi::{self, VariantOptions};
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut options = VariantOptions::default();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dpi" => {
+                let list = args.next().ok_or_else(|| anyhow!("--dpi needs a list of DPIs."))?;
+                options.dpis = list.split(',')
+                    .map(|dpi| dpi.trim().parse().with_context(|| anyhow!("Invalid DPI \"{}\"", dpi)))
+                    .collect::<Result<_, Error>>()?;
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path] = paths[..] else {
+        return Err(anyhow!("Usage: kanayacl dpi-variants <theme> [--dpi DPI,...]"));
+    };
+
+    let theme_path = Path::new(theme_path);
+    let mut theme = formats::read_theme(theme_path)?;
+    let report = dpi::generate_variants(&mut theme, &options)?;
+    for variant in &report.generated {
+        let name = restyle_ini::format_section_name(&variant.class, variant.part.as_deref(), variant.state.as_deref());
+        println!("[{}] {} = {} ({} DPI from {} DPI)", name, variant.property, variant.path, variant.dpi, variant.source_dpi);
+    }
+    for (image_ref, reason) in &report.failed {
+        let name = restyle_ini::format_section_name(&image_ref.class, image_ref.part.as_deref(), image_ref.state.as_deref());
+        eprintln!("kanayacl: warning: skipped [{}] {}: {}", name, image_ref.path, reason);
+    }
+    formats::write_theme(&theme, theme_path, theme_path)
+}
@@ -0,0 +1,40 @@
+/*
+ * Bulk image export.
+ *
+ *     kanayacl export-images Kanaya.msstyles images --split
+ *
+ * Writes every image in a visual style or Restyle project as a PNG, with an index.json that maps
+ * the files back to their properties.
+ */
+
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use kanaya_common::formats;
+use kanaya_common::image::export::{self, ExportOptions};
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut options = ExportOptions::default();
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--split" => options.split_strips = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    let [input, output] = paths[..] else {
+        return Err(anyhow!("Usage: kanayacl export-images <theme> <directory> [--split]"));
+    };
+
+    let theme = formats::read_theme(Path::new(input))?;
+    let summary = export::export_images(&theme, Path::new(output), &options)?;
+    println!("Exported {} images.", summary.files.len());
+    for path in &summary.missing {
+        eprintln!("kanayacl: warning: \"{}\" is referenced but missing.", path);
+    }
+    for (path, reason) in &summary.failed {
+        eprintln!("kanayacl: warning: skipped \"{}\": {}", path, reason);
+    }
+    Ok(())
+}
@@ -0,0 +1,47 @@
+/*
+ * Control galleries.
+ *
+ *     kanayacl gallery theme.ini gallery.png [--dpi 144] [--rtl]
+ *
+ * Draws every common control in each of its states into one labelled sheet, and saves it as a
+ * PNG.
+ */
+
+use std::fs;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use kanaya_common::formats;
+use kanaya_common::render::gallery::GALLERY;
+use kanaya_common::render::{self, Renderer};
+use kanaya_common::theme::sysmetrics::BASE_DPI;
+
+const USAGE: &str = "Usage: kanayacl gallery <theme> <output.png> [--dpi DPI] [--rtl]";
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut dpi = BASE_DPI;
+    let mut mirrored = false;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dpi" => {
+                let value = args.next().ok_or_else(|| anyhow!("--dpi needs a DPI."))?;
+                dpi = value.parse().with_context(|| anyhow!("Invalid DPI \"{}\"", value))?;
+            }
+            "--rtl" => mirrored = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path, output_path] = paths[..] else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let theme = formats::read_theme(Path::new(theme_path))?;
+    let mut renderer = Renderer::with_dpi(&theme, dpi);
+    renderer.mirrored = mirrored;
+    let sheet = renderer.draw_gallery(GALLERY)?;
+    fs::write(output_path, render::to_png(&sheet)?).with_context(|| anyhow!("Failed to write \"{}\"", output_path))
+}
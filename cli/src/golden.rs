@@ -0,0 +1,85 @@
+/*
+ * Golden image regression testing.
+ *
+ *     kanayacl golden check theme.ini tests/golden [--report report]
+ *     kanayacl golden accept theme.ini tests/golden [CASE]...
+ *
+ * check renders the cases of the golden directory's suite.json (or the default suite) and
+ * compares them with the golden images, failing if any differ. With --report, the cases that
+ * differ are written into an HTML report. accept writes the renderings as the new golden images,
+ * for every case or the named ones.
+ */
+
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use kanaya_common::formats;
+use kanaya_common::render::golden::{self, CaseStatus, GoldenSuite, REPORT_FILE_NAME};
+
+const USAGE: &str = "Usage: kanayacl golden check <theme> <golden directory> [--report DIRECTORY]\n       kanayacl golden accept <theme> <golden directory> [CASE]...";
+
+fn check(args: &[String]) -> Result<(), Error> {
+    let mut report_dir = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--report" {
+            report_dir = Some(args.next().ok_or_else(|| anyhow!("--report needs a directory."))?);
+        }
+        else {
+            paths.push(arg);
+        }
+    }
+
+    let [theme_path, golden_dir] = paths[..] else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let theme = formats::read_theme(Path::new(theme_path))?;
+    let golden_dir = Path::new(golden_dir);
+    let report = golden::run_suite(&theme, &GoldenSuite::load(golden_dir)?, golden_dir)?;
+    for result in &report.results {
+        let status = match result.status {
+            CaseStatus::Passed => "passed".to_owned(),
+            CaseStatus::Missing => "missing".to_owned(),
+            CaseStatus::SizeChanged => "size changed".to_owned(),
+            CaseStatus::Failed => match &result.comparison {
+                Some(comparison) => format!("FAILED ({} of {} pixels differ)", comparison.differing, comparison.total),
+                None => "FAILED".to_owned(),
+            },
+        };
+        println!("{}: {}", result.name, status);
+    }
+
+    if let Some(report_dir) = report_dir {
+        golden::write_report(&report, Path::new(report_dir))?;
+        println!("Wrote {}", Path::new(report_dir).join(REPORT_FILE_NAME).display());
+    }
+
+    let failures = report.failures().count();
+    if failures > 0 {
+        return Err(anyhow!("{} of {} cases did not match. Run \"kanayacl golden accept\" to accept them.", failures, report.results.len()));
+    }
+    Ok(())
+}
+
+fn accept(args: &[String]) -> Result<(), Error> {
+    let [theme_path, golden_dir, names @ ..] = args else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let theme = formats::read_theme(Path::new(theme_path))?;
+    let golden_dir = Path::new(golden_dir);
+    for name in golden::accept(&theme, &GoldenSuite::load(golden_dir)?, golden_dir, names)? {
+        println!("Accepted {}", name);
+    }
+    Ok(())
+}
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    match args.split_first() {
+        Some((command, args)) if command == "check" => check(args),
+        Some((command, args)) if command == "accept" => accept(args),
+        _ => Err(anyhow!(USAGE)),
+    }
+}
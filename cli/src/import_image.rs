@@ -0,0 +1,56 @@
+/*
+ * Image import.
+ *
+ *     kanayacl import-image theme.ini "Button.PushButton" ImageFile PushButton.png
+ *
+ * Replaces the image that a property refers to, converting the new image to the encoding of the
+ * one that it replaces, and saves the theme in place. For a Restyle project that only writes the
+ * image file. With --premultiplied, a 32-bit BMP that is replaced is read and written back with
+ * premultiplied alpha.
+ */
+
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use kanaya_common::formats;
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::import::{self, ImportOptions};
+use kanaya_common::image::BmpAlpha;
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut options = ImportOptions::default();
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--premultiplied" => options.target.bmp_alpha = BmpAlpha::Premultiplied,
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path, section, property, image_path] = paths[..] else {
+        return Err(anyhow!("Usage: kanayacl import-image <theme> <section> <property> <image> [--premultiplied]"));
+    };
+
+    let theme_path = Path::new(theme_path);
+    let mut theme = formats::read_theme(theme_path)?;
+    let section = restyle_ini::parse_section_name(section)?;
+    let report = import::import_image_file(
+        &mut theme,
+        &section.class,
+        section.part.as_deref(),
+        section.state.as_deref(),
+        property,
+        Path::new(image_path),
+        &options,
+    )?;
+
+    for warning in &report.warnings {
+        let image_ref = &warning.image_ref;
+        let name = restyle_ini::format_section_name(&image_ref.class, image_ref.part.as_deref(), image_ref.state.as_deref());
+        eprintln!("kanayacl: warning: [{}] {}: {}", name, image_ref.property, warning.message);
+    }
+
+    formats::write_theme(&theme, theme_path, theme_path)?;
+    println!("Replaced \"{}\" with a {}x{} {}.", report.path, report.width, report.height, report.encoding.extension());
+    Ok(())
+}
@@ -0,0 +1,131 @@
+/*
+ * Kanaya command-line tools.
+ *
+ * Unlike the editor, these do not depend on any Windows API and build on every platform, so that
+ * they can run in scripts, git hooks and CI.
+ */
+
+mod convert_transparency;
+mod dedupe_images;
+mod detect_margins;
+mod dpi_variants;
+mod export_images;
+mod gallery;
+mod golden;
+mod import_image;
+mod optimize_pngs;
+mod pack;
+mod preview;
+mod recolor;
+mod render;
+mod textconv;
+mod themepack;
+mod transition;
+mod window;
+
+use anyhow::Error;
+use anyhow::anyhow;
+
+const USAGE: &str = "\
+Usage: kanayacl <command> [arguments]
+
+Commands:
+    textconv <file>    Print a canonical text dump of an .msstyles file or a theme source.
+                       Suitable for use as a git textconv filter.
+    export-images <theme> <directory> [--split]
+                       Write every image in a theme as a PNG, with an index.json. --split
+                       writes each frame of a multi-state strip as its own file.
+    gallery <theme> <output.png> [--dpi DPI] [--rtl]
+                       Draw every common control in each of its states into one labelled sheet,
+                       as a PNG.
+    golden check <theme> <golden directory> [--report DIRECTORY]
+                       Render the cases of a golden image suite and compare them with the golden
+                       images, with an HTML report of the differences.
+    golden accept <theme> <golden directory> [CASE]...
+                       Save the renderings of a golden image suite as its new golden images.
+    import-image <theme> <section> <property> <image> [--premultiplied]
+                       Replace the image that a property refers to, converting it to the
+                       encoding of the image that it replaces. --premultiplied reads a 32-bit
+                       BMP that is replaced as premultiplied, and keeps it that way.
+    dpi-variants <theme> [--dpi DPI,...]
+                       Generate missing ImageFile1..7 DPI variants from the highest resolution
+                       image of each state.
+    convert-transparency <theme> [--antialias]
+                       Turn color keyed images (Transparent, TransparentColor) into images with
+                       an alpha channel.
+    dedupe-images <theme> [--dry-run]
+                       Share pixel-identical images between properties, and remove images that
+                       nothing refers to.
+    detect-margins <theme> [--apply] [--min-confidence CONFIDENCE]
+                       Propose SizingMargins and ContentMargins for stretched images, and with
+                       --apply set the proposals that are at least as confident as given.
+    optimize-pngs <theme>
+                       Recompress every PNG image of a theme losslessly.
+    pack <theme> <output.msstyles> [--theme-file] [--windows-path PATH]
+                       Compile a theme into a visual style, and with --theme-file write or update
+                       the .theme file next to it. PATH is the visual style path that the .theme
+                       file gives Windows.
+    preview <theme> <output directory> [--dpi DPI,...]
+                       Draw the mock windows and the control gallery at each DPI, left to right
+                       and right to left, into PNGs.
+    recolor <theme> <output> [--hue DEGREES] [--saturation AMOUNT] [--lightness AMOUNT]
+            [--curve IN:OUT,...] [--include CLASS,...] [--exclude CLASS,...] [--keep-neutrals]
+                       Apply a color transform to every image and color of a theme, and save the
+                       result as a new theme.
+    render <theme> <section> <width>x<height> <output.png> [--dpi DPI] [--text TEXT] [--rtl]
+                       Draw the background of a state like DrawThemeBackground, and text on it
+                       like DrawThemeText, into a PNG.
+    themepack build <file.theme> <output.themepack> [--var NAME=VALUE]...
+                       Bundle a .theme file and the files that it refers to.
+    themepack extract <file.themepack> <directory>
+                       Extract a .themepack or .deskthemepack file.
+    transition <theme> <class.part> <from state> <to state> <width>x<height> <output.png> [--fps FPS]
+               [--duration MILLISECONDS] [--strip] [--dpi DPI] [--text TEXT]
+                       Render a part fading from one state to another, timed by its
+                       TransitionDurations, as an animated PNG or a strip of frames.
+    window <theme> <output.png> [--size WIDTHxHEIGHT] [--dpi DPI] [--title TITLE] [--menu ITEM,...]
+           [--button BUTTON=STATE]... [--maximized] [--rtl]
+                       Draw an active and an inactive window with their caption, frame, caption
+                       buttons and menu bar, into a PNG.
+";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    if let Err(e) = run(&args) {
+        eprintln!("kanayacl: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), Error> {
+    let Some((command, args)) = args.split_first() else {
+        print!("{}", USAGE);
+        return Ok(());
+    };
+
+    match command.as_str() {
+        "textconv" => textconv::run(args),
+        "themepack" => themepack::run(args),
+        "export-images" => export_images::run(args),
+        "gallery" => gallery::run(args),
+        "golden" => golden::run(args),
+        "import-image" => import_image::run(args),
+        "dpi-variants" => dpi_variants::run(args),
+        "convert-transparency" => convert_transparency::run(args),
+        "dedupe-images" => dedupe_images::run(args),
+        "detect-margins" => detect_margins::run(args),
+        "optimize-pngs" => optimize_pngs::run(args),
+        "pack" => pack::run(args),
+        "preview" => preview::run(args),
+        "recolor" => recolor::run(args),
+        "render" => render::run(args),
+        "transition" => transition::run(args),
+        "window" => window::run(args),
+        "help" | "--help" | "-h" => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(anyhow!("Unknown command \"{}\". Run \"kanayacl help\" for a list of commands.", command)),
+    }
+}
@@ -0,0 +1,29 @@
+/*
+ * PNG optimization.
+ *
+ *     kanayacl optimize-pngs Kanaya.msstyles
+ *
+ * Recompresses every PNG image of a theme losslessly, and saves the theme in place.
+ */
+
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use kanaya_common::formats;
+use kanaya_common::image::png_optimize;
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let [theme_path] = args else {
+        return Err(anyhow!("Usage: kanayacl optimize-pngs <theme>"));
+    };
+
+    let theme_path = Path::new(theme_path);
+    let mut theme = formats::read_theme(theme_path)?;
+    let optimized = png_optimize::optimize_theme(&mut theme)?;
+    for image in &optimized {
+        println!("\"{}\": {} -> {} bytes", image.path, image.before, image.after);
+    }
+    let saved: usize = optimized.iter().map(|image| image.before - image.after).sum();
+    println!("{} bytes saved.", saved);
+    formats::write_theme(&theme, theme_path, theme_path)
+}
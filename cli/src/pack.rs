@@ -0,0 +1,51 @@
+/*
+ * Visual style compiler.
+ *
+ *     kanayacl pack theme.ini Kanaya.msstyles --theme-file
+ *     kanayacl pack theme.ini out/Kanaya.msstyles --theme-file --windows-path "%ResourceDir%\Themes\Kanaya.msstyles"
+ *
+ * Compiles a theme into a visual style. With --theme-file, the .theme file that goes with it is
+ * written next to it, or updated to point at it if it already exists.
+ */
+
+use std::fs;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use kanaya_common::formats::{self, msstyles, theme_file};
+
+const USAGE: &str = "Usage: kanayacl pack <theme> <output.msstyles> [--theme-file] [--windows-path PATH]";
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut write_theme_file = false;
+    let mut windows_path = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--theme-file" => write_theme_file = true,
+            "--windows-path" => windows_path = Some(args.next().ok_or_else(|| anyhow!("--windows-path needs a path."))?.as_str()),
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path, output] = paths[..] else {
+        return Err(anyhow!(USAGE));
+    };
+    if windows_path.is_some() && !write_theme_file {
+        return Err(anyhow!("--windows-path is only used with --theme-file."));
+    }
+
+    let theme = formats::read_theme(Path::new(theme_path))?;
+    let output = Path::new(output);
+    let data = msstyles::pack(&theme, &msstyles::PackOptions::from_environment())?;
+    fs::write(output, data).with_context(|| anyhow!("Failed to write \"{}\"", output.display()))?;
+    println!("{}", output.display());
+
+    if write_theme_file {
+        let theme_path = theme_file::write_matching_theme_file(&theme, output, windows_path)?;
+        println!("{}", theme_path.display());
+    }
+    Ok(())
+}
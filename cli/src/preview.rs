@@ -0,0 +1,70 @@
+/*
+ * DPI and right-to-left previews.
+ *
+ *     kanayacl preview theme.ini previews/ [--dpi 96,120,144,192]
+ *
+ * Draws the mock windows and the control gallery at each DPI, left to right and right to left,
+ * into a directory: window-144.png, window-144-rtl.png, gallery-144.png and so on. Each DPI picks
+ * its own ImageFile1..7 variants and scales margins and metrics the way Windows does, and right to
+ * left mirrors layouts and images that are not marked MirrorImage = FALSE.
+ */
+
+use std::fs;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use kanaya_common::formats;
+use kanaya_common::render::gallery::GALLERY;
+use kanaya_common::render::window::MockWindow;
+use kanaya_common::render::{self, Renderer};
+
+use crate::window::draw_windows;
+
+const USAGE: &str = "Usage: kanayacl preview <theme> <output directory> [--dpi DPI,...]";
+
+/**
+ * 100%, 125%, 150% and 200% scaling.
+ */
+const DEFAULT_DPIS: [u32; 4] = [96, 120, 144, 192];
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut dpis = DEFAULT_DPIS.to_vec();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dpi" => {
+                let list = args.next().ok_or_else(|| anyhow!("--dpi needs a list of DPIs."))?;
+                dpis = list.split(',')
+                    .map(|dpi| dpi.trim().parse().with_context(|| anyhow!("Invalid DPI \"{}\"", dpi)))
+                    .collect::<Result<_, _>>()?;
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path, output_dir] = paths[..] else {
+        return Err(anyhow!(USAGE));
+    };
+    let output_dir = Path::new(output_dir);
+
+    let theme = formats::read_theme(Path::new(theme_path))?;
+    fs::create_dir_all(output_dir).with_context(|| anyhow!("Failed to create \"{}\"", output_dir.display()))?;
+    for dpi in dpis {
+        for mirrored in [false, true] {
+            let mut renderer = Renderer::with_dpi(&theme, dpi);
+            renderer.mirrored = mirrored;
+            let suffix = if mirrored { "-rtl" } else { "" };
+
+            let windows = draw_windows(&renderer, &MockWindow::default(), None)?;
+            let gallery = renderer.draw_gallery(GALLERY)?;
+            for (name, image) in [("window", windows), ("gallery", gallery)] {
+                let path = output_dir.join(format!("{}-{}{}.png", name, dpi, suffix));
+                fs::write(&path, render::to_png(&image)?).with_context(|| anyhow!("Failed to write \"{}\"", path.display()))?;
+                println!("{}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
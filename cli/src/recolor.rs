@@ -0,0 +1,88 @@
+/*
+ * Theme recoloring.
+ *
+ *     kanayacl recolor Blue.msstyles Green.msstyles --hue 100 --keep-neutrals
+ *     kanayacl recolor theme.ini graphite/theme.ini --saturation -1 --exclude Start,Taskband
+ *     kanayacl recolor theme.ini dark/theme.ini --curve 0:0,128:80,255:200
+ *
+ * Applies one color transform to every image and Color property of a theme, and saves the
+ * result as a new theme.
+ */
+
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use kanaya_common::formats;
+use kanaya_common::image::recolor::{self, ColorTransform, RecolorOptions};
+
+const USAGE: &str = "Usage: kanayacl recolor <theme> <output> [--hue DEGREES] [--saturation AMOUNT] [--lightness AMOUNT] \
+    [--curve IN:OUT,...] [--include CLASS,...] [--exclude CLASS,...] [--keep-neutrals]";
+
+fn parse_number(text: &str) -> Result<f32, Error> {
+    text.trim().parse().with_context(|| anyhow!("Invalid number \"{}\"", text))
+}
+
+fn parse_curve(text: &str) -> Result<[u8; 256], Error> {
+    let points = text.split(',')
+        .map(|point| {
+            let (input, output) = point.split_once(':').ok_or_else(|| anyhow!("Invalid curve point \"{}\"", point))?;
+            let input = input.trim().parse().with_context(|| anyhow!("Invalid curve point \"{}\"", point))?;
+            let output = output.trim().parse().with_context(|| anyhow!("Invalid curve point \"{}\"", point))?;
+            Ok((input, output))
+        })
+        .collect::<Result<Vec<(u8, u8)>, Error>>()?;
+    Ok(recolor::curve(&points))
+}
+
+fn parse_list(text: &str) -> Vec<String> {
+    text.split(',').map(|name| name.trim().to_owned()).filter(|name| !name.is_empty()).collect()
+}
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let (mut hue, mut saturation, mut lightness) = (0.0, 0.0, 0.0);
+    let mut curve = None;
+    let mut include_classes = Vec::new();
+    let mut exclude_classes = Vec::new();
+    let mut keep_neutrals = false;
+    let mut paths = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value.", arg));
+        match arg.as_str() {
+            "--hue" => hue = parse_number(value()?)?,
+            "--saturation" => saturation = parse_number(value()?)?,
+            "--lightness" => lightness = parse_number(value()?)?,
+            "--curve" => curve = Some(parse_curve(value()?)?),
+            "--include" => include_classes = parse_list(value()?),
+            "--exclude" => exclude_classes = parse_list(value()?),
+            "--keep-neutrals" => keep_neutrals = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    let [input, output] = paths[..] else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let transform = match curve {
+        Some(_) if (hue, saturation, lightness) != (0.0, 0.0, 0.0) => {
+            return Err(anyhow!("--curve cannot be combined with --hue, --saturation or --lightness."));
+        }
+        Some(table) => ColorTransform::Curves { red: Box::new(table), green: Box::new(table), blue: Box::new(table) },
+        None => ColorTransform::Hsl { hue, saturation, lightness },
+    };
+    let options = RecolorOptions { include_classes, exclude_classes, keep_neutrals, ..RecolorOptions::new(transform) };
+
+    let mut theme = formats::read_theme(Path::new(input))?;
+    let report = recolor::recolor(&mut theme, &options);
+    println!("Recolored {} images and {} colors.", report.images.len(), report.properties);
+    for (original, copy) in &report.copied {
+        println!("\"{}\" is also used by classes that were not recolored, so it was copied to \"{}\".", original, copy);
+    }
+    for (path, reason) in &report.skipped {
+        eprintln!("kanayacl: warning: skipped \"{}\": {}", path, reason);
+    }
+    formats::write_theme(&theme, Path::new(input), Path::new(output))
+}
@@ -0,0 +1,65 @@
+/*
+ * Part rendering.
+ *
+ *     kanayacl render theme.ini "Button.PushButton(Hot)" 75x23 button.png [--dpi 144] [--text "&OK"] [--rtl]
+ *
+ * Draws the background of a state the way DrawThemeBackground would, and saves it as a PNG. With
+ * --text, the text is drawn on top, centered in the content rectangle like a button's label.
+ * With --rtl, both are drawn right to left.
+ */
+
+use std::fs;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use kanaya_common::formats;
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::RgbaImage;
+use kanaya_common::render::text::{ThemeText, DT_CENTER, DT_END_ELLIPSIS, DT_SINGLELINE, DT_VCENTER};
+use kanaya_common::render::{self, Renderer};
+use kanaya_common::theme::Rect;
+use kanaya_common::theme::sysmetrics::BASE_DPI;
+
+const USAGE: &str = "Usage: kanayacl render <theme> <section> <width>x<height> <output.png> [--dpi DPI] [--text TEXT] [--rtl]";
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut dpi = BASE_DPI;
+    let mut text = None;
+    let mut mirrored = false;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dpi" => {
+                let value = args.next().ok_or_else(|| anyhow!("--dpi needs a DPI."))?;
+                dpi = value.parse().with_context(|| anyhow!("Invalid DPI \"{}\"", value))?;
+            }
+            "--text" => text = Some(args.next().ok_or_else(|| anyhow!("--text needs a text."))?),
+            "--rtl" => mirrored = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path, section, size, output_path] = paths[..] else {
+        return Err(anyhow!(USAGE));
+    };
+    let (width, height) = size.split_once('x')
+        .and_then(|(width, height)| Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?)))
+        .ok_or_else(|| anyhow!("Invalid size \"{}\". {}", size, USAGE))?;
+
+    let theme = formats::read_theme(Path::new(theme_path))?;
+    let section = restyle_ini::parse_section_name(section)?;
+    let mut renderer = Renderer::with_dpi(&theme, dpi);
+    renderer.mirrored = mirrored;
+    let mut canvas = RgbaImage::new(width, height);
+    let rect = Rect { left: 0, top: 0, right: width as i32, bottom: height as i32 };
+    let (class, part, state) = (&section.class, section.part.as_deref(), section.state.as_deref());
+    renderer.draw_background(&mut canvas, class, part, state, rect)?;
+    if let Some(text) = text {
+        let text = ThemeText { text, flags: DT_CENTER | DT_VCENTER | DT_SINGLELINE | DT_END_ELLIPSIS, font: None, color: None };
+        renderer.draw_text(&mut canvas, class, part, state, &text, renderer.content_rect(class, part, state, rect))?;
+    }
+
+    fs::write(output_path, render::to_png(&canvas)?).with_context(|| anyhow!("Failed to write \"{}\"", output_path))
+}
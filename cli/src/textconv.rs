@@ -0,0 +1,33 @@
+/*
+ * git textconv driver.
+ *
+ * To diff compiled visual styles as text, add this to .gitattributes:
+ *
+ *     *.msstyles diff=msstyles
+ *
+ * and this to the repository configuration:
+ *
+ *     git config diff.msstyles.textconv "kanayacl textconv"
+ *     git config diff.msstyles.cachetextconv true
+ */
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use kanaya_common::formats::dump;
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let [path] = args else {
+        return Err(anyhow!("Usage: kanayacl textconv <file>"));
+    };
+
+    let path = Path::new(path);
+    let data = fs::read(path).with_context(|| anyhow!("Failed to read \"{}\"", path.display()))?;
+
+    // git closes the pipe early when it only needs part of the output, which is not an error.
+    let _ = std::io::stdout().write_all(dump::dump_file(&data).as_bytes());
+    Ok(())
+}
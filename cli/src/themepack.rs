@@ -0,0 +1,58 @@
+/*
+ * Theme pack building and extraction.
+ *
+ *     kanayacl themepack build Kanaya.theme Kanaya.themepack --var ResourceDir=build
+ *     kanayacl themepack extract Kanaya.themepack out
+ *
+ * Paths in the .theme file that use environment variables are only bundled when the variable is
+ * given with --var, so that files which ship with Windows (i.e. %SystemRoot%\media) stay out of
+ * the pack. The timestamp of the files in the pack comes from SOURCE_DATE_EPOCH.
+ */
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use kanaya_common::formats::themepack::{PackOptions, ThemePack};
+
+const USAGE: &str = "Usage: kanayacl themepack build <file.theme> <output.themepack> [--var NAME=VALUE]...\n       kanayacl themepack extract <file.themepack> <directory>";
+
+fn build(args: &[String]) -> Result<(), Error> {
+    let mut paths = Vec::new();
+    let mut variables = BTreeMap::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--var" {
+            let definition = args.next().ok_or_else(|| anyhow!("--var needs a NAME=VALUE argument."))?;
+            let (name, value) = definition.split_once('=').ok_or_else(|| anyhow!("Invalid variable definition \"{}\", expected NAME=VALUE.", definition))?;
+            variables.insert(name.to_owned(), value.to_owned());
+        }
+        else {
+            paths.push(arg);
+        }
+    }
+
+    let [input, output] = paths[..] else {
+        return Err(anyhow!(USAGE));
+    };
+
+    let pack = ThemePack::from_theme_file(Path::new(input), &variables)?;
+    pack.write_file(Path::new(output), &PackOptions::from_environment())
+}
+
+fn extract(args: &[String]) -> Result<(), Error> {
+    let [input, output] = args else {
+        return Err(anyhow!(USAGE));
+    };
+
+    ThemePack::read_file(Path::new(input))?.extract(Path::new(output))
+}
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    match args.split_first() {
+        Some((command, args)) if command == "build" => build(args),
+        Some((command, args)) if command == "extract" => extract(args),
+        _ => Err(anyhow!(USAGE)),
+    }
+}
@@ -0,0 +1,88 @@
+/*
+ * State transition previews.
+ *
+ *     kanayacl transition theme.ini Button.PushButton Normal Hot 75x23 fade.png [--fps 30]
+ *         [--duration 200] [--strip] [--dpi 144] [--text "&OK"]
+ *
+ * Renders a part fading from one state to another as frames at a frame rate, timed by the part's
+ * TransitionDurations unless --duration gives the fade a length, and saves them as an animated
+ * PNG, or with --strip as a horizontal strip of frames.
+ */
+
+use std::fs;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use kanaya_common::formats;
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::{png, strip};
+use kanaya_common::render::{self, Renderer};
+use kanaya_common::theme::sysmetrics::BASE_DPI;
+
+const USAGE: &str = "Usage: kanayacl transition <theme> <class.part> <from state> <to state> <width>x<height> <output.png> \
+    [--fps FPS] [--duration MILLISECONDS] [--strip] [--dpi DPI] [--text TEXT]";
+
+const DEFAULT_FRAME_RATE: u16 = 30;
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut frame_rate = DEFAULT_FRAME_RATE;
+    let mut duration = None;
+    let mut as_strip = false;
+    let mut dpi = BASE_DPI;
+    let mut text = None;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| anyhow!("{} needs a value.", name));
+        match arg.as_str() {
+            "--fps" => {
+                let value = value("--fps")?;
+                frame_rate = value.parse().ok().filter(|fps| *fps > 0).ok_or_else(|| anyhow!("Invalid frame rate \"{}\"", value))?;
+            }
+            "--duration" => {
+                let value = value("--duration")?;
+                duration = Some(value.parse::<u32>().with_context(|| anyhow!("Invalid duration \"{}\"", value))?);
+            }
+            "--strip" => as_strip = true,
+            "--dpi" => {
+                let value = value("--dpi")?;
+                dpi = value.parse().with_context(|| anyhow!("Invalid DPI \"{}\"", value))?;
+            }
+            "--text" => text = Some(value("--text")?),
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path, section, from, to, size, output_path] = paths[..] else {
+        return Err(anyhow!(USAGE));
+    };
+    let size = size.split_once('x')
+        .and_then(|(width, height)| Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?)))
+        .ok_or_else(|| anyhow!("Invalid size \"{}\". {}", size, USAGE))?;
+
+    let theme = formats::read_theme(Path::new(theme_path))?;
+    let name = restyle_ini::parse_section_name(section)?;
+    if name.state.is_some() {
+        return Err(anyhow!("Give the part without a state, and the states to fade between after it. {}", USAGE));
+    }
+    let renderer = Renderer::with_dpi(&theme, dpi);
+    let (class, part) = (&name.class, name.part.as_deref());
+    let mut transition = renderer.draw_transition(class, part, Some(from), Some(to), text.map(String::as_str), size)?;
+    match duration {
+        Some(duration) => transition.duration = duration,
+        None if transition.duration == 0 => println!("{} has no transition from {} to {}, so the state changes at once.", section, from, to),
+        None => {}
+    }
+
+    let frames = transition.frames(frame_rate as u32);
+    let data = if as_strip {
+        render::to_png(&strip::join(&frames, false)?)?
+    }
+    else {
+        png::encode_animated(&frames, 1, frame_rate)?
+    };
+    fs::write(output_path, data).with_context(|| anyhow!("Failed to write \"{}\"", output_path))?;
+    println!("{} frames over {} ms", frames.len(), transition.duration);
+    Ok(())
+}
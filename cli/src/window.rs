@@ -0,0 +1,99 @@
+/*
+ * Mock window rendering.
+ *
+ *     kanayacl window theme.ini window.png [--size 480x320] [--dpi 144] [--title TITLE]
+ *         [--menu File,Edit,View] [--button close=Hot]... [--maximized] [--rtl]
+ *
+ * Draws an inactive window with an active one in front of it, on the desktop background color,
+ * and saves them as a PNG. Caption buttons are Normal unless --button gives them a state, which
+ * applies to the active window. With --rtl, the windows are laid out right to left.
+ */
+
+use std::fs;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use kanaya_common::formats;
+use kanaya_common::image::RgbaImage;
+use kanaya_common::render::window::{CaptionButton, MockWindow};
+use kanaya_common::render::{self, fill_rect, Renderer};
+use kanaya_common::theme::sysmetrics::{scale_px, BASE_DPI};
+use kanaya_common::theme::{Color, Rect};
+
+const USAGE: &str = "Usage: kanayacl window <theme> <output.png> [--size WIDTHxHEIGHT] [--dpi DPI] [--title TITLE] \
+    [--menu ITEM,...] [--button BUTTON=STATE]... [--maximized] [--rtl]";
+
+/**
+ * The size of each window at 96 DPI, without --size.
+ */
+const DEFAULT_SIZE: (u32, u32) = (480, 320);
+
+/**
+ * How far the active window is from the inactive one behind it, at 96 DPI.
+ */
+const WINDOW_OFFSET: i32 = 48;
+
+pub fn run(args: &[String]) -> Result<(), Error> {
+    let mut dpi = BASE_DPI;
+    let mut size = None;
+    let mut window = MockWindow::default();
+    let mut mirrored = false;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| anyhow!("{} needs a value.", name));
+        match arg.as_str() {
+            "--dpi" => {
+                let value = value("--dpi")?;
+                dpi = value.parse().with_context(|| anyhow!("Invalid DPI \"{}\"", value))?;
+            }
+            "--size" => {
+                let value = value("--size")?;
+                size = Some(value.split_once('x')
+                    .and_then(|(width, height)| Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?)))
+                    .ok_or_else(|| anyhow!("Invalid size \"{}\". {}", value, USAGE))?);
+            }
+            "--title" => window.title = value("--title")?.clone(),
+            "--menu" => window.menu = value("--menu")?.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_owned).collect(),
+            "--button" => {
+                let value = value("--button")?;
+                let (button, state) = value.split_once('=').ok_or_else(|| anyhow!("Invalid button state \"{}\". Use BUTTON=STATE.", value))?;
+                let button = CaptionButton::from_name(button.trim())
+                    .ok_or_else(|| anyhow!("Unknown caption button \"{}\". Use Minimize, Maximize or Close.", button))?;
+                window.button_states[button as usize] = Some(state.trim().to_owned());
+            }
+            "--maximized" => window.maximized = true,
+            "--rtl" => mirrored = true,
+            _ => paths.push(arg),
+        }
+    }
+
+    let [theme_path, output_path] = paths[..] else {
+        return Err(anyhow!(USAGE));
+    };
+    let theme = formats::read_theme(Path::new(theme_path))?;
+    let mut renderer = Renderer::with_dpi(&theme, dpi);
+    renderer.mirrored = mirrored;
+    let canvas = draw_windows(&renderer, &window, size)?;
+    fs::write(output_path, render::to_png(&canvas)?).with_context(|| anyhow!("Failed to write \"{}\"", output_path))
+}
+
+/**
+ * Draw an inactive copy of a window with the window in front of it, on the desktop background
+ * color. Without a size, each is the default size for the renderer's DPI.
+ */
+pub fn draw_windows(renderer: &Renderer, window: &MockWindow, size: Option<(u32, u32)>) -> Result<RgbaImage, Error> {
+    let dpi = renderer.dpi;
+    let (width, height) = size.unwrap_or((scale_px(DEFAULT_SIZE.0 as i32, dpi) as u32, scale_px(DEFAULT_SIZE.1 as i32, dpi) as u32));
+    let offset = scale_px(WINDOW_OFFSET, dpi);
+    let (canvas_width, canvas_height) = (width + offset as u32, height + offset as u32);
+    let mut canvas = RgbaImage::new(canvas_width, canvas_height);
+    let desktop = renderer.system_colors().get("Background").unwrap_or(Color::new(0, 0, 0));
+    fill_rect(&mut canvas, Rect { left: 0, top: 0, right: canvas_width as i32, bottom: canvas_height as i32 }, desktop, 255);
+
+    let inactive = MockWindow { active: false, button_states: Default::default(), ..window.clone() };
+    renderer.draw_window(&mut canvas, &inactive, Rect { left: 0, top: 0, right: width as i32, bottom: height as i32 })?;
+    renderer.draw_window(&mut canvas, window, Rect { left: offset, top: offset, right: offset + width as i32, bottom: offset + height as i32 })?;
+    Ok(canvas)
+}
@@ -0,0 +1,306 @@
+/*
+ * Microsoft cabinet (CAB) archives.
+ *
+ * Theme packs are cabinets, so this implements just enough of the format for them: a single
+ * cabinet (no spanning), read with either no compression or MSZIP, and written with one folder.
+ * LZX and Quantum compressed folders are reported as errors.
+ *
+ * MSZIP splits the data of a folder into blocks of at most 32 KiB. Each block is a complete
+ * deflate stream prefixed with "CK", but may refer back into the previous block's output. The
+ * writer compresses every block on its own, which every reader accepts.
+ *
+ * Output is deterministic: files are written in the order given, with the date and time given.
+ */
+
+use anyhow::Error;
+use anyhow::anyhow;
+use chrono::{DateTime, Datelike, Timelike};
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::TINFLStatus;
+use miniz_oxide::inflate::core::{self as inflate, DecompressorOxide};
+
+const HEADER_SIZE: usize = 36;
+const FOLDER_SIZE: usize = 8;
+const DATA_HEADER_SIZE: usize = 8;
+const MAX_BLOCK_SIZE: usize = 0x8000;
+
+const CFHDR_PREV_CABINET: u16 = 0x0001;
+const CFHDR_NEXT_CABINET: u16 = 0x0002;
+const CFHDR_RESERVE_PRESENT: u16 = 0x0004;
+
+const TYPE_NONE: u16 = 0;
+const TYPE_MSZIP: u16 = 1;
+
+pub const ATTRIBUTE_ARCHIVE: u16 = 0x20;
+pub const ATTRIBUTE_NAME_IS_UTF: u16 = 0x80;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    #[default]
+    MsZip,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CabinetFile {
+    /**
+     * The path of the file inside of the cabinet, using backslashes.
+     */
+    pub name: String,
+    pub data: Vec<u8>,
+    /**
+     * MS-DOS date and time.
+     */
+    pub date: u16,
+    pub time: u16,
+    pub attributes: u16,
+}
+
+impl CabinetFile {
+    pub fn new(name: &str, data: Vec<u8>, timestamp: u32) -> Self {
+        let (date, time) = dos_date_time(timestamp);
+        Self { name: name.replace('/', "\\"), data, date, time, attributes: ATTRIBUTE_ARCHIVE }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cabinet {
+    pub files: Vec<CabinetFile>,
+}
+
+/**
+ * Convert seconds since the Unix epoch to an MS-DOS date and time. MS-DOS dates start in 1980, so
+ * anything earlier (including zero) becomes 1980-01-01 00:00:00.
+ */
+pub fn dos_date_time(timestamp: u32) -> (u16, u16) {
+    let Some(time) = DateTime::from_timestamp(timestamp as i64, 0).filter(|time| time.year() >= 1980) else {
+        return ((1 << 5) | 1, 0);
+    };
+    let date = (((time.year() - 1980) as u16) << 9) | ((time.month() as u16) << 5) | time.day() as u16;
+    let time = ((time.hour() as u16) << 11) | ((time.minute() as u16) << 5) | (time.second() / 2) as u16;
+    (date, time)
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+/**
+ * The CFDATA checksum: an XOR of little-endian words, with the trailing bytes taken big-endian.
+ */
+fn checksum(data: &[u8], seed: u32) -> u32 {
+    let mut chunks = data.chunks_exact(4);
+    let mut result = seed;
+    for chunk in &mut chunks {
+        result ^= u32::from_le_bytes(chunk.try_into().unwrap());
+    }
+    result ^ chunks.remainder().iter().fold(0, |value, byte| (value << 8) | *byte as u32)
+}
+
+fn data_checksum(compressed_size: u16, uncompressed_size: u16, data: &[u8]) -> u32 {
+    let mut sizes = Vec::with_capacity(4);
+    put_u16(&mut sizes, compressed_size);
+    put_u16(&mut sizes, uncompressed_size);
+    checksum(&sizes, checksum(data, 0))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn bytes(&self, offset: usize, length: usize) -> Result<&[u8], Error> {
+        offset.checked_add(length)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| anyhow!("Unexpected end of cabinet at offset {:#x}.", offset))
+    }
+
+    fn u8(&self, offset: usize) -> Result<u8, Error> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.bytes(offset, 2)?.try_into()?))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.bytes(offset, 4)?.try_into()?))
+    }
+
+    /**
+     * Read a null-terminated string, returning it and the offset after the terminator.
+     */
+    fn string(&self, offset: usize) -> Result<(&[u8], usize), Error> {
+        let rest = self.data.get(offset..).ok_or_else(|| anyhow!("Unexpected end of cabinet at offset {:#x}.", offset))?;
+        let length = rest.iter().position(|byte| *byte == 0).ok_or_else(|| anyhow!("Unterminated string at offset {:#x}.", offset))?;
+        Ok((&rest[..length], offset + length + 1))
+    }
+}
+
+fn inflate_block(output: &mut Vec<u8>, block: &[u8], uncompressed_size: usize) -> Result<(), Error> {
+    let block = block.strip_prefix(b"CK").ok_or_else(|| anyhow!("MSZIP block is missing its signature."))?;
+    let start = output.len();
+    output.resize(start + uncompressed_size, 0);
+
+    let mut decompressor = DecompressorOxide::new();
+    let flags = inflate::inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+    let (status, _, written) = inflate::decompress(&mut decompressor, block, output, start, flags);
+    if status != TINFLStatus::Done || written != uncompressed_size {
+        return Err(anyhow!("Corrupt MSZIP block ({:?}, {} of {} bytes).", status, written, uncompressed_size));
+    }
+    Ok(())
+}
+
+impl Cabinet {
+    pub fn read(data: &[u8]) -> Result<Self, Error> {
+        let reader = Reader { data };
+        if reader.bytes(0, 4)? != b"MSCF" {
+            return Err(anyhow!("Not a cabinet file."));
+        }
+
+        let files_offset = reader.u32(16)? as usize;
+        let folder_count = reader.u16(26)? as usize;
+        let file_count = reader.u16(28)? as usize;
+        let flags = reader.u16(30)?;
+        if flags & (CFHDR_PREV_CABINET | CFHDR_NEXT_CABINET) != 0 {
+            return Err(anyhow!("Cabinets that span several files are not supported."));
+        }
+
+        let mut offset = HEADER_SIZE;
+        let (folder_reserve, data_reserve) = if flags & CFHDR_RESERVE_PRESENT != 0 {
+            let header_reserve = reader.u16(offset)? as usize;
+            let reserves = (reader.u8(offset + 2)? as usize, reader.u8(offset + 3)? as usize);
+            offset += 4 + header_reserve;
+            reserves
+        }
+        else {
+            (0, 0)
+        };
+
+        let mut folders = Vec::with_capacity(folder_count);
+        for _ in 0..folder_count {
+            let mut contents = Vec::new();
+            let mut block_offset = reader.u32(offset)? as usize;
+            let block_count = reader.u16(offset + 4)?;
+            let compression = reader.u16(offset + 6)?;
+            offset += FOLDER_SIZE + folder_reserve;
+
+            for _ in 0..block_count {
+                let compressed_size = reader.u16(block_offset + 4)? as usize;
+                let uncompressed_size = reader.u16(block_offset + 6)? as usize;
+                let block = reader.bytes(block_offset + DATA_HEADER_SIZE + data_reserve, compressed_size)?;
+                block_offset += DATA_HEADER_SIZE + data_reserve + compressed_size;
+
+                match compression & 0xF {
+                    TYPE_NONE => contents.extend_from_slice(block),
+                    TYPE_MSZIP => inflate_block(&mut contents, block, uncompressed_size)?,
+                    other => return Err(anyhow!("Unsupported cabinet compression type {}.", other)),
+                }
+            }
+            folders.push(contents);
+        }
+
+        let mut files = Vec::with_capacity(file_count);
+        offset = files_offset;
+        for _ in 0..file_count {
+            let size = reader.u32(offset)? as usize;
+            let folder_offset = reader.u32(offset + 4)? as usize;
+            let folder = reader.u16(offset + 8)? as usize;
+            let date = reader.u16(offset + 10)?;
+            let time = reader.u16(offset + 12)?;
+            let attributes = reader.u16(offset + 14)?;
+            let (name, next) = reader.string(offset + 16)?;
+            offset = next;
+
+            let name = if attributes & ATTRIBUTE_NAME_IS_UTF != 0 {
+                String::from_utf8(name.to_vec())?
+            }
+            else {
+                name.iter().map(|byte| *byte as char).collect()
+            };
+
+            let contents = folders.get(folder).ok_or_else(|| anyhow!("File \"{}\" is in a folder that does not exist.", name))?;
+            let data = contents.get(folder_offset..folder_offset + size)
+                .ok_or_else(|| anyhow!("File \"{}\" extends past the end of its folder.", name))?
+                .to_vec();
+            files.push(CabinetFile { name, data, date, time, attributes });
+        }
+
+        Ok(Self { files })
+    }
+
+    pub fn write(&self, compression: Compression) -> Result<Vec<u8>, Error> {
+        let file_count = u16::try_from(self.files.len()).map_err(|_| anyhow!("Too many files for one cabinet."))?;
+
+        let mut file_entries = Vec::new();
+        let mut contents = Vec::new();
+        for file in &self.files {
+            let mut attributes = file.attributes & !ATTRIBUTE_NAME_IS_UTF;
+            if !file.name.is_ascii() {
+                attributes |= ATTRIBUTE_NAME_IS_UTF;
+            }
+            put_u32(&mut file_entries, file.data.len() as u32);
+            put_u32(&mut file_entries, contents.len() as u32);
+            put_u16(&mut file_entries, 0);
+            put_u16(&mut file_entries, file.date);
+            put_u16(&mut file_entries, file.time);
+            put_u16(&mut file_entries, attributes);
+            file_entries.extend_from_slice(file.name.as_bytes());
+            file_entries.push(0);
+            contents.extend_from_slice(&file.data);
+        }
+
+        let mut blocks = Vec::new();
+        for chunk in contents.chunks(MAX_BLOCK_SIZE) {
+            let payload = match compression {
+                Compression::None => chunk.to_vec(),
+                Compression::MsZip => [&b"CK"[..], &compress_to_vec(chunk, 9)].concat(),
+            };
+            let compressed_size = payload.len() as u16;
+            let uncompressed_size = chunk.len() as u16;
+            put_u32(&mut blocks, data_checksum(compressed_size, uncompressed_size, &payload));
+            put_u16(&mut blocks, compressed_size);
+            put_u16(&mut blocks, uncompressed_size);
+            blocks.extend_from_slice(&payload);
+        }
+        let block_count = u16::try_from(contents.len().div_ceil(MAX_BLOCK_SIZE)).map_err(|_| anyhow!("Too much data for one cabinet folder."))?;
+
+        let files_offset = HEADER_SIZE + FOLDER_SIZE;
+        let blocks_offset = files_offset + file_entries.len();
+        let total_size = blocks_offset + blocks.len();
+
+        let mut result = Vec::with_capacity(total_size);
+        result.extend_from_slice(b"MSCF");
+        put_u32(&mut result, 0);
+        put_u32(&mut result, total_size as u32);
+        put_u32(&mut result, 0);
+        put_u32(&mut result, files_offset as u32);
+        put_u32(&mut result, 0);
+        result.extend_from_slice(&[3, 1]);
+        put_u16(&mut result, 1);
+        put_u16(&mut result, file_count);
+        put_u16(&mut result, 0);
+        put_u16(&mut result, 0);
+        put_u16(&mut result, 0);
+
+        put_u32(&mut result, blocks_offset as u32);
+        put_u16(&mut result, block_count);
+        put_u16(&mut result, match compression {
+            Compression::None => TYPE_NONE,
+            Compression::MsZip => TYPE_MSZIP,
+        });
+
+        result.extend_from_slice(&file_entries);
+        result.extend_from_slice(&blocks);
+        Ok(result)
+    }
+
+    pub fn file(&self, name: &str) -> Option<&CabinetFile> {
+        let name = name.replace('/', "\\");
+        self.files.iter().find(|file| file.name.eq_ignore_ascii_case(&name))
+    }
+}
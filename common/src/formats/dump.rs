@@ -0,0 +1,218 @@
+/*
+ * Canonical text dumps of themes.
+ *
+ * The dump is meant for diffing, i.e. as a git textconv filter, so everything in it is sorted and
+ * nothing in it depends on the order that the theme was authored or packed in. Images are listed
+ * by their hash rather than their contents.
+ *
+ * Compiled styles that Kanaya cannot read as a theme, such as the ones that ship with Windows,
+ * are dumped at the resource level instead: the class map, every property record of every color
+ * variant with its raw value, and the images.
+ */
+
+use std::fmt::Write;
+use sha2::{Digest, Sha256};
+
+use crate::formats::msstyles::{self, RawRecord};
+use crate::formats::pe::{self, ResourceName};
+use crate::formats::restyle_ini;
+use crate::image::{is_bmp, is_png};
+use crate::theme::*;
+use crate::theme::parts;
+use crate::theme::schema::{self, PropertyType};
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().fold(String::new(), |mut result, byte| {
+        let _ = write!(result, "{:02x}", byte);
+        result
+    })
+}
+
+fn type_name(value: &PropertyValue) -> String {
+    format!("{:?}", value.property_type()).to_lowercase()
+}
+
+fn image_kind(data: &[u8]) -> &'static str {
+    if is_png(data) {
+        "png"
+    }
+    else if is_bmp(data) {
+        "bmp"
+    }
+    else {
+        "unknown"
+    }
+}
+
+/**
+ * Sort key for parts and states: numbered by their identifier when it is known, then by name.
+ */
+fn sort_key(id: Option<i32>, name: Option<&str>) -> (i32, String) {
+    (id.unwrap_or(i32::MAX), name.unwrap_or_default().to_lowercase())
+}
+
+/**
+ * Dump the classes, parts, states and properties of a theme, followed by its images.
+ */
+pub fn dump_theme(theme: &Theme) -> String {
+    let mut result = String::new();
+
+    let mut classes: Vec<&ThemeClass> = theme.classes.iter().collect();
+    classes.sort_by_key(|class| class.name.to_lowercase());
+
+    for class in classes {
+        let mut part_list: Vec<&ThemePart> = class.parts.iter().collect();
+        part_list.sort_by_key(|part| sort_key(parts::part_id(&class.name, part.name.as_deref()), part.name.as_deref()));
+
+        for part in part_list {
+            let mut states: Vec<&ThemeState> = part.states.iter().collect();
+            states.sort_by_key(|state| sort_key(parts::state_id(&class.name, part.name.as_deref(), state.name.as_deref()), state.name.as_deref()));
+
+            for state in states {
+                let _ = writeln!(result, "[{}]", restyle_ini::format_section_name(&class.name, part.name.as_deref(), state.name.as_deref()));
+
+                let mut properties: Vec<&Property> = state.properties.iter().collect();
+                properties.sort_by_key(|property| property.name.to_lowercase());
+
+                for property in properties {
+                    let _ = write!(result, "    {} ({}) = {}", property.name, type_name(&property.value), restyle_ini::format_value(&property.name, &property.value));
+                    if let PropertyValue::FileName(path) = &property.value {
+                        match theme.image(path) {
+                            Some(data) => { let _ = write!(result, " [sha256:{}]", sha256_hex(data)); }
+                            None => result.push_str(" [missing]"),
+                        }
+                    }
+                    result.push('\n');
+                }
+            }
+        }
+    }
+
+    if !theme.images.is_empty() {
+        result.push_str("\n[Images]\n");
+        let mut images: Vec<(&String, &Vec<u8>)> = theme.images.iter().collect();
+        images.sort_by_key(|(path, _)| normalize_image_path(path));
+        for (path, data) in images {
+            let _ = writeln!(result, "    {} {} {} bytes sha256:{}", path, image_kind(data), data.len(), sha256_hex(data));
+        }
+    }
+
+    result
+}
+
+/**
+ * Dump a compiled visual style: its resources, then its theme data.
+ *
+ * This never fails. A file that cannot be fully read still gets as much of a dump as possible,
+ * with the error included, so that a diff is always shown.
+ */
+pub fn dump_msstyles(data: &[u8]) -> String {
+    let mut result = String::new();
+    let _ = writeln!(result, "[Metadata]\n    Format = msstyles\n    Size = {} bytes\n    SHA256 = {}", data.len(), sha256_hex(data));
+
+    let table = match pe::read_image(data) {
+        Ok(table) => table,
+        Err(e) => {
+            let _ = writeln!(result, "    Error = {}", e);
+            return result;
+        }
+    };
+
+    if let Some(version) = table.find(&ResourceName::name("PACKTHEM_VERSION"), &ResourceName::Id(1)) {
+        if let Ok(version) = <[u8; 2]>::try_from(version) {
+            let _ = writeln!(result, "    PackthemVersion = {}", u16::from_le_bytes(version));
+        }
+    }
+
+    result.push_str("\n[Resources]\n");
+    for ((ty, name, language), data) in &table.entries {
+        let _ = writeln!(result, "    {}/{}/{} {} bytes sha256:{}", ty, name, language, data.len(), sha256_hex(data));
+    }
+    result.push('\n');
+
+    match msstyles::unpack(data) {
+        Ok(theme) => result.push_str(&dump_theme(&theme)),
+        Err(e) => {
+            let _ = writeln!(result, "[Error]\n    {}\n", e);
+            result.push_str(&dump_records(&table));
+        }
+    }
+
+    result
+}
+
+fn record_value(record: &RawRecord) -> String {
+    if record.data.is_empty() {
+        format!("inline {}", record.inline)
+    }
+    else {
+        format!("{} bytes sha256:{}", record.data.len(), sha256_hex(&record.data))
+    }
+}
+
+/**
+ * Dump the class map, property records and images of a compiled visual style without
+ * interpreting the values, for files that cannot be unpacked.
+ */
+pub fn dump_records(table: &pe::ResourceTable) -> String {
+    let mut result = String::new();
+    let class_names = msstyles::class_map(table).unwrap_or_default();
+    result.push_str("[ClassMap]\n");
+    for (id, name) in class_names.iter().enumerate() {
+        let _ = writeln!(result, "    {} = {}", id, name);
+    }
+
+    for (name, variant) in table.of_type(&ResourceName::name("VARIANT")) {
+        let _ = writeln!(result, "\n[Variant {}]", name);
+        let records = match msstyles::read_records(variant) {
+            Ok(records) => records,
+            Err(e) => {
+                let _ = writeln!(result, "    Error = {}", e);
+                continue;
+            }
+        };
+
+        // Sorted by what each record describes, so that the order records were packed in does
+        // not show up in diffs.
+        let mut lines: Vec<((String, i32, i32, u32), String)> = records.iter().map(|record| {
+            let class = class_names.get(record.class_id as usize).cloned().unwrap_or_else(|| format!("#{}", record.class_id));
+            let part = (record.part_id != 0).then(|| parts::part_name(&class, record.part_id).map(str::to_owned).unwrap_or_else(|| record.part_id.to_string()));
+            let state = (record.state_id != 0).then(|| parts::state_name(&class, record.part_id, record.state_id).map(str::to_owned).unwrap_or_else(|| record.state_id.to_string()));
+            let property = schema::find_property_by_id(record.property_id).map_or_else(|| format!("#{}", record.property_id), |info| info.name.to_owned());
+            let ty = PropertyType::from_id(record.type_id).map_or_else(|| format!("#{}", record.type_id), |ty| format!("{:?}", ty).to_lowercase());
+            let line = format!(
+                "    [{}] {} ({}) = {}",
+                restyle_ini::format_section_name(&class, part.as_deref(), state.as_deref()), property, ty, record_value(record),
+            );
+            ((class.to_lowercase(), record.part_id, record.state_id, record.property_id), line)
+        }).collect();
+        lines.sort();
+        for (_, line) in lines {
+            result.push_str(&line);
+            result.push('\n');
+        }
+    }
+
+    result.push_str("\n[Images]\n");
+    for ((ty, name, _), data) in &table.entries {
+        if *ty == ResourceName::name("IMAGE") || *ty == ResourceName::name("STREAM") || *ty == ResourceName::Id(pe::RT_BITMAP) {
+            let kind = if *ty == ResourceName::Id(pe::RT_BITMAP) { "bmp" } else { image_kind(data) };
+            let _ = writeln!(result, "    {}/{} {} {} bytes sha256:{}", ty, name, kind, data.len(), sha256_hex(data));
+        }
+    }
+    result
+}
+
+/**
+ * Dump either a compiled visual style or a theme source, depending on the contents of the file.
+ */
+pub fn dump_file(data: &[u8]) -> String {
+    if data.starts_with(b"MZ") {
+        return dump_msstyles(data);
+    }
+
+    match restyle_ini::decode(data).and_then(|source| restyle_ini::parse(&source)) {
+        Ok(theme) => dump_theme(&theme),
+        Err(e) => format!("[Error]\n    {}\n", e),
+    }
+}
@@ -0,0 +1,321 @@
+/*
+ * Lossless INI documents.
+ *
+ * Windows configuration files such as .theme files are edited by hand as often as by tools, so
+ * rewriting them must not lose anything. This document keeps every line as it was read,
+ * including comments, blank lines, unknown keys, key casing and spacing, the text encoding and
+ * the line endings. Only lines that are changed through the API are regenerated.
+ *
+ * Files that were edited on different systems can mix CRLF and LF, so every line keeps its own
+ * line ending as part of its text. Lines that are added get the document's line ending.
+ */
+
+use anyhow::Error;
+use anyhow::anyhow;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /**
+     * Bytes that are not valid UTF-8. Each byte is read as the character with the same code
+     * point, so that files in an ANSI code page survive a round trip unchanged.
+     */
+    Ansi,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line {
+    /**
+     * A blank line, comment or anything else that is not a key. Kept verbatim, with its line
+     * ending if it had one.
+     */
+    Other(String),
+    Entry {
+        key: String,
+        value: String,
+        /**
+         * The original text of the line with its line ending, or None if the entry was added or
+         * changed.
+         */
+        raw: Option<String>,
+        /**
+         * The original line ending, which is kept when the entry is changed. Empty for entries
+         * that were added, and for a last line without one.
+         */
+        ending: &'static str,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    /**
+     * The original text of the header line with its line ending, or None if the section was
+     * added.
+     */
+    pub raw: Option<String>,
+    pub lines: Vec<Line>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Document {
+    /**
+     * Lines before the first section header.
+     */
+    pub preamble: Vec<Line>,
+    pub sections: Vec<Section>,
+    pub encoding: Encoding,
+    /**
+     * The line ending for lines that are added: the first line ending of the document, or CRLF.
+     */
+    pub line_ending: &'static str,
+    /**
+     * Whether the last line ends with a line ending, which decides it for a last line that is
+     * added or changed.
+     */
+    pub trailing_newline: bool,
+    /**
+     * What goes between the key and the value of lines that are added or changed: "=", or
+     * " = " if that is how the first entry of the document is written.
+     */
+    pub separator: &'static str,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self {
+            preamble: Vec::new(),
+            sections: Vec::new(),
+            encoding: Encoding::Utf8,
+            line_ending: "\r\n",
+            trailing_newline: true,
+            separator: "=",
+        }
+    }
+}
+
+/**
+ * Split a line into its text and its line ending.
+ */
+fn split_ending(line: &str) -> (&str, &'static str) {
+    if let Some(text) = line.strip_suffix("\r\n") {
+        (text, "\r\n")
+    }
+    else if let Some(text) = line.strip_suffix('\n') {
+        (text, "\n")
+    }
+    else {
+        (line, "")
+    }
+}
+
+fn parse_line(line: &str) -> Line {
+    let (text, ending) = split_ending(line);
+    let trimmed = text.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with(';') {
+        return Line::Other(line.to_owned());
+    }
+    match text.split_once('=') {
+        Some((key, value)) => Line::Entry { key: key.trim().to_owned(), value: value.trim().to_owned(), raw: Some(line.to_owned()), ending },
+        None => Line::Other(line.to_owned()),
+    }
+}
+
+impl Section {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines.iter().find_map(|line| match line {
+            Line::Entry { key: entry_key, value, .. } if entry_key.eq_ignore_ascii_case(key) => Some(value.as_str()),
+            _ => None,
+        })
+    }
+
+    /**
+     * Set a key, keeping its position (and the casing of its name) if it already exists.
+     */
+    pub fn set(&mut self, key: &str, new_value: &str) {
+        for line in &mut self.lines {
+            if let Line::Entry { key: entry_key, value, raw, .. } = line {
+                if entry_key.eq_ignore_ascii_case(key) {
+                    if value != new_value {
+                        *value = new_value.to_owned();
+                        *raw = None;
+                    }
+                    return;
+                }
+            }
+        }
+
+        // New keys go after the last entry, so that trailing blank lines stay in place.
+        let position = self.lines.iter().rposition(|line| matches!(line, Line::Entry { .. })).map(|i| i + 1).unwrap_or(0);
+        self.lines.insert(position, Line::Entry { key: key.to_owned(), value: new_value.to_owned(), raw: None, ending: "" });
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let index = self.lines.iter().position(|line| matches!(line, Line::Entry { key: entry_key, .. } if entry_key.eq_ignore_ascii_case(key)))?;
+        match self.lines.remove(index) {
+            Line::Entry { value, .. } => Some(value),
+            Line::Other(_) => None,
+        }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Entry { key, value, .. } => Some((key.as_str(), value.as_str())),
+            Line::Other(_) => None,
+        })
+    }
+}
+
+/**
+ * Decode the raw bytes of an INI file, and tell which encoding they were in: UTF-16 with a byte
+ * order mark, UTF-8 with or without one, or failing that, ANSI.
+ */
+pub fn decode_text(bytes: &[u8]) -> Result<(String, Encoding), Error> {
+    let utf16 = |bytes: &[u8], read: fn([u8; 2]) -> u16| -> Result<String, Error> {
+        if !bytes.len().is_multiple_of(2) {
+            return Err(anyhow!("Invalid UTF-16 text: the file has an odd number of bytes."));
+        }
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| read([pair[0], pair[1]])).collect();
+        String::from_utf16(&units).map_err(|_| anyhow!("Invalid UTF-16 text."))
+    };
+
+    Ok(match bytes {
+        [0xFF, 0xFE, rest @ ..] => (utf16(rest, u16::from_le_bytes)?, Encoding::Utf16Le),
+        [0xFE, 0xFF, rest @ ..] => (utf16(rest, u16::from_be_bytes)?, Encoding::Utf16Be),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => (String::from_utf8(rest.to_vec())?, Encoding::Utf8Bom),
+        _ => match String::from_utf8(bytes.to_vec()) {
+            Ok(text) => (text, Encoding::Utf8),
+            Err(_) => (bytes.iter().map(|byte| *byte as char).collect(), Encoding::Ansi),
+        },
+    })
+}
+
+impl Document {
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let (text, encoding) = decode_text(bytes)?;
+        let mut document = Self::parse(&text);
+        document.encoding = encoding;
+        Ok(document)
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let first_ending = text.split_inclusive('\n').next().map(|line| split_ending(line).1).filter(|ending| !ending.is_empty());
+        let mut document = Document {
+            line_ending: first_ending.unwrap_or("\r\n"),
+            trailing_newline: text.is_empty() || text.ends_with('\n'),
+            ..Default::default()
+        };
+
+        for line in text.split_inclusive('\n') {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') && trimmed.ends_with(']') {
+                document.sections.push(Section {
+                    name: trimmed[1..trimmed.len() - 1].trim().to_owned(),
+                    raw: Some(line.to_owned()),
+                    lines: Vec::new(),
+                });
+                continue;
+            }
+
+            let parsed = parse_line(line);
+            match document.sections.last_mut() {
+                Some(section) => section.lines.push(parsed),
+                None => document.preamble.push(parsed),
+            }
+        }
+
+        let first_entry = document.preamble.iter().chain(document.sections.iter().flat_map(|section| &section.lines))
+            .find_map(|line| match line {
+                Line::Entry { raw: Some(raw), .. } => raw.split_once('='),
+                _ => None,
+            });
+        if first_entry.is_some_and(|(key, _)| key.ends_with([' ', '\t'])) {
+            document.separator = " = ";
+        }
+
+        document
+    }
+
+    pub fn to_text(&self) -> String {
+        // Each line with the line ending that it has of its own, if any.
+        let mut lines: Vec<(String, &'static str)> = Vec::new();
+        let push_lines = |lines_in: &[Line], lines: &mut Vec<(String, &'static str)>| {
+            for line in lines_in {
+                lines.push(match line {
+                    Line::Other(text) => (text.clone(), ""),
+                    Line::Entry { raw: Some(raw), .. } => (raw.clone(), ""),
+                    Line::Entry { key, value, raw: None, ending } => (format!("{}{}{}", key, self.separator, value), *ending),
+                });
+            }
+        };
+
+        push_lines(&self.preamble, &mut lines);
+        for section in &self.sections {
+            lines.push((section.raw.clone().unwrap_or_else(|| format!("[{}]", section.name)), ""));
+            push_lines(&section.lines, &mut lines);
+        }
+
+        let mut text = String::new();
+        let count = lines.len();
+        for (index, (line, ending)) in lines.into_iter().enumerate() {
+            let last = index + 1 == count;
+            text.push_str(&line);
+            if line.ends_with('\n') {
+                continue;
+            }
+            if !ending.is_empty() {
+                text.push_str(ending);
+            }
+            else if !last || self.trailing_newline {
+                text.push_str(self.line_ending);
+            }
+        }
+        text
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let text = self.to_text();
+        match self.encoding {
+            Encoding::Utf8 => text.into_bytes(),
+            Encoding::Utf8Bom => [&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat(),
+            Encoding::Utf16Le => [0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect(),
+            Encoding::Utf16Be => [0xFE, 0xFF].into_iter().chain(text.encode_utf16().flat_map(u16::to_be_bytes)).collect(),
+            Encoding::Ansi => text.chars().map(|c| u8::try_from(c).unwrap_or(b'?')).collect(),
+        }
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name.eq_ignore_ascii_case(name))
+    }
+
+    /**
+     * Get a section for editing, appending it (after a blank line) if it does not exist yet.
+     */
+    pub fn section_mut(&mut self, name: &str) -> &mut Section {
+        let index = match self.sections.iter().position(|section| section.name.eq_ignore_ascii_case(name)) {
+            Some(index) => index,
+            None => {
+                if let Some(last) = self.sections.last_mut() {
+                    if !matches!(last.lines.last(), Some(Line::Other(text)) if text.trim().is_empty()) {
+                        last.lines.push(Line::Other(String::new()));
+                    }
+                }
+                self.sections.push(Section { name: name.to_owned(), raw: None, lines: Vec::new() });
+                self.sections.len() - 1
+            }
+        };
+        &mut self.sections[index]
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.section(section)?.get(key)
+    }
+
+    pub fn set(&mut self, section: &str, key: &str, value: &str) {
+        self.section_mut(section).set(key, value);
+    }
+}
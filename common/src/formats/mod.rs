@@ -0,0 +1,61 @@
+pub mod restyle_ini;
+pub mod pe;
+pub mod msstyles;
+pub mod dump;
+pub mod ini;
+pub mod theme_file;
+pub mod cab;
+pub mod themepack;
+pub mod reg;
+
+use std::fs;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use crate::theme::Theme;
+
+/**
+ * Read a theme from either a compiled visual style or a Restyle project, depending on the
+ * contents of the file.
+ */
+pub fn read_theme(path: &Path) -> Result<Theme, Error> {
+    let data = fs::read(path).with_context(|| anyhow!("Failed to read \"{}\"", path.display()))?;
+    if data.starts_with(b"MZ") {
+        msstyles::unpack(&data).with_context(|| anyhow!("Failed to read visual style \"{}\"", path.display()))
+    }
+    else {
+        restyle_ini::read_project(path)
+    }
+}
+
+/**
+ * Whether two paths are the same existing file.
+ */
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/**
+ * Write a theme as a compiled visual style if the path ends in .msstyles, and as a Restyle
+ * project otherwise. When the path is the Restyle project that the theme was read from (source),
+ * the project is updated in place, so that its comments and formatting are kept and the images
+ * that the theme dropped are deleted. Any other file is overwritten, and nothing is deleted.
+ */
+pub fn write_theme(theme: &Theme, source: &Path, path: &Path) -> Result<(), Error> {
+    let is_msstyles = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("msstyles"));
+    if is_msstyles {
+        let data = msstyles::pack(theme, &msstyles::PackOptions::from_environment())?;
+        fs::write(path, data).with_context(|| anyhow!("Failed to write \"{}\"", path.display()))
+    }
+    else if same_file(source, path) {
+        restyle_ini::update_project(theme, path)
+    }
+    else {
+        restyle_ini::write_project(theme, path)
+    }
+}
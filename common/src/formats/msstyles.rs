@@ -0,0 +1,550 @@
+/*
+ * Compiled visual style (.msstyles) packer and unpacker.
+ *
+ * A compiled visual style is a resource-only DLL. Kanaya writes the following resources:
+ *
+ * - CMAP/CMAP: the class map, a list of null-terminated UTF-16 class names. A class's index in
+ *   this list is its class identifier.
+ * - VARIANT/NORMAL: the property records of every class, part and state.
+ * - IMAGE/#n: PNG images; BITMAP/#n: bitmaps, without their file header.
+ * - STREAM/#n: the PNG atlases of Windows 8 and later styles, which AtlasImage refers to.
+ * - PACKTHEM_VERSION/#1: the format version.
+ *
+ * Each property record is a 32-byte header made of eight 32-bit integers: the property
+ * identifier, the type identifier, the class, part and state identifiers, an inline value, a
+ * reserved field and the size of the data that follows. Values that fit in 32 bits (integers,
+ * enumerations, booleans, colors, sizes and image resource identifiers) are stored inline and
+ * have no data. Everything else follows the header, padded to 8 bytes.
+ *
+ * Image properties refer to their resource by identifier: FileName records to an IMAGE or
+ * BITMAP resource, and DiskStream records (AtlasImage) to a STREAM resource. Stream records hold
+ * their image in their data instead. All of them become image paths in the theme model.
+ *
+ * Packing is deterministic. Classes are sorted by name, parts and states by identifier, and
+ * properties by identifier. Images are numbered in the order of their sorted paths and are
+ * embedded exactly as they are stored in the theme, without re-encoding. The only timestamp in
+ * the output is the PE header's, which comes from PackOptions.
+ */
+
+use std::collections::{BTreeMap, BTreeSet};
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use crate::formats::pe::{self, ResourceName, ResourceTable};
+use crate::image::{is_bmp, is_png};
+use crate::theme::*;
+use crate::theme::parts;
+use crate::theme::schema::{self, PropertyType};
+
+pub const PACKTHEM_VERSION: u16 = 4;
+
+const RECORD_HEADER_SIZE: usize = 32;
+const LOGFONT_SIZE: usize = 92;
+const LF_FACESIZE: usize = 32;
+const BITMAP_FILE_HEADER_SIZE: usize = 14;
+
+#[derive(Default)]
+pub struct PackOptions {
+    /**
+     * The timestamp written to the PE header, in seconds since the Unix epoch. Zero by default.
+     */
+    pub timestamp: u32,
+}
+
+impl PackOptions {
+    /**
+     * Take the timestamp from the SOURCE_DATE_EPOCH environment variable, which is the
+     * convention used by reproducible build tooling. Without it, the timestamp is zero.
+     */
+    pub fn from_environment() -> Self {
+        let timestamp = std::env::var("SOURCE_DATE_EPOCH").ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(0);
+        Self { timestamp }
+    }
+}
+
+fn cmap_name() -> (ResourceName, ResourceName) {
+    (ResourceName::name("CMAP"), ResourceName::name("CMAP"))
+}
+
+fn variant_name() -> (ResourceName, ResourceName) {
+    (ResourceName::name("VARIANT"), ResourceName::name("NORMAL"))
+}
+
+fn image_type() -> ResourceName {
+    ResourceName::name("IMAGE")
+}
+
+fn stream_type() -> ResourceName {
+    ResourceName::name("STREAM")
+}
+
+fn put_i32(buffer: &mut Vec<u8>, value: i32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_utf16z(buffer: &mut Vec<u8>, text: &str) {
+    for unit in text.encode_utf16().chain(std::iter::once(0)) {
+        buffer.extend_from_slice(&unit.to_le_bytes());
+    }
+}
+
+/**
+ * Serialize a LOGFONTW. Heights are negative (character height) and in pixels at 96 DPI.
+ */
+fn put_logfont(buffer: &mut Vec<u8>, font: &Font) {
+    let start = buffer.len();
+    put_i32(buffer, -((font.size * 96 + 36) / 72));
+    put_i32(buffer, 0); // lfWidth
+    put_i32(buffer, 0); // lfEscapement
+    put_i32(buffer, 0); // lfOrientation
+    put_i32(buffer, font.weight);
+    buffer.push(font.italic as u8);
+    buffer.push(font.underline as u8);
+    buffer.push(font.strikeout as u8);
+    buffer.push(1); // DEFAULT_CHARSET
+    buffer.extend_from_slice(&[0, 0, 0, 0]); // Precision, quality, pitch and family
+    let face: Vec<u16> = font.face.encode_utf16().take(LF_FACESIZE - 1).collect();
+    for unit in face {
+        buffer.extend_from_slice(&unit.to_le_bytes());
+    }
+    buffer.resize(start + LOGFONT_SIZE, 0);
+}
+
+fn read_logfont(data: &[u8]) -> Result<Font, Error> {
+    if data.len() < LOGFONT_SIZE {
+        return Err(anyhow!("LOGFONT is too small."));
+    }
+    let height = i32::from_le_bytes(data[0..4].try_into()?);
+    let face: Vec<u16> = data[28..28 + 2 * LF_FACESIZE]
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect();
+    Ok(Font {
+        face: String::from_utf16_lossy(&face),
+        size: (height.abs() * 72 + 48) / 96,
+        weight: i32::from_le_bytes(data[16..20].try_into()?),
+        italic: data[20] != 0,
+        underline: data[21] != 0,
+        strikeout: data[22] != 0,
+    })
+}
+
+/**
+ * Encode a property value. Returns the inline value, and the data that follows the header.
+ */
+fn encode_value(value: &PropertyValue, images: &BTreeMap<String, u16>) -> Result<(i32, Vec<u8>), Error> {
+    let mut data = Vec::new();
+    let inline = match value {
+        PropertyValue::Enum(value) | PropertyValue::Int(value) | PropertyValue::Size(value) => *value,
+        PropertyValue::Bool(value) => *value as i32,
+        PropertyValue::Color(color) => color.to_colorref() as i32,
+        PropertyValue::FileName(path) => *images.get(&normalize_image_path(path))
+            .ok_or_else(|| anyhow!("Image \"{}\" is missing.", path))? as i32,
+        PropertyValue::String(text) => {
+            put_utf16z(&mut data, text);
+            0
+        }
+        PropertyValue::Margins(margins) => {
+            for value in [margins.left, margins.right, margins.top, margins.bottom] {
+                put_i32(&mut data, value);
+            }
+            0
+        }
+        PropertyValue::Rect(rect) => {
+            for value in [rect.left, rect.top, rect.right, rect.bottom] {
+                put_i32(&mut data, value);
+            }
+            0
+        }
+        PropertyValue::Position(point) => {
+            put_i32(&mut data, point.x);
+            put_i32(&mut data, point.y);
+            0
+        }
+        PropertyValue::Font(font) => {
+            put_logfont(&mut data, font);
+            0
+        }
+        PropertyValue::IntList(values) => {
+            put_i32(&mut data, values.len() as i32);
+            for value in values {
+                put_i32(&mut data, *value);
+            }
+            0
+        }
+        PropertyValue::Float(value) => {
+            data.extend_from_slice(&value.to_le_bytes());
+            0
+        }
+        PropertyValue::FloatList(values) => {
+            put_i32(&mut data, values.len() as i32);
+            for value in values {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            0
+        }
+    };
+    Ok((inline, data))
+}
+
+/**
+ * The image paths of the resources that image properties refer to, by resource identifier.
+ */
+#[derive(Default)]
+struct ImageResources {
+    images: BTreeMap<i32, String>,
+    streams: BTreeMap<i32, String>,
+}
+
+/**
+ * Decode a property value. Stream records carry their image, which is added to the theme.
+ */
+fn decode_value(ty: PropertyType, inline: i32, data: &[u8], resources: &ImageResources, theme: &mut Theme) -> Result<PropertyValue, Error> {
+    let ints = |count: usize| -> Result<Vec<i32>, Error> {
+        if count.checked_mul(4).is_none_or(|size| data.len() < size) {
+            return Err(anyhow!("Property data is too small."));
+        }
+        Ok(data.chunks_exact(4).take(count).map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap())).collect())
+    };
+    // Lists start with their length, which is followed by that many values.
+    let list = || -> Result<Vec<i32>, Error> {
+        let count = *ints(1)?.first().unwrap();
+        let count = usize::try_from(count).map_err(|_| anyhow!("List property has a negative length ({}).", count))?;
+        let length = count.checked_add(1).ok_or_else(|| anyhow!("Property data is too small."))?;
+        Ok(ints(length)?.split_off(1))
+    };
+
+    Ok(match ty {
+        PropertyType::Enum => PropertyValue::Enum(inline),
+        PropertyType::Int => PropertyValue::Int(inline),
+        PropertyType::Size => PropertyValue::Size(inline),
+        PropertyType::Bool => PropertyValue::Bool(inline != 0),
+        PropertyType::Color => PropertyValue::Color(Color::from_colorref(inline as u32)),
+        PropertyType::FileName => PropertyValue::FileName(
+            resources.images.get(&inline).cloned().ok_or_else(|| anyhow!("Image resource {} is missing.", inline))?
+        ),
+        PropertyType::DiskStream => PropertyValue::FileName(
+            resources.streams.get(&inline).cloned().ok_or_else(|| anyhow!("Stream resource {} is missing.", inline))?
+        ),
+        PropertyType::Stream => {
+            let number = theme.images.keys().filter(|path| path.starts_with("STREAM\\Embedded")).count() + 1;
+            let path = format!("STREAM\\Embedded{}.{}", number, stream_extension(data));
+            theme.images.insert(path.clone(), data.to_vec());
+            PropertyValue::FileName(path)
+        }
+        PropertyType::String => {
+            let units: Vec<u16> = data.chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .take_while(|unit| *unit != 0)
+                .collect();
+            PropertyValue::String(String::from_utf16_lossy(&units))
+        }
+        PropertyType::Margins => {
+            let values = ints(4)?;
+            PropertyValue::Margins(Margins { left: values[0], right: values[1], top: values[2], bottom: values[3] })
+        }
+        PropertyType::Rect => {
+            let values = ints(4)?;
+            PropertyValue::Rect(Rect { left: values[0], top: values[1], right: values[2], bottom: values[3] })
+        }
+        PropertyType::Position => {
+            let values = ints(2)?;
+            PropertyValue::Position(Point { x: values[0], y: values[1] })
+        }
+        PropertyType::Font => PropertyValue::Font(read_logfont(data)?),
+        PropertyType::IntList => PropertyValue::IntList(list()?),
+        PropertyType::Float => PropertyValue::Float(f32::from_bits(*ints(1)?.first().unwrap() as u32)),
+        PropertyType::FloatList => PropertyValue::FloatList(list()?.into_iter().map(|bits| f32::from_bits(bits as u32)).collect()),
+        other => return Err(anyhow!("Unsupported property type {:?}.", other)),
+    })
+}
+
+/**
+ * The file extension for the image of a stream, which is a PNG in every style that Windows ships.
+ */
+fn stream_extension(data: &[u8]) -> &'static str {
+    if is_png(data) { "png" } else { "bin" }
+}
+
+/**
+ * Whether a property is stored as a DiskStream record, which refers to a STREAM resource.
+ */
+fn is_disk_stream(property: &Property) -> bool {
+    matches!(property.value, PropertyValue::FileName(_))
+        && schema::find_property(&property.name).is_some_and(|info| info.ty == PropertyType::DiskStream)
+}
+
+/**
+ * Assign resource identifiers to the images referenced by the theme, in sorted path order: the
+ * ones that DiskStream properties refer to, or the ones that other properties refer to. An image
+ * that both refer to is stored twice.
+ */
+fn number_images(theme: &Theme, disk_stream: bool) -> BTreeMap<String, u16> {
+    let paths: BTreeSet<String> = theme.states()
+        .flat_map(|(_, _, state)| state.properties.iter())
+        .filter(|property| is_disk_stream(property) == disk_stream)
+        .filter_map(|property| match &property.value {
+            PropertyValue::FileName(path) => Some(normalize_image_path(path)),
+            _ => None,
+        })
+        .collect();
+    paths.into_iter().zip(1..).collect()
+}
+
+/**
+ * Build the resource table of a compiled visual style.
+ */
+pub fn build_resources(theme: &Theme) -> Result<ResourceTable, Error> {
+    let mut table = ResourceTable::default();
+
+    let mut classes: Vec<&ThemeClass> = theme.classes.iter().collect();
+    classes.sort_by_key(|class| class.name.to_uppercase());
+
+    let mut cmap = Vec::new();
+    for class in &classes {
+        put_utf16z(&mut cmap, &class.name);
+    }
+    let (ty, name) = cmap_name();
+    table.insert(ty, name, pe::LANG_NEUTRAL, cmap);
+
+    let images = number_images(theme, false);
+    for (path, id) in &images {
+        let data = theme.image(path).ok_or_else(|| anyhow!("Image \"{}\" is missing.", path))?;
+        if is_png(data) {
+            table.insert(image_type(), ResourceName::Id(*id), pe::LANG_NEUTRAL, data.to_vec());
+        }
+        else if is_bmp(data) {
+            // BITMAP resources hold the DIB without the file header.
+            let dib = if data.starts_with(b"BM") { data.get(BITMAP_FILE_HEADER_SIZE..).unwrap_or_default() } else { data };
+            table.insert(ResourceName::Id(pe::RT_BITMAP), ResourceName::Id(*id), pe::LANG_NEUTRAL, dib.to_vec());
+        }
+        else {
+            return Err(anyhow!("Image \"{}\" is neither a PNG nor a bitmap.", path));
+        }
+    }
+
+    let streams = number_images(theme, true);
+    for (path, id) in &streams {
+        let data = theme.image(path).ok_or_else(|| anyhow!("Image \"{}\" is missing.", path))?;
+        if !is_png(data) {
+            return Err(anyhow!("Atlas image \"{}\" is not a PNG.", path));
+        }
+        table.insert(stream_type(), ResourceName::Id(*id), pe::LANG_NEUTRAL, data.to_vec());
+    }
+
+    let mut records: Vec<(i32, i32, u32, Vec<u8>)> = Vec::new();
+    for (class_id, class) in classes.iter().enumerate() {
+        let class_start = records.len();
+        for part in &class.parts {
+            let part_id = parts::part_id(&class.name, part.name.as_deref())
+                .ok_or_else(|| anyhow!(
+                    "Unknown part \"{}\" in class \"{}\". Parts that Kanaya does not know can be given by number, i.e. [{}.3].",
+                    part.name.as_deref().unwrap_or_default(), class.name, class.name,
+                ))?;
+            for state in &part.states {
+                let state_id = parts::state_id(&class.name, part.name.as_deref(), state.name.as_deref())
+                    .ok_or_else(|| anyhow!(
+                        "Unknown state \"{}\" in class \"{}\". States that Kanaya does not know can be given by number, i.e. [{}.{}(2)].",
+                        state.name.as_deref().unwrap_or_default(), class.name, class.name, part_id,
+                    ))?;
+
+                for property in &state.properties {
+                    let info = schema::find_property(&property.name)
+                        .ok_or_else(|| anyhow!("Unknown property \"{}\" in class \"{}\".", property.name, class.name))?;
+                    let disk_stream = is_disk_stream(property);
+                    let (inline, data) = encode_value(&property.value, if disk_stream { &streams } else { &images })
+                        .with_context(|| anyhow!("Failed to encode property \"{}\" in class \"{}\".", property.name, class.name))?;
+                    let ty = if disk_stream { PropertyType::DiskStream } else { property.value.property_type() };
+
+                    let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + data.len());
+                    put_i32(&mut record, info.id as i32);
+                    put_i32(&mut record, ty.id() as i32);
+                    put_i32(&mut record, class_id as i32);
+                    put_i32(&mut record, part_id);
+                    put_i32(&mut record, state_id);
+                    put_i32(&mut record, inline);
+                    put_i32(&mut record, 0);
+                    put_i32(&mut record, data.len() as i32);
+                    record.extend_from_slice(&data);
+                    record.resize(record.len().div_ceil(8) * 8, 0);
+
+                    records.push((part_id, state_id, info.id, record));
+                }
+            }
+        }
+
+        // Canonical ordering within the class. The sort is stable, so duplicate properties
+        // keep their source order.
+        records[class_start..].sort_by_key(|(part, state, id, _)| (*part, *state, *id));
+    }
+
+    let variant: Vec<u8> = records.into_iter().flat_map(|(_, _, _, record)| record).collect();
+    let (ty, name) = variant_name();
+    table.insert(ty, name, pe::LANG_NEUTRAL, variant);
+
+    table.insert(ResourceName::name("PACKTHEM_VERSION"), ResourceName::Id(1), pe::LANG_NEUTRAL, PACKTHEM_VERSION.to_le_bytes().to_vec());
+
+    Ok(table)
+}
+
+/**
+ * Compile a theme to the bytes of an .msstyles file.
+ */
+pub fn pack(theme: &Theme, options: &PackOptions) -> Result<Vec<u8>, Error> {
+    Ok(pe::write_image(&build_resources(theme)?, options.timestamp))
+}
+
+/**
+ * A property record of a VARIANT resource, as it is stored.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawRecord {
+    pub property_id: u32,
+    pub type_id: u32,
+    pub class_id: i32,
+    pub part_id: i32,
+    pub state_id: i32,
+    /**
+     * The value of small properties, which are stored in the header instead of the data.
+     */
+    pub inline: i32,
+    pub data: Vec<u8>,
+}
+
+/**
+ * The class names of a compiled visual style, from its CMAP resource, in class ID order.
+ */
+pub fn class_map(table: &ResourceTable) -> Result<Vec<String>, Error> {
+    let (ty, name) = cmap_name();
+    let cmap = table.find(&ty, &name).ok_or_else(|| anyhow!("The class map is missing."))?;
+    let units: Vec<u16> = cmap.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+    Ok(units.split(|unit| *unit == 0)
+        .filter(|name| !name.is_empty())
+        .map(String::from_utf16_lossy)
+        .collect())
+}
+
+/**
+ * Split a VARIANT resource into its property records, without interpreting them.
+ */
+pub fn read_records(variant: &[u8]) -> Result<Vec<RawRecord>, Error> {
+    let field = |offset: usize| -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(variant.get(offset..offset + 4).ok_or_else(|| anyhow!("Truncated property record."))?.try_into()?))
+    };
+
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset + RECORD_HEADER_SIZE <= variant.len() {
+        let size = field(offset + 28)?;
+        let size = usize::try_from(size).map_err(|_| anyhow!("Property record has a negative size ({}).", size))?;
+        let data_start = offset + RECORD_HEADER_SIZE;
+        let data_end = data_start.checked_add(size).ok_or_else(|| anyhow!("Truncated property data."))?;
+        let data = variant.get(data_start..data_end).ok_or_else(|| anyhow!("Truncated property data."))?;
+        records.push(RawRecord {
+            property_id: field(offset)? as u32,
+            type_id: field(offset + 4)? as u32,
+            class_id: field(offset + 8)?,
+            part_id: field(offset + 12)?,
+            state_id: field(offset + 16)?,
+            inline: field(offset + 20)?,
+            data: data.to_vec(),
+        });
+        offset = data_end.div_ceil(8) * 8;
+    }
+    Ok(records)
+}
+
+/**
+ * Read a compiled visual style back into the theme model.
+ *
+ * Images are named after their resources, i.e. "IMAGE\12.png", "BITMAP\3.bmp" and
+ * "STREAM\1.png", and images that Stream records carry are named "STREAM\Embedded1.png" and so
+ * on. Bitmaps get their file header back so that they are valid .bmp files.
+ */
+pub fn unpack(data: &[u8]) -> Result<Theme, Error> {
+    let table = pe::read_image(data)?;
+    let mut theme = Theme::default();
+
+    let class_names = class_map(&table)?;
+
+    let mut resources = ImageResources::default();
+    for (name, data) in table.of_type(&image_type()) {
+        if let ResourceName::Id(id) = name {
+            let path = format!("IMAGE\\{}.png", id);
+            theme.images.insert(path.clone(), data.to_vec());
+            resources.images.insert(*id as i32, path);
+        }
+    }
+    for (name, data) in table.of_type(&ResourceName::Id(pe::RT_BITMAP)) {
+        if let ResourceName::Id(id) = name {
+            let path = format!("BITMAP\\{}.bmp", id);
+            theme.images.insert(path.clone(), add_bitmap_file_header(data)?);
+            resources.images.entry(*id as i32).or_insert(path);
+        }
+    }
+    for (name, data) in table.of_type(&stream_type()) {
+        if let ResourceName::Id(id) = name {
+            let path = format!("STREAM\\{}.{}", id, stream_extension(data));
+            theme.images.insert(path.clone(), data.to_vec());
+            resources.streams.insert(*id as i32, path);
+        }
+    }
+
+    let (ty, name) = variant_name();
+    let variant = table.find(&ty, &name).ok_or_else(|| anyhow!("The property data is missing."))?;
+    for RawRecord { property_id, type_id, class_id, part_id, state_id, inline, data } in read_records(variant)? {
+        let class = class_names.get(class_id as usize)
+            .ok_or_else(|| anyhow!("Class {} is not in the class map.", class_id))?;
+        let ty = PropertyType::from_id(type_id).ok_or_else(|| anyhow!("Unknown property type {}.", type_id))?;
+        let value = decode_value(ty, inline, &data, &resources, &mut theme)?;
+        let property_name = schema::find_property_by_id(property_id)
+            .map(|info| info.name.to_owned())
+            .unwrap_or_else(|| property_id.to_string());
+
+        let part = (part_id != 0).then(|| parts::part_name(class, part_id).map(str::to_owned).unwrap_or_else(|| part_id.to_string()));
+        let state = (state_id != 0).then(|| parts::state_name(class, part_id, state_id).map(str::to_owned).unwrap_or_else(|| state_id.to_string()));
+        theme.state_mut(class, part.as_deref(), state.as_deref())
+            .properties
+            .push(Property { name: property_name, value });
+    }
+
+    Ok(theme)
+}
+
+/**
+ * Turn a packed DIB from a BITMAP resource back into a .bmp file.
+ */
+fn add_bitmap_file_header(dib: &[u8]) -> Result<Vec<u8>, Error> {
+    let field = |offset: usize| -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(dib.get(offset..offset + 4).ok_or_else(|| anyhow!("Truncated bitmap header."))?.try_into()?))
+    };
+    let header_size = field(0)?;
+    let bit_count = u16::from_le_bytes(dib.get(14..16).ok_or_else(|| anyhow!("Truncated bitmap header."))?.try_into()?);
+    let compression = field(16)?;
+    let colors_used = if header_size >= 40 { field(32)? } else { 0 };
+
+    let palette_entries = if colors_used != 0 {
+        colors_used
+    }
+    else if bit_count <= 8 {
+        1 << bit_count
+    }
+    else {
+        0
+    };
+    // BI_BITFIELDS masks follow a BITMAPINFOHEADER.
+    let masks = if compression == 3 && header_size == 40 { 12 } else { 0 };
+    let bits_offset = BITMAP_FILE_HEADER_SIZE as u32 + header_size + masks + palette_entries * 4;
+
+    let mut file = Vec::with_capacity(BITMAP_FILE_HEADER_SIZE + dib.len());
+    file.extend_from_slice(b"BM");
+    file.extend_from_slice(&((BITMAP_FILE_HEADER_SIZE + dib.len()) as u32).to_le_bytes());
+    file.extend_from_slice(&[0, 0, 0, 0]);
+    file.extend_from_slice(&bits_offset.to_le_bytes());
+    file.extend_from_slice(dib);
+    Ok(file)
+}
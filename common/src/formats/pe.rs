@@ -0,0 +1,465 @@
+/*
+ * Resource-only portable executable images.
+ *
+ * A compiled visual style is a DLL that contains nothing but resources. This module writes such
+ * images from a resource table and reads the resource table back out of existing ones.
+ *
+ * The writer is fully deterministic: the resource directory is emitted in the canonical order
+ * required by the PE specification, every directory timestamp is zero, and the header timestamp
+ * is whatever the caller provides.
+ */
+
+use std::collections::BTreeMap;
+use anyhow::Error;
+use anyhow::anyhow;
+
+/**
+ * A resource type or name. Named entries sort before numbered entries, which is the order the
+ * PE specification requires within a resource directory.
+ */
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ResourceName {
+    Name(String),
+    Id(u16),
+}
+
+impl ResourceName {
+    /**
+     * Create a named resource identifier. The resource compiler always stores names in upper
+     * case, and FindResource looks them up that way as well.
+     */
+    pub fn name(name: &str) -> Self {
+        ResourceName::Name(name.to_uppercase())
+    }
+}
+
+impl std::fmt::Display for ResourceName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceName::Name(name) => write!(f, "{}", name),
+            ResourceName::Id(id) => write!(f, "#{}", id),
+        }
+    }
+}
+
+pub const RT_BITMAP: u16 = 2;
+pub const RT_STRING: u16 = 6;
+pub const RT_VERSION: u16 = 16;
+
+pub const LANG_NEUTRAL: u16 = 0;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceTable {
+    pub entries: BTreeMap<(ResourceName, ResourceName, u16), Vec<u8>>,
+}
+
+impl ResourceTable {
+    pub fn insert(&mut self, ty: ResourceName, name: ResourceName, language: u16, data: Vec<u8>) {
+        self.entries.insert((ty, name, language), data);
+    }
+
+    /**
+     * Find a resource regardless of its language, preferring the lowest language identifier.
+     */
+    pub fn find(&self, ty: &ResourceName, name: &ResourceName) -> Option<&[u8]> {
+        self.entries.iter()
+            .find(|((entry_ty, entry_name, _), _)| entry_ty == ty && entry_name == name)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    /**
+     * Iterate over all of the resources of one type.
+     */
+    pub fn of_type<'a>(&'a self, ty: &'a ResourceName) -> impl Iterator<Item = (&'a ResourceName, &'a [u8])> + 'a {
+        self.entries.iter()
+            .filter(move |((entry_ty, _, _), _)| entry_ty == ty)
+            .map(|((_, name, _), data)| (name, data.as_slice()))
+    }
+}
+
+const FILE_ALIGNMENT: u32 = 0x200;
+const SECTION_ALIGNMENT: u32 = 0x1000;
+const DOS_HEADER_SIZE: u32 = 0x40;
+const OPTIONAL_HEADER_SIZE: u32 = 240;
+const HEADERS_SIZE: u32 = DOS_HEADER_SIZE + 4 + 20 + OPTIONAL_HEADER_SIZE + 40;
+const CHECKSUM_OFFSET: usize = DOS_HEADER_SIZE as usize + 4 + 20 + 64;
+
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+
+fn align(value: u32, alignment: u32) -> u32 {
+    value.div_ceil(alignment) * alignment
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn pad_to(buffer: &mut Vec<u8>, alignment: usize) {
+    buffer.resize(buffer.len().div_ceil(alignment) * alignment, 0);
+}
+
+/**
+ * Lay out the .rsrc section. The layout is: every directory table (types, then names, then
+ * languages), the data entries, the name strings, and finally the data itself.
+ */
+fn build_resource_section(table: &ResourceTable, section_rva: u32) -> Vec<u8> {
+    let mut tree: BTreeMap<&ResourceName, BTreeMap<&ResourceName, BTreeMap<u16, &Vec<u8>>>> = BTreeMap::new();
+    for ((ty, name, language), data) in &table.entries {
+        tree.entry(ty).or_default().entry(name).or_default().insert(*language, data);
+    }
+
+    let directory_size = |count: usize| 16 + 8 * count as u32;
+
+    // First pass: compute the offsets of everything.
+    let mut offset = directory_size(tree.len());
+    let mut name_directory_offsets = Vec::new();
+    for names in tree.values() {
+        name_directory_offsets.push(offset);
+        offset += directory_size(names.len());
+    }
+
+    let mut language_directory_offsets = Vec::new();
+    for names in tree.values() {
+        for languages in names.values() {
+            language_directory_offsets.push(offset);
+            offset += directory_size(languages.len());
+        }
+    }
+
+    let data_entries_offset = offset;
+    let data_entry_count = table.entries.len() as u32;
+    offset += 16 * data_entry_count;
+
+    let mut string_offsets: BTreeMap<&str, u32> = BTreeMap::new();
+    for (ty, names) in &tree {
+        for name in std::iter::once(*ty).chain(names.keys().copied()) {
+            if let ResourceName::Name(name) = name {
+                string_offsets.insert(name, 0);
+            }
+        }
+    }
+    for (name, string_offset) in string_offsets.iter_mut() {
+        *string_offset = offset;
+        offset += 2 + 2 * name.encode_utf16().count() as u32;
+    }
+
+    let mut data_offsets = Vec::new();
+    for data in table.entries.values() {
+        offset = align(offset, 8);
+        data_offsets.push(offset);
+        offset += data.len() as u32;
+    }
+
+    // Second pass: emit everything in the same order.
+    let mut section = Vec::with_capacity(offset as usize);
+    let put_directory = |section: &mut Vec<u8>, keys: Vec<&ResourceName>| {
+        let named = keys.iter().filter(|key| matches!(key, ResourceName::Name(_))).count();
+        put_u32(section, 0); // Characteristics
+        put_u32(section, 0); // TimeDateStamp
+        put_u16(section, 0); // MajorVersion
+        put_u16(section, 0); // MinorVersion
+        put_u16(section, named as u16);
+        put_u16(section, (keys.len() - named) as u16);
+    };
+    let entry_name = |key: &ResourceName| match key {
+        ResourceName::Name(name) => 0x8000_0000 | string_offsets[name.as_str()],
+        ResourceName::Id(id) => *id as u32,
+    };
+
+    put_directory(&mut section, tree.keys().copied().collect());
+    for (ty, directory_offset) in tree.keys().zip(&name_directory_offsets) {
+        put_u32(&mut section, entry_name(ty));
+        put_u32(&mut section, 0x8000_0000 | directory_offset);
+    }
+
+    let mut language_directories = language_directory_offsets.iter();
+    for names in tree.values() {
+        put_directory(&mut section, names.keys().copied().collect());
+        for name in names.keys() {
+            put_u32(&mut section, entry_name(name));
+            put_u32(&mut section, 0x8000_0000 | language_directories.next().unwrap());
+        }
+    }
+
+    let mut data_entry_index = 0;
+    for names in tree.values() {
+        for languages in names.values() {
+            put_u32(&mut section, 0);
+            put_u32(&mut section, 0);
+            put_u16(&mut section, 0);
+            put_u16(&mut section, 0);
+            put_u16(&mut section, 0);
+            put_u16(&mut section, languages.len() as u16);
+            for language in languages.keys() {
+                put_u32(&mut section, *language as u32);
+                put_u32(&mut section, data_entries_offset + 16 * data_entry_index);
+                data_entry_index += 1;
+            }
+        }
+    }
+
+    for (data, data_offset) in table.entries.values().zip(&data_offsets) {
+        put_u32(&mut section, section_rva + data_offset);
+        put_u32(&mut section, data.len() as u32);
+        put_u32(&mut section, 0); // CodePage
+        put_u32(&mut section, 0); // Reserved
+    }
+
+    for name in string_offsets.keys() {
+        let units: Vec<u16> = name.encode_utf16().collect();
+        put_u16(&mut section, units.len() as u16);
+        for unit in units {
+            put_u16(&mut section, unit);
+        }
+    }
+
+    for data in table.entries.values() {
+        pad_to(&mut section, 8);
+        section.extend_from_slice(data);
+    }
+
+    section
+}
+
+/**
+ * Compute the PE image checksum, as done by CheckSumMappedFile.
+ */
+fn compute_checksum(image: &[u8]) -> u32 {
+    let mut sum: u64 = 0;
+    for (i, chunk) in image.chunks(2).enumerate() {
+        if i * 2 == CHECKSUM_OFFSET || i * 2 == CHECKSUM_OFFSET + 2 {
+            continue;
+        }
+        let word = chunk[0] as u64 | (*chunk.get(1).unwrap_or(&0) as u64) << 8;
+        sum += word;
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    sum = (sum & 0xFFFF) + (sum >> 16);
+    (sum as u32).wrapping_add(image.len() as u32)
+}
+
+/**
+ * Write a 64-bit resource-only DLL containing the given resources.
+ */
+pub fn write_image(table: &ResourceTable, timestamp: u32) -> Vec<u8> {
+    let section_rva = SECTION_ALIGNMENT;
+    let section_data = build_resource_section(table, section_rva);
+    let section_raw_size = align(section_data.len() as u32, FILE_ALIGNMENT);
+    let headers_size = align(HEADERS_SIZE, FILE_ALIGNMENT);
+    let image_size = align(section_rva + section_data.len().max(1) as u32, SECTION_ALIGNMENT);
+
+    let mut image = Vec::with_capacity((headers_size + section_raw_size) as usize);
+
+    // DOS header. There is no DOS stub program; e_lfanew points straight past the header.
+    put_u16(&mut image, 0x5A4D);
+    image.resize(0x3C, 0);
+    put_u32(&mut image, DOS_HEADER_SIZE);
+
+    // PE signature and COFF file header:
+    image.extend_from_slice(b"PE\0\0");
+    put_u16(&mut image, IMAGE_FILE_MACHINE_AMD64);
+    put_u16(&mut image, 1); // NumberOfSections
+    put_u32(&mut image, timestamp);
+    put_u32(&mut image, 0); // PointerToSymbolTable
+    put_u32(&mut image, 0); // NumberOfSymbols
+    put_u16(&mut image, OPTIONAL_HEADER_SIZE as u16);
+    put_u16(&mut image, 0x2022); // EXECUTABLE_IMAGE | LARGE_ADDRESS_AWARE | DLL
+
+    // PE32+ optional header:
+    put_u16(&mut image, 0x20B);
+    image.push(14); // MajorLinkerVersion
+    image.push(0); // MinorLinkerVersion
+    put_u32(&mut image, 0); // SizeOfCode
+    put_u32(&mut image, section_raw_size); // SizeOfInitializedData
+    put_u32(&mut image, 0); // SizeOfUninitializedData
+    put_u32(&mut image, 0); // AddressOfEntryPoint
+    put_u32(&mut image, 0); // BaseOfCode
+    put_u64(&mut image, 0x1_8000_0000); // ImageBase
+    put_u32(&mut image, SECTION_ALIGNMENT);
+    put_u32(&mut image, FILE_ALIGNMENT);
+    put_u16(&mut image, 6); // MajorOperatingSystemVersion
+    put_u16(&mut image, 0);
+    put_u16(&mut image, 0); // MajorImageVersion
+    put_u16(&mut image, 0);
+    put_u16(&mut image, 6); // MajorSubsystemVersion
+    put_u16(&mut image, 0);
+    put_u32(&mut image, 0); // Win32VersionValue
+    put_u32(&mut image, image_size);
+    put_u32(&mut image, headers_size);
+    put_u32(&mut image, 0); // CheckSum, filled in below.
+    put_u16(&mut image, 2); // IMAGE_SUBSYSTEM_WINDOWS_GUI
+    put_u16(&mut image, 0x0140); // DYNAMIC_BASE | NX_COMPAT
+    put_u64(&mut image, 0x10_0000); // SizeOfStackReserve
+    put_u64(&mut image, 0x1000); // SizeOfStackCommit
+    put_u64(&mut image, 0x10_0000); // SizeOfHeapReserve
+    put_u64(&mut image, 0x1000); // SizeOfHeapCommit
+    put_u32(&mut image, 0); // LoaderFlags
+    put_u32(&mut image, 16); // NumberOfRvaAndSizes
+    for i in 0..16 {
+        if i == IMAGE_DIRECTORY_ENTRY_RESOURCE {
+            put_u32(&mut image, section_rva);
+            put_u32(&mut image, section_data.len() as u32);
+        }
+        else {
+            put_u64(&mut image, 0);
+        }
+    }
+
+    // Section table:
+    image.extend_from_slice(b".rsrc\0\0\0");
+    put_u32(&mut image, section_data.len() as u32); // VirtualSize
+    put_u32(&mut image, section_rva);
+    put_u32(&mut image, section_raw_size);
+    put_u32(&mut image, headers_size); // PointerToRawData
+    put_u32(&mut image, 0); // PointerToRelocations
+    put_u32(&mut image, 0); // PointerToLinenumbers
+    put_u16(&mut image, 0); // NumberOfRelocations
+    put_u16(&mut image, 0); // NumberOfLinenumbers
+    put_u32(&mut image, 0x4000_0040); // CNT_INITIALIZED_DATA | MEM_READ
+
+    image.resize(headers_size as usize, 0);
+    image.extend_from_slice(&section_data);
+    image.resize((headers_size + section_raw_size) as usize, 0);
+
+    let checksum = compute_checksum(&image);
+    image[CHECKSUM_OFFSET..CHECKSUM_OFFSET + 4].copy_from_slice(&checksum.to_le_bytes());
+
+    image
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn bytes(&self, offset: usize, length: usize) -> Result<&[u8], Error> {
+        offset.checked_add(length)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| anyhow!("Unexpected end of image at offset {:#x}.", offset))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.bytes(offset, 2)?.try_into()?))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.bytes(offset, 4)?.try_into()?))
+    }
+}
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_pointer: u32,
+    raw_size: u32,
+}
+
+fn rva_to_offset(sections: &[Section], rva: u32) -> Result<usize, Error> {
+    sections.iter()
+        .find(|section| rva >= section.virtual_address && rva < section.virtual_address + section.virtual_size.max(section.raw_size))
+        .map(|section| (rva - section.virtual_address + section.raw_pointer) as usize)
+        .ok_or_else(|| anyhow!("RVA {:#x} is not inside of any section.", rva))
+}
+
+/**
+ * Read the resource table from a PE image. Both PE32 and PE32+ images are supported.
+ */
+pub fn read_image(data: &[u8]) -> Result<ResourceTable, Error> {
+    let reader = Reader { data };
+    if reader.u16(0)? != 0x5A4D {
+        return Err(anyhow!("Not an executable image."));
+    }
+
+    let pe_offset = reader.u32(0x3C)? as usize;
+    if reader.bytes(pe_offset, 4)? != b"PE\0\0" {
+        return Err(anyhow!("Missing PE signature."));
+    }
+
+    let section_count = reader.u16(pe_offset + 6)? as usize;
+    let optional_header_size = reader.u16(pe_offset + 20)? as usize;
+    let optional_header = pe_offset + 24;
+    let data_directories = match reader.u16(optional_header)? {
+        0x10B => optional_header + 96,
+        0x20B => optional_header + 112,
+        magic => return Err(anyhow!("Unknown optional header magic {:#x}.", magic)),
+    };
+
+    let resource_rva = reader.u32(data_directories + 8 * IMAGE_DIRECTORY_ENTRY_RESOURCE)?;
+    let mut table = ResourceTable::default();
+    if resource_rva == 0 {
+        return Ok(table);
+    }
+
+    let section_table = optional_header + optional_header_size;
+    let sections = (0..section_count)
+        .map(|i| {
+            let header = section_table + 40 * i;
+            Ok(Section {
+                virtual_size: reader.u32(header + 8)?,
+                virtual_address: reader.u32(header + 12)?,
+                raw_size: reader.u32(header + 16)?,
+                raw_pointer: reader.u32(header + 20)?,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let root = rva_to_offset(&sections, resource_rva)?;
+
+    let read_name = |entry: usize| -> Result<ResourceName, Error> {
+        let name = reader.u32(entry)?;
+        if name & 0x8000_0000 != 0 {
+            let string = root + (name & 0x7FFF_FFFF) as usize;
+            let length = reader.u16(string)? as usize;
+            let units = reader.bytes(string + 2, length * 2)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect::<Vec<_>>();
+            Ok(ResourceName::Name(String::from_utf16_lossy(&units)))
+        }
+        else {
+            Ok(ResourceName::Id(name as u16))
+        }
+    };
+
+    let read_directory = |offset: usize| -> Result<Vec<(usize, u32)>, Error> {
+        let count = reader.u16(offset + 12)? as usize + reader.u16(offset + 14)? as usize;
+        (0..count)
+            .map(|i| {
+                let entry = offset + 16 + 8 * i;
+                Ok((entry, reader.u32(entry + 4)?))
+            })
+            .collect()
+    };
+
+    let subdirectory = |target: u32| -> Result<usize, Error> {
+        if target & 0x8000_0000 == 0 {
+            return Err(anyhow!("Expected a resource subdirectory."));
+        }
+        Ok(root + (target & 0x7FFF_FFFF) as usize)
+    };
+
+    for (type_entry, type_target) in read_directory(root)? {
+        let ty = read_name(type_entry)?;
+        for (name_entry, name_target) in read_directory(subdirectory(type_target)?)? {
+            let name = read_name(name_entry)?;
+            for (language_entry, data_entry) in read_directory(subdirectory(name_target)?)? {
+                let language = reader.u32(language_entry)? as u16;
+                let data_entry = root + data_entry as usize;
+                let offset = rva_to_offset(&sections, reader.u32(data_entry)?)?;
+                let size = reader.u32(data_entry + 4)? as usize;
+                table.insert(ty.clone(), name.clone(), language, reader.bytes(offset, size)?.to_vec());
+            }
+        }
+    }
+
+    Ok(table)
+}
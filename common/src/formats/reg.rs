@@ -0,0 +1,72 @@
+/*
+ * Registry (.reg) color schemes.
+ *
+ * Classic color schemes are often shared as registry exports of HKCU\Control Panel\Colors:
+ *
+ *     Windows Registry Editor Version 5.00
+ *
+ *     [HKEY_CURRENT_USER\Control Panel\Colors]
+ *     "ButtonFace"="212 208 200"
+ *
+ * Both the version 5 format (UTF-16) and the older REGEDIT4 format (ANSI) are read. Files are
+ * written in the version 5 format, like regedit does.
+ */
+
+use anyhow::Error;
+use anyhow::anyhow;
+
+use crate::formats::ini::{Document, Encoding, Line};
+use crate::formats::theme_file;
+use crate::theme::system_colors::{self, ColorScheme};
+
+pub const COLORS_KEY: &str = "HKEY_CURRENT_USER\\Control Panel\\Colors";
+
+const HEADER_V5: &str = "Windows Registry Editor Version 5.00";
+const HEADER_V4: &str = "REGEDIT4";
+
+fn unquote(text: &str) -> Option<&str> {
+    text.trim().strip_prefix('"')?.strip_suffix('"')
+}
+
+/**
+ * Read the system colors from a registry export. Values that are not system colors, and keys
+ * other than HKCU\Control Panel\Colors, are ignored.
+ */
+pub fn read_color_scheme(bytes: &[u8]) -> Result<ColorScheme, Error> {
+    let document = Document::decode(bytes)?;
+    let header = document.preamble.iter()
+        .find_map(|line| match line {
+            Line::Other(text) if !text.trim().is_empty() => Some(text.trim()),
+            _ => None,
+        });
+    if header != Some(HEADER_V5) && header != Some(HEADER_V4) {
+        return Err(anyhow!("Not a registry file."));
+    }
+
+    let section = document.section(COLORS_KEY).ok_or_else(|| anyhow!("The file does not contain [{}].", COLORS_KEY))?;
+    let mut scheme = ColorScheme::default();
+    for (name, value) in section.entries() {
+        let (Some(name), Some(value)) = (unquote(name), unquote(value)) else {
+            continue;
+        };
+        let Some(info) = system_colors::find_system_color(name) else {
+            continue;
+        };
+        let color = theme_file::parse_color(value).ok_or_else(|| anyhow!("Invalid color \"{}\" for {}.", value, name))?;
+        scheme.colors[info.index] = Some(color);
+    }
+    Ok(scheme)
+}
+
+/**
+ * Write the colors that are set in a scheme as a registry export.
+ */
+pub fn write_color_scheme(scheme: &ColorScheme) -> Vec<u8> {
+    let mut document = Document::parse(&format!("{}\r\n\r\n", HEADER_V5));
+    document.encoding = Encoding::Utf16Le;
+    let section = document.section_mut(COLORS_KEY);
+    for (info, color) in scheme.iter() {
+        section.set(&format!("\"{}\"", info.registry_name), &format!("\"{}\"", color));
+    }
+    document.encode()
+}
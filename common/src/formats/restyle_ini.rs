@@ -0,0 +1,429 @@
+/*
+ * Restyle theme source format.
+ *
+ * Restyle projects describe a visual style with the same INI dialect as Microsoft's packthem
+ * tool. Each section names a class, and optionally a part and a state:
+ *
+ *     [Explorer::ListView.Item(Hot)]
+ *     ImageFile = Images\ListViewItem.png
+ *     SizingMargins = 3, 3, 3, 3
+ *
+ * Property values are written as text and are interpreted according to the property schema.
+ * Properties that are not in the schema are kept as plain strings so that they survive a
+ * round trip.
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use crate::formats::ini::{self, Document};
+use crate::theme::*;
+use crate::theme::schema::{self, PropertyType};
+
+/**
+ * Decode the raw bytes of an INI file. Restyle and packthem both accept UTF-16 sources with a
+ * byte order mark, and UTF-8 (with or without one). This is the same decoding that
+ * update_project edits the file with.
+ */
+pub fn decode(bytes: &[u8]) -> Result<String, Error> {
+    ini::decode_text(bytes).map(|(text, _)| text)
+}
+
+pub fn read_file(path: &Path) -> Result<Theme, Error> {
+    let bytes = fs::read(path)
+        .with_context(|| anyhow!("Failed to read \"{}\"", path.display()))?;
+    parse(&decode(&bytes)?)
+        .with_context(|| anyhow!("Failed to parse \"{}\"", path.display()))
+}
+
+pub fn write_file(theme: &Theme, path: &Path) -> Result<(), Error> {
+    fs::write(path, write(theme))
+        .with_context(|| anyhow!("Failed to write \"{}\"", path.display()))
+}
+
+/**
+ * Convert an image path from a theme source (which uses backslashes) to a native path.
+ */
+pub fn native_image_path(root: &Path, path: &str) -> PathBuf {
+    path.split(['\\', '/']).filter(|component| !component.is_empty()).fold(root.to_path_buf(), |result, component| result.join(component))
+}
+
+/**
+ * Read a Restyle project: the theme source, and every image it references. Image paths are
+ * relative to the directory of the source file.
+ */
+pub fn read_project(path: &Path) -> Result<Theme, Error> {
+    let mut theme = read_file(path)?;
+    let root = path.parent().unwrap_or(Path::new("."));
+
+    let image_paths: Vec<String> = theme.states()
+        .flat_map(|(_, _, state)| state.properties.iter())
+        .filter_map(|property| match &property.value {
+            PropertyValue::FileName(image) => Some(image.clone()),
+            _ => None,
+        })
+        .collect();
+
+    for image in image_paths {
+        if theme.image(&image).is_some() {
+            continue;
+        }
+        let image_path = native_image_path(root, &image);
+        let data = fs::read(&image_path)
+            .with_context(|| anyhow!("Failed to read image \"{}\"", image_path.display()))?;
+        theme.images.insert(image, data);
+    }
+
+    Ok(theme)
+}
+
+/**
+ * Write a Restyle project: the theme source, and every image that it refers to next to it.
+ * Images that nothing refers to are left out, as they are when compiling.
+ */
+pub fn write_project(theme: &Theme, path: &Path) -> Result<(), Error> {
+    let root = path.parent().unwrap_or(Path::new("."));
+    let referenced = theme.referenced_images();
+    for (image, data) in &theme.images {
+        if !referenced.contains(&normalize_image_path(image)) {
+            continue;
+        }
+        let image_path = native_image_path(root, image);
+        if let Some(parent) = image_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&image_path, data)
+            .with_context(|| anyhow!("Failed to write image \"{}\"", image_path.display()))?;
+    }
+    write_file(theme, path)
+}
+
+/**
+ * Save a theme over the Restyle project that it was read from, changing only what is different.
+ * Properties that changed are rewritten in place, new ones are added to their section (or to a
+ * new section at the end), and removed ones are taken out, so that the source keeps its
+ * comments, formatting, encoding and line endings. Only images that changed are written, and
+ * images that the theme no longer has (i.e. duplicates that were merged) are deleted.
+ */
+pub fn update_project(theme: &Theme, path: &Path) -> Result<(), Error> {
+    let before = read_project(path)?;
+    let bytes = fs::read(path).with_context(|| anyhow!("Failed to read \"{}\"", path.display()))?;
+    let mut document = Document::decode(&bytes).with_context(|| anyhow!("Failed to parse \"{}\"", path.display()))?;
+
+    let section_names: Vec<Option<SectionName>> = document.sections.iter()
+        .map(|section| parse_section_name(&section.name).ok())
+        .collect();
+    let matches = |index: usize, class: &str, part: Option<&str>, state: Option<&str>| {
+        let same = |a: Option<&str>, b: Option<&str>| match (a, b) {
+            (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        section_names[index].as_ref().is_some_and(|name| {
+            name.class.eq_ignore_ascii_case(class) && same(name.part.as_deref(), part) && same(name.state.as_deref(), state)
+        })
+    };
+
+    // States that are gone lose their sections.
+    let mut removed_sections = Vec::new();
+    for (index, name) in section_names.iter().enumerate() {
+        if let Some(name) = name {
+            if theme.state(&name.class, name.part.as_deref(), name.state.as_deref()).is_none() {
+                removed_sections.push(index);
+            }
+        }
+    }
+
+    for (class, part, new_state) in theme.states() {
+        let (class, part, state) = (class.name.as_str(), part.name.as_deref(), new_state.name.as_deref());
+        let old = before.state(class, part, state);
+        let mut sections: Vec<usize> = (0..section_names.len()).filter(|index| matches(*index, class, part, state)).collect();
+
+        for property in &new_state.properties {
+            if old.and_then(|old| old.property(&property.name)) == Some(&property.value) {
+                continue;
+            }
+            if sections.is_empty() {
+                document.section_mut(&format_section_name(class, part, state));
+                sections.push(document.sections.len() - 1);
+            }
+            // A property is changed in the section that has it, in case a state has more than one.
+            let index = sections.iter().copied()
+                .find(|index| document.sections[*index].get(&property.name).is_some())
+                .unwrap_or(sections[0]);
+            document.sections[index].set(&property.name, &format_value(&property.name, &property.value));
+        }
+        if sections.is_empty() && old.is_none() {
+            document.section_mut(&format_section_name(class, part, state));
+        }
+
+        for property in old.map(|old| old.properties.as_slice()).unwrap_or_default() {
+            if new_state.property(&property.name).is_none() {
+                for index in &sections {
+                    document.sections[*index].remove(&property.name);
+                }
+            }
+        }
+    }
+
+    for index in removed_sections.into_iter().rev() {
+        document.sections.remove(index);
+    }
+
+    let root = path.parent().unwrap_or(Path::new("."));
+    let referenced = theme.referenced_images();
+    for (image, data) in &theme.images {
+        if !referenced.contains(&normalize_image_path(image)) || before.image(image) == Some(data.as_slice()) {
+            continue;
+        }
+        let image_path = native_image_path(root, image);
+        if let Some(parent) = image_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&image_path, data)
+            .with_context(|| anyhow!("Failed to write image \"{}\"", image_path.display()))?;
+    }
+    for image in before.images.keys() {
+        if theme.image(image).is_none() {
+            let image_path = native_image_path(root, image);
+            fs::remove_file(&image_path)
+                .with_context(|| anyhow!("Failed to delete image \"{}\"", image_path.display()))?;
+        }
+    }
+
+    let updated = document.encode();
+    if updated != bytes {
+        fs::write(path, updated).with_context(|| anyhow!("Failed to write \"{}\"", path.display()))?;
+    }
+    Ok(())
+}
+
+/**
+ * A parsed section header. The class name keeps its application prefix.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub struct SectionName {
+    pub class: String,
+    pub part: Option<String>,
+    pub state: Option<String>,
+}
+
+pub fn parse_section_name(text: &str) -> Result<SectionName, Error> {
+    let text = text.trim();
+    let (rest, state) = match text.strip_suffix(')') {
+        Some(without_paren) => {
+            let open = without_paren.rfind('(').ok_or_else(|| anyhow!("Unbalanced parenthesis in section \"{}\"", text))?;
+            (&without_paren[..open], Some(without_paren[open + 1..].trim().to_owned()))
+        }
+        None => (text, None),
+    };
+
+    // The part separator is the first dot after the application prefix, if there is one.
+    let class_start = rest.rfind("::").map(|i| i + 2).unwrap_or(0);
+    let (class, part) = match rest[class_start..].find('.') {
+        Some(dot) => (&rest[..class_start + dot], Some(rest[class_start + dot + 1..].trim().to_owned())),
+        None => (rest, None),
+    };
+
+    let class = class.trim();
+    if class.is_empty() || part.as_deref() == Some("") || state.as_deref() == Some("") {
+        return Err(anyhow!("Invalid section name \"{}\"", text));
+    }
+
+    Ok(SectionName { class: class.to_owned(), part, state })
+}
+
+pub fn format_section_name(class: &str, part: Option<&str>, state: Option<&str>) -> String {
+    let mut result = class.to_owned();
+    if let Some(part) = part {
+        result.push('.');
+        result.push_str(part);
+    }
+    if let Some(state) = state {
+        result.push('(');
+        result.push_str(state);
+        result.push(')');
+    }
+    result
+}
+
+pub fn parse(source: &str) -> Result<Theme, Error> {
+    let mut theme = Theme::default();
+    let mut current: Option<SectionName> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header.strip_suffix(']')
+                .ok_or_else(|| anyhow!("Line {}: Unterminated section header.", line_number))?;
+            let section = parse_section_name(header)
+                .with_context(|| anyhow!("Line {}: Bad section header.", line_number))?;
+
+            // Make sure that empty sections survive a round trip as well.
+            theme.state_mut(&section.class, section.part.as_deref(), section.state.as_deref());
+            current = Some(section);
+            continue;
+        }
+
+        let section = current.as_ref()
+            .ok_or_else(|| anyhow!("Line {}: Property outside of a section.", line_number))?;
+        let (name, value) = line.split_once('=')
+            .ok_or_else(|| anyhow!("Line {}: Expected \"Name = Value\".", line_number))?;
+        let name = name.trim();
+        let value = parse_value(name, value.trim())
+            .with_context(|| anyhow!("Line {}: Bad value for property \"{}\".", line_number, name))?;
+
+        theme.state_mut(&section.class, section.part.as_deref(), section.state.as_deref())
+            .properties
+            .push(Property::new(name, value));
+    }
+
+    Ok(theme)
+}
+
+pub fn write(theme: &Theme) -> String {
+    let mut result = String::new();
+
+    for (class, part, state) in theme.states() {
+        if !result.is_empty() {
+            result.push('\n');
+        }
+
+        result.push('[');
+        result.push_str(&format_section_name(&class.name, part.name.as_deref(), state.name.as_deref()));
+        result.push_str("]\n");
+
+        for property in &state.properties {
+            result.push_str(&format!("{} = {}\n", property.name, format_value(&property.name, &property.value)));
+        }
+    }
+
+    result
+}
+
+fn split_list(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| c == ',' || c.is_whitespace()).filter(|item| !item.is_empty())
+}
+
+fn parse_ints<const N: usize>(text: &str) -> Result<[i32; N], Error> {
+    let values = split_list(text)
+        .map(|item| item.parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()?;
+    values.try_into().map_err(|values: Vec<i32>| anyhow!("Expected {} numbers, found {}.", N, values.len()))
+}
+
+fn parse_bool(text: &str) -> Result<bool, Error> {
+    match text.to_ascii_lowercase().as_str() {
+        "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(anyhow!("Expected True or False.")),
+    }
+}
+
+fn parse_font(text: &str) -> Result<Font, Error> {
+    let mut items = text.split(',').map(str::trim);
+    let face = items.next().filter(|face| !face.is_empty()).ok_or_else(|| anyhow!("Missing font face."))?;
+    let size = items.next().ok_or_else(|| anyhow!("Missing font size."))?.parse()?;
+
+    let mut font = Font { face: face.to_owned(), size, weight: FW_NORMAL, ..Default::default() };
+    for item in items {
+        let item = item.to_ascii_lowercase();
+        if let Some(weight) = item.strip_prefix("weight") {
+            font.weight = weight.trim().parse().map_err(|_| anyhow!("Bad font weight \"{}\"", weight.trim()))?;
+            continue;
+        }
+        match item.as_str() {
+            "bold" => font.weight = FW_BOLD,
+            "italic" => font.italic = true,
+            "underline" => font.underline = true,
+            "strikeout" => font.strikeout = true,
+            other => return Err(anyhow!("Unknown font attribute \"{}\"", other)),
+        }
+    }
+    Ok(font)
+}
+
+/**
+ * Parse a property value according to its type in the schema.
+ */
+pub fn parse_value(name: &str, text: &str) -> Result<PropertyValue, Error> {
+    let Some(info) = schema::find_property(name) else {
+        return Ok(PropertyValue::String(text.to_owned()));
+    };
+
+    Ok(match info.ty {
+        PropertyType::Enum => {
+            let info = schema::find_enum(name).ok_or_else(|| anyhow!("No enumeration for property."))?;
+            PropertyValue::Enum(match info.value_of(text) {
+                Some(value) => value,
+                None => text.parse().map_err(|_| anyhow!("Unknown value \"{}\"", text))?,
+            })
+        }
+        PropertyType::Int => PropertyValue::Int(text.parse()?),
+        PropertyType::Size => PropertyValue::Size(text.parse()?),
+        PropertyType::Bool => PropertyValue::Bool(parse_bool(text)?),
+        PropertyType::Color => {
+            let [r, g, b] = parse_ints::<3>(text)?;
+            let channel = |value: i32| u8::try_from(value).map_err(|_| anyhow!("Color channel {} out of range.", value));
+            PropertyValue::Color(Color::new(channel(r)?, channel(g)?, channel(b)?))
+        }
+        PropertyType::Margins => {
+            let [left, right, top, bottom] = parse_ints::<4>(text)?;
+            PropertyValue::Margins(Margins { left, right, top, bottom })
+        }
+        PropertyType::Rect => {
+            let [left, top, right, bottom] = parse_ints::<4>(text)?;
+            PropertyValue::Rect(Rect { left, top, right, bottom })
+        }
+        PropertyType::Position => {
+            let [x, y] = parse_ints::<2>(text)?;
+            PropertyValue::Position(Point { x, y })
+        }
+        PropertyType::Font => PropertyValue::Font(parse_font(text)?),
+        PropertyType::IntList => PropertyValue::IntList(
+            split_list(text).map(|item| item.parse()).collect::<Result<_, _>>()?
+        ),
+        PropertyType::Float => PropertyValue::Float(text.parse()?),
+        PropertyType::FloatList => PropertyValue::FloatList(
+            split_list(text).map(|item| item.parse()).collect::<Result<_, _>>()?
+        ),
+        // AtlasImage, whose PNG is packed as a STREAM resource rather than an IMAGE.
+        PropertyType::FileName | PropertyType::DiskStream => PropertyValue::FileName(text.to_owned()),
+        _ => PropertyValue::String(text.to_owned()),
+    })
+}
+
+fn join_list<T: ToString>(values: &[T]) -> String {
+    values.iter().map(T::to_string).collect::<Vec<_>>().join(", ")
+}
+
+/**
+ * Format a property value the way it is written in theme sources.
+ */
+pub fn format_value(name: &str, value: &PropertyValue) -> String {
+    match value {
+        PropertyValue::Enum(value) => schema::find_enum(name)
+            .and_then(|info| info.name_of(*value))
+            .map(str::to_owned)
+            .unwrap_or_else(|| value.to_string()),
+        PropertyValue::String(text) | PropertyValue::FileName(text) => text.clone(),
+        PropertyValue::Int(value) | PropertyValue::Size(value) => value.to_string(),
+        PropertyValue::Bool(value) => if *value { "True" } else { "False" }.to_owned(),
+        PropertyValue::Color(color) => color.to_string(),
+        PropertyValue::Margins(margins) => margins.to_string(),
+        PropertyValue::Position(point) => point.to_string(),
+        PropertyValue::Rect(rect) => rect.to_string(),
+        PropertyValue::Font(font) => font.to_string(),
+        PropertyValue::IntList(values) => join_list(values),
+        PropertyValue::Float(value) => value.to_string(),
+        PropertyValue::FloatList(values) => join_list(values),
+    }
+}
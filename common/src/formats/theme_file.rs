@@ -0,0 +1,345 @@
+/*
+ * Windows .theme files.
+ *
+ * A .theme file ties a visual style to the rest of a desktop theme: system colors, wallpaper,
+ * cursors, sounds and the screen saver. It is an INI file with well-known sections:
+ *
+ *     [Theme]                      DisplayName, BrandImage, ...
+ *     [VisualStyles]               Path, ColorStyle, Size, ColorizationColor, ...
+ *     [Control Panel\Colors]       ActiveTitle=153 180 209, ...
+ *     [Control Panel\Desktop]      Wallpaper, TileWallpaper, WallpaperStyle, ...
+ *     [Control Panel\Cursors]      Arrow, Help, AppStarting, ...
+ *     [AppEvents\Schemes\Apps\.Default\<Event>\.Current]   DefaultValue
+ *     [MasterThemeSelector]        MTSM=DABJDKT
+ *
+ * ThemeFile wraps a lossless INI document, so anything that it does not know about (comments,
+ * unknown sections and keys, formatting) is written back exactly as it was read.
+ */
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use crate::formats::ini::{Document, Encoding};
+use crate::formats::restyle_ini;
+use crate::theme::{Color, PropertyValue, Theme};
+use crate::theme::system_colors::{self, ColorScheme};
+
+pub const SECTION_THEME: &str = "Theme";
+pub const SECTION_VISUAL_STYLES: &str = "VisualStyles";
+pub const SECTION_COLORS: &str = "Control Panel\\Colors";
+pub const SECTION_DESKTOP: &str = "Control Panel\\Desktop";
+pub const SECTION_CURSORS: &str = "Control Panel\\Cursors";
+pub const SECTION_MASTER_THEME_SELECTOR: &str = "MasterThemeSelector";
+pub const SOUND_SECTION_PREFIX: &str = "AppEvents\\Schemes\\Apps\\";
+
+/**
+ * Windows refuses to load a .theme file without this magic value.
+ */
+pub const MTSM_MAGIC: &str = "DABJDKT";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VisualStyleSettings {
+    pub path: Option<String>,
+    pub color_style: Option<String>,
+    pub size: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DesktopSettings {
+    pub wallpaper: Option<String>,
+    pub tile_wallpaper: Option<bool>,
+    pub wallpaper_style: Option<i32>,
+}
+
+/**
+ * A sound scheme entry, i.e. [AppEvents\Schemes\Apps\.Default\SystemAsterisk\.Current].
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SoundEvent {
+    pub app: String,
+    pub event: String,
+    pub path: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThemeFile {
+    pub document: Document,
+}
+
+/**
+ * Parse a color written as "R G B", the way that both .theme files and the registry store them.
+ */
+pub fn parse_color(value: &str) -> Option<Color> {
+    let channels: Vec<u8> = value.split_whitespace().map(|channel| channel.parse().ok()).collect::<Option<_>>()?;
+    match channels[..] {
+        [r, g, b] => Some(Color::new(r, g, b)),
+        _ => None,
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim() {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    }
+}
+
+/**
+ * Expand %VARIABLE% references in a path, such as %SystemRoot% or %ResourceDir%. Variable names
+ * are case insensitive. Unknown variables are left alone.
+ */
+pub fn expand_path(path: &str, variables: &BTreeMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find('%') {
+        let Some(length) = rest[start + 1..].find('%') else {
+            break;
+        };
+        let name = &rest[start + 1..start + 1 + length];
+        result.push_str(&rest[..start]);
+        match variables.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)) {
+            Some((_, value)) => result.push_str(value),
+            None => result.push_str(&rest[start..start + length + 2]),
+        }
+        rest = &rest[start + length + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+impl ThemeFile {
+    pub fn parse(text: &str) -> Self {
+        Self { document: Document::parse(text) }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Self { document: Document::decode(bytes)? })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.document.encode()
+    }
+
+    pub fn read_file(path: &Path) -> Result<Self, Error> {
+        let bytes = fs::read(path).with_context(|| anyhow!("Failed to read \"{}\"", path.display()))?;
+        Self::decode(&bytes).with_context(|| anyhow!("Failed to parse \"{}\"", path.display()))
+    }
+
+    pub fn write_file(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, self.encode()).with_context(|| anyhow!("Failed to write \"{}\"", path.display()))
+    }
+
+    pub fn display_name(&self) -> Option<&str> {
+        self.document.get(SECTION_THEME, "DisplayName")
+    }
+
+    pub fn set_display_name(&mut self, name: &str) {
+        self.document.set(SECTION_THEME, "DisplayName", name);
+    }
+
+    pub fn visual_style(&self) -> VisualStyleSettings {
+        let get = |key| self.document.get(SECTION_VISUAL_STYLES, key).map(str::to_owned);
+        VisualStyleSettings {
+            path: get("Path"),
+            color_style: get("ColorStyle"),
+            size: get("Size"),
+        }
+    }
+
+    pub fn set_visual_style(&mut self, settings: &VisualStyleSettings) {
+        let section = self.document.section_mut(SECTION_VISUAL_STYLES);
+        for (key, value) in [("Path", &settings.path), ("ColorStyle", &settings.color_style), ("Size", &settings.size)] {
+            match value {
+                Some(value) => section.set(key, value),
+                None => { section.remove(key); }
+            }
+        }
+    }
+
+    /**
+     * The system colors from [Control Panel\Colors], keyed by their registry names (i.e.
+     * "ButtonFace"). Values that are not valid colors are skipped.
+     */
+    pub fn colors(&self) -> BTreeMap<String, Color> {
+        self.document.section(SECTION_COLORS)
+            .map(|section| section.entries()
+                .filter_map(|(key, value)| Some((key.to_owned(), parse_color(value)?)))
+                .collect())
+            .unwrap_or_default()
+    }
+
+    pub fn set_color(&mut self, name: &str, color: Color) {
+        self.document.set(SECTION_COLORS, name, &color.to_string());
+    }
+
+    /**
+     * The system colors from [Control Panel\Colors] as a scheme. Unknown names are ignored.
+     */
+    pub fn color_scheme(&self) -> ColorScheme {
+        let mut scheme = ColorScheme::default();
+        for (name, color) in self.colors() {
+            if let Some(info) = system_colors::find_system_color(&name) {
+                scheme.colors[info.index] = Some(color);
+            }
+        }
+        scheme
+    }
+
+    /**
+     * Write every color that is set in a scheme to [Control Panel\Colors], using the registry
+     * names.
+     */
+    pub fn set_color_scheme(&mut self, scheme: &ColorScheme) {
+        for (info, color) in scheme.iter() {
+            self.set_color(info.registry_name, color);
+        }
+    }
+
+    pub fn desktop(&self) -> DesktopSettings {
+        let get = |key| self.document.get(SECTION_DESKTOP, key);
+        DesktopSettings {
+            wallpaper: get("Wallpaper").map(str::to_owned),
+            tile_wallpaper: get("TileWallpaper").and_then(parse_bool),
+            wallpaper_style: get("WallpaperStyle").and_then(|value| value.trim().parse().ok()),
+        }
+    }
+
+    pub fn set_desktop(&mut self, settings: &DesktopSettings) {
+        let section = self.document.section_mut(SECTION_DESKTOP);
+        match &settings.wallpaper {
+            Some(wallpaper) => section.set("Wallpaper", wallpaper),
+            None => { section.remove("Wallpaper"); }
+        }
+        match settings.tile_wallpaper {
+            Some(tile) => section.set("TileWallpaper", if tile { "1" } else { "0" }),
+            None => { section.remove("TileWallpaper"); }
+        }
+        match settings.wallpaper_style {
+            Some(style) => section.set("WallpaperStyle", &style.to_string()),
+            None => { section.remove("WallpaperStyle"); }
+        }
+    }
+
+    /**
+     * The cursors from [Control Panel\Cursors], keyed by their registry names (i.e. "Arrow").
+     * The DefaultValue key is the scheme name, and is not a cursor.
+     */
+    pub fn cursors(&self) -> BTreeMap<String, String> {
+        self.document.section(SECTION_CURSORS)
+            .map(|section| section.entries()
+                .filter(|(key, _)| !key.eq_ignore_ascii_case("DefaultValue"))
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect())
+            .unwrap_or_default()
+    }
+
+    pub fn set_cursor(&mut self, name: &str, path: &str) {
+        self.document.set(SECTION_CURSORS, name, path);
+    }
+
+    pub fn sounds(&self) -> Vec<SoundEvent> {
+        self.document.sections.iter()
+            .filter_map(|section| {
+                let rest = section.name.get(SOUND_SECTION_PREFIX.len()..)
+                    .filter(|_| section.name[..SOUND_SECTION_PREFIX.len()].eq_ignore_ascii_case(SOUND_SECTION_PREFIX))?;
+                let mut components = rest.split('\\');
+                let app = components.next()?;
+                let event = components.next()?;
+                if !components.next()?.eq_ignore_ascii_case(".Current") {
+                    return None;
+                }
+                Some(SoundEvent {
+                    app: app.to_owned(),
+                    event: event.to_owned(),
+                    path: section.get("DefaultValue")?.to_owned(),
+                })
+            })
+            .collect()
+    }
+
+    pub fn set_sound(&mut self, app: &str, event: &str, path: &str) {
+        let section = format!("{}{}\\{}\\.Current", SOUND_SECTION_PREFIX, app, event);
+        self.document.set(&section, "DefaultValue", path);
+    }
+
+    /**
+     * The visual style path with environment variables expanded, such as %ResourceDir% (the
+     * Windows "Resources" directory) or %SystemRoot%.
+     */
+    pub fn resolve_visual_style_path(&self, variables: &BTreeMap<String, String>) -> Option<String> {
+        self.document.get(SECTION_VISUAL_STYLES, "Path").map(|path| expand_path(path, variables))
+    }
+
+    /**
+     * Create a .theme file for a visual style, using the display name from the visual style's
+     * [Documentation] class.
+     *
+     * The path should be the one that Windows will see, i.e.
+     * "%ResourceDir%\Themes\Kanaya\Kanaya.msstyles".
+     */
+    pub fn for_visual_style(theme: &Theme, msstyles_path: &str) -> Self {
+        let mut file = Self::default();
+        file.document.encoding = Encoding::Utf16Le;
+
+        let display_name = match theme.resolve_property("Documentation", None, None, "DisplayName") {
+            Some(PropertyValue::String(name)) => name.clone(),
+            Some(other) => restyle_ini::format_value("DisplayName", other),
+            None => Path::new(&msstyles_path.replace('\\', "/"))
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("Theme")
+                .to_owned(),
+        };
+
+        file.set_display_name(&display_name);
+        file.set_visual_style(&VisualStyleSettings {
+            path: Some(msstyles_path.to_owned()),
+            color_style: Some("NormalColor".to_owned()),
+            size: Some("NormalSize".to_owned()),
+        });
+        file.document.set(SECTION_MASTER_THEME_SELECTOR, "MTSM", MTSM_MAGIC);
+        file
+    }
+
+    /**
+     * Point an existing .theme file at a visual style, keeping everything else as it is.
+     */
+    pub fn link_visual_style(&mut self, msstyles_path: &str) {
+        self.document.set(SECTION_VISUAL_STYLES, "Path", msstyles_path);
+        if self.document.get(SECTION_MASTER_THEME_SELECTOR, "MTSM").is_none() {
+            self.document.set(SECTION_MASTER_THEME_SELECTOR, "MTSM", MTSM_MAGIC);
+        }
+    }
+}
+
+/**
+ * Write the .theme file that goes with a saved visual style, next to it and with the same name.
+ * An existing .theme file is updated to point at the visual style rather than replaced, so that
+ * its colors, wallpaper, sounds and comments are kept.
+ *
+ * The Windows path is the visual style path as written into the .theme file. If it is None, the
+ * usual install location "%ResourceDir%\Themes\<name>\<name>.msstyles" is used.
+ */
+pub fn write_matching_theme_file(theme: &Theme, msstyles_file: &Path, windows_path: Option<&str>) -> Result<std::path::PathBuf, Error> {
+    let theme_path = msstyles_file.with_extension("theme");
+    let name = msstyles_file.file_stem().and_then(|stem| stem.to_str()).unwrap_or("Theme");
+    let windows_path = windows_path.map(str::to_owned)
+        .unwrap_or_else(|| format!("%ResourceDir%\\Themes\\{}\\{}.msstyles", name, name));
+
+    let file = if theme_path.exists() {
+        let mut file = ThemeFile::read_file(&theme_path)?;
+        file.link_visual_style(&windows_path);
+        file
+    }
+    else {
+        ThemeFile::for_visual_style(theme, &windows_path)
+    };
+
+    file.write_file(&theme_path)?;
+    Ok(theme_path)
+}
@@ -0,0 +1,291 @@
+/*
+ * Theme packs (.themepack and .deskthemepack).
+ *
+ * A theme pack is a cabinet that holds a .theme file and everything that it refers to. Both
+ * extensions use the same format; .deskthemepack is what Windows 8 and later save. Windows
+ * installs a pack named "Name" into %LOCALAPPDATA%\Microsoft\Windows\Themes\Name, and this is
+ * the layout of the cabinet (and of that directory):
+ *
+ *     Name.theme
+ *     DesktopBackground\...    wallpapers
+ *     Cursors\...              cursors
+ *     Sounds\...               sounds
+ *     VisualStyles\...         the visual style
+ *
+ * When building a pack, the paths in the bundled .theme file are rewritten to point into the
+ * installed directory. Paths that still contain environment variables after expansion, such as
+ * "%SystemRoot%\media\Windows Ding.wav", refer to files that ship with Windows and are left alone,
+ * and so are absolute paths into the Windows directory, such as "C:\Windows\Web\Wallpaper\...".
+ * Other absolute paths (with a drive letter, or UNC paths) can only be bundled on Windows.
+ */
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use crate::formats::cab::{Cabinet, CabinetFile, Compression};
+use crate::formats::theme_file::{self, ThemeFile};
+
+pub const DESKTOP_BACKGROUND_DIR: &str = "DesktopBackground";
+pub const CURSORS_DIR: &str = "Cursors";
+pub const SOUNDS_DIR: &str = "Sounds";
+pub const VISUAL_STYLES_DIR: &str = "VisualStyles";
+
+#[derive(Default)]
+pub struct PackOptions {
+    /**
+     * The modification time of every file in the cabinet, in seconds since the Unix epoch. Zero
+     * (which becomes 1980-01-01, the earliest cabinet date) by default.
+     */
+    pub timestamp: u32,
+    pub compression: Compression,
+}
+
+impl PackOptions {
+    /**
+     * Take the timestamp from the SOURCE_DATE_EPOCH environment variable, like
+     * msstyles::PackOptions::from_environment.
+     */
+    pub fn from_environment() -> Self {
+        let timestamp = std::env::var("SOURCE_DATE_EPOCH").ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(0);
+        Self { timestamp, ..Default::default() }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ThemePack {
+    /**
+     * The name of the theme, which is also the name of the .theme file and the directory that
+     * the pack is installed into.
+     */
+    pub name: String,
+    pub theme_file: ThemeFile,
+    /**
+     * Every other file in the pack, keyed by its path inside of the pack, i.e.
+     * "DesktopBackground\img0.jpg".
+     */
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+/**
+ * The drive letter and colon that a Windows path starts with, i.e. "C:", if any.
+ */
+fn drive_prefix(path: &str) -> Option<&str> {
+    let bytes = path.as_bytes();
+    (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':').then(|| &path[..2])
+}
+
+/**
+ * Whether a Windows path does not depend on the directory that it is used from: it starts with
+ * a drive letter, i.e. "C:\", at the root of a drive, i.e. "\Windows", or it is a UNC path, i.e.
+ * "\\server\share".
+ */
+pub fn is_absolute_windows_path(path: &str) -> bool {
+    drive_prefix(path).is_some() || path.starts_with(['\\', '/'])
+}
+
+/**
+ * The components of a path inside of a pack, which may use either slash. Paths that could point
+ * outside of the directory that the pack is extracted into (absolute paths, drive letters and
+ * ".." components) are rejected.
+ */
+fn pack_path_components(path: &str) -> Result<Vec<&str>, Error> {
+    let components: Vec<&str> = path.split(['\\', '/']).collect();
+    if components.iter().any(|component| component.is_empty() || *component == "." || *component == ".." || component.contains(':')) {
+        return Err(anyhow!("\"{}\" is not a relative path inside of the theme pack.", path));
+    }
+    Ok(components)
+}
+
+/**
+ * Turn a path from a .theme file into a path on this machine, or None if it refers to a file that
+ * ships with Windows.
+ */
+fn local_path(path: &str, base_dir: &Path, variables: &BTreeMap<String, String>) -> Result<Option<PathBuf>, Error> {
+    let expanded = theme_file::expand_path(path, variables);
+    if expanded.contains('%') {
+        return Ok(None);
+    }
+    if !is_absolute_windows_path(&expanded) {
+        return Ok(Some(base_dir.join(expanded.replace('\\', "/"))));
+    }
+
+    let normalized = expanded.replace('/', "\\").to_lowercase();
+    let rest = &normalized[drive_prefix(&normalized).map_or(0, str::len)..];
+    if rest.starts_with("\\windows\\") {
+        Ok(None)
+    }
+    else if cfg!(windows) {
+        Ok(Some(PathBuf::from(expanded)))
+    }
+    else {
+        Err(anyhow!("\"{}\" is an absolute Windows path, which can only be bundled on Windows. Use a path relative to the .theme file, or a variable.", expanded))
+    }
+}
+
+impl ThemePack {
+    /**
+     * The directory that Windows installs this pack into, as written in the bundled .theme file.
+     */
+    pub fn install_dir(&self) -> String {
+        format!("%USERPROFILE%\\AppData\\Local\\Microsoft\\Windows\\Themes\\{}", self.name)
+    }
+
+    /**
+     * Add a file to the pack, returning its installed path. A file with the same name but
+     * different contents is renamed with a number, i.e. "img0 (2).jpg".
+     */
+    pub fn add_file(&mut self, dir: &str, file_name: &str, data: Vec<u8>) -> String {
+        let (stem, extension) = match file_name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+            _ => (file_name, String::new()),
+        };
+
+        let mut pack_path = format!("{}\\{}", dir, file_name);
+        let mut number = 2;
+        while let Some(existing) = self.files.get(&pack_path) {
+            if *existing == data {
+                break;
+            }
+            pack_path = format!("{}\\{} ({}){}", dir, stem, number, extension);
+            number += 1;
+        }
+
+        self.files.insert(pack_path.clone(), data);
+        format!("{}\\{}", self.install_dir(), pack_path)
+    }
+
+    /**
+     * Bundle a .theme file with everything that it refers to: the visual style, wallpaper,
+     * cursors and sounds. Relative paths are relative to the .theme file. Environment variables
+     * in paths are expanded with the given variables, so that for example %ResourceDir% can be
+     * pointed at a build directory.
+     */
+    pub fn from_theme_file(path: &Path, variables: &BTreeMap<String, String>) -> Result<Self, Error> {
+        let theme_file = ThemeFile::read_file(path)?;
+        let name = path.file_stem().and_then(|stem| stem.to_str())
+            .ok_or_else(|| anyhow!("\"{}\" does not have a usable file name.", path.display()))?
+            .to_owned();
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+
+        let mut pack = Self { name, theme_file: theme_file.clone(), files: BTreeMap::new() };
+
+        let bundle = |pack: &mut Self, dir: &str, original: &str| -> Result<Option<String>, Error> {
+            if original.trim().is_empty() {
+                return Ok(None);
+            }
+            let Some(local) = local_path(original, base_dir, variables)? else {
+                return Ok(None);
+            };
+            let data = fs::read(&local).with_context(|| anyhow!("Failed to read \"{}\", referenced as \"{}\"", local.display(), original))?;
+            let file_name = local.file_name().and_then(|name| name.to_str()).unwrap_or("file").to_owned();
+            Ok(Some(pack.add_file(dir, &file_name, data)))
+        };
+
+        if let Some(original) = theme_file.visual_style().path {
+            if let Some(installed) = bundle(&mut pack, VISUAL_STYLES_DIR, &original)? {
+                pack.theme_file.link_visual_style(&installed);
+            }
+        }
+
+        if let Some(original) = theme_file.desktop().wallpaper {
+            if let Some(installed) = bundle(&mut pack, DESKTOP_BACKGROUND_DIR, &original)? {
+                pack.theme_file.document.set(theme_file::SECTION_DESKTOP, "Wallpaper", &installed);
+            }
+        }
+
+        for (cursor, original) in theme_file.cursors() {
+            if let Some(installed) = bundle(&mut pack, CURSORS_DIR, &original)? {
+                pack.theme_file.set_cursor(&cursor, &installed);
+            }
+        }
+
+        for sound in theme_file.sounds() {
+            if let Some(installed) = bundle(&mut pack, SOUNDS_DIR, &sound.path)? {
+                pack.theme_file.set_sound(&sound.app, &sound.event, &installed);
+            }
+        }
+
+        if pack.theme_file.document.get("Slideshow", "ImagesRootPath").is_some() {
+            let images_root = format!("{}\\{}", pack.install_dir(), DESKTOP_BACKGROUND_DIR);
+            pack.theme_file.document.set("Slideshow", "ImagesRootPath", &images_root);
+        }
+
+        Ok(pack)
+    }
+
+    /**
+     * Read a theme pack. It must contain exactly one .theme file, at the top level.
+     */
+    pub fn read(data: &[u8]) -> Result<Self, Error> {
+        let cabinet = Cabinet::read(data)?;
+        for file in &cabinet.files {
+            pack_path_components(&file.name)?;
+        }
+
+        let mut theme_files = cabinet.files.iter()
+            .filter(|file| !file.name.contains(['\\', '/']) && file.name.to_lowercase().ends_with(".theme"));
+        let theme = theme_files.next().ok_or_else(|| anyhow!("The theme pack does not contain a .theme file."))?;
+        if let Some(other) = theme_files.next() {
+            return Err(anyhow!("The theme pack contains more than one .theme file (\"{}\" and \"{}\").", theme.name, other.name));
+        }
+
+        let name = theme.name[..theme.name.len() - ".theme".len()].to_owned();
+        let theme_file = ThemeFile::decode(&theme.data).with_context(|| anyhow!("Failed to parse \"{}\"", theme.name))?;
+        let files = cabinet.files.iter()
+            .filter(|file| !std::ptr::eq(*file, theme))
+            .map(|file| (file.name.clone(), file.data.clone()))
+            .collect();
+
+        Ok(Self { name, theme_file, files })
+    }
+
+    pub fn read_file(path: &Path) -> Result<Self, Error> {
+        let data = fs::read(path).with_context(|| anyhow!("Failed to read \"{}\"", path.display()))?;
+        Self::read(&data).with_context(|| anyhow!("Failed to read theme pack \"{}\"", path.display()))
+    }
+
+    /**
+     * Write the pack as a cabinet. The .theme file comes first, followed by the other files in
+     * sorted order.
+     */
+    pub fn write(&self, options: &PackOptions) -> Result<Vec<u8>, Error> {
+        let mut cabinet = Cabinet::default();
+        cabinet.files.push(CabinetFile::new(&format!("{}.theme", self.name), self.theme_file.encode(), options.timestamp));
+        for (path, data) in &self.files {
+            cabinet.files.push(CabinetFile::new(path, data.clone(), options.timestamp));
+        }
+        cabinet.write(options.compression)
+    }
+
+    pub fn write_file(&self, path: &Path, options: &PackOptions) -> Result<(), Error> {
+        fs::write(path, self.write(options)?).with_context(|| anyhow!("Failed to write \"{}\"", path.display()))
+    }
+
+    /**
+     * Extract the pack into a directory, using the same layout as inside of the pack. Nothing is
+     * written unless every path stays inside of the directory.
+     */
+    pub fn extract(&self, dir: &Path) -> Result<(), Error> {
+        let theme_name = format!("{}.theme", self.name);
+        let theme_data = self.theme_file.encode();
+        let mut files = vec![(pack_path_components(&theme_name)?, theme_data.as_slice())];
+        for (path, data) in &self.files {
+            files.push((pack_path_components(path)?, data.as_slice()));
+        }
+
+        for (components, data) in files {
+            let path = components.iter().fold(dir.to_path_buf(), |path, component| path.join(component));
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).with_context(|| anyhow!("Failed to create \"{}\"", parent.display()))?;
+            }
+            fs::write(&path, data).with_context(|| anyhow!("Failed to write \"{}\"", path.display()))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+/*
+ * Atlas images.
+ *
+ * Windows 8 and later styles keep many of their small images in one PNG, an atlas, instead of one
+ * image per state. AtlasImage names the atlas (packed as a STREAM resource), and AtlasRect the
+ * part of it that a state draws, in pixels:
+ *
+ *     [Button.PushButton]
+ *     AtlasImage = STREAM\1.png
+ *
+ *     [Button.PushButton(Hot)]
+ *     AtlasRect = 0, 23, 75, 46
+ */
+
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use super::{decode, DecodeOptions, RgbaImage};
+use crate::theme::{PropertyValue, Rect, Theme};
+
+/**
+ * The atlas that a state draws from and its rectangle in the atlas, if it has both.
+ */
+pub fn atlas_region(theme: &Theme, class: &str, part: Option<&str>, state: Option<&str>) -> Option<(String, Rect)> {
+    let Some(PropertyValue::FileName(path)) = theme.resolve_property(class, part, state, "AtlasImage") else {
+        return None;
+    };
+    let Some(PropertyValue::Rect(rect)) = theme.resolve_property(class, part, state, "AtlasRect") else {
+        return None;
+    };
+    Some((path.clone(), *rect))
+}
+
+/**
+ * Crop the image that a state draws out of its atlas. Returns None for states without an atlas
+ * image, and an error if the rectangle does not lie within the atlas.
+ */
+pub fn atlas_image(theme: &Theme, class: &str, part: Option<&str>, state: Option<&str>) -> Result<Option<RgbaImage>, Error> {
+    let Some((path, rect)) = atlas_region(theme, class, part, state) else {
+        return Ok(None);
+    };
+    let data = theme.image(&path).ok_or_else(|| anyhow!("Atlas image \"{}\" is missing.", path))?;
+    let atlas = decode(data, &DecodeOptions::default()).with_context(|| anyhow!("Failed to decode \"{}\"", path))?.image;
+
+    if rect.left < 0 || rect.top < 0 || rect.right <= rect.left || rect.bottom <= rect.top
+        || rect.right > atlas.width as i32 || rect.bottom > atlas.height as i32
+    {
+        return Err(anyhow!(
+            "AtlasRect {}, {}, {}, {} does not lie within the {}x{} atlas \"{}\".",
+            rect.left, rect.top, rect.right, rect.bottom, atlas.width, atlas.height, path,
+        ));
+    }
+    Ok(Some(atlas.crop(rect)))
+}
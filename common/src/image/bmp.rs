@@ -0,0 +1,182 @@
+/*
+ * 24 and 32-bit BMP images, with or without a BITMAPFILEHEADER.
+ */
+
+use anyhow::Error;
+use anyhow::anyhow;
+
+use super::{premultiply, unpremultiply, BmpAlpha, ImageEncoding, RgbaImage};
+
+const FILE_HEADER_SIZE: usize = 14;
+const INFO_HEADER_SIZE: usize = 40;
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, Error> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| anyhow!("Unexpected end of bitmap at offset {:#x}.", offset))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, Error> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or_else(|| anyhow!("Unexpected end of bitmap at offset {:#x}.", offset))
+}
+
+/**
+ * Whether the data looks like a bare DIB: a BITMAPINFOHEADER (or a later version of it) without
+ * a file header, as found in RT_BITMAP resources.
+ */
+pub fn is_dib(data: &[u8]) -> bool {
+    matches!(u32_at(data, 0), Ok(40 | 52 | 56 | 108 | 124))
+        && matches!(u16_at(data, 12), Ok(1))
+}
+
+fn row_stride(width: u32, bits: u16) -> usize {
+    (width as usize * bits as usize).div_ceil(32) * 4
+}
+
+pub fn decode(data: &[u8], alpha: BmpAlpha) -> Result<(RgbaImage, ImageEncoding), Error> {
+    let file_header = data.starts_with(b"BM");
+    let dib = if file_header { &data[FILE_HEADER_SIZE.min(data.len())..] } else { data };
+
+    let header_size = u32_at(dib, 0)? as usize;
+    if header_size < INFO_HEADER_SIZE {
+        return Err(anyhow!("OS/2 bitmaps are not supported."));
+    }
+    let width = u32_at(dib, 4)? as i32;
+    let height = u32_at(dib, 8)? as i32;
+    let bits = u16_at(dib, 14)?;
+    let compression = u32_at(dib, 16)?;
+    let colors_used = u32_at(dib, 32)? as usize;
+
+    if bits != 24 && bits != 32 {
+        return Err(anyhow!("{}-bit bitmaps are not supported.", bits));
+    }
+    if width <= 0 || height == 0 {
+        return Err(anyhow!("Invalid bitmap size {}x{}.", width, height));
+    }
+
+    let mut masks_size = 0;
+    match compression {
+        BI_RGB => {}
+        BI_BITFIELDS if bits == 32 => {
+            // The masks follow a BITMAPINFOHEADER, and are part of the later header versions.
+            let masks_offset = INFO_HEADER_SIZE;
+            if header_size == INFO_HEADER_SIZE {
+                masks_size = 12;
+            }
+            let masks = [u32_at(dib, masks_offset)?, u32_at(dib, masks_offset + 4)?, u32_at(dib, masks_offset + 8)?];
+            if masks != [0x00FF_0000, 0x0000_FF00, 0x0000_00FF] {
+                return Err(anyhow!("Bitmaps with color masks {:08X?} are not supported.", masks));
+            }
+        }
+        other => return Err(anyhow!("Compressed bitmaps (compression {}) are not supported.", other)),
+    }
+
+    let pixel_offset = if file_header {
+        (u32_at(data, 10)? as usize).checked_sub(FILE_HEADER_SIZE).ok_or_else(|| anyhow!("Invalid pixel data offset."))?
+    }
+    else {
+        header_size + masks_size + colors_used * 4
+    };
+
+    let top_down = height < 0;
+    let (width, height) = (width as u32, height.unsigned_abs());
+    let stride = row_stride(width, bits);
+    let pixel_data = dib.get(pixel_offset..pixel_offset + stride * height as usize)
+        .ok_or_else(|| anyhow!("The bitmap is missing pixel data."))?;
+
+    let bytes_per_pixel = bits as usize / 8;
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        let row = if top_down { y } else { height - 1 - y } as usize;
+        let row = &pixel_data[row * stride..];
+        for x in 0..width {
+            let source = &row[x as usize * bytes_per_pixel..];
+            let alpha = if bits == 32 { source[3] } else { 255 };
+            image.set_pixel(x, y, [source[2], source[1], source[0], alpha]);
+        }
+    }
+
+    let alpha = match (bits, alpha) {
+        (24, _) => BmpAlpha::Ignored,
+        (_, BmpAlpha::Auto) => {
+            if image.pixels.chunks_exact(4).all(|pixel| pixel[3] == 0) {
+                BmpAlpha::Ignored
+            }
+            else {
+                BmpAlpha::Straight
+            }
+        }
+        (_, alpha) => alpha,
+    };
+
+    for pixel in image.pixels.chunks_exact_mut(4) {
+        match alpha {
+            BmpAlpha::Ignored => pixel[3] = 255,
+            BmpAlpha::Premultiplied => {
+                let straight = unpremultiply(pixel.try_into().unwrap());
+                pixel.copy_from_slice(&straight);
+            }
+            _ => {}
+        }
+    }
+
+    Ok((image, ImageEncoding::Bmp { bits, alpha, top_down, file_header }))
+}
+
+pub fn encode(image: &RgbaImage, encoding: &ImageEncoding) -> Result<Vec<u8>, Error> {
+    let &ImageEncoding::Bmp { bits, alpha, top_down, file_header } = encoding else {
+        return Err(anyhow!("Not a bitmap encoding."));
+    };
+
+    let opaque = image.is_opaque();
+    let (bits, alpha) = match (bits, alpha) {
+        (24, _) if opaque => (24, BmpAlpha::Ignored),
+        (_, BmpAlpha::Ignored | BmpAlpha::Auto) if !opaque => (32, BmpAlpha::Straight),
+        (_, alpha) => (32, alpha),
+    };
+
+    let stride = row_stride(image.width, bits);
+    let image_size = stride * image.height as usize;
+    let mut result = Vec::with_capacity(FILE_HEADER_SIZE + INFO_HEADER_SIZE + image_size);
+
+    if file_header {
+        result.extend_from_slice(b"BM");
+        result.extend_from_slice(&((FILE_HEADER_SIZE + INFO_HEADER_SIZE + image_size) as u32).to_le_bytes());
+        result.extend_from_slice(&[0; 4]);
+        result.extend_from_slice(&((FILE_HEADER_SIZE + INFO_HEADER_SIZE) as u32).to_le_bytes());
+    }
+
+    let height = if top_down { -(image.height as i32) } else { image.height as i32 };
+    result.extend_from_slice(&(INFO_HEADER_SIZE as u32).to_le_bytes());
+    result.extend_from_slice(&(image.width as i32).to_le_bytes());
+    result.extend_from_slice(&height.to_le_bytes());
+    result.extend_from_slice(&1u16.to_le_bytes());
+    result.extend_from_slice(&bits.to_le_bytes());
+    result.extend_from_slice(&BI_RGB.to_le_bytes());
+    result.extend_from_slice(&(image_size as u32).to_le_bytes());
+    result.extend_from_slice(&[0; 16]);
+
+    for y in 0..image.height {
+        let y = if top_down { y } else { image.height - 1 - y };
+        let row_start = result.len();
+        for x in 0..image.width {
+            let pixel = image.pixel(x, y);
+            let [r, g, b, a] = match alpha {
+                BmpAlpha::Premultiplied => premultiply(pixel),
+                BmpAlpha::Ignored => [pixel[0], pixel[1], pixel[2], 0],
+                _ => pixel,
+            };
+            result.extend_from_slice(&[b, g, r]);
+            if bits == 32 {
+                result.push(a);
+            }
+        }
+        result.resize(row_start + stride, 0);
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,143 @@
+/*
+ * Image deduplication and garbage collection.
+ *
+ * Themes tend to collect copies of the same image under different names, and images that no
+ * property refers to any more. Images are compared by their decoded pixels, so a BMP and a PNG
+ * of the same picture are duplicates too; properties can then share the smallest copy, and the
+ * rest, along with anything unreferenced, is removed.
+ */
+
+use std::collections::BTreeMap;
+use sha2::{Digest, Sha256};
+
+use super::{decode, DecodeOptions};
+use crate::theme::{normalize_image_path, PropertyValue, Theme};
+
+/**
+ * Images with the same pixels.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DuplicateGroup {
+    /**
+     * The image that the others can be replaced with: the smallest one.
+     */
+    pub keep: String,
+    pub duplicates: Vec<String>,
+    /**
+     * Whether the duplicates are also byte for byte the same as the kept image.
+     */
+    pub identical_bytes: bool,
+    /**
+     * The size of the duplicates, in bytes.
+     */
+    pub saved: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImageAnalysis {
+    pub duplicates: Vec<DuplicateGroup>,
+    /**
+     * Images that no property refers to, with their sizes.
+     */
+    pub unreferenced: Vec<(String, usize)>,
+}
+
+impl ImageAnalysis {
+    /**
+     * The number of bytes that deduplicating and removing unreferenced images saves.
+     */
+    pub fn saved(&self) -> usize {
+        self.duplicates.iter().map(|group| group.saved).sum::<usize>()
+            + self.unreferenced.iter().map(|(_, size)| size).sum::<usize>()
+    }
+}
+
+/**
+ * Find duplicate and unreferenced images. Duplicates are only looked for among referenced images.
+ */
+pub fn analyze(theme: &Theme) -> ImageAnalysis {
+    let referenced = theme.referenced_images();
+    let mut analysis = ImageAnalysis::default();
+
+    // Images that do not decode can still be byte for byte duplicates.
+    let mut groups: BTreeMap<(bool, [u8; 32]), Vec<&String>> = BTreeMap::new();
+    for (path, data) in &theme.images {
+        if !referenced.contains(&normalize_image_path(path)) {
+            analysis.unreferenced.push((path.clone(), data.len()));
+            continue;
+        }
+        let mut hasher = Sha256::new();
+        let decoded = decode(data, &DecodeOptions::default()).ok();
+        match &decoded {
+            Some(decoded) => {
+                hasher.update(decoded.image.width.to_le_bytes());
+                hasher.update(decoded.image.height.to_le_bytes());
+                hasher.update(&decoded.image.pixels);
+            }
+            None => hasher.update(data),
+        }
+        groups.entry((decoded.is_some(), hasher.finalize().into())).or_default().push(path);
+    }
+
+    for mut paths in groups.into_values() {
+        if paths.len() < 2 {
+            continue;
+        }
+        paths.sort_by_key(|path| (theme.images[*path].len(), *path));
+        let keep = &theme.images[paths[0]];
+        let duplicates = &paths[1..];
+        analysis.duplicates.push(DuplicateGroup {
+            keep: paths[0].clone(),
+            duplicates: duplicates.iter().map(|path| (*path).clone()).collect(),
+            identical_bytes: duplicates.iter().all(|path| &theme.images[*path] == keep),
+            saved: duplicates.iter().map(|path| theme.images[*path].len()).sum(),
+        });
+    }
+    analysis.duplicates.sort_by(|a, b| a.keep.cmp(&b.keep));
+    analysis
+}
+
+/**
+ * Point every property that refers to a duplicate at the image that is kept instead, and remove
+ * the duplicates and the unreferenced images.
+ */
+pub fn deduplicate(theme: &mut Theme, analysis: &ImageAnalysis) {
+    let mut replacements = BTreeMap::new();
+    for group in &analysis.duplicates {
+        for duplicate in &group.duplicates {
+            replacements.insert(normalize_image_path(duplicate), group.keep.clone());
+        }
+    }
+
+    for class in &mut theme.classes {
+        for part in &mut class.parts {
+            for state in &mut part.states {
+                for property in &mut state.properties {
+                    if let PropertyValue::FileName(path) = &mut property.value {
+                        if let Some(keep) = replacements.get(&normalize_image_path(path)) {
+                            *path = keep.clone();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    collect_garbage(theme);
+}
+
+/**
+ * Remove the images that no property refers to, and return their paths and sizes.
+ */
+pub fn collect_garbage(theme: &mut Theme) -> Vec<(String, usize)> {
+    let referenced = theme.referenced_images();
+    let mut removed = Vec::new();
+    theme.images.retain(|path, data| {
+        let keep = referenced.contains(&normalize_image_path(path));
+        if !keep {
+            removed.push((path.clone(), data.len()));
+        }
+        keep
+    });
+    removed
+}
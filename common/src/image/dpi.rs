@@ -0,0 +1,348 @@
+/*
+ * DPI variants.
+ *
+ * When ImageSelectType is DPI, uxtheme draws the first of ImageFile1..7 whose MinDpi is at most
+ * the display DPI, going from the highest. Themes with only 96 DPI art are stretched instead,
+ * which looks blurry, so this generates the missing variants from the highest resolution image
+ * that a state has.
+ *
+ * States that pick their image by size instead (ImageSelectType = SIZE) get a MinSize for each
+ * generated variant as well: the size of its frame, so that it is drawn once there is room for it.
+ *
+ * Margins stay in 96 DPI units, which uxtheme scales to the DPI of the variant that it draws. A
+ * generated variant is scaled as a nine-grid so that its corners and edges land exactly where
+ * those scaled sizing margins put them, and each frame of a strip is scaled on its own so that
+ * the frames stay aligned.
+ */
+
+use std::collections::BTreeMap;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use super::resample::{resample, resample_nine_grid};
+use super::strip::{self, StripLayout};
+use super::{decode, encode, DecodeOptions};
+use crate::theme::image_properties::{min_dpi_property, min_size_property, variant_property, ImageRef, VARIANT_COUNT, VARIANT_DPIS};
+use crate::theme::schema::{IST_DPI, IST_NONE, IST_SIZE, ST_TRUESIZE};
+use crate::theme::sysmetrics::BASE_DPI;
+use crate::theme::{normalize_image_path, Margins, Point, PropertyValue, Theme};
+
+#[derive(Clone, Debug)]
+pub struct VariantOptions {
+    /**
+     * The DPIs to make variants for. Each must be one of VARIANT_DPIS, as that is what decides
+     * which ImageFile property the variant goes into.
+     */
+    pub dpis: Vec<u32>,
+}
+
+impl Default for VariantOptions {
+    fn default() -> Self {
+        Self { dpis: VARIANT_DPIS.to_vec() }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedVariant {
+    pub class: String,
+    pub part: Option<String>,
+    pub state: Option<String>,
+    pub property: String,
+    pub path: String,
+    pub dpi: u32,
+    pub source_dpi: u32,
+    /**
+     * The size of one frame of the variant.
+     */
+    pub frame_width: u32,
+    pub frame_height: u32,
+    /**
+     * The sizing and content margins of the state as uxtheme scales them for this variant.
+     */
+    pub sizing_margins: Option<Margins>,
+    pub content_margins: Option<Margins>,
+}
+
+/**
+ * Scale a length in 96 DPI units to a DPI, rounding like uxtheme does.
+ */
+fn scale(value: i32, dpi: u32) -> i32 {
+    (value as i64 * dpi as i64 + BASE_DPI as i64 / 2).div_euclid(BASE_DPI as i64) as i32
+}
+
+/**
+ * Scale margins in 96 DPI units to a DPI.
+ */
+pub fn scale_margins(margins: Margins, dpi: u32) -> Margins {
+    Margins {
+        left: scale(margins.left, dpi),
+        right: scale(margins.right, dpi),
+        top: scale(margins.top, dpi),
+        bottom: scale(margins.bottom, dpi),
+    }
+}
+
+/**
+ * How specific a level is: the class-wide part, a part-wide state, or a state.
+ */
+fn depth(part: Option<&str>, state: Option<&str>) -> u8 {
+    part.is_some() as u8 + state.is_some() as u8
+}
+
+/**
+ * The path for a generated variant, i.e. "Images\Button_192dpi.png" for "Images\Button.png".
+ */
+fn variant_path(theme: &Theme, path: &str, dpi: u32) -> String {
+    let name_start = path.rfind(['\\', '/']).map_or(0, |i| i + 1);
+    let (stem, extension) = match path[name_start..].rfind('.') {
+        Some(dot) => path.split_at(name_start + dot),
+        None => (path, ""),
+    };
+
+    let mut candidate = format!("{}_{}dpi{}", stem, dpi, extension);
+    let mut number = 2;
+    while theme.image(&candidate).is_some() {
+        candidate = format!("{}_{}dpi_{}{}", stem, dpi, number, extension);
+        number += 1;
+    }
+    candidate
+}
+
+struct Level {
+    class: String,
+    part: Option<String>,
+    state: Option<String>,
+}
+
+/**
+ * What decides the pixels of a generated variant, so that states that share an image and its
+ * layout share the variant too.
+ */
+type CacheKey = (String, u32, String);
+
+/**
+ * A variant of a level's image that is ready to be added to the theme.
+ */
+struct PlannedVariant {
+    variant: u8,
+    dpi: u32,
+    path: String,
+    /**
+     * The encoded image if it is a new file, rather than the source or a variant made for
+     * another state.
+     */
+    image: Option<(CacheKey, Vec<u8>)>,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VariantReport {
+    pub generated: Vec<GeneratedVariant>,
+    /**
+     * The source images whose variants could not be generated, and why. Their states are left
+     * as they were.
+     */
+    pub failed: Vec<(ImageRef, String)>,
+}
+
+/**
+ * Generate the missing DPI variants of every image in a theme. Variants that a state already has
+ * are left alone, and so is everything about states without images. An image that cannot be
+ * scaled does not stop the others.
+ */
+pub fn generate_variants(theme: &mut Theme, options: &VariantOptions) -> Result<VariantReport, Error> {
+    let mut slots = Vec::new();
+    for dpi in &options.dpis {
+        let index = VARIANT_DPIS.iter().position(|variant_dpi| variant_dpi == dpi)
+            .ok_or_else(|| anyhow!("{} DPI is not one of the variant DPIs {:?}.", dpi, VARIANT_DPIS))?;
+        slots.push((index as u8 + 1, *dpi));
+    }
+
+    // Every level that has an image of its own.
+    let mut levels: Vec<Level> = Vec::new();
+    for image_ref in theme.image_refs() {
+        let seen = levels.iter().any(|level| {
+            level.class == image_ref.class && level.part == image_ref.part && level.state == image_ref.state
+        });
+        if !image_ref.glyph && !seen {
+            levels.push(Level { class: image_ref.class, part: image_ref.part, state: image_ref.state });
+        }
+    }
+
+    let mut report = VariantReport::default();
+    let mut cache: BTreeMap<CacheKey, String> = BTreeMap::new();
+
+    for level in levels {
+        let (class, part, state) = (level.class.as_str(), level.part.as_deref(), level.state.as_deref());
+        let base_depth = theme.property_level(class, part, state, "ImageFile").map(|(part, state)| depth(part, state));
+
+        // Variants only belong to this level's image if they are not inherited from a level
+        // above the one that ImageFile comes from, which would be some other image's variants.
+        let mut existing = vec![None; VARIANT_COUNT as usize];
+        for variant in 1..=VARIANT_COUNT {
+            let property = variant_property(variant);
+            let Some(PropertyValue::FileName(path)) = theme.resolve_property(class, part, state, &property) else {
+                continue;
+            };
+            let variant_depth = theme.property_level(class, part, state, &property).map(|(part, state)| depth(part, state));
+            if base_depth.is_some_and(|base_depth| variant_depth < Some(base_depth)) {
+                continue;
+            }
+            let image_ref = ImageRef {
+                class: class.to_owned(),
+                part: part.map(str::to_owned),
+                state: state.map(str::to_owned),
+                property,
+                path: path.clone(),
+                variant: Some(variant),
+                glyph: false,
+            };
+            let dpi = image_ref.dpi(theme);
+            existing[variant as usize - 1] = Some((image_ref, dpi));
+        }
+
+        let base = match theme.resolve_property(class, part, state, "ImageFile") {
+            Some(PropertyValue::FileName(path)) => Some((ImageRef {
+                class: class.to_owned(),
+                part: part.map(str::to_owned),
+                state: state.map(str::to_owned),
+                property: "ImageFile".to_owned(),
+                path: path.clone(),
+                variant: None,
+                glyph: false,
+            }, BASE_DPI)),
+            _ => None,
+        };
+        let Some((source, source_dpi)) = existing.iter().flatten().cloned().chain(base)
+            .filter(|(image_ref, _)| theme.image(&image_ref.path).is_some())
+            .max_by_key(|(_, dpi)| *dpi)
+        else {
+            continue;
+        };
+
+        let select_type = theme.resolve_property(class, part, state, "ImageSelectType").cloned();
+        let by_size = matches!(select_type, Some(PropertyValue::Enum(IST_SIZE)));
+        let true_size = matches!(theme.resolve_property(class, part, state, "SizingType"), Some(PropertyValue::Enum(ST_TRUESIZE)));
+        let sizing_margins = match theme.resolve_property(class, part, state, "SizingMargins") {
+            Some(PropertyValue::Margins(margins)) => Some(*margins),
+            _ => None,
+        };
+        let content_margins = match theme.resolve_property(class, part, state, "ContentMargins") {
+            Some(PropertyValue::Margins(margins)) => Some(*margins),
+            _ => None,
+        };
+
+        let slots: Vec<_> = slots.iter().copied().filter(|(variant, _)| existing[*variant as usize - 1].is_none()).collect();
+        let planned = match plan_variants(theme, &level, &source.path, source_dpi, &slots, &cache) {
+            Ok(planned) => planned,
+            Err(error) => {
+                report.failed.push((source, format!("{:#}", error)));
+                continue;
+            }
+        };
+
+        for planned in &planned {
+            if let Some((key, image)) = &planned.image {
+                theme.set_image(&planned.path, image.clone());
+                cache.insert(key.clone(), planned.path.clone());
+            }
+
+            let target = theme.state_mut(class, part, state);
+            target.set_property(&variant_property(planned.variant), PropertyValue::FileName(planned.path.clone()));
+            target.set_property(&min_dpi_property(planned.variant), PropertyValue::Int(planned.dpi as i32));
+            // True size images are picked by their frame size, without MinSize.
+            if by_size && !true_size {
+                let min_size = Point { x: planned.width as i32, y: planned.height as i32 };
+                target.set_property(&min_size_property(planned.variant), PropertyValue::Position(min_size));
+            }
+
+            report.generated.push(GeneratedVariant {
+                class: class.to_owned(),
+                part: part.map(str::to_owned),
+                state: state.map(str::to_owned),
+                property: variant_property(planned.variant),
+                path: planned.path.clone(),
+                dpi: planned.dpi,
+                source_dpi,
+                frame_width: planned.width,
+                frame_height: planned.height,
+                sizing_margins: sizing_margins.map(|margins| scale_margins(margins, planned.dpi)),
+                content_margins: content_margins.map(|margins| scale_margins(margins, planned.dpi)),
+            });
+        }
+
+        if !planned.is_empty() && matches!(select_type, None | Some(PropertyValue::Enum(IST_NONE))) {
+            theme.state_mut(class, part, state).set_property("ImageSelectType", PropertyValue::Enum(IST_DPI));
+        }
+    }
+
+    Ok(report)
+}
+
+/**
+ * Scale a level's source image to each of the given variant slots, without touching the theme,
+ * so that a level that fails halfway leaves nothing behind.
+ */
+fn plan_variants(
+    theme: &Theme,
+    level: &Level,
+    source_path: &str,
+    source_dpi: u32,
+    slots: &[(u8, u32)],
+    cache: &BTreeMap<CacheKey, String>,
+) -> Result<Vec<PlannedVariant>, Error> {
+    let (class, part, state) = (level.class.as_str(), level.part.as_deref(), level.state.as_deref());
+    let layout = StripLayout::of(theme, class, part, state);
+    let true_size = matches!(theme.resolve_property(class, part, state, "SizingType"), Some(PropertyValue::Enum(ST_TRUESIZE)));
+    let sizing_margins = match theme.resolve_property(class, part, state, "SizingMargins") {
+        Some(PropertyValue::Margins(margins)) => Some(*margins),
+        _ => None,
+    };
+
+    let decoded = decode(theme.image(source_path).unwrap_or_default(), &DecodeOptions::default())
+        .with_context(|| anyhow!("Failed to decode \"{}\"", source_path))?;
+    if !layout.fits(decoded.image.width, decoded.image.height) {
+        return Err(anyhow!(
+            "\"{}\" is {}x{}, which does not divide into its {} frames.",
+            source_path, decoded.image.width, decoded.image.height, layout.count,
+        ));
+    }
+    let (frame_width, frame_height) = layout.frame_size(decoded.image.width, decoded.image.height);
+
+    let mut planned = Vec::new();
+    for &(variant, dpi) in slots {
+        let target_width = ((frame_width as u64 * dpi as u64 + source_dpi as u64 / 2) / source_dpi as u64).max(1) as u32;
+        let target_height = ((frame_height as u64 * dpi as u64 + source_dpi as u64 / 2) / source_dpi as u64).max(1) as u32;
+
+        let (path, image) = if dpi == source_dpi {
+            (source_path.to_owned(), None)
+        }
+        else {
+            let key = (normalize_image_path(source_path), dpi, format!("{:?} {:?} {}", layout, sizing_margins, true_size));
+            match cache.get(&key) {
+                Some(path) => (path.clone(), None),
+                None => {
+                    let frames: Vec<_> = strip::split(&decoded.image, layout)?.iter()
+                        .map(|frame| match sizing_margins {
+                            Some(margins) if !true_size => resample_nine_grid(
+                                frame,
+                                scale_margins(margins, source_dpi),
+                                target_width,
+                                target_height,
+                                scale_margins(margins, dpi),
+                            ),
+                            _ => resample(frame, target_width, target_height),
+                        })
+                        .collect();
+                    let image = strip::join(&frames, layout.vertical)?;
+                    (variant_path(theme, source_path, dpi), Some((key, encode(&image, &decoded.encoding)?)))
+                }
+            }
+        };
+
+        planned.push(PlannedVariant { variant, dpi, path, image, width: target_width, height: target_height });
+    }
+    Ok(planned)
+}
@@ -0,0 +1,163 @@
+/*
+ * Bulk image export.
+ *
+ * Writes every image that a theme refers to as a PNG, named after the property that refers to
+ * it: Class_Part_State_Property_DPI.png, i.e. "Button_PushButton_ImageFile_96dpi.png". Levels
+ * without a name (the class-wide part and the part-wide state) are left out of the name. Strips
+ * can be split into one file per frame, named after the state that each frame is drawn for.
+ *
+ * An index.json next to the images maps every file back to its owning property:
+ *
+ *     {
+ *       "images": [
+ *         { "file": "...", "class": "Button", "part": "PushButton", "state": null,
+ *           "property": "ImageFile", "dpi": 96, "source": "Images\\PushButton.png",
+ *           "width": 8, "height": 6, "frame": 0, "frameCount": 5, "layout": "vertical" }
+ *       ],
+ *       "missing": [ { "class": ..., "property": ..., "source": ... } ],
+ *       "failed": [ { "class": ..., "property": ..., "source": ..., "error": "..." } ]
+ *     }
+ *
+ * "frame" is null for images that were not split. Images that cannot be decoded are listed under
+ * "failed" and skipped, and the rest are still exported.
+ */
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use serde_json::{json, Value};
+
+use super::strip::{self, StripLayout};
+use super::{decode, encode, DecodeOptions, ImageEncoding, PngColorType};
+use crate::theme::{parts, Theme};
+
+pub const INDEX_FILE_NAME: &str = "index.json";
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ExportOptions {
+    /**
+     * Write each frame of a strip as its own file.
+     */
+    pub split_strips: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ExportSummary {
+    pub files: Vec<String>,
+    /**
+     * Image paths that are referenced but are not in the theme.
+     */
+    pub missing: Vec<String>,
+    /**
+     * Image paths that could not be decoded, and why.
+     */
+    pub failed: Vec<(String, String)>,
+}
+
+/**
+ * Make a name usable as part of a file name on every platform.
+ */
+fn file_name_component(name: &str) -> String {
+    name.replace("::", "-")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect()
+}
+
+fn base_name(class: &str, part: Option<&str>, state: Option<&str>, property: &str, dpi: u32) -> String {
+    let mut components = vec![file_name_component(class)];
+    components.extend(part.map(file_name_component));
+    components.extend(state.map(file_name_component));
+    components.push(file_name_component(property));
+    components.push(format!("{}dpi", dpi));
+    components.join("_")
+}
+
+/**
+ * Export every image in a theme into a directory, along with an index.json.
+ */
+pub fn export_images(theme: &Theme, dir: &Path, options: &ExportOptions) -> Result<ExportSummary, Error> {
+    fs::create_dir_all(dir).with_context(|| anyhow!("Failed to create \"{}\"", dir.display()))?;
+
+    let png = ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false };
+    let mut summary = ExportSummary::default();
+    let mut used_names = BTreeSet::new();
+    let mut entries = Vec::new();
+    let mut missing = Vec::new();
+    let mut failed = Vec::new();
+
+    for image_ref in theme.image_refs() {
+        let (class, part, state) = (image_ref.class.as_str(), image_ref.part.as_deref(), image_ref.state.as_deref());
+        let Some(data) = theme.image(&image_ref.path) else {
+            missing.push(json!({ "class": class, "part": part, "state": state, "property": image_ref.property, "source": image_ref.path }));
+            summary.missing.push(image_ref.path.clone());
+            continue;
+        };
+
+        let decoded = match decode(data, &DecodeOptions::default()) {
+            Ok(decoded) => decoded,
+            Err(error) => {
+                let error = format!("{:#}", error);
+                failed.push(json!({ "class": class, "part": part, "state": state, "property": image_ref.property, "source": image_ref.path, "error": error }));
+                summary.failed.push((image_ref.path.clone(), error));
+                continue;
+            }
+        };
+        let dpi = image_ref.dpi(theme);
+        let layout = StripLayout::of(theme, class, part, state);
+        let base = base_name(class, part, state, &image_ref.property, dpi);
+
+        let frames = if options.split_strips && layout.count > 1 && layout.fits(decoded.image.width, decoded.image.height) {
+            strip::split(&decoded.image, layout)?.into_iter().enumerate()
+                .map(|(index, frame)| {
+                    let state_name = parts::part_id(class, part)
+                        .and_then(|part_id| parts::state_name(class, part_id, index as i32 + 1))
+                        .map(file_name_component)
+                        .unwrap_or_else(|| format!("Frame{}", index + 1));
+                    (Some(index), format!("{}_{}", base, state_name), frame)
+                })
+                .collect()
+        }
+        else {
+            vec![(None, base, decoded.image)]
+        };
+
+        for (frame, name, image) in frames {
+            let mut file = format!("{}.png", name);
+            let mut number = 2;
+            while !used_names.insert(file.to_lowercase()) {
+                file = format!("{}_{}.png", name, number);
+                number += 1;
+            }
+
+            let path = dir.join(&file);
+            fs::write(&path, encode(&image, &png)?).with_context(|| anyhow!("Failed to write \"{}\"", path.display()))?;
+
+            entries.push(json!({
+                "file": file,
+                "class": class,
+                "part": part,
+                "state": state,
+                "property": image_ref.property,
+                "dpi": dpi,
+                "source": image_ref.path,
+                "width": image.width,
+                "height": image.height,
+                "frame": frame,
+                "frameCount": layout.count,
+                "layout": if layout.vertical { "vertical" } else { "horizontal" },
+            }));
+            summary.files.push(file);
+        }
+    }
+
+    let index = json!({ "images": Value::Array(entries), "missing": Value::Array(missing), "failed": Value::Array(failed) });
+    let index_path = dir.join(INDEX_FILE_NAME);
+    fs::write(&index_path, serde_json::to_string_pretty(&index)? + "\n")
+        .with_context(|| anyhow!("Failed to write \"{}\"", index_path.display()))?;
+
+    Ok(summary)
+}
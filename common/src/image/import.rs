@@ -0,0 +1,209 @@
+/*
+ * Image import.
+ *
+ * Replaces the image that a property refers to with a new one, converted to the encoding that the
+ * theme stores it in: a PNG from a designer becomes a 32-bit bottom-up BMP if that is what it
+ * replaces, premultiplied if the existing image is read as premultiplied. The new image can also
+ * go into part of the existing one, i.e. one image of an atlas.
+ *
+ * Importing checks the new size against every property that uses the image, since a strip that
+ * no longer divides into ImageCount frames, or a frame that is smaller than its sizing margins,
+ * draws wrong without any error from uxtheme.
+ */
+
+use std::fs;
+use std::path::Path;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use super::strip::StripLayout;
+use super::{decode, encode, BmpAlpha, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use crate::theme::image_properties::{find_image_property, ImageRef};
+use crate::theme::schema::ST_TRUESIZE;
+use crate::theme::{normalize_image_path, PropertyValue, Rect, Theme};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImportOptions {
+    /**
+     * How to decode the imported file.
+     */
+    pub source: DecodeOptions,
+    /**
+     * How to read the image that is replaced. Premultiplied bitmaps cannot be detected, so a
+     * theme that stores them needs BmpAlpha::Premultiplied here to keep storing them that way.
+     */
+    pub target: DecodeOptions,
+    /**
+     * The encoding to store the image in. By default this is the encoding of the image that is
+     * replaced, including whether a 32-bit BMP is premultiplied, or for a new image, a 32-bit BMP
+     * or an RGBA PNG depending on the extension of its path. A BMP encoding given here also says
+     * how to read the alpha of the image that is replaced, instead of target.
+     */
+    pub encoding: Option<ImageEncoding>,
+    /**
+     * Put the imported image into this part of the existing image instead of replacing all of
+     * it. The imported image must be the size of the region.
+     */
+    pub region: Option<Rect>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportWarning {
+    /**
+     * The property that the new image does not suit.
+     */
+    pub image_ref: ImageRef,
+    pub message: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportReport {
+    /**
+     * The path that the image was stored under.
+     */
+    pub path: String,
+    pub encoding: ImageEncoding,
+    pub width: u32,
+    pub height: u32,
+    pub warnings: Vec<ImportWarning>,
+}
+
+/**
+ * The encoding for an image that does not exist yet.
+ */
+fn default_encoding(path: &str) -> ImageEncoding {
+    if path.to_lowercase().ends_with(".bmp") {
+        ImageEncoding::Bmp { bits: 32, alpha: BmpAlpha::Straight, top_down: false, file_header: true }
+    }
+    else {
+        ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false }
+    }
+}
+
+/**
+ * Check an image against the layout and margins of a property that uses it.
+ */
+fn check_image_ref(theme: &Theme, image_ref: &ImageRef, width: u32, height: u32, warnings: &mut Vec<ImportWarning>) {
+    let (class, part, state) = (image_ref.class.as_str(), image_ref.part.as_deref(), image_ref.state.as_deref());
+    let mut warn = |message: String| warnings.push(ImportWarning { image_ref: image_ref.clone(), message });
+
+    let layout = StripLayout::of(theme, class, part, state);
+    if !layout.fits(width, height) {
+        warn(format!(
+            "A {}x{} image does not divide into ImageCount = {} {} frames.",
+            width, height, layout.count, if layout.vertical { "vertical" } else { "horizontal" },
+        ));
+    }
+    let (frame_width, frame_height) = layout.frame_size(width, height);
+
+    // Glyphs and true size images are drawn as they are, so their margins do not cut them up.
+    let true_size = matches!(theme.resolve_property(class, part, state, "SizingType"), Some(PropertyValue::Enum(ST_TRUESIZE)));
+    if image_ref.glyph || true_size {
+        return;
+    }
+    // Content margins apply to the destination rectangle, not to the image, so only the sizing
+    // margins have to fit.
+    if let Some(PropertyValue::Margins(margins)) = theme.resolve_property(class, part, state, "SizingMargins") {
+        if margins.left + margins.right > frame_width as i32 || margins.top + margins.bottom > frame_height as i32 {
+            warn(format!("SizingMargins ({}) do not fit in a {}x{} frame.", margins, frame_width, frame_height));
+        }
+    }
+}
+
+/**
+ * Replace an image file of a theme with an encoded image (PNG or BMP), converting it to the
+ * encoding that the theme needs.
+ */
+pub fn replace_image(theme: &mut Theme, path: &str, data: &[u8], options: &ImportOptions) -> Result<ImportReport, Error> {
+    let source = decode(data, &options.source).context("Failed to decode the imported image")?;
+    // The existing image is read the way it is written back, so that a region import leaves the
+    // rest of it as it was.
+    let target = match options.encoding {
+        Some(ImageEncoding::Bmp { alpha, .. }) => DecodeOptions { bmp_alpha: alpha },
+        _ => options.target,
+    };
+    let existing = match theme.image(path) {
+        Some(existing) => Some(decode(existing, &target).with_context(|| anyhow!("Failed to decode \"{}\"", path))?),
+        None => None,
+    };
+
+    let mut encoding = options.encoding
+        .or(existing.as_ref().map(|existing| existing.encoding))
+        .unwrap_or_else(|| default_encoding(path));
+    // Bitmaps are kept with their file header, which compiling strips again.
+    if let ImageEncoding::Bmp { file_header, .. } = &mut encoding {
+        *file_header = true;
+    }
+
+    let image: RgbaImage = match options.region {
+        Some(region) => {
+            let Some(existing) = existing else {
+                return Err(anyhow!("\"{}\" does not exist, so a region of it cannot be replaced.", path));
+            };
+            let mut image = existing.image;
+            let fits = region.left >= 0 && region.top >= 0 && region.right <= image.width as i32 && region.bottom <= image.height as i32;
+            if !fits {
+                return Err(anyhow!("The region {} is outside of the {}x{} image \"{}\".", region, image.width, image.height, path));
+            }
+            if region.width() != source.image.width as i32 || region.height() != source.image.height as i32 {
+                return Err(anyhow!(
+                    "A {}x{} image does not fit the {}x{} region {}.",
+                    source.image.width, source.image.height, region.width(), region.height(), region,
+                ));
+            }
+            image.paste(&source.image, region.left, region.top);
+            image
+        }
+        None => source.image,
+    };
+
+    let mut warnings = Vec::new();
+    let normalized = normalize_image_path(path);
+    for image_ref in theme.image_refs() {
+        if normalize_image_path(&image_ref.path) == normalized {
+            check_image_ref(theme, &image_ref, image.width, image.height, &mut warnings);
+        }
+    }
+
+    theme.set_image(path, encode(&image, &encoding)?);
+    Ok(ImportReport { path: path.to_owned(), encoding, width: image.width, height: image.height, warnings })
+}
+
+/**
+ * Replace the image that an image property of a state refers to, following the property up to
+ * the part and the class like uxtheme does.
+ */
+pub fn import_image(
+    theme: &mut Theme,
+    class: &str,
+    part: Option<&str>,
+    state: Option<&str>,
+    property: &str,
+    data: &[u8],
+    options: &ImportOptions,
+) -> Result<ImportReport, Error> {
+    let info = find_image_property(property).ok_or_else(|| anyhow!("\"{}\" is not an image property.", property))?;
+    let Some(PropertyValue::FileName(path)) = theme.resolve_property(class, part, state, info.name) else {
+        return Err(anyhow!("{} is not set for this state.", info.name));
+    };
+    let path = path.clone();
+    replace_image(theme, &path, data, options)
+}
+
+/**
+ * Like import_image, with the image read from a file.
+ */
+pub fn import_image_file(
+    theme: &mut Theme,
+    class: &str,
+    part: Option<&str>,
+    state: Option<&str>,
+    property: &str,
+    file: &Path,
+    options: &ImportOptions,
+) -> Result<ImportReport, Error> {
+    let data = fs::read(file).with_context(|| anyhow!("Failed to read \"{}\"", file.display()))?;
+    import_image(theme, class, part, state, property, &data, options)
+        .with_context(|| anyhow!("Failed to import \"{}\"", file.display()))
+}
@@ -0,0 +1,259 @@
+/*
+ * Sizing margin detection.
+ *
+ * uxtheme stretches or tiles the middle of an image between its SizingMargins and draws the
+ * corners and edges as they are. Stretching is only lossless where the middle is uniform along
+ * the direction it is stretched in, so the sizing margins go around the widest band of identical
+ * columns and the tallest band of identical rows. For a strip, a band has to be uniform in every
+ * frame, as all frames share the margins.
+ *
+ * Content margins are proposed from the interior: the distance from each edge to where the
+ * border gives way to the fill color in the middle of the image.
+ *
+ * A proposal is a guess. Its confidence is low when the image has no uniform band, i.e. for a
+ * gradient that runs along the stretched direction, which any sizing margins would distort.
+ */
+
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use super::strip::{self, StripLayout};
+use super::{decode, DecodeOptions, RgbaImage};
+use crate::theme::image_properties::ImageRef;
+use crate::theme::schema::ST_TRUESIZE;
+use crate::theme::sysmetrics::BASE_DPI;
+use crate::theme::{Margins, PropertyValue, Theme};
+
+/**
+ * How far apart the channels of two pixels can be for them to count as the same color in the
+ * interior, which is often a slight gradient.
+ */
+const CONTENT_TOLERANCE: u8 = 24;
+
+#[derive(Clone, Copy, Debug)]
+pub struct MarginOptions {
+    /**
+     * How far apart the channels of two pixels can be for them to count as identical when
+     * looking for uniform bands, to allow for dithering.
+     */
+    pub tolerance: u8,
+}
+
+impl Default for MarginOptions {
+    fn default() -> Self {
+        Self { tolerance: 2 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MarginProposal {
+    /**
+     * In pixels of the image from detect_margins, and in 96 DPI units from propose_margins.
+     */
+    pub sizing: Margins,
+    pub content: Margins,
+    /**
+     * From 0 to 1: how much of the image the stretchable bands cover, for the axis with the
+     * narrower band. A band that covers half of the image or more counts as certain.
+     */
+    pub confidence: f32,
+}
+
+fn similar(a: [u8; 4], b: [u8; 4], tolerance: u8) -> bool {
+    // Fully transparent pixels are the same whatever their color channels hold.
+    if a[3] == 0 && b[3] == 0 {
+        return true;
+    }
+    a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= tolerance)
+}
+
+/**
+ * The longest run of identical lines (columns, or rows if `rows` is set) across all frames, as a
+ * start and an exclusive end. Ties go to the run closest to the middle.
+ */
+fn find_band(frames: &[RgbaImage], rows: bool, tolerance: u8) -> (u32, u32) {
+    let (length, breadth) = if rows { (frames[0].height, frames[0].width) } else { (frames[0].width, frames[0].height) };
+    let pixel = |frame: &RgbaImage, line: u32, offset: u32| if rows { frame.pixel(offset, line) } else { frame.pixel(line, offset) };
+    let same = |a: u32, b: u32| {
+        frames.iter().all(|frame| (0..breadth).all(|offset| similar(pixel(frame, a, offset), pixel(frame, b, offset), tolerance)))
+    };
+
+    let distance_to_middle = |(start, end): (u32, u32)| (start + end).abs_diff(length);
+    let mut best = (length / 2, length / 2 + 1);
+    let mut start = 0;
+    for line in 1..=length {
+        if line < length && same(line - 1, line) {
+            continue;
+        }
+        let run = (start, line);
+        let (run_length, best_length) = (run.1 - run.0, best.1 - best.0);
+        if run_length > best_length || run_length == best_length && distance_to_middle(run) < distance_to_middle(best) {
+            best = run;
+        }
+        start = line;
+    }
+    best
+}
+
+/**
+ * The distance from an edge to the first pixel of the fill color, along a line from that edge to
+ * the middle.
+ */
+fn inset(frame: &RgbaImage, fill: [u8; 4], points: impl Iterator<Item = (u32, u32)>) -> i32 {
+    let mut distance = 0;
+    for (x, y) in points {
+        let pixel = frame.pixel(x, y);
+        if pixel[3] != 0 && similar(pixel, fill, CONTENT_TOLERANCE) {
+            break;
+        }
+        distance += 1;
+    }
+    distance
+}
+
+/**
+ * Propose margins for an image, or for the frames of a strip, which must all be the same size.
+ */
+pub fn detect_margins(frames: &[RgbaImage], options: &MarginOptions) -> Result<MarginProposal, Error> {
+    let Some(first) = frames.first() else {
+        return Err(anyhow!("There are no frames to look at."));
+    };
+    let (width, height) = (first.width, first.height);
+    if width == 0 || height == 0 {
+        return Err(anyhow!("The image is empty."));
+    }
+    if frames.iter().any(|frame| (frame.width, frame.height) != (width, height)) {
+        return Err(anyhow!("The frames are not all the same size."));
+    }
+
+    let columns = find_band(frames, false, options.tolerance);
+    let rows = find_band(frames, true, options.tolerance);
+    let sizing = Margins {
+        left: columns.0 as i32,
+        right: (width - columns.1) as i32,
+        top: rows.0 as i32,
+        bottom: (height - rows.1) as i32,
+    };
+
+    let (middle_x, middle_y) = ((columns.0 + columns.1) / 2, (rows.0 + rows.1) / 2);
+    let mut content = Margins::default();
+    for frame in frames {
+        let fill = frame.pixel(middle_x, middle_y);
+        content.left = content.left.max(inset(frame, fill, (0..middle_x).map(|x| (x, middle_y))));
+        content.right = content.right.max(inset(frame, fill, (middle_x + 1..width).rev().map(|x| (x, middle_y))));
+        content.top = content.top.max(inset(frame, fill, (0..middle_y).map(|y| (middle_x, y))));
+        content.bottom = content.bottom.max(inset(frame, fill, (middle_y + 1..height).rev().map(|y| (middle_x, y))));
+    }
+
+    let coverage = |(start, end): (u32, u32), length: u32| ((end - start) as f32 * 2.0 / length as f32).min(1.0);
+    let confidence = coverage(columns, width).min(coverage(rows, height));
+    Ok(MarginProposal { sizing, content, confidence })
+}
+
+/**
+ * Scale margins in pixels of an image for a DPI to the 96 DPI units that the theme uses.
+ */
+fn unscale_margins(margins: Margins, dpi: u32) -> Margins {
+    let unscale = |value: i32| ((value as i64 * BASE_DPI as i64 + dpi as i64 / 2) / dpi as i64) as i32;
+    Margins {
+        left: unscale(margins.left),
+        right: unscale(margins.right),
+        top: unscale(margins.top),
+        bottom: unscale(margins.bottom),
+    }
+}
+
+/**
+ * Propose margins for the state that an image property belongs to, from each frame of its
+ * image. The margins are in 96 DPI units, like the theme's.
+ */
+pub fn propose_margins(theme: &Theme, image_ref: &ImageRef, options: &MarginOptions) -> Result<MarginProposal, Error> {
+    let data = theme.image(&image_ref.path).ok_or_else(|| anyhow!("The image \"{}\" is missing.", image_ref.path))?;
+    let decoded = decode(data, &DecodeOptions::default()).with_context(|| anyhow!("Failed to decode \"{}\"", image_ref.path))?;
+    let layout = StripLayout::of(theme, &image_ref.class, image_ref.part.as_deref(), image_ref.state.as_deref());
+    let frames = strip::split(&decoded.image, layout)?;
+
+    let proposal = detect_margins(&frames, options).with_context(|| anyhow!("Failed to analyze \"{}\"", image_ref.path))?;
+    let dpi = image_ref.dpi(theme);
+    Ok(MarginProposal {
+        sizing: unscale_margins(proposal.sizing, dpi),
+        content: unscale_margins(proposal.content, dpi),
+        confidence: proposal.confidence,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateMargins {
+    pub image_ref: ImageRef,
+    pub proposal: MarginProposal,
+    /**
+     * The margins that the state has now, including inherited ones.
+     */
+    pub sizing: Option<Margins>,
+    pub content: Option<Margins>,
+}
+
+impl StateMargins {
+    pub fn is_unchanged(&self) -> bool {
+        self.sizing == Some(self.proposal.sizing) && self.content == Some(self.proposal.content)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MarginReport {
+    pub states: Vec<StateMargins>,
+    /**
+     * The images that could not be analyzed, and why.
+     */
+    pub failed: Vec<(ImageRef, String)>,
+}
+
+/**
+ * Propose margins for every state that defines a stretched image, from its ImageFile or, without
+ * one, its lowest DPI variant. Glyphs and true size images are not stretched, so they are left
+ * out. An image that cannot be analyzed does not stop the others.
+ */
+pub fn propose_theme_margins(theme: &Theme, options: &MarginOptions) -> MarginReport {
+    let mut sources: Vec<ImageRef> = Vec::new();
+    for image_ref in theme.image_refs() {
+        let (class, part, state) = (image_ref.class.as_str(), image_ref.part.as_deref(), image_ref.state.as_deref());
+        if image_ref.glyph || matches!(theme.resolve_property(class, part, state, "SizingType"), Some(PropertyValue::Enum(ST_TRUESIZE))) {
+            continue;
+        }
+        let same_state = |other: &ImageRef| (&other.class, &other.part, &other.state) == (&image_ref.class, &image_ref.part, &image_ref.state);
+        match sources.iter_mut().find(|other| same_state(other)) {
+            Some(other) if image_ref.variant < other.variant => *other = image_ref,
+            Some(_) => {}
+            None => sources.push(image_ref),
+        }
+    }
+
+    let mut report = MarginReport::default();
+    for image_ref in sources {
+        let (class, part, state) = (image_ref.class.as_str(), image_ref.part.as_deref(), image_ref.state.as_deref());
+        let margins = |name| match theme.resolve_property(class, part, state, name) {
+            Some(PropertyValue::Margins(margins)) => Some(*margins),
+            _ => None,
+        };
+        match propose_margins(theme, &image_ref, options) {
+            Ok(proposal) => report.states.push(StateMargins {
+                proposal,
+                sizing: margins("SizingMargins"),
+                content: margins("ContentMargins"),
+                image_ref,
+            }),
+            Err(error) => report.failed.push((image_ref, format!("{:#}", error))),
+        }
+    }
+    report
+}
+
+/**
+ * Set the proposed SizingMargins and ContentMargins on a state.
+ */
+pub fn apply_margins(theme: &mut Theme, class: &str, part: Option<&str>, state: Option<&str>, proposal: &MarginProposal) {
+    let level = theme.state_mut(class, part, state);
+    level.set_property("SizingMargins", PropertyValue::Margins(proposal.sizing));
+    level.set_property("ContentMargins", PropertyValue::Margins(proposal.content));
+}
@@ -0,0 +1,286 @@
+/*
+ * Theme images.
+ *
+ * Visual styles store their images as PNGs or as 24 and 32-bit BMPs, and the alpha channel of a
+ * 32-bit BMP is sometimes premultiplied. Everything that works with pixels (rendering, export,
+ * editing) uses one representation instead: an 8-bit RGBA buffer with straight alpha.
+ *
+ * Decoding records how the image was stored, so that an edited image can be saved the same way,
+ * and an image that was not edited is saved as exactly the bytes that it was read from.
+ */
+
+pub mod bmp;
+pub mod png;
+pub mod atlas;
+pub mod strip;
+pub mod export;
+pub mod import;
+pub mod margins;
+pub mod resample;
+pub mod dpi;
+pub mod transparency;
+pub mod dedup;
+pub mod png_optimize;
+pub mod recolor;
+
+use anyhow::Error;
+use anyhow::anyhow;
+use sha2::{Digest, Sha256};
+
+use crate::theme::{Color, Rect};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    /**
+     * Rows from top to bottom, four bytes per pixel in R, G, B, A order, straight alpha.
+     */
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, pixels: vec![0; width as usize * height as usize * 4] }
+    }
+
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, Error> {
+        if pixels.len() != width as usize * height as usize * 4 {
+            return Err(anyhow!("{} bytes of pixels do not make a {}x{} image.", pixels.len(), width, height));
+        }
+        Ok(Self { width, height, pixels })
+    }
+
+    fn offset(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let offset = self.offset(x, y);
+        self.pixels[offset..offset + 4].try_into().unwrap()
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: [u8; 4]) {
+        let offset = self.offset(x, y);
+        self.pixels[offset..offset + 4].copy_from_slice(&pixel);
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.pixels.chunks_exact(4).all(|pixel| pixel[3] == 255)
+    }
+
+    pub fn is_grayscale(&self) -> bool {
+        self.pixels.chunks_exact(4).all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2])
+    }
+
+    /**
+     * Copy part of the image, i.e. one frame of a strip or one image of an atlas. The rectangle
+     * is clipped to the image.
+     */
+    pub fn crop(&self, rect: Rect) -> RgbaImage {
+        let left = rect.left.clamp(0, self.width as i32) as u32;
+        let top = rect.top.clamp(0, self.height as i32) as u32;
+        let right = rect.right.clamp(left as i32, self.width as i32) as u32;
+        let bottom = rect.bottom.clamp(top as i32, self.height as i32) as u32;
+
+        let mut result = RgbaImage::new(right - left, bottom - top);
+        let row_bytes = result.width as usize * 4;
+        for y in top..bottom {
+            let source = self.offset(left, y);
+            let target = result.offset(0, y - top);
+            result.pixels[target..target + row_bytes].copy_from_slice(&self.pixels[source..source + row_bytes]);
+        }
+        result
+    }
+
+    /**
+     * Copy another image into this one at a position, replacing the pixels (no blending). Pixels
+     * that fall outside of this image are dropped.
+     */
+    pub fn paste(&mut self, image: &RgbaImage, x: i32, y: i32) {
+        for source_y in 0..image.height {
+            let target_y = y + source_y as i32;
+            if target_y < 0 || target_y >= self.height as i32 {
+                continue;
+            }
+            for source_x in 0..image.width {
+                let target_x = x + source_x as i32;
+                if target_x >= 0 && target_x < self.width as i32 {
+                    self.set_pixel(target_x as u32, target_y as u32, image.pixel(source_x, source_y));
+                }
+            }
+        }
+    }
+
+    pub fn fill(&mut self, color: Color, alpha: u8) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[color.r, color.g, color.b, alpha]);
+        }
+    }
+}
+
+/**
+ * Premultiply a straight alpha pixel.
+ */
+pub fn premultiply(pixel: [u8; 4]) -> [u8; 4] {
+    let alpha = pixel[3] as u32;
+    let channel = |value: u8| ((value as u32 * alpha + 127) / 255) as u8;
+    [channel(pixel[0]), channel(pixel[1]), channel(pixel[2]), pixel[3]]
+}
+
+/**
+ * Undo premultiplication. Premultiplying the result again gives back the original pixel.
+ */
+pub fn unpremultiply(pixel: [u8; 4]) -> [u8; 4] {
+    let alpha = pixel[3] as u32;
+    if alpha == 0 {
+        return [0, 0, 0, 0];
+    }
+    let channel = |value: u8| ((value as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+    [channel(pixel[0]), channel(pixel[1]), channel(pixel[2]), pixel[3]]
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BmpAlpha {
+    /**
+     * Straight alpha, which is what uxtheme expects. If the alpha channel is all zero it is
+     * ignored instead, as older tools write 32-bit images without alpha that way.
+     */
+    #[default]
+    Auto,
+    /**
+     * The fourth byte is not alpha, and the image is opaque.
+     */
+    Ignored,
+    Straight,
+    /**
+     * The colors are multiplied by the alpha, as some theme editors store them. This cannot be
+     * told from the pixels (a dark translucent shadow looks the same either way), so it is
+     * only used when asked for.
+     */
+    Premultiplied,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DecodeOptions {
+    pub bmp_alpha: BmpAlpha,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PngColorType {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+    Indexed,
+}
+
+/**
+ * How an image was stored. Encoding an image with the same encoding stores it the same way again.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageEncoding {
+    Bmp {
+        /**
+         * 24 or 32.
+         */
+        bits: u16,
+        /**
+         * What the fourth byte of a 32-bit BMP was taken to be. Never Auto.
+         */
+        alpha: BmpAlpha,
+        top_down: bool,
+        /**
+         * Whether the data had a BITMAPFILEHEADER, or was a bare DIB as stored in resources.
+         */
+        file_header: bool,
+    },
+    Png {
+        color_type: PngColorType,
+        bit_depth: u8,
+        interlaced: bool,
+    },
+}
+
+impl ImageEncoding {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Bmp { .. } => "bmp",
+            Self::Png { .. } => "png",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedImage {
+    pub image: RgbaImage,
+    pub encoding: ImageEncoding,
+    /**
+     * The bytes that the image was decoded from, and a hash of the pixels that they decoded to.
+     * While the pixels are unchanged, encoding gives back exactly these bytes.
+     */
+    pub original: Vec<u8>,
+    original_pixels: [u8; 32],
+}
+
+fn pixel_hash(image: &RgbaImage) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(image.width.to_le_bytes());
+    hasher.update(image.height.to_le_bytes());
+    hasher.update(&image.pixels);
+    hasher.finalize().into()
+}
+
+pub fn is_png(data: &[u8]) -> bool {
+    data.starts_with(b"\x89PNG\r\n\x1A\n")
+}
+
+pub fn is_bmp(data: &[u8]) -> bool {
+    data.starts_with(b"BM") || bmp::is_dib(data)
+}
+
+/**
+ * Decode a PNG or BMP image.
+ */
+pub fn decode(data: &[u8], options: &DecodeOptions) -> Result<DecodedImage, Error> {
+    let (image, encoding) = if is_png(data) {
+        png::decode(data)?
+    }
+    else if is_bmp(data) {
+        bmp::decode(data, options.bmp_alpha)?
+    }
+    else {
+        return Err(anyhow!("Unknown image format."));
+    };
+
+    let original_pixels = pixel_hash(&image);
+    Ok(DecodedImage { image, encoding, original: data.to_vec(), original_pixels })
+}
+
+/**
+ * Encode an image in a given encoding. Encodings that cannot hold the image (i.e. a 24-bit BMP
+ * for an image with transparency, or a grayscale PNG for a color image) are widened as needed.
+ */
+pub fn encode(image: &RgbaImage, encoding: &ImageEncoding) -> Result<Vec<u8>, Error> {
+    match encoding {
+        ImageEncoding::Bmp { .. } => bmp::encode(image, encoding),
+        ImageEncoding::Png { .. } => png::encode(image, encoding),
+    }
+}
+
+impl DecodedImage {
+    pub fn is_modified(&self) -> bool {
+        pixel_hash(&self.image) != self.original_pixels
+    }
+
+    /**
+     * Encode the image the way it was stored: the original bytes if it is unchanged, and the
+     * original encoding otherwise.
+     */
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        if !self.is_modified() {
+            return Ok(self.original.clone());
+        }
+        encode(&self.image, &self.encoding)
+    }
+}
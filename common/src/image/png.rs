@@ -0,0 +1,227 @@
+/*
+ * PNG images, in every color type and bit depth. Interlaced images are written without
+ * interlacing, which the png crate does not support.
+ */
+
+use std::collections::HashMap;
+use anyhow::Error;
+use anyhow::anyhow;
+use ::png::{BitDepth, ColorType, Transformations};
+
+use super::{ImageEncoding, PngColorType, RgbaImage};
+
+pub fn decode(data: &[u8]) -> Result<(RgbaImage, ImageEncoding), Error> {
+    let mut decoder = ::png::Decoder::new(data);
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+
+    let info = reader.info();
+    let color_type = match info.color_type {
+        ColorType::Grayscale => PngColorType::Gray,
+        ColorType::GrayscaleAlpha => PngColorType::GrayAlpha,
+        ColorType::Rgb => PngColorType::Rgb,
+        ColorType::Rgba => PngColorType::Rgba,
+        ColorType::Indexed => PngColorType::Indexed,
+    };
+    let encoding = ImageEncoding::Png { color_type, bit_depth: info.bit_depth as u8, interlaced: info.interlaced };
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer)?;
+    let (output_color, _) = reader.output_color_type();
+
+    let mut image = RgbaImage::new(frame.width, frame.height);
+    for y in 0..frame.height {
+        let row = &buffer[y as usize * frame.line_size..];
+        for x in 0..frame.width as usize {
+            let pixel = match output_color {
+                ColorType::Grayscale => [row[x], row[x], row[x], 255],
+                ColorType::GrayscaleAlpha => [row[x * 2], row[x * 2], row[x * 2], row[x * 2 + 1]],
+                ColorType::Rgb => [row[x * 3], row[x * 3 + 1], row[x * 3 + 2], 255],
+                ColorType::Rgba => row[x * 4..x * 4 + 4].try_into().unwrap(),
+                ColorType::Indexed => return Err(anyhow!("The PNG decoder did not expand the palette.")),
+            };
+            image.set_pixel(x as u32, y, pixel);
+        }
+    }
+
+    Ok((image, encoding))
+}
+
+/**
+ * Pack 8-bit samples into a row of a smaller bit depth, most significant bits first.
+ */
+pub fn pack_row(samples: impl Iterator<Item = u8>, depth: u8, output: &mut Vec<u8>) {
+    let per_byte = 8 / depth;
+    let mut byte = 0;
+    let mut count = 0;
+    for sample in samples {
+        byte |= sample << (8 - depth * (count + 1));
+        count += 1;
+        if count == per_byte {
+            output.push(byte);
+            byte = 0;
+            count = 0;
+        }
+    }
+    if count > 0 {
+        output.push(byte);
+    }
+}
+
+pub fn bit_depth(depth: u8) -> BitDepth {
+    match depth {
+        1 => BitDepth::One,
+        2 => BitDepth::Two,
+        4 => BitDepth::Four,
+        16 => BitDepth::Sixteen,
+        _ => BitDepth::Eight,
+    }
+}
+
+/**
+ * The smallest of the bit depths 1, 2, 4 and 8 that holds every value exactly, for a grayscale
+ * image or the indices of a palette.
+ */
+pub fn smallest_depth(fits: impl Fn(u8) -> bool) -> u8 {
+    [1, 2, 4].into_iter().find(|depth| fits(*depth)).unwrap_or(8)
+}
+
+pub fn encode(image: &RgbaImage, encoding: &ImageEncoding) -> Result<Vec<u8>, Error> {
+    let &ImageEncoding::Png { color_type, bit_depth: requested_depth, .. } = encoding else {
+        return Err(anyhow!("Not a PNG encoding."));
+    };
+
+    let opaque = image.is_opaque();
+    let grayscale = image.is_grayscale();
+
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    if color_type == PngColorType::Indexed {
+        let mut seen = HashMap::new();
+        for pixel in image.pixels.chunks_exact(4) {
+            let pixel: [u8; 4] = pixel.try_into().unwrap();
+            if !seen.contains_key(&pixel) && seen.len() <= 256 {
+                seen.insert(pixel, palette.len());
+                palette.push(pixel);
+            }
+        }
+    }
+
+    // Widen the color type until it can hold the image.
+    let color_type = match color_type {
+        PngColorType::Indexed if palette.len() <= 256 => PngColorType::Indexed,
+        PngColorType::Gray if grayscale && opaque => PngColorType::Gray,
+        PngColorType::Gray | PngColorType::GrayAlpha if grayscale => PngColorType::GrayAlpha,
+        PngColorType::Gray | PngColorType::Rgb if opaque => PngColorType::Rgb,
+        _ => PngColorType::Rgba,
+    };
+
+    let samples = |pixel: &[u8]| -> Vec<u8> {
+        match color_type {
+            PngColorType::Gray => vec![pixel[0]],
+            PngColorType::GrayAlpha => vec![pixel[0], pixel[3]],
+            PngColorType::Rgb => pixel[..3].to_vec(),
+            _ => pixel.to_vec(),
+        }
+    };
+
+    let depth = match color_type {
+        PngColorType::Indexed => {
+            let depth = smallest_depth(|depth| palette.len() <= 1 << depth);
+            depth.max(requested_depth.min(8))
+        }
+        PngColorType::Gray if requested_depth < 8 => {
+            let depth = smallest_depth(|depth| {
+                let step = 255 / ((1u32 << depth) - 1);
+                image.pixels.chunks_exact(4).all(|pixel| (pixel[0] as u32).is_multiple_of(step))
+            });
+            depth.max(requested_depth)
+        }
+        _ if requested_depth == 16 => 16,
+        _ => 8,
+    };
+
+    let mut data = Vec::new();
+    let index_of: HashMap<[u8; 4], u8> = palette.iter().enumerate().map(|(index, pixel)| (*pixel, index as u8)).collect();
+    for y in 0..image.height {
+        let row = &image.pixels[y as usize * image.width as usize * 4..(y as usize + 1) * image.width as usize * 4];
+        match (color_type, depth) {
+            (PngColorType::Indexed, _) => {
+                let indices = row.chunks_exact(4).map(|pixel| index_of[&<[u8; 4]>::try_from(pixel).unwrap()]);
+                if depth == 8 {
+                    data.extend(indices);
+                }
+                else {
+                    pack_row(indices, depth, &mut data);
+                }
+            }
+            (PngColorType::Gray, 1 | 2 | 4) => {
+                let step = 255 / ((1u8 << depth) - 1);
+                pack_row(row.chunks_exact(4).map(|pixel| pixel[0] / step), depth, &mut data);
+            }
+            (_, 16) => {
+                for pixel in row.chunks_exact(4) {
+                    for sample in samples(pixel) {
+                        data.extend_from_slice(&[sample, sample]);
+                    }
+                }
+            }
+            _ => {
+                for pixel in row.chunks_exact(4) {
+                    data.extend(samples(pixel));
+                }
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    {
+        let mut encoder = ::png::Encoder::new(&mut result, image.width, image.height);
+        encoder.set_color(match color_type {
+            PngColorType::Gray => ColorType::Grayscale,
+            PngColorType::GrayAlpha => ColorType::GrayscaleAlpha,
+            PngColorType::Rgb => ColorType::Rgb,
+            PngColorType::Rgba => ColorType::Rgba,
+            PngColorType::Indexed => ColorType::Indexed,
+        });
+        encoder.set_depth(bit_depth(depth));
+        encoder.set_compression(::png::Compression::Best);
+        if color_type == PngColorType::Indexed {
+            encoder.set_palette(palette.iter().flat_map(|pixel| pixel[..3].to_vec()).collect::<Vec<u8>>());
+            let alphas: Vec<u8> = palette.iter().map(|pixel| pixel[3]).collect();
+            if let Some(last) = alphas.iter().rposition(|alpha| *alpha != 255) {
+                encoder.set_trns(alphas[..=last].to_vec());
+            }
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&data)?;
+    }
+    Ok(result)
+}
+
+/**
+ * Encode frames of the same size as an animated PNG that loops forever, showing each frame for
+ * delay_numerator / delay_denominator seconds. Viewers without APNG support show the first frame.
+ */
+pub fn encode_animated(frames: &[RgbaImage], delay_numerator: u16, delay_denominator: u16) -> Result<Vec<u8>, Error> {
+    let Some(first) = frames.first() else {
+        return Err(anyhow!("An animation needs at least one frame."));
+    };
+    if frames.iter().any(|frame| frame.width != first.width || frame.height != first.height) {
+        return Err(anyhow!("The frames of an animation must all be {}x{}.", first.width, first.height));
+    }
+
+    let mut result = Vec::new();
+    {
+        let mut encoder = ::png::Encoder::new(&mut result, first.width, first.height);
+        encoder.set_color(ColorType::Rgba);
+        encoder.set_depth(BitDepth::Eight);
+        encoder.set_compression(::png::Compression::Best);
+        encoder.set_animated(frames.len() as u32, 0)?;
+        encoder.set_frame_delay(delay_numerator, delay_denominator)?;
+        let mut writer = encoder.write_header()?;
+        for frame in frames {
+            writer.write_image_data(&frame.pixels)?;
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,202 @@
+/*
+ * Lossless PNG optimization.
+ *
+ * Rewrites PNG images as small as they go without changing a pixel: every color type and bit
+ * depth that holds the image (a palette, grayscale, or no alpha channel) is encoded with each of
+ * the png crate's scanline filters and its adaptive filtering, at its best compression, and the
+ * smallest result wins. Ancillary chunks (text, gamma, color profiles, timestamps) are left out,
+ * as uxtheme does not use them.
+ *
+ * Every result is decoded again and compared with the original pixels before it is used, and
+ * the original is kept unless the result is smaller. 16-bit images are left alone, since they
+ * are decoded to 8 bits and could not be compared exactly.
+ */
+
+use std::collections::{HashMap, HashSet};
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use ::png::{AdaptiveFilterType, ColorType, Compression, FilterType};
+
+use super::png::{bit_depth, pack_row, smallest_depth};
+use super::{is_png, ImageEncoding, RgbaImage};
+use crate::theme::Theme;
+
+/**
+ * The filter strategies to try: adaptive filtering, which picks a filter per row, and each
+ * filter on every row.
+ */
+const FILTERS: [(AdaptiveFilterType, FilterType); 6] = [
+    (AdaptiveFilterType::Adaptive, FilterType::Sub),
+    (AdaptiveFilterType::NonAdaptive, FilterType::NoFilter),
+    (AdaptiveFilterType::NonAdaptive, FilterType::Sub),
+    (AdaptiveFilterType::NonAdaptive, FilterType::Up),
+    (AdaptiveFilterType::NonAdaptive, FilterType::Avg),
+    (AdaptiveFilterType::NonAdaptive, FilterType::Paeth),
+];
+
+/**
+ * An image laid out for one color type and bit depth, as the png crate takes it.
+ */
+struct Layout {
+    color_type: ColorType,
+    depth: u8,
+    data: Vec<u8>,
+    palette: Vec<[u8; 4]>,
+}
+
+fn rows(image: &RgbaImage, depth: u8, samples: impl Fn(&[u8], &mut Vec<u8>)) -> Vec<u8> {
+    let mut data = Vec::new();
+    for row in image.pixels.chunks_exact(image.width as usize * 4) {
+        let mut unpacked = Vec::new();
+        row.chunks_exact(4).for_each(|pixel| samples(pixel, &mut unpacked));
+        if depth == 8 {
+            data.extend(unpacked);
+        }
+        else {
+            pack_row(unpacked.into_iter(), depth, &mut data);
+        }
+    }
+    data
+}
+
+/**
+ * Every lossless layout for an image.
+ */
+fn layouts(image: &RgbaImage) -> Vec<Layout> {
+    let opaque = image.is_opaque();
+    let grayscale = image.is_grayscale();
+    let mut layouts = Vec::new();
+
+    // Partly transparent entries go first, so that the tRNS chunk can stop early.
+    let mut palette: Vec<[u8; 4]> = Vec::new();
+    let mut seen = HashSet::new();
+    for pixel in image.pixels.chunks_exact(4) {
+        let pixel: [u8; 4] = pixel.try_into().unwrap();
+        if palette.len() > 256 {
+            break;
+        }
+        if seen.insert(pixel) {
+            palette.push(pixel);
+        }
+    }
+    if palette.len() <= 256 {
+        palette.sort_by_key(|pixel| pixel[3] == 255);
+        let index_of: HashMap<[u8; 4], u8> = palette.iter().enumerate().map(|(index, pixel)| (*pixel, index as u8)).collect();
+        let depth = smallest_depth(|depth| palette.len() <= 1 << depth);
+        layouts.push(Layout {
+            color_type: ColorType::Indexed,
+            depth,
+            data: rows(image, depth, |pixel, output| output.push(index_of[&<[u8; 4]>::try_from(pixel).unwrap()])),
+            palette,
+        });
+    }
+
+    if grayscale && opaque {
+        let depth = smallest_depth(|depth| {
+            let step = 255 / ((1u32 << depth) - 1);
+            image.pixels.chunks_exact(4).all(|pixel| (pixel[0] as u32).is_multiple_of(step))
+        });
+        let step = 255 / ((1u32 << depth) - 1) as u8;
+        layouts.push(Layout {
+            color_type: ColorType::Grayscale,
+            depth,
+            data: rows(image, depth, |pixel, output| output.push(pixel[0] / step)),
+            palette: Vec::new(),
+        });
+    }
+    if grayscale {
+        layouts.push(Layout {
+            color_type: ColorType::GrayscaleAlpha,
+            depth: 8,
+            data: rows(image, 8, |pixel, output| output.extend_from_slice(&[pixel[0], pixel[3]])),
+            palette: Vec::new(),
+        });
+    }
+    if opaque {
+        layouts.push(Layout {
+            color_type: ColorType::Rgb,
+            depth: 8,
+            data: rows(image, 8, |pixel, output| output.extend_from_slice(&pixel[..3])),
+            palette: Vec::new(),
+        });
+    }
+    layouts.push(Layout {
+        color_type: ColorType::Rgba,
+        depth: 8,
+        data: image.pixels.clone(),
+        palette: Vec::new(),
+    });
+    layouts
+}
+
+fn encode(image: &RgbaImage, layout: &Layout, adaptive: AdaptiveFilterType, filter: FilterType) -> Result<Vec<u8>, Error> {
+    let mut result = Vec::new();
+    {
+        let mut encoder = ::png::Encoder::new(&mut result, image.width, image.height);
+        encoder.set_color(layout.color_type);
+        encoder.set_depth(bit_depth(layout.depth));
+        encoder.set_compression(Compression::Best);
+        encoder.set_adaptive_filter(adaptive);
+        encoder.set_filter(filter);
+        if layout.color_type == ColorType::Indexed {
+            encoder.set_palette(layout.palette.iter().flat_map(|pixel| pixel[..3].to_vec()).collect::<Vec<u8>>());
+            let transparent = layout.palette.iter().take_while(|pixel| pixel[3] != 255).count();
+            if transparent > 0 {
+                encoder.set_trns(layout.palette[..transparent].iter().map(|pixel| pixel[3]).collect::<Vec<u8>>());
+            }
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&layout.data)?;
+    }
+    Ok(result)
+}
+
+/**
+ * Recompress a PNG image. Returns None if the image cannot be made smaller.
+ */
+pub fn optimize_png(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let (image, encoding) = super::png::decode(data)?;
+    if matches!(encoding, ImageEncoding::Png { bit_depth: 16, .. }) || image.width == 0 || image.height == 0 {
+        return Ok(None);
+    }
+
+    let mut best: Option<Vec<u8>> = None;
+    for layout in layouts(&image) {
+        for (adaptive, filter) in FILTERS {
+            let candidate = encode(&image, &layout, adaptive, filter)?;
+            if candidate.len() >= best.as_ref().map_or(data.len(), Vec::len) {
+                continue;
+            }
+            // Only use a result that decodes to exactly the same pixels.
+            if super::png::decode(&candidate).is_ok_and(|(pixels, _)| pixels == image) {
+                best = Some(candidate);
+            }
+        }
+    }
+    Ok(best)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OptimizedImage {
+    pub path: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+/**
+ * Recompress every PNG image of a theme, and list the ones that got smaller.
+ */
+pub fn optimize_theme(theme: &mut Theme) -> Result<Vec<OptimizedImage>, Error> {
+    let mut optimized = Vec::new();
+    for (path, data) in &mut theme.images {
+        if !is_png(data) {
+            continue;
+        }
+        if let Some(smaller) = optimize_png(data).with_context(|| anyhow!("Failed to optimize \"{}\"", path))? {
+            optimized.push(OptimizedImage { path: path.clone(), before: data.len(), after: smaller.len() });
+            *data = smaller;
+        }
+    }
+    Ok(optimized)
+}
@@ -0,0 +1,285 @@
+/*
+ * Whole-theme recoloring.
+ *
+ * Applies one color transform to every image and every Color property of a theme, i.e. to turn
+ * a blue theme into a green or graphite one. The transform is either a hue, saturation and
+ * lightness adjustment or a set of curves, and can be limited to some classes.
+ *
+ * Color keys are left alone, both the TransparentColor properties and the key pixels of the
+ * images that use them, as the key would stop matching otherwise. An image that is also used by
+ * classes that are not recolored is copied, and only the recolored classes use the copy.
+ *
+ * The theme model, like the visual styles that Kanaya packs, holds one color variant (the
+ * VARIANT/NORMAL resource), so recoloring replaces the theme's colors; to keep the original,
+ * recolor a copy and save it under another name. Visual styles can carry several color variants
+ * in one file, like Luna's NormalColor, HomeStead and Metallic, and a recolored copy is what such
+ * a variant would hold, but packing more than one variant into a file is not supported yet.
+ */
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::transparency::color_key;
+use super::{decode, DecodeOptions};
+use crate::theme::{normalize_image_path, parts, Color, PropertyValue, Theme};
+
+/**
+ * Colors less saturated than this count as neutral.
+ */
+pub const NEUTRAL_SATURATION: f32 = 0.12;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorTransform {
+    /**
+     * Rotate the hue by a number of degrees, and adjust the saturation and lightness by -1 to
+     * 1: negative values move toward grey and black, positive ones toward full saturation and
+     * white.
+     */
+    Hsl { hue: f32, saturation: f32, lightness: f32 },
+    /**
+     * Map each channel through a lookup table.
+     */
+    Curves { red: Box<[u8; 256]>, green: Box<[u8; 256]>, blue: Box<[u8; 256]> },
+}
+
+/**
+ * A lookup table that interpolates linearly between points, i.e. [(0, 0), (128, 96), (255, 255)]
+ * to darken the midtones. Values outside of the first and last point keep their value.
+ */
+pub fn curve(points: &[(u8, u8)]) -> [u8; 256] {
+    let mut points = points.to_vec();
+    points.sort();
+    let mut table = [0; 256];
+    for (value, entry) in table.iter_mut().enumerate() {
+        let value = value as u8;
+        let after = points.iter().position(|(x, _)| *x >= value);
+        *entry = match after {
+            None => value,
+            Some(0) if points[0].0 > value => value,
+            Some(index) if points[index].0 == value => points[index].1,
+            Some(index) => {
+                let ((x0, y0), (x1, y1)) = (points[index - 1], points[index]);
+                let t = (value - x0) as f32 / (x1 - x0) as f32;
+                (y0 as f32 + (y1 as f32 - y0 as f32) * t).round() as u8
+            }
+        };
+    }
+    table
+}
+
+fn to_hsl(color: Color) -> (f32, f32, f32) {
+    let (r, g, b) = (color.r as f32 / 255.0, color.g as f32 / 255.0, color.b as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    let delta = max - min;
+    if delta < 1e-6 {
+        return (0.0, 0.0, lightness);
+    }
+
+    let saturation = delta / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    }
+    else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    }
+    else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    (hue, saturation.min(1.0), lightness)
+}
+
+fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Color {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let channel = |value: f32| ((value + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    Color::new(channel(r), channel(g), channel(b))
+}
+
+/**
+ * Move a value in 0 to 1 toward 0 or toward 1 by an amount in -1 to 1.
+ */
+fn adjust(value: f32, amount: f32) -> f32 {
+    let amount = amount.clamp(-1.0, 1.0);
+    if amount < 0.0 { value * (1.0 + amount) } else { value + (1.0 - value) * amount }
+}
+
+impl ColorTransform {
+    pub fn apply(&self, color: Color) -> Color {
+        match self {
+            Self::Hsl { hue, saturation, lightness } => {
+                let (h, s, l) = to_hsl(color);
+                from_hsl(h + hue, adjust(s, *saturation), adjust(l, *lightness))
+            }
+            Self::Curves { red, green, blue } => Color::new(red[color.r as usize], green[color.g as usize], blue[color.b as usize]),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RecolorOptions {
+    pub transform: ColorTransform,
+    /**
+     * The classes to recolor, or every class if empty. Names without an application prefix
+     * also match the classes that have one.
+     */
+    pub include_classes: Vec<String>,
+    pub exclude_classes: Vec<String>,
+    /**
+     * Leave greys alone, and fade the transform in for colors that are nearly grey.
+     */
+    pub keep_neutrals: bool,
+}
+
+impl RecolorOptions {
+    pub fn new(transform: ColorTransform) -> Self {
+        Self { transform, include_classes: Vec::new(), exclude_classes: Vec::new(), keep_neutrals: false }
+    }
+
+    fn matches(names: &[String], class: &str) -> bool {
+        names.iter().any(|name| name.eq_ignore_ascii_case(class) || name.eq_ignore_ascii_case(parts::base_class_name(class)))
+    }
+
+    pub fn includes_class(&self, class: &str) -> bool {
+        (self.include_classes.is_empty() || Self::matches(&self.include_classes, class))
+            && !Self::matches(&self.exclude_classes, class)
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        let result = self.transform.apply(color);
+        if !self.keep_neutrals {
+            return result;
+        }
+        let weight = (to_hsl(color).1 / NEUTRAL_SATURATION).min(1.0);
+        let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * weight).round() as u8;
+        Color::new(mix(color.r, result.r), mix(color.g, result.g), mix(color.b, result.b))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RecolorReport {
+    pub properties: usize,
+    pub images: Vec<String>,
+    /**
+     * Images that were copied because classes that are not recolored use them too, with the
+     * path of the copy.
+     */
+    pub copied: Vec<(String, String)>,
+    /**
+     * Images that could not be recolored and were left alone, and why.
+     */
+    pub skipped: Vec<(String, String)>,
+}
+
+fn copy_path(theme: &Theme, path: &str) -> String {
+    let name_start = path.rfind(['\\', '/']).map_or(0, |i| i + 1);
+    let (stem, extension) = match path[name_start..].rfind('.') {
+        Some(dot) => path.split_at(name_start + dot),
+        None => (path, ""),
+    };
+    let mut candidate = format!("{}_Recolored{}", stem, extension);
+    let mut number = 2;
+    while theme.image(&candidate).is_some() {
+        candidate = format!("{}_Recolored{}{}", stem, number, extension);
+        number += 1;
+    }
+    candidate
+}
+
+/**
+ * Recolor a theme in place. Images that cannot be decoded are left alone.
+ */
+pub fn recolor(theme: &mut Theme, options: &RecolorOptions) -> RecolorReport {
+    let mut report = RecolorReport::default();
+
+    // Which images the recolored classes use, with their color keys, and which are shared with
+    // classes that are not recolored.
+    let mut keys: BTreeMap<String, (String, BTreeSet<Color>)> = BTreeMap::new();
+    let mut shared = BTreeSet::new();
+    for image_ref in theme.image_refs() {
+        let path = normalize_image_path(&image_ref.path);
+        if !options.includes_class(&image_ref.class) {
+            shared.insert(path);
+            continue;
+        }
+        let key = color_key(theme, &image_ref.class, image_ref.part.as_deref(), image_ref.state.as_deref(), image_ref.glyph);
+        let entry = keys.entry(path).or_insert_with(|| (image_ref.path.clone(), BTreeSet::new()));
+        entry.1.extend(key);
+    }
+
+    let mut renamed = BTreeMap::new();
+    for (normalized, (path, keys)) in &keys {
+        let Some(data) = theme.image(path) else {
+            continue;
+        };
+        let recolored = decode(data, &DecodeOptions::default()).and_then(|mut decoded| {
+            let mut cache = HashMap::new();
+            for pixel in decoded.image.pixels.chunks_exact_mut(4) {
+                let color = Color::new(pixel[0], pixel[1], pixel[2]);
+                if pixel[3] == 0 || keys.contains(&color) {
+                    continue;
+                }
+                let result = *cache.entry(color).or_insert_with(|| options.apply(color));
+                pixel[..3].copy_from_slice(&[result.r, result.g, result.b]);
+            }
+            decoded.encode()
+        });
+        let data = match recolored {
+            Ok(data) => data,
+            Err(error) => {
+                report.skipped.push((path.clone(), format!("{:#}", error)));
+                continue;
+            }
+        };
+
+        let target = if shared.contains(normalized) {
+            let copy = copy_path(theme, path);
+            report.copied.push((path.clone(), copy.clone()));
+            renamed.insert(normalized.clone(), copy.clone());
+            copy
+        }
+        else {
+            path.clone()
+        };
+        theme.set_image(&target, data);
+        report.images.push(target);
+    }
+
+    for class in &mut theme.classes {
+        if !options.includes_class(&class.name) {
+            continue;
+        }
+        for part in &mut class.parts {
+            for state in &mut part.states {
+                for property in &mut state.properties {
+                    match &mut property.value {
+                        // Color keys have to keep matching the key pixels of the images.
+                        PropertyValue::Color(_) if property.name.to_lowercase().ends_with("transparentcolor") => {}
+                        PropertyValue::Color(color) => {
+                            *color = options.apply(*color);
+                            report.properties += 1;
+                        }
+                        PropertyValue::FileName(path) => {
+                            if let Some(copy) = renamed.get(&normalize_image_path(path)) {
+                                *path = copy.clone();
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    report
+}
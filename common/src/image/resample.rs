@@ -0,0 +1,160 @@
+/*
+ * Image resampling.
+ *
+ * Images are scaled with a separable Lanczos-3 filter in premultiplied alpha, so that the color
+ * of transparent pixels does not bleed into their neighbours. When shrinking, the filter is
+ * widened by the scale factor so that every source pixel contributes to the result.
+ */
+
+use std::f32::consts::PI;
+
+use super::RgbaImage;
+use crate::theme::{Margins, Rect};
+
+const LOBES: f32 = 3.0;
+
+fn lanczos(x: f32) -> f32 {
+    let x = x.abs();
+    if x < 1e-6 {
+        1.0
+    }
+    else if x < LOBES {
+        let px = PI * x;
+        LOBES * px.sin() * (px / LOBES).sin() / (px * px)
+    }
+    else {
+        0.0
+    }
+}
+
+/**
+ * The filter weights for each target pixel along one axis, as the first source pixel and the
+ * weights from there. Weights past the edge of the image are left out, and the rest normalized.
+ */
+fn axis_weights(source: u32, target: u32) -> Vec<(usize, Vec<f32>)> {
+    let scale = target as f32 / source as f32;
+    let support = (1.0 / scale).max(1.0);
+    let radius = LOBES * support;
+
+    (0..target).map(|i| {
+        let center = (i as f32 + 0.5) / scale;
+        let start = ((center - radius).floor().max(0.0) as usize).min(source as usize - 1);
+        let end = ((center + radius).ceil() as usize).clamp(start + 1, source as usize);
+        let mut weights: Vec<f32> = (start..end).map(|j| lanczos((j as f32 + 0.5 - center) / support)).collect();
+        let sum: f32 = weights.iter().sum();
+        if sum.abs() > 1e-6 {
+            weights.iter_mut().for_each(|weight| *weight /= sum);
+        }
+        else {
+            // Only possible far outside of the image, which the clamping above prevents.
+            weights = vec![0.0; weights.len()];
+            weights[0] = 1.0;
+        }
+        (start, weights)
+    }).collect()
+}
+
+/**
+ * Scale an image to a new size.
+ */
+pub fn resample(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    if (image.width, image.height) == (width, height) {
+        return image.clone();
+    }
+    if image.width == 0 || image.height == 0 || width == 0 || height == 0 {
+        return RgbaImage::new(width, height);
+    }
+
+    let premultiplied: Vec<f32> = image.pixels.chunks_exact(4)
+        .flat_map(|pixel| {
+            let alpha = pixel[3] as f32 / 255.0;
+            [pixel[0] as f32 * alpha, pixel[1] as f32 * alpha, pixel[2] as f32 * alpha, pixel[3] as f32]
+        })
+        .collect();
+
+    // Horizontally into a width x image.height buffer, then vertically.
+    let columns = axis_weights(image.width, width);
+    let mut horizontal = vec![0.0f32; width as usize * image.height as usize * 4];
+    for y in 0..image.height as usize {
+        let row = &premultiplied[y * image.width as usize * 4..];
+        for (x, (start, weights)) in columns.iter().enumerate() {
+            let target = &mut horizontal[(y * width as usize + x) * 4..][..4];
+            for (offset, weight) in weights.iter().enumerate() {
+                let source = &row[(start + offset) * 4..][..4];
+                target.iter_mut().zip(source).for_each(|(target, source)| *target += source * weight);
+            }
+        }
+    }
+
+    let rows = axis_weights(image.height, height);
+    let mut result = RgbaImage::new(width, height);
+    for (y, (start, weights)) in rows.iter().enumerate() {
+        for x in 0..width as usize {
+            let mut sum = [0.0f32; 4];
+            for (offset, weight) in weights.iter().enumerate() {
+                let source = &horizontal[((start + offset) * width as usize + x) * 4..][..4];
+                sum.iter_mut().zip(source).for_each(|(sum, source)| *sum += source * weight);
+            }
+
+            let alpha = sum[3].clamp(0.0, 255.0);
+            let pixel = if alpha < 0.5 {
+                [0, 0, 0, 0]
+            }
+            else {
+                let channel = |value: f32| (value * 255.0 / alpha).round().clamp(0.0, 255.0) as u8;
+                [channel(sum[0]), channel(sum[1]), channel(sum[2]), alpha.round() as u8]
+            };
+            result.set_pixel(x as u32, y as u32, pixel);
+        }
+    }
+    result
+}
+
+/**
+ * The start and end of the three bands of a nine-grid along one axis.
+ */
+fn bands(length: u32, near: i32, far: i32) -> [(u32, u32); 3] {
+    let near = near.max(0) as u32;
+    let far = far.max(0) as u32;
+    [(0, near), (near, length - far), (length - far, length)]
+}
+
+/**
+ * Scale an image as a nine-grid: the corners and edges given by the margins are scaled on their
+ * own, so that they end up exactly at the target margins and the center does not blur into
+ * them. Falls back to scaling the whole image when the margins do not fit.
+ */
+pub fn resample_nine_grid(image: &RgbaImage, margins: Margins, width: u32, height: u32, target_margins: Margins) -> RgbaImage {
+    let fits = |margins: &Margins, width: u32, height: u32| {
+        [margins.left, margins.right, margins.top, margins.bottom].iter().all(|margin| *margin >= 0)
+            && margins.left + margins.right <= width as i32
+            && margins.top + margins.bottom <= height as i32
+    };
+    if !fits(&margins, image.width, image.height) || !fits(&target_margins, width, height) {
+        return resample(image, width, height);
+    }
+
+    let source_columns = bands(image.width, margins.left, margins.right);
+    let source_rows = bands(image.height, margins.top, margins.bottom);
+    let target_columns = bands(width, target_margins.left, target_margins.right);
+    let target_rows = bands(height, target_margins.top, target_margins.bottom);
+
+    let mut result = RgbaImage::new(width, height);
+    for (source_row, target_row) in source_rows.iter().zip(&target_rows) {
+        for (source_column, target_column) in source_columns.iter().zip(&target_columns) {
+            let (target_width, target_height) = (target_column.1 - target_column.0, target_row.1 - target_row.0);
+            if target_width == 0 || target_height == 0 {
+                continue;
+            }
+            let piece = image.crop(Rect {
+                left: source_column.0 as i32,
+                top: source_row.0 as i32,
+                right: source_column.1 as i32,
+                bottom: source_row.1 as i32,
+            });
+            let scaled = resample(&piece, target_width, target_height);
+            result.paste(&scaled, target_column.0 as i32, target_row.0 as i32);
+        }
+    }
+    result
+}
@@ -0,0 +1,293 @@
+/*
+ * Image strips.
+ *
+ * A part with several states usually has one image for all of them: the frames are stacked
+ * vertically or side by side (ImageLayout), and ImageCount says how many there are. State n is
+ * drawn from frame n - 1.
+ *
+ * Editing a strip goes through Strip, which holds the frames separately. Writing it back stores
+ * the joined image in its original encoding and updates ImageCount and ImageLayout wherever the
+ * image is used, so that they keep describing it.
+ */
+
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use super::{decode, DecodeOptions, RgbaImage};
+use crate::formats::restyle_ini::format_section_name;
+use crate::theme::{normalize_image_path, parts, PropertyValue, Rect, Theme};
+use crate::theme::image_properties::{find_image_property, IMAGE_PROPERTIES};
+use crate::theme::schema::{IL_HORIZONTAL, IL_VERTICAL};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StripLayout {
+    pub count: u32,
+    pub vertical: bool,
+}
+
+impl Default for StripLayout {
+    fn default() -> Self {
+        Self { count: 1, vertical: false }
+    }
+}
+
+impl StripLayout {
+    /**
+     * The layout that applies to a state, with the uxtheme defaults of one frame and a
+     * horizontal layout.
+     */
+    pub fn of(theme: &Theme, class: &str, part: Option<&str>, state: Option<&str>) -> Self {
+        let count = match theme.resolve_property(class, part, state, "ImageCount") {
+            Some(PropertyValue::Int(count)) if *count > 0 => *count as u32,
+            _ => 1,
+        };
+        let vertical = matches!(theme.resolve_property(class, part, state, "ImageLayout"), Some(PropertyValue::Enum(IL_VERTICAL)));
+        Self { count, vertical }
+    }
+
+    /**
+     * The size of one frame of a strip. Any remainder (when the strip does not divide evenly)
+     * is not part of any frame.
+     */
+    pub fn frame_size(&self, width: u32, height: u32) -> (u32, u32) {
+        if self.vertical {
+            (width, height / self.count.max(1))
+        }
+        else {
+            (width / self.count.max(1), height)
+        }
+    }
+
+    pub fn frame_rect(&self, width: u32, height: u32, index: u32) -> Rect {
+        let (frame_width, frame_height) = self.frame_size(width, height);
+        let (left, top) = if self.vertical { (0, index * frame_height) } else { (index * frame_width, 0) };
+        Rect { left: left as i32, top: top as i32, right: (left + frame_width) as i32, bottom: (top + frame_height) as i32 }
+    }
+
+    /**
+     * The frame that a state is drawn from. State 0 (the part-wide state) uses the first frame,
+     * and states past the end of the strip use the last one.
+     */
+    pub fn frame_for_state(&self, state_id: i32) -> u32 {
+        (state_id.max(1) as u32 - 1).min(self.count.max(1) - 1)
+    }
+
+    /**
+     * Whether an image of this size divides evenly into frames.
+     */
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        let length = if self.vertical { height } else { width };
+        length % self.count.max(1) == 0 && length >= self.count
+    }
+}
+
+/**
+ * Split a strip into its frames.
+ */
+pub fn split(image: &RgbaImage, layout: StripLayout) -> Result<Vec<RgbaImage>, Error> {
+    if !layout.fits(image.width, image.height) {
+        return Err(anyhow!(
+            "A {}x{} image does not divide into {} {} frames.",
+            image.width, image.height, layout.count, if layout.vertical { "vertical" } else { "horizontal" },
+        ));
+    }
+    Ok((0..layout.count).map(|index| image.crop(layout.frame_rect(image.width, image.height, index))).collect())
+}
+
+/**
+ * Join frames of the same size into a strip.
+ */
+pub fn join(frames: &[RgbaImage], vertical: bool) -> Result<RgbaImage, Error> {
+    let Some(first) = frames.first() else {
+        return Err(anyhow!("A strip needs at least one frame."));
+    };
+    let (width, height) = (first.width, first.height);
+    if let Some(index) = frames.iter().position(|frame| frame.width != width || frame.height != height) {
+        return Err(anyhow!(
+            "Frame {} is {}x{}, but the first frame is {}x{}.",
+            index + 1, frames[index].width, frames[index].height, width, height,
+        ));
+    }
+
+    let count = frames.len() as u32;
+    let mut result = if vertical { RgbaImage::new(width, height * count) } else { RgbaImage::new(width * count, height) };
+    for (index, frame) in frames.iter().enumerate() {
+        let index = index as u32;
+        let (x, y) = if vertical { (0, index * height) } else { (index * width, 0) };
+        result.paste(frame, x as i32, y as i32);
+    }
+    Ok(result)
+}
+
+/**
+ * The frames of a strip, for editing.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Strip {
+    pub frames: Vec<RgbaImage>,
+    pub vertical: bool,
+}
+
+impl Strip {
+    pub fn from_image(image: &RgbaImage, layout: StripLayout) -> Result<Self, Error> {
+        Ok(Self { frames: split(image, layout)?, vertical: layout.vertical })
+    }
+
+    pub fn layout(&self) -> StripLayout {
+        StripLayout { count: self.frames.len() as u32, vertical: self.vertical }
+    }
+
+    pub fn join(&self) -> Result<RgbaImage, Error> {
+        join(&self.frames, self.vertical)
+    }
+
+    /**
+     * Replace one frame with an image of the same size.
+     */
+    pub fn replace_frame(&mut self, index: usize, frame: RgbaImage) -> Result<(), Error> {
+        let count = self.frames.len();
+        let current = self.frames.get_mut(index).ok_or_else(|| anyhow!("The strip has no frame {} (it has {}).", index + 1, count))?;
+        if (frame.width, frame.height) != (current.width, current.height) {
+            return Err(anyhow!("A {}x{} image does not fit a {}x{} frame.", frame.width, frame.height, current.width, current.height));
+        }
+        *current = frame;
+        Ok(())
+    }
+
+    /**
+     * Rearrange the frames: frame n of the result is frame order[n] of the strip. Frames can be
+     * left out or used more than once, which changes the frame count.
+     */
+    pub fn reorder(&mut self, order: &[usize]) -> Result<(), Error> {
+        if order.is_empty() {
+            return Err(anyhow!("A strip needs at least one frame."));
+        }
+        let frames = order.iter()
+            .map(|index| self.frames.get(*index).cloned().ok_or_else(|| anyhow!("The strip has no frame {} (it has {}).", index + 1, self.frames.len())))
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.frames = frames;
+        Ok(())
+    }
+}
+
+/**
+ * Set ImageCount and ImageLayout for every state that draws an image, on the levels that define
+ * the image or its layout, so that the states below them inherit the new values.
+ *
+ * The layout also applies to the other images of each state it reaches (the DPI variants and the
+ * glyph), which would then be cut into the wrong frames. A change that reaches another image is
+ * rejected, and the theme is left as it was.
+ */
+fn update_layouts(theme: &mut Theme, path: &str, layout: StripLayout) -> Result<(), Error> {
+    let path = normalize_image_path(path);
+    let mut levels = Vec::new();
+    for (class, part, state) in theme.states() {
+        let (class_name, part_name, state_name) = (class.name.as_str(), part.name.as_deref(), state.name.as_deref());
+        let uses_image = IMAGE_PROPERTIES.iter().any(|info| {
+            matches!(theme.resolve_property(class_name, part_name, state_name, info.name), Some(PropertyValue::FileName(image)) if normalize_image_path(image) == path)
+        });
+        let defines_layout = state.properties.iter().any(|property| {
+            property.name.eq_ignore_ascii_case("ImageCount")
+                || property.name.eq_ignore_ascii_case("ImageLayout")
+                || matches!(&property.value, PropertyValue::FileName(image) if normalize_image_path(image) == path)
+        });
+        if uses_image && defines_layout && StripLayout::of(theme, class_name, part_name, state_name) != layout {
+            levels.push((class.name.clone(), part.name.clone(), state.name.clone()));
+        }
+    }
+    if levels.is_empty() {
+        return Ok(());
+    }
+
+    // Update a copy of the states, so that the layouts before and after can be compared.
+    let mut updated = Theme { classes: theme.classes.clone(), ..Theme::default() };
+    for (class, part, state) in levels {
+        let current = StripLayout::of(&updated, &class, part.as_deref(), state.as_deref());
+        let state = updated.state_mut(&class, part.as_deref(), state.as_deref());
+        if current.count != layout.count {
+            state.set_property("ImageCount", PropertyValue::Int(layout.count as i32));
+        }
+        if current.vertical != layout.vertical {
+            state.set_property("ImageLayout", PropertyValue::Enum(if layout.vertical { IL_VERTICAL } else { IL_HORIZONTAL }));
+        }
+    }
+
+    for (class, part, state) in theme.states() {
+        let (class_name, part_name, state_name) = (class.name.as_str(), part.name.as_deref(), state.name.as_deref());
+        let before = StripLayout::of(theme, class_name, part_name, state_name);
+        if StripLayout::of(&updated, class_name, part_name, state_name) == before {
+            continue;
+        }
+        for info in IMAGE_PROPERTIES {
+            if let Some(PropertyValue::FileName(image)) = theme.resolve_property(class_name, part_name, state_name, info.name) {
+                if normalize_image_path(image) != path {
+                    return Err(anyhow!(
+                        "{} of {} (\"{}\") is also cut into {} frames, so the frames of this strip cannot change their number or layout.",
+                        info.name, format_section_name(class_name, part_name, state_name), image, before.count,
+                    ));
+                }
+            }
+        }
+    }
+
+    theme.classes = updated.classes;
+    Ok(())
+}
+
+/**
+ * Edit the strip that an image property of a state refers to. The image is stored in its
+ * original encoding afterwards (or as the original bytes if the frames did not change), and
+ * ImageCount and ImageLayout are updated to match the edited strip. Changing the number or layout
+ * of the frames fails if another image of the same states depends on them. Premultiplied bitmaps
+ * cannot be detected, so a theme that stores them needs BmpAlpha::Premultiplied in the options to
+ * keep storing them that way.
+ */
+pub fn edit_strip(
+    theme: &mut Theme,
+    class: &str,
+    part: Option<&str>,
+    state: Option<&str>,
+    property: &str,
+    options: &DecodeOptions,
+    edit: impl FnOnce(&mut Strip) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let info = find_image_property(property).ok_or_else(|| anyhow!("\"{}\" is not an image property.", property))?;
+    let Some(PropertyValue::FileName(path)) = theme.resolve_property(class, part, state, info.name) else {
+        return Err(anyhow!("{} is not set for this state.", info.name));
+    };
+    let path = path.clone();
+    let data = theme.image(&path).ok_or_else(|| anyhow!("Image \"{}\" is missing.", path))?;
+    let mut decoded = decode(data, options).with_context(|| anyhow!("Failed to decode \"{}\"", path))?;
+
+    let mut strip = Strip::from_image(&decoded.image, StripLayout::of(theme, class, part, state))?;
+    edit(&mut strip)?;
+    decoded.image = strip.join()?;
+
+    let data = decoded.encode()?;
+    update_layouts(theme, &path, strip.layout())?;
+    theme.set_image(&path, data);
+    Ok(())
+}
+
+/**
+ * Replace the frame that a state is drawn from, i.e. only the pressed look of a button.
+ */
+pub fn replace_state_frame(
+    theme: &mut Theme,
+    class: &str,
+    part: Option<&str>,
+    state: &str,
+    property: &str,
+    options: &DecodeOptions,
+    frame: RgbaImage,
+) -> Result<(), Error> {
+    let state_id = parts::state_id(class, part, Some(state)).ok_or_else(|| anyhow!("Unknown state \"{}\".", state))?;
+    edit_strip(theme, class, part, Some(state), property, options, |strip| {
+        // States past the end of the strip share the last frame, which is not this state's own.
+        if state_id < 1 || state_id as usize > strip.frames.len() {
+            return Err(anyhow!("The strip has {} frames, so {} has no frame of its own.", strip.frames.len(), state));
+        }
+        strip.replace_frame(state_id as usize - 1, frame)
+    })
+}
@@ -0,0 +1,230 @@
+/*
+ * Color key to alpha conversion.
+ *
+ * Before alpha channels, themes made parts of an image transparent with a color key: with
+ * Transparent set, uxtheme leaves out every pixel of TransparentColor (magenta by default).
+ * Glyphs have their own GlyphTransparent and GlyphTransparentColor. Converting the key to an
+ * alpha channel gives the same result, and lets the edges be anti-aliased, which a color key
+ * cannot do.
+ */
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{decode, encode, BmpAlpha, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use crate::theme::image_properties::IMAGE_PROPERTIES;
+use crate::theme::{normalize_image_path, Color, PropertyValue, Theme};
+
+/**
+ * The color key that uxtheme uses when TransparentColor is not set.
+ */
+pub const DEFAULT_TRANSPARENT_COLOR: Color = Color::new(255, 0, 255);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TransparencyOptions {
+    /**
+     * Soften the edges around the keyed out pixels, which are jagged otherwise.
+     */
+    pub antialias: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TransparencyReport {
+    /**
+     * The images that now have an alpha channel.
+     */
+    pub converted: Vec<String>,
+    /**
+     * Images that use a color key but were left alone, and why.
+     */
+    pub skipped: Vec<(String, String)>,
+}
+
+fn key_properties(glyph: bool) -> (&'static str, &'static str) {
+    if glyph { ("GlyphTransparent", "GlyphTransparentColor") } else { ("Transparent", "TransparentColor") }
+}
+
+/**
+ * The color key that a state draws an image property with, if it uses one.
+ */
+pub fn color_key(theme: &Theme, class: &str, part: Option<&str>, state: Option<&str>, glyph: bool) -> Option<Color> {
+    let (transparent, transparent_color) = key_properties(glyph);
+    if !matches!(theme.resolve_property(class, part, state, transparent), Some(PropertyValue::Bool(true))) {
+        return None;
+    }
+    match theme.resolve_property(class, part, state, transparent_color) {
+        Some(PropertyValue::Color(color)) => Some(*color),
+        _ => Some(DEFAULT_TRANSPARENT_COLOR),
+    }
+}
+
+/**
+ * Make every pixel of the key color transparent. With antialiasing, opaque pixels next to
+ * transparent ones get the coverage of a small tent filter over the mask as their alpha.
+ */
+pub fn key_to_alpha(image: &mut RgbaImage, key: Color, antialias: bool) {
+    let keyed: Vec<bool> = image.pixels.chunks_exact(4)
+        .map(|pixel| pixel[..3] == [key.r, key.g, key.b])
+        .collect();
+
+    let (width, height) = (image.width as i32, image.height as i32);
+    let is_keyed = |x: i32, y: i32| {
+        // Outside of the image counts as opaque, so that edges of the image are not softened.
+        x >= 0 && y >= 0 && x < width && y < height && keyed[(y * width + x) as usize]
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            if is_keyed(x, y) {
+                image.set_pixel(x as u32, y as u32, [0, 0, 0, 0]);
+                continue;
+            }
+            if !antialias {
+                continue;
+            }
+
+            let mut covered = 0;
+            for (dx, dy) in [(-1i32, -1i32), (0, -1), (1, -1), (-1, 0), (0, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                let weight = 4 >> (dx.abs() + dy.abs());
+                if !is_keyed(x + dx, y + dy) {
+                    covered += weight;
+                }
+            }
+            if covered < 16 {
+                let mut pixel = image.pixel(x as u32, y as u32);
+                pixel[3] = (pixel[3] as u32 * covered / 16) as u8;
+                image.set_pixel(x as u32, y as u32, pixel);
+            }
+        }
+    }
+}
+
+/**
+ * The encoding for a converted image: the same kind of file, with an alpha channel.
+ */
+fn alpha_encoding(encoding: ImageEncoding) -> ImageEncoding {
+    match encoding {
+        ImageEncoding::Bmp { alpha, top_down, .. } => {
+            let alpha = if alpha == BmpAlpha::Premultiplied { alpha } else { BmpAlpha::Straight };
+            ImageEncoding::Bmp { bits: 32, alpha, top_down, file_header: true }
+        }
+        ImageEncoding::Png { interlaced, .. } => ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced },
+    }
+}
+
+/**
+ * Where a state takes an image from, and the color key it draws it with.
+ */
+#[derive(PartialEq)]
+struct ImageUse {
+    /**
+     * The normalized path of the image, and the path as written.
+     */
+    path: String,
+    written_path: String,
+    key: Option<Color>,
+}
+
+/**
+ * A state and one of its image properties.
+ */
+type ImageSlot = (String, Option<String>, Option<String>, &'static str);
+
+/**
+ * The images that every state draws.
+ */
+fn image_uses(theme: &Theme) -> BTreeMap<ImageSlot, ImageUse> {
+    let mut uses = BTreeMap::new();
+    for (class, part, state) in theme.states() {
+        let (class, part, state) = (class.name.as_str(), part.name.as_deref(), state.name.as_deref());
+        for info in IMAGE_PROPERTIES {
+            if let Some(PropertyValue::FileName(path)) = theme.resolve_property(class, part, state, info.name) {
+                let key = color_key(theme, class, part, state, info.glyph);
+                uses.insert(
+                    (class.to_owned(), part.map(str::to_owned), state.map(str::to_owned), info.name),
+                    ImageUse { path: normalize_image_path(path), written_path: path.clone(), key },
+                );
+            }
+        }
+    }
+    uses
+}
+
+/**
+ * Convert every color keyed image of a theme to an alpha channel, and turn the color keys off.
+ * An image is left alone when the states that use it disagree about its color key, or when it
+ * cannot be decoded.
+ */
+pub fn convert_color_keys(theme: &mut Theme, options: &TransparencyOptions) -> TransparencyReport {
+    let before = image_uses(theme);
+
+    let mut keys: BTreeMap<&str, (&str, BTreeSet<Option<Color>>)> = BTreeMap::new();
+    for image_use in before.values() {
+        keys.entry(&image_use.path).or_insert((&image_use.written_path, BTreeSet::new())).1.insert(image_use.key);
+    }
+
+    let mut report = TransparencyReport::default();
+    let mut converted = BTreeSet::new();
+    for (normalized_path, (path, keys)) in &keys {
+        let key = match keys.iter().collect::<Vec<_>>()[..] {
+            [Some(key)] => *key,
+            [None] => continue,
+            _ => {
+                report.skipped.push((path.to_string(), "The states that use it have different color keys.".to_owned()));
+                continue;
+            }
+        };
+        let Some(data) = theme.image(path) else {
+            report.skipped.push((path.to_string(), "The image is missing.".to_owned()));
+            continue;
+        };
+
+        let converted_data = decode(data, &DecodeOptions::default()).and_then(|mut decoded| {
+            key_to_alpha(&mut decoded.image, key, options.antialias);
+            encode(&decoded.image, &alpha_encoding(decoded.encoding))
+        });
+        let data = match converted_data {
+            Ok(data) => data,
+            Err(error) => {
+                report.skipped.push((path.to_string(), format!("{:#}", error)));
+                continue;
+            }
+        };
+        theme.set_image(path, data);
+        converted.insert(normalized_path.to_string());
+        report.converted.push(path.to_string());
+    }
+
+    // Turn the color keys off where the converted images are used, and then put back any key
+    // that other images inherited from the same levels.
+    for ((class, part, state, property), image_use) in &before {
+        if !converted.contains(&image_use.path) {
+            continue;
+        }
+        let glyph = IMAGE_PROPERTIES.iter().any(|info| info.name == *property && info.glyph);
+        let (transparent, transparent_color) = key_properties(glyph);
+        let level = theme.state_mut(class, part.as_deref(), state.as_deref());
+        level.remove_property(transparent_color);
+        level.remove_property(transparent);
+        if color_key(theme, class, part.as_deref(), state.as_deref(), glyph).is_some() {
+            theme.state_mut(class, part.as_deref(), state.as_deref()).set_property(transparent, PropertyValue::Bool(false));
+        }
+    }
+
+    for ((class, part, state, property), image_use) in before {
+        let Some(key) = image_use.key else {
+            continue;
+        };
+        if converted.contains(&image_use.path) {
+            continue;
+        }
+        let glyph = IMAGE_PROPERTIES.iter().any(|info| info.name == property && info.glyph);
+        if color_key(theme, &class, part.as_deref(), state.as_deref(), glyph) != Some(key) {
+            let (transparent, transparent_color) = key_properties(glyph);
+            let level = theme.state_mut(&class, part.as_deref(), state.as_deref());
+            level.set_property(transparent, PropertyValue::Bool(true));
+            level.set_property(transparent_color, PropertyValue::Color(key));
+        }
+    }
+
+    report
+}
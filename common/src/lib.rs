@@ -1 +1,5 @@
-pub mod build_common;
\ No newline at end of file
+pub mod build_common;
+pub mod theme;
+pub mod formats;
+pub mod image;
+pub mod render;
\ No newline at end of file
@@ -0,0 +1,316 @@
+/*
+ * DrawThemeBackground.
+ *
+ * BgType picks how a part is drawn: from an image, as a border around a fill (see fill.rs), or
+ * not at all.
+ *
+ * An image is cut out of its strip by state, and drawn by SizingType: a true size image is drawn
+ * at its own size and aligned in the rectangle (and shrunk when it does not fit), while the
+ * others are drawn as a nine-grid, with the corners as they are and the edges and middle
+ * stretched or tiled between the SizingMargins. Stretching takes the nearest pixel, like
+ * StretchBlt and AlphaBlend do. ImageSelectType picks one of ImageFile1..7 by DPI or by size
+ * instead of ImageFile, and an image glyph is drawn on top, inside the ContentMargins.
+ *
+ * Drawing right to left mirrors the whole background, glyph included, unless MirrorImage is
+ * FALSE, as for parts whose images contain text or check marks.
+ */
+
+use anyhow::Error;
+
+use super::{blend_pixel, deflate_rect, Renderer};
+use crate::image::dpi::scale_margins;
+use crate::image::strip::StripLayout;
+use crate::image::transparency::color_key;
+use crate::image::RgbaImage;
+use crate::theme::image_properties::{min_dpi_property, min_size_property, variant_property, VARIANT_COUNT, VARIANT_DPIS};
+use crate::theme::schema::{
+    BT_BORDERFILL, BT_IMAGEFILE, GT_IMAGEGLYPH, HA_CENTER, HA_LEFT, HA_RIGHT, IST_DPI, IST_NONE, IST_SIZE, ST_STRETCH, ST_TILE,
+    ST_TRUESIZE, TSST_DPI, TSST_SIZE, VA_BOTTOM, VA_CENTER, VA_TOP,
+};
+use crate::theme::sysmetrics::BASE_DPI;
+use crate::theme::{parts, Margins, Rect};
+
+/**
+ * The image that a state is drawn with.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectedImage {
+    pub property: String,
+    pub path: String,
+    /**
+     * The DPI that the image is for.
+     */
+    pub dpi: u32,
+}
+
+/**
+ * Draw part of an image into part of the canvas, stretched or tiled to fill it.
+ */
+fn blit(canvas: &mut RgbaImage, image: &RgbaImage, source: Rect, target: Rect, clip: Rect, tile: bool, opacity: u8) {
+    let (source_width, source_height) = (source.width(), source.height());
+    let (target_width, target_height) = (target.width(), target.height());
+    if source_width <= 0 || source_height <= 0 || target_width <= 0 || target_height <= 0 {
+        return;
+    }
+
+    let sample = |offset: i32, source_length: i32, target_length: i32| {
+        if tile { offset.rem_euclid(source_length) } else { ((offset * 2 + 1) * source_length / (target_length * 2)).min(source_length - 1) }
+    };
+    for y in target.top.max(clip.top).max(0)..target.bottom.min(clip.bottom).min(canvas.height as i32) {
+        let source_y = source.top + sample(y - target.top, source_height, target_height);
+        for x in target.left.max(clip.left).max(0)..target.right.min(clip.right).min(canvas.width as i32) {
+            let source_x = source.left + sample(x - target.left, source_width, target_width);
+            blend_pixel(canvas, x, y, image.pixel(source_x as u32, source_y as u32), opacity);
+        }
+    }
+}
+
+/**
+ * Margins that fit into a length, shrunk in proportion when they do not.
+ */
+fn fit_margins(start: i32, end: i32, length: i32) -> (i32, i32) {
+    let (start, end, length) = (start.max(0), end.max(0), length.max(0));
+    if start + end <= length {
+        return (start, end);
+    }
+    let start = start * length / (start + end);
+    (start, length - start)
+}
+
+/**
+ * The cells of a nine-grid, as source and target rectangles, row by row.
+ */
+fn nine_grid_cells(source: Rect, source_margins: Margins, target: Rect, target_margins: Margins) -> Vec<(Rect, Rect)> {
+    let (source_left, source_right) = fit_margins(source_margins.left, source_margins.right, source.width());
+    let (source_top, source_bottom) = fit_margins(source_margins.top, source_margins.bottom, source.height());
+    let (target_left, target_right) = fit_margins(target_margins.left, target_margins.right, target.width());
+    let (target_top, target_bottom) = fit_margins(target_margins.top, target_margins.bottom, target.height());
+
+    let source_columns = [source.left, source.left + source_left, source.right - source_right, source.right];
+    let source_rows = [source.top, source.top + source_top, source.bottom - source_bottom, source.bottom];
+    let target_columns = [target.left, target.left + target_left, target.right - target_right, target.right];
+    let target_rows = [target.top, target.top + target_top, target.bottom - target_bottom, target.bottom];
+
+    let cell = |columns: &[i32; 4], rows: &[i32; 4], row: usize, column: usize| Rect {
+        left: columns[column],
+        top: rows[row],
+        right: columns[column + 1],
+        bottom: rows[row + 1],
+    };
+    (0..9)
+        .map(|index| {
+            let (row, column) = (index / 3, index % 3);
+            (cell(&source_columns, &source_rows, row, column), cell(&target_columns, &target_rows, row, column))
+        })
+        .collect()
+}
+
+/**
+ * Place a size in a rectangle by HAlign and VAlign.
+ */
+fn align(rect: Rect, width: i32, height: i32, horizontal: i32, vertical: i32) -> Rect {
+    let left = match horizontal {
+        HA_LEFT => rect.left,
+        HA_RIGHT => rect.right - width,
+        _ => rect.left + (rect.width() - width) / 2,
+    };
+    let top = match vertical {
+        VA_TOP => rect.top,
+        VA_BOTTOM => rect.bottom - height,
+        _ => rect.top + (rect.height() - height) / 2,
+    };
+    Rect { left, top, right: left + width, bottom: top + height }
+}
+
+fn scale(value: i32, to_dpi: u32, from_dpi: u32) -> i32 {
+    ((value as i64 * to_dpi as i64 + from_dpi as i64 / 2) / from_dpi.max(1) as i64) as i32
+}
+
+impl Renderer<'_> {
+    /**
+     * The DPI that an ImageFile1..7 variant is for: its MinDpi, or the conventional DPI of the
+     * variant without one.
+     */
+    fn variant_dpi(&self, class: &str, part: Option<&str>, state: Option<&str>, variant: u8) -> u32 {
+        match self.int(class, part, state, &min_dpi_property(variant)) {
+            Some(dpi) if dpi > 0 => dpi as u32,
+            _ => VARIANT_DPIS[variant as usize - 1],
+        }
+    }
+
+    /**
+     * The rectangle of the frame that a state uses in a strip.
+     */
+    pub fn frame_rect(&self, image: &RgbaImage, class: &str, part: Option<&str>, state: Option<&str>) -> Rect {
+        let layout = StripLayout::of(self.theme, class, part, state);
+        let state_id = parts::state_id(class, part, state).unwrap_or(0);
+        layout.frame_rect(image.width, image.height, layout.frame_for_state(state_id))
+    }
+
+    /**
+     * Pick the image that a state is drawn with in a rectangle, like uxtheme does by
+     * ImageSelectType. By DPI, that is the variant with the highest MinDpi up to the DPI being
+     * drawn at; by size, the last variant whose MinSize (or for true size images, whose frame)
+     * fits in the rectangle. Without a match, the first variant is used.
+     */
+    pub fn select_image(&self, class: &str, part: Option<&str>, state: Option<&str>, rect: Rect, glyph: bool) -> Result<Option<SelectedImage>, Error> {
+        let selected = |property: String, path: &str, dpi: u32| Some(SelectedImage { property, path: path.to_owned(), dpi });
+        if glyph {
+            let path = self.file_name(class, part, state, "GlyphImageFile");
+            return Ok(path.and_then(|path| selected("GlyphImageFile".to_owned(), path, BASE_DPI)));
+        }
+
+        let variants: Vec<(u8, &str)> = (1..=VARIANT_COUNT)
+            .filter_map(|variant| Some((variant, self.file_name(class, part, state, &variant_property(variant))?)))
+            .collect();
+        let variant = match self.enum_value(class, part, state, "ImageSelectType").unwrap_or(IST_NONE) {
+            IST_DPI => variants.iter().rev().find(|(variant, _)| self.variant_dpi(class, part, state, *variant) <= self.dpi).or(variants.first()).copied(),
+            IST_SIZE => {
+                let true_size = self.enum_value(class, part, state, "SizingType") == Some(ST_TRUESIZE);
+                let mut fitting = None;
+                for (variant, path) in variants.iter().rev() {
+                    let size = match self.position(class, part, state, &min_size_property(*variant)) {
+                        Some(size) if !true_size => (size.x, size.y),
+                        _ => {
+                            let frame = self.frame_rect(&*self.image(path, None)?, class, part, state);
+                            (frame.width(), frame.height())
+                        }
+                    };
+                    if size.0 <= rect.width() && size.1 <= rect.height() {
+                        fitting = Some((*variant, *path));
+                        break;
+                    }
+                }
+                fitting.or(variants.first().copied())
+            }
+            _ => None,
+        };
+
+        if let Some((variant, path)) = variant {
+            return Ok(selected(variant_property(variant), path, self.variant_dpi(class, part, state, variant)));
+        }
+        match self.file_name(class, part, state, "ImageFile") {
+            Some(path) => Ok(selected("ImageFile".to_owned(), path, BASE_DPI)),
+            None => Ok(variants.first().and_then(|(variant, path)| selected(variant_property(*variant), path, self.variant_dpi(class, part, state, *variant)))),
+        }
+    }
+
+    /**
+     * The part of a background that content goes in, inside its ContentMargins, like
+     * GetThemeBackgroundContentRect.
+     */
+    pub fn content_rect(&self, class: &str, part: Option<&str>, state: Option<&str>, rect: Rect) -> Rect {
+        let content_margins = self.margins(class, part, state, "ContentMargins").unwrap_or_default();
+        deflate_rect(rect, scale_margins(content_margins, self.dpi))
+    }
+
+    /**
+     * Draw the background of a state into a rectangle of the canvas, like DrawThemeBackground.
+     */
+    pub fn draw_background(&self, canvas: &mut RgbaImage, class: &str, part: Option<&str>, state: Option<&str>, rect: Rect) -> Result<(), Error> {
+        if !self.mirrored {
+            return self.draw_layers(canvas, class, part, state, rect);
+        }
+        if rect.width() <= 0 || rect.height() <= 0 {
+            return Ok(());
+        }
+
+        // Draw left to right into a layer of its own and flip that into place, which is what a
+        // right-to-left DC does. Parts that are not to be mirrored are put in place as they are.
+        let mut layer = RgbaImage::new(rect.width() as u32, rect.height() as u32);
+        self.draw_layers(&mut layer, class, part, state, Rect { left: 0, top: 0, right: rect.width(), bottom: rect.height() })?;
+        let flip = self.bool(class, part, state, "MirrorImage").unwrap_or(true);
+        let target = self.device_rect(canvas, rect);
+        for y in 0..layer.height {
+            for x in 0..layer.width {
+                let source_x = if flip { layer.width - 1 - x } else { x };
+                blend_pixel(canvas, target.left + x as i32, target.top + y as i32, layer.pixel(source_x, y), 255);
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Draw the image or border and fill of a background, and its glyph, left to right.
+     */
+    fn draw_layers(&self, canvas: &mut RgbaImage, class: &str, part: Option<&str>, state: Option<&str>, rect: Rect) -> Result<(), Error> {
+        match self.enum_value(class, part, state, "BgType").unwrap_or(BT_BORDERFILL) {
+            BT_IMAGEFILE => {
+                if !self.bool(class, part, state, "GlyphOnly").unwrap_or(false) {
+                    self.draw_image(canvas, class, part, state, rect, false)?;
+                }
+                if self.enum_value(class, part, state, "GlyphType") == Some(GT_IMAGEGLYPH) {
+                    self.draw_image(canvas, class, part, state, self.content_rect(class, part, state, rect), true)?;
+                }
+            }
+            BT_BORDERFILL => self.draw_border_fill(canvas, class, part, state, rect)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn draw_image(&self, canvas: &mut RgbaImage, class: &str, part: Option<&str>, state: Option<&str>, rect: Rect, glyph: bool) -> Result<(), Error> {
+        let Some(selected) = self.select_image(class, part, state, rect, glyph)? else {
+            return Ok(());
+        };
+        let image = self.image(&selected.path, color_key(self.theme, class, part, state, glyph))?;
+        let source = self.frame_rect(&image, class, part, state);
+        let opacity = self.int(class, part, state, "AlphaLevel").unwrap_or(255).clamp(0, 255) as u8;
+
+        // Glyphs are always drawn at their own size.
+        let sizing_type = if glyph { ST_TRUESIZE } else { self.enum_value(class, part, state, "SizingType").unwrap_or(ST_STRETCH) };
+        if sizing_type != ST_TRUESIZE {
+            let margins = self.margins(class, part, state, "SizingMargins").unwrap_or_default();
+            let border_only = self.bool(class, part, state, "BorderOnly").unwrap_or(false);
+            let cells = nine_grid_cells(source, scale_margins(margins, selected.dpi), rect, scale_margins(margins, self.dpi));
+            for (index, (source, target)) in cells.into_iter().enumerate() {
+                let (row, column) = (index / 3, index % 3);
+                if border_only && row == 1 && column == 1 {
+                    continue;
+                }
+                // Corners are only ever scaled, when the margins do not fit.
+                let corner = row != 1 && column != 1;
+                blit(canvas, &image, source, target, rect, sizing_type == ST_TILE && !corner, opacity);
+            }
+            return Ok(());
+        }
+
+        let (mut width, mut height) = (source.width(), source.height());
+        match self.enum_value(class, part, state, "TrueSizeScalingType") {
+            Some(TSST_DPI) => (width, height) = (scale(width, self.dpi, selected.dpi), scale(height, self.dpi, selected.dpi)),
+            Some(TSST_SIZE) => {
+                let (fit_width, fit_height) = (rect.width().max(0), rect.height().max(0));
+                if width * fit_height > height * fit_width {
+                    (width, height) = (fit_width, height * fit_width / width.max(1));
+                }
+                else {
+                    (width, height) = (width * fit_height / height.max(1), fit_height);
+                }
+            }
+            _ => {}
+        }
+
+        // Shrink the image to the rectangle when it is larger in either direction by more than
+        // TrueSizeStretchMark percent.
+        let stretch_mark = self.int(class, part, state, "TrueSizeStretchMark").unwrap_or(100);
+        let (target_width, target_height) = (rect.width(), rect.height());
+        if target_width > 0 && target_height > 0 && (width * 100 / target_width > stretch_mark || height * 100 / target_height > stretch_mark) {
+            if self.bool(class, part, state, "UniformSizing").unwrap_or(false) {
+                if width * target_height > height * target_width {
+                    (width, height) = (target_width, height * target_width / width.max(1));
+                }
+                else {
+                    (width, height) = (width * target_height / height.max(1), target_height);
+                }
+            }
+            else {
+                (width, height) = (width.min(target_width), height.min(target_height));
+            }
+        }
+
+        let horizontal = self.enum_value(class, part, state, "HAlign").unwrap_or(HA_CENTER);
+        let vertical = self.enum_value(class, part, state, "VAlign").unwrap_or(VA_CENTER);
+        blit(canvas, &image, source, align(rect, width, height, horizontal, vertical), rect, false, opacity);
+        Ok(())
+    }
+}
@@ -0,0 +1,182 @@
+/*
+ * BorderFill backgrounds.
+ *
+ * A BorderFill part is a shape (BorderType: a rectangle, a rounded rectangle with corners of
+ * RoundCornerWidth by RoundCornerHeight, or an ellipse) with a border of BorderSize in
+ * BorderColor, filled by FillType: FillColor, a gradient, or the image of the state tiled.
+ *
+ * Gradients run through GradientColor1..5. GradientRatio1..5 are the share of the length that
+ * each color takes, out of 255: the colors sit at the running totals of the ratios, so that
+ * ratios of 0 and 255 put the first color at the start and the second at the end. Past the last
+ * color the gradient stays solid. Without ratios, the colors are spread evenly. A vertical
+ * gradient runs from top to bottom, a horizontal one from left to right, and a radial one from
+ * the middle out to the edges of the ellipse that fits in the rectangle.
+ *
+ * Shapes are not anti-aliased, like the GDI shapes that uxtheme draws them with.
+ */
+
+use anyhow::Error;
+
+use super::{blend_pixel, deflate_rect, Renderer};
+use crate::image::RgbaImage;
+use crate::theme::schema::{
+    BT_ELLIPSE, BT_RECT, BT_ROUNDRECT, FT_HORZGRADIENT, FT_RADIALGRADIENT, FT_SOLID, FT_TILEIMAGE, FT_VERTGRADIENT,
+};
+use crate::theme::{Color, Margins, Rect};
+
+/**
+ * The number of GradientColor properties.
+ */
+pub const GRADIENT_COLOR_COUNT: u8 = 5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    /**
+     * Colors and their positions from 0 to 1, in order.
+     */
+    pub stops: Vec<(f32, Color)>,
+}
+
+impl Gradient {
+    pub fn color_at(&self, position: f32) -> Color {
+        let Some(&(first_position, first_color)) = self.stops.first() else {
+            return Color::new(0, 0, 0);
+        };
+        if position <= first_position {
+            return first_color;
+        }
+        for pair in self.stops.windows(2) {
+            let ((start, from), (end, to)) = (pair[0], pair[1]);
+            if position <= end {
+                let t = if end > start { (position - start) / (end - start) } else { 1.0 };
+                let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+                return Color::new(mix(from.r, to.r), mix(from.g, to.g), mix(from.b, to.b));
+            }
+        }
+        self.stops.last().unwrap().1
+    }
+}
+
+/**
+ * Whether the middle of a pixel is inside a shape.
+ */
+fn inside(shape: i32, rect: Rect, corner_width: i32, corner_height: i32, x: i32, y: i32) -> bool {
+    let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+    if x < rect.left as f32 || y < rect.top as f32 || x > rect.right as f32 || y > rect.bottom as f32 {
+        return false;
+    }
+    let (radius_x, radius_y) = match shape {
+        BT_ELLIPSE => (rect.width() as f32 / 2.0, rect.height() as f32 / 2.0),
+        BT_ROUNDRECT => (
+            (corner_width as f32 / 2.0).min(rect.width() as f32 / 2.0),
+            (corner_height as f32 / 2.0).min(rect.height() as f32 / 2.0),
+        ),
+        _ => return true,
+    };
+    if radius_x <= 0.0 || radius_y <= 0.0 {
+        return true;
+    }
+
+    // Only the corners are curved: measure from the middle of the nearest corner's ellipse.
+    let center_x = x.clamp(rect.left as f32 + radius_x, rect.right as f32 - radius_x);
+    let center_y = y.clamp(rect.top as f32 + radius_y, rect.bottom as f32 - radius_y);
+    let (dx, dy) = ((x - center_x) / radius_x, (y - center_y) / radius_y);
+    dx * dx + dy * dy <= 1.0
+}
+
+impl Renderer<'_> {
+    /**
+     * The gradient of a state, from its GradientColor and GradientRatio properties.
+     */
+    pub fn gradient(&self, class: &str, part: Option<&str>, state: Option<&str>) -> Gradient {
+        let colors: Vec<(Option<i32>, Color)> = (1..=GRADIENT_COLOR_COUNT)
+            .map_while(|index| {
+                let color = self.color(class, part, state, &format!("GradientColor{}", index))?;
+                Some((self.int(class, part, state, &format!("GradientRatio{}", index)), color))
+            })
+            .collect();
+
+        let stops = if colors.iter().all(|(ratio, _)| ratio.is_none()) {
+            let last = colors.len().saturating_sub(1).max(1) as f32;
+            colors.iter().enumerate().map(|(index, (_, color))| (index as f32 / last, *color)).collect()
+        }
+        else {
+            let mut total = 0;
+            colors.iter()
+                .map(|(ratio, color)| {
+                    total += ratio.unwrap_or(0).max(0);
+                    (total.min(255) as f32 / 255.0, *color)
+                })
+                .collect()
+        };
+        Gradient { stops }
+    }
+
+    /**
+     * Draw a BorderFill background into a rectangle of the canvas.
+     */
+    pub fn draw_border_fill(&self, canvas: &mut RgbaImage, class: &str, part: Option<&str>, state: Option<&str>, rect: Rect) -> Result<(), Error> {
+        let shape = self.enum_value(class, part, state, "BorderType").unwrap_or(BT_RECT);
+        let border_size = self.int(class, part, state, "BorderSize").unwrap_or(1).max(0);
+        let border_color = self.color(class, part, state, "BorderColor").unwrap_or(Color::new(0, 0, 0));
+        let corner_width = self.int(class, part, state, "RoundCornerWidth").unwrap_or(0);
+        let corner_height = self.int(class, part, state, "RoundCornerHeight").unwrap_or(0);
+        let border_only = self.bool(class, part, state, "BorderOnly").unwrap_or(false);
+
+        let inner = deflate_rect(rect, Margins { left: border_size, right: border_size, top: border_size, bottom: border_size });
+        let (inner_corner_width, inner_corner_height) = ((corner_width - border_size * 2).max(0), (corner_height - border_size * 2).max(0));
+
+        let fill_type = self.enum_value(class, part, state, "FillType").unwrap_or(FT_SOLID);
+        let fill_color = self.color(class, part, state, "FillColor").unwrap_or(Color::new(255, 255, 255));
+        let gradient = self.gradient(class, part, state);
+        let tile = match fill_type {
+            FT_TILEIMAGE => match self.select_image(class, part, state, inner, false)? {
+                Some(selected) => {
+                    let image = self.image(&selected.path, None)?;
+                    let frame = self.frame_rect(&image, class, part, state);
+                    Some((image, frame))
+                }
+                None => None,
+            },
+            _ => None,
+        };
+
+        let (width, height) = (inner.width().max(1) as f32, inner.height().max(1) as f32);
+        let fill_pixel = |x: i32, y: i32| -> [u8; 4] {
+            let (offset_x, offset_y) = (x - inner.left, y - inner.top);
+            let color = match fill_type {
+                FT_VERTGRADIENT => gradient.color_at((offset_y as f32 + 0.5) / height),
+                FT_HORZGRADIENT => gradient.color_at((offset_x as f32 + 0.5) / width),
+                FT_RADIALGRADIENT => {
+                    let dx = (offset_x as f32 + 0.5 - width / 2.0) / (width / 2.0);
+                    let dy = (offset_y as f32 + 0.5 - height / 2.0) / (height / 2.0);
+                    gradient.color_at((dx * dx + dy * dy).sqrt())
+                }
+                FT_TILEIMAGE => match &tile {
+                    Some((image, frame)) if frame.width() > 0 && frame.height() > 0 => {
+                        let source_x = frame.left + offset_x.rem_euclid(frame.width());
+                        let source_y = frame.top + offset_y.rem_euclid(frame.height());
+                        return image.pixel(source_x as u32, source_y as u32);
+                    }
+                    _ => fill_color,
+                },
+                _ => fill_color,
+            };
+            [color.r, color.g, color.b, 255]
+        };
+
+        for y in rect.top.max(0)..rect.bottom.min(canvas.height as i32) {
+            for x in rect.left.max(0)..rect.right.min(canvas.width as i32) {
+                if inside(shape, inner, inner_corner_width, inner_corner_height, x, y) {
+                    if !border_only {
+                        blend_pixel(canvas, x, y, fill_pixel(x, y), 255);
+                    }
+                }
+                else if inside(shape, rect, corner_width, corner_height, x, y) {
+                    blend_pixel(canvas, x, y, [border_color.r, border_color.g, border_color.b, 255], 255);
+                }
+            }
+        }
+        Ok(())
+    }
+}
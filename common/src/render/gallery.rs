@@ -0,0 +1,401 @@
+/*
+ * Control galleries.
+ *
+ * Draws the common controls in each of their states and lays them out in one labelled sheet: a
+ * row for each control, with a cell for each state and the state's name under it. Controls are
+ * drawn from their theme parts the way comctl32 6 composes them, i.e. a combo box is its border
+ * with the drop-down button on its right, and a scroll bar is its arrows, tracks and thumb.
+ *
+ * Most controls show the Normal, Hot, Pressed, Disabled and Focused states. Controls that do not
+ * have one of them show the nearest state that they do have, and a focus rectangle like
+ * DrawFocusRect where focus is only shown by one. Controls with states of their own, like the
+ * progress bar's Error and Paused fills, show those instead.
+ *
+ * Labels and control text are drawn in the MsgBox font, and colors that no part sets come from
+ * the theme's system colors.
+ */
+
+use anyhow::Error;
+
+use super::text::{ThemeText, DT_CENTER, DT_END_ELLIPSIS, DT_LEFT, DT_SINGLELINE, DT_VCENTER};
+use super::{blend_pixel, deflate_rect, Renderer};
+use crate::image::RgbaImage;
+use crate::theme::sysmetrics::{scale_px, FontMetric, SizeMetric};
+use crate::theme::{Color, Margins, Rect};
+
+/**
+ * Draws a control into a cell, in one of its gallery states.
+ */
+type DrawCell = fn(&Renderer, &mut RgbaImage, Rect, &str) -> Result<(), Error>;
+
+pub struct GalleryControl {
+    pub name: &'static str,
+    /**
+     * The size of a cell at 96 DPI.
+     */
+    pub size: (i32, i32),
+    pub states: &'static [&'static str],
+    pub draw: DrawCell,
+}
+
+pub const STANDARD_STATES: &[&str] = &["Normal", "Hot", "Pressed", "Disabled", "Focused"];
+
+pub const GALLERY: &[GalleryControl] = &[
+    GalleryControl { name: "Push button", size: (75, 23), states: STANDARD_STATES, draw: draw_push_button },
+    GalleryControl { name: "Check box", size: (90, 17), states: STANDARD_STATES, draw: draw_check_box },
+    GalleryControl { name: "Check box, checked", size: (90, 17), states: STANDARD_STATES, draw: draw_checked_check_box },
+    GalleryControl { name: "Radio button", size: (90, 17), states: STANDARD_STATES, draw: draw_radio_button },
+    GalleryControl { name: "Radio button, checked", size: (90, 17), states: STANDARD_STATES, draw: draw_checked_radio_button },
+    GalleryControl { name: "Edit", size: (100, 23), states: STANDARD_STATES, draw: draw_edit },
+    GalleryControl { name: "Combo box", size: (100, 23), states: STANDARD_STATES, draw: draw_combo_box },
+    GalleryControl { name: "List view item", size: (100, 20), states: STANDARD_STATES, draw: draw_list_item },
+    GalleryControl { name: "Tree view item", size: (100, 20), states: STANDARD_STATES, draw: draw_tree_item },
+    GalleryControl { name: "Tab", size: (70, 30), states: STANDARD_STATES, draw: draw_tab },
+    GalleryControl { name: "Progress bar", size: (100, 15), states: &["Normal", "Error", "Paused", "Partial"], draw: draw_progress_bar },
+    GalleryControl { name: "Trackbar", size: (100, 26), states: STANDARD_STATES, draw: draw_trackbar },
+    GalleryControl { name: "Scroll bar", size: (120, 17), states: &["Normal", "Hot", "Pressed", "Disabled", "Hover"], draw: draw_scroll_bar },
+    GalleryControl { name: "Toolbar button", size: (50, 22), states: &["Normal", "Hot", "Pressed", "Disabled", "Checked"], draw: draw_toolbar_button },
+    GalleryControl { name: "Header item", size: (90, 24), states: &["Normal", "Hot", "Pressed", "SortedNormal"], draw: draw_header_item },
+    GalleryControl { name: "Tooltip", size: (90, 24), states: &["Normal", "Link"], draw: draw_tooltip },
+];
+
+/**
+ * The space around the sheet and between its cells, at 96 DPI.
+ */
+const SPACING: i32 = 12;
+
+/**
+ * The space between a cell and its label, at 96 DPI.
+ */
+const LABEL_GAP: i32 = 3;
+
+/**
+ * The size of a check box or radio button glyph, at 96 DPI.
+ */
+const GLYPH_SIZE: i32 = 13;
+
+/**
+ * The padding between a control's edge and its text where the theme has no ContentMargins, at 96
+ * DPI.
+ */
+const TEXT_PADDING: i32 = 4;
+
+/**
+ * Pick the state for a gallery column from the states of a part, in the order of
+ * STANDARD_STATES. Other columns are passed through.
+ */
+fn pick<'s>(column: &'s str, states: [&'s str; 5]) -> &'s str {
+    match STANDARD_STATES.iter().position(|state| *state == column) {
+        Some(index) => states[index],
+        None => column,
+    }
+}
+
+/**
+ * The first part that the theme defines, or the last one.
+ */
+fn defined_part<'p>(renderer: &Renderer, class: &str, parts: &[&'p str]) -> &'p str {
+    let theme_class = renderer.theme.class(class);
+    parts.iter()
+        .find(|part| theme_class.and_then(|theme_class| theme_class.part(Some(part))).is_some())
+        .or(parts.last())
+        .copied()
+        .unwrap_or_default()
+}
+
+/**
+ * Draw a dotted rectangle, like DrawFocusRect.
+ */
+fn draw_focus_rect(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect) {
+    let rect = renderer.device_rect(canvas, rect);
+    for x in rect.left..rect.right {
+        for y in [rect.top, rect.bottom - 1] {
+            if (x + y) % 2 == 0 {
+                blend_pixel(canvas, x, y, [0, 0, 0, 255], 255);
+            }
+        }
+    }
+    for y in rect.top..rect.bottom {
+        for x in [rect.left, rect.right - 1] {
+            if (x + y) % 2 == 0 {
+                blend_pixel(canvas, x, y, [0, 0, 0, 255], 255);
+            }
+        }
+    }
+}
+
+fn inset(rect: Rect, amount: i32) -> Rect {
+    deflate_rect(rect, Margins { left: amount, right: amount, top: amount, bottom: amount })
+}
+
+/**
+ * Text in the MsgBox font, and a system color for states without a TextColor.
+ */
+fn label<'t>(renderer: &Renderer, text: &'t str, flags: u32, color: &str) -> ThemeText<'t> {
+    let font = renderer.sys_metrics().font(FontMetric::MsgBox).cloned();
+    ThemeText { text, flags: flags | DT_SINGLELINE | DT_VCENTER | DT_END_ELLIPSIS, font, color: renderer.system_colors().get(color) }
+}
+
+fn draw_push_button(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    let state = pick(column, ["Normal", "Hot", "Pressed", "Disabled", "Defaulted"]);
+    renderer.draw_background(canvas, "Button", Some("PushButton"), Some(state), rect)?;
+    let content = renderer.content_rect("Button", Some("PushButton"), Some(state), rect);
+    renderer.draw_text(canvas, "Button", Some("PushButton"), Some(state), &label(renderer, "Button", DT_CENTER, "ButtonText"), content)?;
+    if column == "Focused" {
+        draw_focus_rect(renderer, canvas, content);
+    }
+    Ok(())
+}
+
+/**
+ * Draw a check box or radio button: the glyph on the left, vertically centered, and its text.
+ */
+fn draw_button_glyph(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, part: &str, state: &str, focused: bool) -> Result<(), Error> {
+    let glyph_size = scale_px(GLYPH_SIZE, renderer.dpi);
+    let top = rect.top + (rect.height() - glyph_size) / 2;
+    let glyph = Rect { left: rect.left, top, right: rect.left + glyph_size, bottom: top + glyph_size };
+    renderer.draw_background(canvas, "Button", Some(part), Some(state), glyph)?;
+
+    let text = Rect { left: glyph.right + scale_px(TEXT_PADDING, renderer.dpi), ..rect };
+    let label = label(renderer, if part == "CheckBox" { "Check" } else { "Option" }, DT_LEFT, "ButtonText");
+    renderer.draw_text(canvas, "Button", Some(part), Some(state), &label, text)?;
+    if focused {
+        let extent = renderer.text_extent("Button", Some(part), Some(state), &label, None)?;
+        let top = text.top + (text.height() - extent.y) / 2;
+        draw_focus_rect(renderer, canvas, Rect { left: text.left - 1, top, right: text.left + extent.x + 1, bottom: top + extent.y });
+    }
+    Ok(())
+}
+
+fn draw_check_box(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    let state = pick(column, ["UncheckedNormal", "UncheckedHot", "UncheckedPressed", "UncheckedDisabled", "UncheckedNormal"]);
+    draw_button_glyph(renderer, canvas, rect, "CheckBox", state, column == "Focused")
+}
+
+fn draw_checked_check_box(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    let state = pick(column, ["CheckedNormal", "CheckedHot", "CheckedPressed", "CheckedDisabled", "CheckedNormal"]);
+    draw_button_glyph(renderer, canvas, rect, "CheckBox", state, column == "Focused")
+}
+
+fn draw_radio_button(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    let state = pick(column, ["UncheckedNormal", "UncheckedHot", "UncheckedPressed", "UncheckedDisabled", "UncheckedNormal"]);
+    draw_button_glyph(renderer, canvas, rect, "RadioButton", state, column == "Focused")
+}
+
+fn draw_checked_radio_button(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    let state = pick(column, ["CheckedNormal", "CheckedHot", "CheckedPressed", "CheckedDisabled", "CheckedNormal"]);
+    draw_button_glyph(renderer, canvas, rect, "RadioButton", state, column == "Focused")
+}
+
+/**
+ * Edits have no pressed state: while the mouse is down they have focus.
+ */
+fn draw_edit(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    let part = defined_part(renderer, "Edit", &["EditBorder_NoScroll", "EditText"]);
+    let state = pick(column, ["Normal", "Hot", "Focused", "Disabled", "Focused"]);
+    renderer.fill(canvas, inset(rect, 1), renderer.system_colors().get("Window").unwrap_or(Color::new(255, 255, 255)));
+    renderer.draw_background(canvas, "Edit", Some(part), Some(state), rect)?;
+    let text = inset(rect, scale_px(TEXT_PADDING, renderer.dpi));
+    let text_state = if state == "Disabled" { "Disabled" } else { "Normal" };
+    renderer.draw_text(canvas, "Edit", Some("EditText"), Some(text_state), &label(renderer, "Text", DT_LEFT, "WindowText"), text)
+}
+
+fn draw_combo_box(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    let border_state = pick(column, ["Normal", "Hot", "Focused", "Disabled", "Focused"]);
+    let button_state = pick(column, ["Normal", "Hot", "Pressed", "Disabled", "Normal"]);
+    renderer.fill(canvas, inset(rect, 1), renderer.system_colors().get("Window").unwrap_or(Color::new(255, 255, 255)));
+    renderer.draw_background(canvas, "ComboBox", Some("Border"), Some(border_state), rect)?;
+
+    let button_width = scale_px(renderer.sys_metrics().size(SizeMetric::ScrollbarWidth).unwrap_or(17), renderer.dpi);
+    let button = Rect { left: rect.right - 1 - button_width, top: rect.top + 1, right: rect.right - 1, bottom: rect.bottom - 1 };
+    let part = defined_part(renderer, "ComboBox", &["DropDownButtonRight", "DropDownButton"]);
+    renderer.draw_background(canvas, "ComboBox", Some(part), Some(button_state), button)?;
+
+    let text = Rect { right: button.left, ..inset(rect, scale_px(TEXT_PADDING, renderer.dpi)) };
+    let text_state = if border_state == "Disabled" { "Disabled" } else { "Normal" };
+    renderer.draw_text(canvas, "ComboBox", Some("Border"), Some(text_state), &label(renderer, "Item", DT_LEFT, "WindowText"), text)?;
+    if column == "Focused" {
+        draw_focus_rect(renderer, canvas, text);
+    }
+    Ok(())
+}
+
+/**
+ * Draw a list view or tree view item on the window color. A pressed item is selected while the
+ * mouse is over it, and a focused one is selected with a focus rectangle.
+ */
+fn draw_item(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, class: &str, part: &str, column: &str) -> Result<(), Error> {
+    let state = pick(column, ["Normal", "Hot", "HotSelected", "Disabled", "Selected"]);
+    renderer.fill(canvas, rect, renderer.system_colors().get("Window").unwrap_or(Color::new(255, 255, 255)));
+    renderer.draw_background(canvas, class, Some(part), Some(state), rect)?;
+    let text = renderer.content_rect(class, Some(part), Some(state), inset(rect, scale_px(TEXT_PADDING, renderer.dpi)));
+    renderer.draw_text(canvas, class, Some(part), Some(state), &label(renderer, "Item", DT_LEFT, "WindowText"), text)?;
+    if column == "Focused" {
+        draw_focus_rect(renderer, canvas, rect);
+    }
+    Ok(())
+}
+
+fn draw_list_item(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    draw_item(renderer, canvas, rect, "ListView", "ListItem", column)
+}
+
+fn draw_tree_item(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    // The expand glyph goes in a square to the left of the item.
+    let glyph = Rect { right: rect.left + rect.height(), ..rect };
+    renderer.fill(canvas, glyph, renderer.system_colors().get("Window").unwrap_or(Color::new(255, 255, 255)));
+    renderer.draw_background(canvas, "TreeView", Some("Glyph"), Some("Opened"), glyph)?;
+    draw_item(renderer, canvas, Rect { left: glyph.right, ..rect }, "TreeView", "TreeItem", column)
+}
+
+/**
+ * Draw a tab on top of the edge of its pane. Selected tabs are taller than the others.
+ */
+fn draw_tab(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    let state = pick(column, ["Normal", "Hot", "Selected", "Disabled", "Focused"]);
+    let pane_top = rect.bottom - scale_px(6, renderer.dpi);
+    renderer.draw_background(canvas, "Tab", Some("Pane"), None, Rect { top: pane_top, ..rect })?;
+
+    let lift = if state == "Selected" || state == "Focused" { scale_px(2, renderer.dpi) } else { 0 };
+    let tab = Rect { left: rect.left + scale_px(2, renderer.dpi) - lift, right: rect.right - scale_px(2, renderer.dpi) + lift, top: rect.top + scale_px(2, renderer.dpi) - lift, bottom: pane_top + 1 };
+    let part = defined_part(renderer, "Tab", &["TopTabItem", "TabItem"]);
+    renderer.draw_background(canvas, "Tab", Some(part), Some(state), tab)?;
+    let content = renderer.content_rect("Tab", Some(part), Some(state), tab);
+    renderer.draw_text(canvas, "Tab", Some(part), Some(state), &label(renderer, "Tab", DT_CENTER, "ButtonText"), content)?;
+    if column == "Focused" {
+        draw_focus_rect(renderer, canvas, inset(tab, scale_px(2, renderer.dpi)));
+    }
+    Ok(())
+}
+
+/**
+ * Draw a bar that is 60% full. Themes without a Fill part, like those from before Vista, fill
+ * with Chunk instead, which has no states.
+ */
+fn draw_progress_bar(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    renderer.draw_background(canvas, "Progress", Some("Bar"), None, rect)?;
+    let content = renderer.content_rect("Progress", Some("Bar"), None, rect);
+    let filled = Rect { right: content.left + content.width() * 3 / 5, ..content };
+    let part = defined_part(renderer, "Progress", &["Fill", "Chunk"]);
+    let state = if part == "Fill" { Some(column) } else { None };
+    renderer.draw_background(canvas, "Progress", Some(part), state, filled)
+}
+
+fn draw_trackbar(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, column: &str) -> Result<(), Error> {
+    let state = pick(column, ["Normal", "Hot", "Pressed", "Disabled", "Focused"]);
+    let track_height = scale_px(4, renderer.dpi);
+    let middle = rect.top + rect.height() / 2;
+    let track = Rect { top: middle - track_height / 2, bottom: middle - track_height / 2 + track_height, ..inset(rect, scale_px(4, renderer.dpi)) };
+    renderer.draw_background(canvas, "TrackBar", Some("Track"), Some("Normal"), track)?;
+
+    let (thumb_width, thumb_height) = (scale_px(11, renderer.dpi), scale_px(19, renderer.dpi));
+    let left = rect.left + rect.width() * 2 / 5;
+    let thumb = Rect { left, top: middle - thumb_height / 2, right: left + thumb_width, bottom: middle - thumb_height / 2 + thumb_height };
+    renderer.draw_background(canvas, "TrackBar", Some("Thumb"), Some(state), thumb)?;
+    if column == "Focused" {
+        draw_focus_rect(renderer, canvas, rect);
+    }
+    Ok(())
+}
+
+/**
+ * Draw a horizontal scroll bar: its arrows, the tracks on either side of the thumb, and the thumb
+ * with its gripper. The state applies to the whole bar. Disabled scroll bars have no thumb.
+ */
+fn draw_scroll_bar(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, state: &str) -> Result<(), Error> {
+    let arrow_width = rect.height();
+    let left_arrow = Rect { right: rect.left + arrow_width, ..rect };
+    let right_arrow = Rect { left: rect.right - arrow_width, ..rect };
+    renderer.draw_background(canvas, "ScrollBar", Some("ArrowBtn"), Some(&format!("Left{}", state)), left_arrow)?;
+    renderer.draw_background(canvas, "ScrollBar", Some("ArrowBtn"), Some(&format!("Right{}", state)), right_arrow)?;
+
+    let track = Rect { left: left_arrow.right, right: right_arrow.left, ..rect };
+    if state == "Disabled" {
+        return renderer.draw_background(canvas, "ScrollBar", Some("LowerTrackHorz"), Some(state), track);
+    }
+    let thumb_left = track.left + track.width() / 4;
+    let thumb = Rect { left: thumb_left, right: thumb_left + track.width() / 3, ..track };
+    renderer.draw_background(canvas, "ScrollBar", Some("LowerTrackHorz"), Some(state), Rect { right: thumb.left, ..track })?;
+    renderer.draw_background(canvas, "ScrollBar", Some("UpperTrackHorz"), Some(state), Rect { left: thumb.right, ..track })?;
+    renderer.draw_background(canvas, "ScrollBar", Some("ThumbBtnHorz"), Some(state), thumb)?;
+    renderer.draw_background(canvas, "ScrollBar", Some("GripperHorz"), Some(state), thumb)
+}
+
+fn draw_toolbar_button(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, state: &str) -> Result<(), Error> {
+    renderer.draw_background(canvas, "Toolbar", Some("Button"), Some(state), rect)?;
+    let content = renderer.content_rect("Toolbar", Some("Button"), Some(state), rect);
+    renderer.draw_text(canvas, "Toolbar", Some("Button"), Some(state), &label(renderer, "Tool", DT_CENTER, "ButtonText"), content)
+}
+
+fn draw_header_item(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, state: &str) -> Result<(), Error> {
+    renderer.draw_background(canvas, "Header", Some("HeaderItem"), Some(state), rect)?;
+    let content = renderer.content_rect("Header", Some("HeaderItem"), Some(state), inset(rect, scale_px(TEXT_PADDING, renderer.dpi)));
+    renderer.draw_text(canvas, "Header", Some("HeaderItem"), Some(state), &label(renderer, "Column", DT_LEFT, "ButtonText"), content)?;
+    if state.starts_with("Sorted") {
+        // The sort arrow sits at the top middle of the item, as in Vista and later.
+        let arrow_size = scale_px(8, renderer.dpi);
+        let left = rect.left + (rect.width() - arrow_size) / 2;
+        let arrow = Rect { left, top: rect.top, right: left + arrow_size, bottom: rect.top + arrow_size / 2 + 1 };
+        renderer.draw_background(canvas, "Header", Some("HeaderSortArrow"), Some("SortedUp"), arrow)?;
+    }
+    Ok(())
+}
+
+fn draw_tooltip(renderer: &Renderer, canvas: &mut RgbaImage, rect: Rect, state: &str) -> Result<(), Error> {
+    renderer.draw_background(canvas, "Tooltip", Some("Standard"), Some(state), rect)?;
+    let content = renderer.content_rect("Tooltip", Some("Standard"), Some(state), inset(rect, scale_px(TEXT_PADDING, renderer.dpi)));
+    renderer.draw_text(canvas, "Tooltip", Some("Standard"), Some(state), &label(renderer, "Tooltip", DT_LEFT, "InfoText"), content)
+}
+
+impl Renderer<'_> {
+    /**
+     * Draw a sheet of controls in each of their states, on the button face color.
+     */
+    pub fn draw_gallery(&self, controls: &[GalleryControl]) -> Result<RgbaImage, Error> {
+        let spacing = scale_px(SPACING, self.dpi);
+        let label_gap = scale_px(LABEL_GAP, self.dpi);
+        let measure = |text: &str| self.text_extent("Globals", None, None, &label(self, text, DT_LEFT, "WindowText"), None);
+
+        let mut name_width = 0;
+        for control in controls {
+            name_width = name_width.max(measure(control.name)?.x);
+        }
+        let label_height = measure("Normal")?.y;
+
+        // Each row is as wide as its cells or their labels, whichever is wider.
+        let mut rows = Vec::new();
+        let (mut sheet_width, mut sheet_height) = (0, spacing);
+        for control in controls {
+            let (cell_width, cell_height) = (scale_px(control.size.0, self.dpi), scale_px(control.size.1, self.dpi));
+            let mut columns = Vec::new();
+            let mut x = spacing + name_width + spacing;
+            for state in control.states {
+                let width = cell_width.max(measure(state)?.x);
+                columns.push((x, width));
+                x += width + spacing;
+            }
+            sheet_width = sheet_width.max(x);
+            rows.push((sheet_height, cell_width, cell_height, columns));
+            sheet_height += cell_height + label_gap + label_height + spacing;
+        }
+
+        let mut canvas = RgbaImage::new(sheet_width.max(1) as u32, sheet_height.max(1) as u32);
+        let face = self.system_colors().get("ButtonFace").unwrap_or(Color::new(240, 240, 240));
+        self.fill(&mut canvas, Rect { left: 0, top: 0, right: sheet_width, bottom: sheet_height }, face);
+
+        for (control, (top, cell_width, cell_height, columns)) in controls.iter().zip(rows) {
+            let name = Rect { left: spacing, top, right: spacing + name_width, bottom: top + cell_height };
+            self.draw_text(&mut canvas, "Globals", None, None, &label(self, control.name, DT_LEFT, "WindowText"), name)?;
+            for (state, (x, width)) in control.states.iter().zip(columns) {
+                let left = x + (width - cell_width) / 2;
+                let cell = Rect { left, top, right: left + cell_width, bottom: top + cell_height };
+                (control.draw)(self, &mut canvas, cell, state)?;
+
+                let label_top = cell.bottom + label_gap;
+                let label_rect = Rect { left: x, top: label_top, right: x + width, bottom: label_top + label_height };
+                self.draw_text(&mut canvas, "Globals", None, None, &label(self, state, DT_CENTER, "WindowText"), label_rect)?;
+            }
+        }
+        Ok(canvas)
+    }
+}
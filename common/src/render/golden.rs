@@ -0,0 +1,464 @@
+/*
+ * Golden image regression testing.
+ *
+ * A suite renders a set of cases (parts, mock windows and control galleries) from a theme and
+ * compares each with a golden PNG of the same name in a directory. The suite comes from a
+ * suite.json in that directory:
+ *
+ *     {
+ *       "tolerance": { "pixel": 12, "fraction": 0.001 },
+ *       "cases": [
+ *         { "name": "button-hot", "part": "Button.PushButton(Hot)", "size": "75x23", "text": "OK" },
+ *         { "name": "window", "window": { "title": "Notepad", "active": true, "buttons": { "Close": "Hot" } }, "size": "480x320" },
+ *         { "name": "gallery-144", "gallery": true, "dpi": 144 },
+ *         { "name": "window-rtl", "window": {}, "rtl": true }
+ *       ]
+ *     }
+ *
+ * Without one, the default suite covers an active and an inactive window and the control gallery.
+ *
+ * Comparisons are perceptual rather than exact, so that rounding changes in the renderer do not
+ * fail every case. The distance between two pixels is the larger of their alpha difference and
+ * the "redmean" weighted distance between their colors over white, which tracks how different
+ * two colors look much better than the plain RGB distance, scaled to 0..255. A case passes when
+ * no more than a fraction of its pixels are further apart than the pixel tolerance.
+ *
+ * Failed and missing cases can be written into an HTML report with the expected, actual and
+ * difference images side by side. The difference image shows the actual rendering faded, with
+ * the pixels that differ in red.
+ */
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+use serde_json::Value;
+
+use super::gallery::GALLERY;
+use super::text::{ThemeText, DT_CENTER, DT_END_ELLIPSIS, DT_SINGLELINE, DT_VCENTER};
+use super::window::{CaptionButton, MockWindow};
+use super::{to_png, Renderer};
+use crate::formats::restyle_ini;
+use crate::image::{decode, DecodeOptions, RgbaImage};
+use crate::theme::sysmetrics::{scale_px, BASE_DPI};
+use crate::theme::{Rect, Theme};
+
+pub const SUITE_FILE_NAME: &str = "suite.json";
+pub const REPORT_FILE_NAME: &str = "index.html";
+
+/**
+ * The size of a mock window at 96 DPI, for window cases without a size.
+ */
+pub const DEFAULT_WINDOW_SIZE: (u32, u32) = (480, 320);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    /**
+     * How far apart two pixels can be before they count as different, from 0 to 255.
+     */
+    pub pixel: f32,
+    /**
+     * The fraction of the pixels that can differ.
+     */
+    pub fraction: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self { pixel: 12.0, fraction: 0.001 }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum GoldenSubject {
+    /**
+     * The background of a state, with text on it like `kanayacl render --text`.
+     */
+    Part { class: String, part: Option<String>, state: Option<String>, text: Option<String> },
+    Window(MockWindow),
+    Gallery,
+}
+
+#[derive(Clone, Debug)]
+pub struct GoldenCase {
+    /**
+     * The name of the golden image, without ".png".
+     */
+    pub name: String,
+    pub subject: GoldenSubject,
+    /**
+     * The size of the rendering. Galleries size themselves.
+     */
+    pub size: Option<(u32, u32)>,
+    pub dpi: u32,
+    /**
+     * Whether to render right to left.
+     */
+    pub mirrored: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GoldenSuite {
+    pub tolerance: Tolerance,
+    pub cases: Vec<GoldenCase>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaseStatus {
+    Passed,
+    Failed,
+    /**
+     * The rendering has a different size from the golden image.
+     */
+    SizeChanged,
+    /**
+     * There is no golden image yet.
+     */
+    Missing,
+}
+
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /**
+     * The pixels that are further apart than the pixel tolerance.
+     */
+    pub differing: usize,
+    pub total: usize,
+    pub max_distance: f32,
+    pub diff: RgbaImage,
+}
+
+impl Comparison {
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.differing as f32 <= self.total as f32 * tolerance.fraction
+    }
+}
+
+pub struct CaseResult {
+    pub name: String,
+    pub status: CaseStatus,
+    pub expected: Option<RgbaImage>,
+    pub actual: RgbaImage,
+    pub comparison: Option<Comparison>,
+}
+
+#[derive(Default)]
+pub struct SuiteReport {
+    pub results: Vec<CaseResult>,
+}
+
+impl SuiteReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|result| result.status == CaseStatus::Passed)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &CaseResult> {
+        self.results.iter().filter(|result| result.status != CaseStatus::Passed)
+    }
+}
+
+fn parse_size(text: &str) -> Result<(u32, u32), Error> {
+    text.split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .ok_or_else(|| anyhow!("Invalid size \"{}\", expected WIDTHxHEIGHT.", text))
+}
+
+fn parse_window(value: &Value) -> Result<MockWindow, Error> {
+    let mut window = MockWindow::default();
+    if let Some(title) = value.get("title").and_then(Value::as_str) {
+        window.title = title.to_owned();
+    }
+    if let Some(menu) = value.get("menu").and_then(Value::as_array) {
+        window.menu = menu.iter().filter_map(Value::as_str).map(str::to_owned).collect();
+    }
+    window.active = value.get("active").and_then(Value::as_bool).unwrap_or(true);
+    window.maximized = value.get("maximized").and_then(Value::as_bool).unwrap_or(false);
+    if let Some(buttons) = value.get("buttons").and_then(Value::as_object) {
+        for (name, state) in buttons {
+            let button = CaptionButton::from_name(name).ok_or_else(|| anyhow!("Unknown caption button \"{}\"", name))?;
+            let state = state.as_str().ok_or_else(|| anyhow!("The state of the {} button is not a string.", name))?;
+            window.button_states[button as usize] = Some(state.to_owned());
+        }
+    }
+    Ok(window)
+}
+
+fn parse_case(value: &Value) -> Result<GoldenCase, Error> {
+    let name = value.get("name").and_then(Value::as_str).ok_or_else(|| anyhow!("A case has no name."))?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.') {
+        return Err(anyhow!("Invalid case name \"{}\". Use letters, digits, '-', '_' and '.'.", name));
+    }
+    let size = value.get("size").and_then(Value::as_str).map(parse_size).transpose()?;
+    let dpi = value.get("dpi").and_then(Value::as_u64).map_or(BASE_DPI, |dpi| dpi as u32);
+    let mirrored = value.get("rtl").and_then(Value::as_bool).unwrap_or(false);
+
+    let subject = if let Some(section) = value.get("part").and_then(Value::as_str) {
+        if size.is_none() {
+            return Err(anyhow!("The part case \"{}\" has no size.", name));
+        }
+        let section = restyle_ini::parse_section_name(section)?;
+        let text = value.get("text").and_then(Value::as_str).map(str::to_owned);
+        GoldenSubject::Part { class: section.class, part: section.part, state: section.state, text }
+    }
+    else if let Some(window) = value.get("window") {
+        GoldenSubject::Window(parse_window(window)?)
+    }
+    else if value.get("gallery").and_then(Value::as_bool) == Some(true) {
+        GoldenSubject::Gallery
+    }
+    else {
+        return Err(anyhow!("The case \"{}\" has no part, window or gallery.", name));
+    };
+    Ok(GoldenCase { name: name.to_owned(), subject, size, dpi, mirrored })
+}
+
+impl GoldenSuite {
+    /**
+     * An active and an inactive window, and the control gallery.
+     */
+    pub fn default_suite() -> Self {
+        let window = |name: &str, active: bool| GoldenCase {
+            name: name.to_owned(),
+            subject: GoldenSubject::Window(MockWindow { active, ..Default::default() }),
+            size: None,
+            dpi: BASE_DPI,
+            mirrored: false,
+        };
+        Self {
+            tolerance: Tolerance::default(),
+            cases: vec![
+                window("window-active", true),
+                window("window-inactive", false),
+                GoldenCase { name: "gallery".to_owned(), subject: GoldenSubject::Gallery, size: None, dpi: BASE_DPI, mirrored: false },
+            ],
+        }
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, Error> {
+        let value: Value = serde_json::from_str(text)?;
+        let mut tolerance = Tolerance::default();
+        if let Some(pixel) = value.pointer("/tolerance/pixel").and_then(Value::as_f64) {
+            tolerance.pixel = pixel as f32;
+        }
+        if let Some(fraction) = value.pointer("/tolerance/fraction").and_then(Value::as_f64) {
+            tolerance.fraction = fraction as f32;
+        }
+
+        let cases = value.get("cases").and_then(Value::as_array).ok_or_else(|| anyhow!("The suite has no cases."))?
+            .iter()
+            .map(parse_case)
+            .collect::<Result<Vec<_>, _>>()?;
+        for (index, case) in cases.iter().enumerate() {
+            if cases[..index].iter().any(|other| other.name.eq_ignore_ascii_case(&case.name)) {
+                return Err(anyhow!("There is more than one case named \"{}\".", case.name));
+            }
+        }
+        Ok(Self { tolerance, cases })
+    }
+
+    /**
+     * The suite of a golden directory: its suite.json, or the default suite without one.
+     */
+    pub fn load(dir: &Path) -> Result<Self, Error> {
+        let path = dir.join(SUITE_FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default_suite());
+        }
+        let text = fs::read_to_string(&path).with_context(|| anyhow!("Failed to read \"{}\"", path.display()))?;
+        Self::from_json(&text).with_context(|| anyhow!("Invalid suite \"{}\"", path.display()))
+    }
+}
+
+impl GoldenCase {
+    pub fn render(&self, theme: &Theme) -> Result<RgbaImage, Error> {
+        let mut renderer = Renderer::with_dpi(theme, self.dpi);
+        renderer.mirrored = self.mirrored;
+        let (width, height) = self.size.unwrap_or((
+            scale_px(DEFAULT_WINDOW_SIZE.0 as i32, self.dpi) as u32,
+            scale_px(DEFAULT_WINDOW_SIZE.1 as i32, self.dpi) as u32,
+        ));
+        let rect = Rect { left: 0, top: 0, right: width as i32, bottom: height as i32 };
+
+        match &self.subject {
+            GoldenSubject::Part { class, part, state, text } => {
+                let (part, state) = (part.as_deref(), state.as_deref());
+                let mut canvas = RgbaImage::new(width, height);
+                renderer.draw_background(&mut canvas, class, part, state, rect)?;
+                if let Some(text) = text {
+                    let text = ThemeText { text, flags: DT_CENTER | DT_VCENTER | DT_SINGLELINE | DT_END_ELLIPSIS, font: None, color: None };
+                    renderer.draw_text(&mut canvas, class, part, state, &text, renderer.content_rect(class, part, state, rect))?;
+                }
+                Ok(canvas)
+            }
+            GoldenSubject::Window(window) => {
+                let mut canvas = RgbaImage::new(width, height);
+                renderer.draw_window(&mut canvas, window, rect)?;
+                Ok(canvas)
+            }
+            GoldenSubject::Gallery => renderer.draw_gallery(GALLERY),
+        }
+    }
+}
+
+/**
+ * A straight alpha pixel over white.
+ */
+fn over_white(pixel: [u8; 4]) -> [f32; 3] {
+    let alpha = pixel[3] as f32 / 255.0;
+    [0, 1, 2].map(|index| pixel[index] as f32 * alpha + 255.0 * (1.0 - alpha))
+}
+
+/**
+ * How different two pixels look, from 0 to 255.
+ */
+pub fn pixel_distance(a: [u8; 4], b: [u8; 4]) -> f32 {
+    let (a_color, b_color) = (over_white(a), over_white(b));
+    let mean_red = (a_color[0] + b_color[0]) / 2.0;
+    let [dr, dg, db] = [0, 1, 2].map(|index| a_color[index] - b_color[index]);
+    let color = ((2.0 + mean_red / 256.0) * dr * dr + 4.0 * dg * dg + (2.0 + (255.0 - mean_red) / 256.0) * db * db).sqrt() / 3.0;
+    color.max((a[3] as f32 - b[3] as f32).abs())
+}
+
+/**
+ * Compare a rendering with its golden image. Images of different sizes are compared over the
+ * larger of the two, with transparent pixels outside of each.
+ */
+pub fn compare(expected: &RgbaImage, actual: &RgbaImage, tolerance: &Tolerance) -> Comparison {
+    let (width, height) = (expected.width.max(actual.width), expected.height.max(actual.height));
+    let pixel = |image: &RgbaImage, x: u32, y: u32| if x < image.width && y < image.height { image.pixel(x, y) } else { [0, 0, 0, 0] };
+
+    let mut diff = RgbaImage::new(width, height);
+    let (mut differing, mut max_distance) = (0, 0.0f32);
+    for y in 0..height {
+        for x in 0..width {
+            let actual_pixel = pixel(actual, x, y);
+            let distance = pixel_distance(pixel(expected, x, y), actual_pixel);
+            max_distance = max_distance.max(distance);
+            if distance > tolerance.pixel {
+                differing += 1;
+                diff.set_pixel(x, y, [255, 0, 0, (128.0 + distance / 2.0).min(255.0) as u8]);
+            }
+            else {
+                let [red, green, blue] = over_white(actual_pixel);
+                let gray = (red * 0.299 + green * 0.587 + blue * 0.114) as u8;
+                diff.set_pixel(x, y, [gray, gray, gray, 48]);
+            }
+        }
+    }
+    Comparison { differing, total: width as usize * height as usize, max_distance, diff }
+}
+
+fn golden_path(dir: &Path, case: &GoldenCase) -> PathBuf {
+    dir.join(format!("{}.png", case.name))
+}
+
+/**
+ * Render every case of a suite and compare it with its golden image.
+ */
+pub fn run_suite(theme: &Theme, suite: &GoldenSuite, dir: &Path) -> Result<SuiteReport, Error> {
+    let mut report = SuiteReport::default();
+    for case in &suite.cases {
+        let actual = case.render(theme).with_context(|| anyhow!("Failed to render \"{}\"", case.name))?;
+        let path = golden_path(dir, case);
+        let expected = match fs::read(&path) {
+            Ok(data) => Some(decode(&data, &DecodeOptions::default()).with_context(|| anyhow!("Failed to decode \"{}\"", path.display()))?.image),
+            Err(_) => None,
+        };
+
+        let comparison = expected.as_ref().map(|expected| compare(expected, &actual, &suite.tolerance));
+        let status = match (&expected, &comparison) {
+            (Some(expected), _) if (expected.width, expected.height) != (actual.width, actual.height) => CaseStatus::SizeChanged,
+            (Some(_), Some(comparison)) if comparison.passes(&suite.tolerance) => CaseStatus::Passed,
+            (Some(_), Some(_)) => CaseStatus::Failed,
+            _ => CaseStatus::Missing,
+        };
+        report.results.push(CaseResult { name: case.name.clone(), status, expected, actual, comparison });
+    }
+    Ok(report)
+}
+
+/**
+ * Render cases as their new golden images. With names, only those cases are accepted. Returns
+ * the names of the cases that were written.
+ */
+pub fn accept(theme: &Theme, suite: &GoldenSuite, dir: &Path, names: &[String]) -> Result<Vec<String>, Error> {
+    for name in names {
+        if !suite.cases.iter().any(|case| case.name == *name) {
+            return Err(anyhow!("The suite has no case named \"{}\".", name));
+        }
+    }
+
+    fs::create_dir_all(dir).with_context(|| anyhow!("Failed to create \"{}\"", dir.display()))?;
+    let mut accepted = Vec::new();
+    for case in suite.cases.iter().filter(|case| names.is_empty() || names.contains(&case.name)) {
+        let image = case.render(theme).with_context(|| anyhow!("Failed to render \"{}\"", case.name))?;
+        let path = golden_path(dir, case);
+        fs::write(&path, to_png(&image)?).with_context(|| anyhow!("Failed to write \"{}\"", path.display()))?;
+        accepted.push(case.name.clone());
+    }
+    Ok(accepted)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/**
+ * Write an HTML report of the cases that did not pass, with their images, into a directory.
+ */
+pub fn write_report(report: &SuiteReport, dir: &Path) -> Result<(), Error> {
+    fs::create_dir_all(dir).with_context(|| anyhow!("Failed to create \"{}\"", dir.display()))?;
+    let write = |file: &str, image: &RgbaImage| -> Result<(), Error> {
+        let path = dir.join(file);
+        fs::write(&path, to_png(image)?).with_context(|| anyhow!("Failed to write \"{}\"", path.display()))
+    };
+
+    let passed = report.results.len() - report.failures().count();
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Golden image report</title>\n<style>\n");
+    html.push_str("body { font-family: sans-serif; }\n");
+    html.push_str("img { image-rendering: pixelated; background: repeating-conic-gradient(#ccc 0% 25%, #fff 0% 50%) 0 0 / 16px 16px; }\n");
+    html.push_str("td { vertical-align: top; padding: 4px 12px 4px 0; }\n");
+    html.push_str("</style>\n</head>\n<body>\n");
+    html.push_str(&format!("<h1>Golden image report</h1>\n<p>{} of {} cases passed.</p>\n", passed, report.results.len()));
+
+    for result in report.failures() {
+        let name = escape_html(&result.name);
+        let summary = match (result.status, &result.comparison) {
+            (CaseStatus::Missing, _) => "No golden image.".to_owned(),
+            (CaseStatus::SizeChanged, _) => {
+                let expected = result.expected.as_ref().map_or((0, 0), |expected| (expected.width, expected.height));
+                format!("The size changed from {}x{} to {}x{}.", expected.0, expected.1, result.actual.width, result.actual.height)
+            }
+            (_, Some(comparison)) => format!(
+                "{} of {} pixels differ, by up to {:.1}.",
+                comparison.differing, comparison.total, comparison.max_distance,
+            ),
+            _ => String::new(),
+        };
+        html.push_str(&format!("<h2>{}</h2>\n<p>{}</p>\n<table>\n<tr><th>Expected</th><th>Actual</th><th>Difference</th></tr>\n<tr>\n", name, summary));
+
+        write(&format!("{}.actual.png", result.name), &result.actual)?;
+        match &result.expected {
+            Some(expected) => {
+                write(&format!("{}.expected.png", result.name), expected)?;
+                html.push_str(&format!("<td><img src=\"{}.expected.png\"></td>\n", name));
+            }
+            None => html.push_str("<td></td>\n"),
+        }
+        html.push_str(&format!("<td><img src=\"{}.actual.png\"></td>\n", name));
+        match &result.comparison {
+            Some(comparison) => {
+                write(&format!("{}.diff.png", result.name), &comparison.diff)?;
+                html.push_str(&format!("<td><img src=\"{}.diff.png\"></td>\n", name));
+            }
+            None => html.push_str("<td></td>\n"),
+        }
+        html.push_str("</tr>\n</table>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+
+    let path = dir.join(REPORT_FILE_NAME);
+    fs::write(&path, html).with_context(|| anyhow!("Failed to write \"{}\"", path.display()))
+}
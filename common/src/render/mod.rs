@@ -0,0 +1,245 @@
+/*
+ * Theme rendering.
+ *
+ * Draws the parts of a theme into an RGBA image the way uxtheme draws them into a window, so that
+ * themes can be previewed and tested without Windows. Where uxtheme's behavior is undocumented,
+ * this follows Wine's implementation of it.
+ *
+ * Drawing composites over the pixels already in the image with straight alpha, like AlphaBlend
+ * does over a window that has an alpha channel, and clips to the image.
+ *
+ * A mirrored renderer draws the way Windows does into a DC with a right-to-left layout, as in
+ * Arabic and Hebrew locales: rectangles are measured from the right of the image, so layouts come
+ * out reversed, and backgrounds are flipped unless their MirrorImage is FALSE. Text stays
+ * readable, with its alignment reversed.
+ */
+
+pub mod background;
+pub mod fill;
+pub mod gallery;
+pub mod golden;
+pub mod text;
+pub mod transition;
+pub mod window;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use anyhow::Error;
+use anyhow::anyhow;
+use anyhow::Context;
+
+use crate::image::transparency::key_to_alpha;
+use crate::image::{decode, encode, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use crate::theme::sysmetrics::{FontMetric, SizeMetric, SysMetrics, BASE_DPI};
+use crate::theme::system_colors::ColorScheme;
+use crate::theme::{normalize_image_path, parts, Color, Margins, Point, PropertyValue, Rect, Theme};
+
+/**
+ * Decoded images by normalized path and the color key that they were drawn with.
+ */
+type ImageCache = HashMap<(String, Option<Color>), Rc<RgbaImage>>;
+
+pub struct Renderer<'a> {
+    pub theme: &'a Theme,
+    /**
+     * The DPI to draw at, which picks the DPI variants of images and scales margins.
+     */
+    pub dpi: u32,
+    /**
+     * Whether to draw right to left.
+     */
+    pub mirrored: bool,
+    images: RefCell<ImageCache>,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(theme: &'a Theme) -> Self {
+        Self::with_dpi(theme, BASE_DPI)
+    }
+
+    pub fn with_dpi(theme: &'a Theme, dpi: u32) -> Self {
+        Self { theme, dpi, mirrored: false, images: RefCell::new(HashMap::new()) }
+    }
+
+    /**
+     * Look up a property like uxtheme does: in the state, its part and its class, then in the
+     * class without its application prefix, and last in [Globals].
+     */
+    pub fn property(&self, class: &str, part: Option<&str>, state: Option<&str>, name: &str) -> Option<&'a PropertyValue> {
+        let theme = self.theme;
+        let base_class = parts::base_class_name(class);
+        theme.resolve_property(class, part, state, name)
+            .or_else(|| if base_class != class { theme.resolve_property(base_class, part, state, name) } else { None })
+            .or_else(|| theme.resolve_property("Globals", None, None, name))
+    }
+
+    pub fn int(&self, class: &str, part: Option<&str>, state: Option<&str>, name: &str) -> Option<i32> {
+        match self.property(class, part, state, name)? {
+            PropertyValue::Int(value) | PropertyValue::Size(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn bool(&self, class: &str, part: Option<&str>, state: Option<&str>, name: &str) -> Option<bool> {
+        match self.property(class, part, state, name)? {
+            PropertyValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn enum_value(&self, class: &str, part: Option<&str>, state: Option<&str>, name: &str) -> Option<i32> {
+        match self.property(class, part, state, name)? {
+            PropertyValue::Enum(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self, class: &str, part: Option<&str>, state: Option<&str>, name: &str) -> Option<Color> {
+        match self.property(class, part, state, name)? {
+            PropertyValue::Color(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn margins(&self, class: &str, part: Option<&str>, state: Option<&str>, name: &str) -> Option<Margins> {
+        match self.property(class, part, state, name)? {
+            PropertyValue::Margins(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn position(&self, class: &str, part: Option<&str>, state: Option<&str>, name: &str) -> Option<Point> {
+        match self.property(class, part, state, name)? {
+            PropertyValue::Position(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn file_name(&self, class: &str, part: Option<&str>, state: Option<&str>, name: &str) -> Option<&'a str> {
+        match self.property(class, part, state, name)? {
+            PropertyValue::FileName(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /**
+     * The theme's SysMetrics, with the Windows defaults for the rest.
+     */
+    pub fn sys_metrics(&self) -> SysMetrics {
+        let mut metrics = SysMetrics::from_theme(self.theme);
+        let defaults = SysMetrics::windows_defaults();
+        for metric in SizeMetric::ALL {
+            if metrics.size(metric).is_none() {
+                metrics.set_size(metric, defaults.size(metric));
+            }
+        }
+        for metric in FontMetric::ALL {
+            if metrics.font(metric).is_none() {
+                metrics.set_font(metric, defaults.font(metric).cloned());
+            }
+        }
+        metrics
+    }
+
+    /**
+     * The theme's system colors, with the Windows Standard scheme for the rest.
+     */
+    pub fn system_colors(&self) -> ColorScheme {
+        let mut scheme = ColorScheme::from_theme(self.theme);
+        scheme.fill_from(&ColorScheme::windows_standard());
+        scheme
+    }
+
+    /**
+     * Where a rectangle goes in the canvas, which is flipped when drawing right to left.
+     */
+    pub fn device_rect(&self, canvas: &RgbaImage, rect: Rect) -> Rect {
+        if !self.mirrored {
+            return rect;
+        }
+        let width = canvas.width as i32;
+        Rect { left: width - rect.right, right: width - rect.left, ..rect }
+    }
+
+    /**
+     * Fill a rectangle of the canvas with a color, like FillRect.
+     */
+    pub fn fill(&self, canvas: &mut RgbaImage, rect: Rect, color: Color) {
+        fill_rect(canvas, self.device_rect(canvas, rect), color, 255);
+    }
+
+    /**
+     * Decode an image, with the pixels of a color key made transparent.
+     */
+    pub fn image(&self, path: &str, key: Option<Color>) -> Result<Rc<RgbaImage>, Error> {
+        let cache_key = (normalize_image_path(path), key);
+        if let Some(image) = self.images.borrow().get(&cache_key) {
+            return Ok(image.clone());
+        }
+
+        let data = self.theme.image(path).ok_or_else(|| anyhow!("The image \"{}\" is missing.", path))?;
+        let mut image = decode(data, &DecodeOptions::default()).with_context(|| anyhow!("Failed to decode \"{}\"", path))?.image;
+        if let Some(key) = key {
+            key_to_alpha(&mut image, key, false);
+        }
+        let image = Rc::new(image);
+        self.images.borrow_mut().insert(cache_key, image.clone());
+        Ok(image)
+    }
+}
+
+/**
+ * Draw a straight alpha pixel over the canvas, at an opacity from 0 to 255. Pixels outside of the
+ * canvas are dropped.
+ */
+pub fn blend_pixel(canvas: &mut RgbaImage, x: i32, y: i32, pixel: [u8; 4], opacity: u8) {
+    if x < 0 || y < 0 || x >= canvas.width as i32 || y >= canvas.height as i32 {
+        return;
+    }
+    let source_alpha = pixel[3] as u32 * opacity as u32;
+    if source_alpha == 0 {
+        return;
+    }
+    if source_alpha == 255 * 255 {
+        canvas.set_pixel(x as u32, y as u32, pixel);
+        return;
+    }
+
+    let below = canvas.pixel(x as u32, y as u32);
+    let source_alpha = source_alpha as f32 / (255.0 * 255.0);
+    let below_alpha = below[3] as f32 / 255.0 * (1.0 - source_alpha);
+    let alpha = source_alpha + below_alpha;
+    let channel = |index: usize| ((pixel[index] as f32 * source_alpha + below[index] as f32 * below_alpha) / alpha).round() as u8;
+    canvas.set_pixel(x as u32, y as u32, [channel(0), channel(1), channel(2), (alpha * 255.0).round() as u8]);
+}
+
+/**
+ * Fill a rectangle of the canvas with a color.
+ */
+pub fn fill_rect(canvas: &mut RgbaImage, rect: Rect, color: Color, opacity: u8) {
+    for y in rect.top.max(0)..rect.bottom.min(canvas.height as i32) {
+        for x in rect.left.max(0)..rect.right.min(canvas.width as i32) {
+            blend_pixel(canvas, x, y, [color.r, color.g, color.b, 255], opacity);
+        }
+    }
+}
+
+/**
+ * Shrink a rectangle by margins.
+ */
+pub fn deflate_rect(rect: Rect, margins: Margins) -> Rect {
+    Rect {
+        left: rect.left + margins.left,
+        top: rect.top + margins.top,
+        right: rect.right - margins.right,
+        bottom: rect.bottom - margins.bottom,
+    }
+}
+
+/**
+ * Encode a rendering as an RGBA PNG.
+ */
+pub fn to_png(image: &RgbaImage) -> Result<Vec<u8>, Error> {
+    encode(image, &ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false })
+}
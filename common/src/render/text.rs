@@ -0,0 +1,496 @@
+/*
+ * DrawThemeText.
+ *
+ * Text is drawn in the state's Font, or the font that the caller would have selected into the DC,
+ * and TextColor. Theme fonts are Windows fonts, so every face is drawn with a bundled copy of
+ * DejaVu Sans (regular or bold) at the font's size, slanted for italics. Glyphs are anti-aliased
+ * in grayscale rather than with ClearType.
+ *
+ * The effects go under the text, from the bottom up: a glow of TextGlowSize in GlowColor, a
+ * shadow offset by TextShadowOffset in TextShadowColor (blurred for the Continuous type), and a
+ * border of TextBorderSize in TextBorderColor.
+ *
+ * The DT_* flags follow DrawText: alignment, single line or word-wrapped text, an ellipsis for
+ * lines that do not fit, and & prefixes that underline the next character. Drawn right to left,
+ * left and right alignment swap, as with DT_RTLREADING in a mirrored DC.
+ */
+
+use ab_glyph::{Font as _, FontRef, PxScale, ScaleFont};
+use anyhow::Error;
+use anyhow::anyhow;
+
+use super::{blend_pixel, Renderer};
+use crate::image::RgbaImage;
+use crate::theme::schema::{TST_CONTINUOUS, TST_NONE};
+use crate::theme::sysmetrics::pt_to_px;
+use crate::theme::{Color, Font, Point, PropertyValue, Rect, FW_SEMIBOLD};
+
+const REGULAR_FONT: &[u8] = include_bytes!("../../fonts/DejaVuSans.ttf");
+const BOLD_FONT: &[u8] = include_bytes!("../../fonts/DejaVuSans-Bold.ttf");
+
+/**
+ * How far italic text leans: the horizontal shift per pixel of height.
+ */
+const ITALIC_SLANT: f32 = 0.2;
+
+pub const DT_TOP: u32 = 0x0;
+pub const DT_LEFT: u32 = 0x0;
+pub const DT_CENTER: u32 = 0x1;
+pub const DT_RIGHT: u32 = 0x2;
+pub const DT_VCENTER: u32 = 0x4;
+pub const DT_BOTTOM: u32 = 0x8;
+pub const DT_WORDBREAK: u32 = 0x10;
+pub const DT_SINGLELINE: u32 = 0x20;
+pub const DT_NOPREFIX: u32 = 0x800;
+pub const DT_END_ELLIPSIS: u32 = 0x8000;
+
+/**
+ * Text to draw, with the arguments of DrawText that go with it.
+ */
+#[derive(Clone, Debug, Default)]
+pub struct ThemeText<'t> {
+    pub text: &'t str,
+    /**
+     * DT_* flags.
+     */
+    pub flags: u32,
+    /**
+     * The font that the caller has selected into the DC, for states without a Font property.
+     * Without one, the font is 9 point.
+     */
+    pub font: Option<Font>,
+    /**
+     * The text color of the DC, for states without a TextColor property. Without one, the text
+     * is black.
+     */
+    pub color: Option<Color>,
+}
+
+/**
+ * A line of text laid out, with the characters to underline.
+ */
+struct Line {
+    text: Vec<char>,
+    underlined: Vec<usize>,
+    width: f32,
+}
+
+/**
+ * Text laid out in one font.
+ */
+struct Layout<'f> {
+    font: ab_glyph::PxScaleFont<&'f FontRef<'static>>,
+    italic: bool,
+    underline: bool,
+    strikeout: bool,
+}
+
+fn load_font(bold: bool) -> Result<FontRef<'static>, Error> {
+    FontRef::try_from_slice(if bold { BOLD_FONT } else { REGULAR_FONT }).map_err(|_| anyhow!("The bundled font is invalid."))
+}
+
+/**
+ * Remove & prefixes, and return the positions of the characters that they underline.
+ */
+fn strip_prefixes(text: &str) -> (Vec<char>, Vec<usize>) {
+    let mut result = Vec::new();
+    let mut underlined = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '&' {
+            match chars.next() {
+                Some('&') => result.push('&'),
+                Some(next) => {
+                    underlined.push(result.len());
+                    result.push(next);
+                }
+                None => {}
+            }
+        }
+        else {
+            result.push(c);
+        }
+    }
+    (result, underlined)
+}
+
+impl Layout<'_> {
+    fn width(&self, text: &[char]) -> f32 {
+        let mut width = 0.0;
+        let mut previous = None;
+        for c in text {
+            let id = self.font.glyph_id(*c);
+            if let Some(previous) = previous {
+                width += self.font.kern(previous, id);
+            }
+            width += self.font.h_advance(id);
+            previous = Some(id);
+        }
+        width
+    }
+
+    fn line_height(&self) -> f32 {
+        (self.font.ascent() - self.font.descent() + self.font.line_gap()).ceil()
+    }
+
+    /**
+     * Break text into lines by the DT_* flags.
+     */
+    fn lines(&self, text: &[char], underlined: &[usize], flags: u32, max_width: f32) -> Vec<Line> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let push = |lines: &mut Vec<Line>, start: usize, end: usize| {
+            let line_text: Vec<char> = text[start..end].iter().map(|c| if *c == '\n' || *c == '\r' { ' ' } else { *c }).collect();
+            let line_underlined = underlined.iter().filter(|index| (start..end).contains(*index)).map(|index| index - start).collect();
+            lines.push(Line { width: self.width(&line_text), text: line_text, underlined: line_underlined });
+        };
+
+        if flags & DT_SINGLELINE != 0 {
+            push(&mut lines, 0, text.len());
+        }
+        else {
+            let mut index = 0;
+            while index <= text.len() {
+                if index == text.len() || text[index] == '\n' {
+                    let end = if index > start && text[index - 1] == '\r' { index - 1 } else { index };
+                    self.wrap(text, start, end, flags, max_width, &mut |start, end| push(&mut lines, start, end));
+                    start = index + 1;
+                }
+                index += 1;
+            }
+        }
+
+        if flags & DT_END_ELLIPSIS != 0 {
+            for line in &mut lines {
+                self.add_ellipsis(line, max_width);
+            }
+        }
+        lines
+    }
+
+    /**
+     * Break a paragraph at spaces so that each line fits, with DT_WORDBREAK.
+     */
+    fn wrap(&self, text: &[char], start: usize, end: usize, flags: u32, max_width: f32, push: &mut dyn FnMut(usize, usize)) {
+        if flags & DT_WORDBREAK == 0 {
+            push(start, end);
+            return;
+        }
+        let mut line_start = start;
+        let mut last_space = None;
+        let mut index = start;
+        while index < end {
+            if text[index] == ' ' {
+                last_space = Some(index);
+            }
+            if self.width(&text[line_start..=index]) > max_width {
+                if let Some(space) = last_space.filter(|space| *space > line_start) {
+                    push(line_start, space);
+                    line_start = space + 1;
+                    last_space = None;
+                    index = line_start;
+                    continue;
+                }
+            }
+            index += 1;
+        }
+        push(line_start, end);
+    }
+
+    fn add_ellipsis(&self, line: &mut Line, max_width: f32) {
+        if line.width <= max_width {
+            return;
+        }
+        let ellipsis = ['.', '.', '.'];
+        let ellipsis_width = self.width(&ellipsis);
+        while !line.text.is_empty() && self.width(&line.text) + ellipsis_width > max_width {
+            line.text.pop();
+        }
+        line.underlined.retain(|index| *index < line.text.len());
+        line.text.extend(ellipsis);
+        line.width = self.width(&line.text);
+    }
+
+    /**
+     * Draw a line into a coverage mask, with its baseline at a position.
+     */
+    fn draw_line(&self, mask: &mut Mask, line: &Line, x: f32, baseline: f32) {
+        let mut pen = x;
+        let mut previous = None;
+        for (index, c) in line.text.iter().enumerate() {
+            let id = self.font.glyph_id(*c);
+            if let Some(previous) = previous {
+                pen += self.font.kern(previous, id);
+            }
+            let advance = self.font.h_advance(id);
+            let glyph = id.with_scale_and_position(self.font.scale(), ab_glyph::point(pen, baseline));
+            if let Some(outline) = self.font.outline_glyph(glyph) {
+                let bounds = outline.px_bounds();
+                outline.draw(|glyph_x, glyph_y, coverage| {
+                    let y = bounds.min.y + glyph_y as f32;
+                    let slant = if self.italic { (baseline - y) * ITALIC_SLANT } else { 0.0 };
+                    mask.add((bounds.min.x + glyph_x as f32 + slant).round() as i32, y as i32, coverage);
+                });
+            }
+            if self.underline || line.underlined.contains(&index) {
+                let y = (baseline + 1.0 + self.font.height() / 16.0).round() as i32;
+                mask.fill_row(y, pen.round() as i32, (pen + advance).round() as i32);
+            }
+            if self.strikeout {
+                let y = (baseline - self.font.ascent() / 3.0).round() as i32;
+                mask.fill_row(y, pen.round() as i32, (pen + advance).round() as i32);
+            }
+            pen += advance;
+            previous = Some(id);
+        }
+    }
+}
+
+/**
+ * Coverage from 0 to 1 for each pixel of an area of the canvas.
+ */
+#[derive(Clone)]
+struct Mask {
+    area: Rect,
+    coverage: Vec<f32>,
+}
+
+impl Mask {
+    fn new(area: Rect) -> Self {
+        let area = Rect { right: area.right.max(area.left), bottom: area.bottom.max(area.top), ..area };
+        Self { area, coverage: vec![0.0; area.width() as usize * area.height() as usize] }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let area = self.area;
+        (x >= area.left && y >= area.top && x < area.right && y < area.bottom)
+            .then(|| ((y - area.top) * area.width() + x - area.left) as usize)
+    }
+
+    fn get(&self, x: i32, y: i32) -> f32 {
+        self.index(x, y).map_or(0.0, |index| self.coverage[index])
+    }
+
+    fn add(&mut self, x: i32, y: i32, coverage: f32) {
+        if let Some(index) = self.index(x, y) {
+            self.coverage[index] = (self.coverage[index] + coverage).min(1.0);
+        }
+    }
+
+    fn fill_row(&mut self, y: i32, left: i32, right: i32) {
+        for x in left..right {
+            self.add(x, y, 1.0);
+        }
+    }
+
+    /**
+     * Build a mask of the same area from a function of the canvas coordinates.
+     */
+    fn map(&self, function: impl Fn(i32, i32) -> f32) -> Mask {
+        let mut result = Mask::new(self.area);
+        for y in self.area.top..self.area.bottom {
+            for x in self.area.left..self.area.right {
+                let index = ((y - self.area.top) * self.area.width() + x - self.area.left) as usize;
+                result.coverage[index] = function(x, y);
+            }
+        }
+        result
+    }
+
+    /**
+     * Keep only the coverage inside a rectangle.
+     */
+    fn clip(&self, rect: Rect) -> Mask {
+        self.map(|x, y| if x >= rect.left && y >= rect.top && x < rect.right && y < rect.bottom { self.get(x, y) } else { 0.0 })
+    }
+
+    fn offset(&self, dx: i32, dy: i32) -> Mask {
+        self.map(|x, y| self.get(x - dx, y - dy))
+    }
+
+    /**
+     * Spread the coverage out to a radius, for borders.
+     */
+    fn dilate(&self, radius: i32) -> Mask {
+        self.map(|x, y| {
+            let mut value: f32 = 0.0;
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    if dx * dx + dy * dy <= radius * radius {
+                        value = value.max(self.get(x + dx, y + dy));
+                    }
+                }
+            }
+            value
+        })
+    }
+
+    /**
+     * Blur with three passes of a box filter, which is close to a Gaussian.
+     */
+    fn blur(&self, radius: i32) -> Mask {
+        let mut result = self.clone();
+        if radius <= 0 {
+            return result;
+        }
+        let size = (radius * 2 + 1) as f32;
+        for _ in 0..3 {
+            for vertical in [false, true] {
+                let source = result;
+                result = source.map(|x, y| {
+                    let sum: f32 = (-radius..=radius)
+                        .map(|d| if vertical { source.get(x, y + d) } else { source.get(x + d, y) })
+                        .sum();
+                    sum / size
+                });
+            }
+        }
+        result
+    }
+
+    fn draw(&self, canvas: &mut RgbaImage, color: Color, strength: f32) {
+        for y in self.area.top..self.area.bottom {
+            for x in self.area.left..self.area.right {
+                let alpha = (self.get(x, y) * strength * 255.0).round().clamp(0.0, 255.0) as u8;
+                if alpha > 0 {
+                    blend_pixel(canvas, x, y, [color.r, color.g, color.b, alpha], 255);
+                }
+            }
+        }
+    }
+}
+
+impl Renderer<'_> {
+    /**
+     * The font that a state draws text in.
+     */
+    pub fn text_font(&self, class: &str, part: Option<&str>, state: Option<&str>, dc_font: Option<&Font>) -> Font {
+        match self.property(class, part, state, "Font") {
+            Some(PropertyValue::Font(font)) => font.clone(),
+            _ => dc_font.cloned().unwrap_or(Font { face: "Segoe UI".to_owned(), size: 9, ..Default::default() }),
+        }
+    }
+
+    fn layout<'f>(&self, font: &Font, face: &'f FontRef<'static>, italic: bool) -> Layout<'f> {
+        let em = pt_to_px(font.size as f64, self.dpi) as f32;
+        let scale = PxScale::from(em * face.height_unscaled() / face.units_per_em().unwrap_or(2048.0));
+        Layout { font: face.as_scaled(scale), italic: italic || font.italic, underline: font.underline, strikeout: font.strikeout }
+    }
+
+    /**
+     * The size that text takes up, like GetThemeTextExtent. With DT_WORDBREAK, lines are wrapped
+     * at the width of the bounding rectangle.
+     */
+    pub fn text_extent(&self, class: &str, part: Option<&str>, state: Option<&str>, text: &ThemeText, bounds: Option<Rect>) -> Result<Point, Error> {
+        let font = self.text_font(class, part, state, text.font.as_ref());
+        let face = load_font(font.weight >= FW_SEMIBOLD)?;
+        let layout = self.layout(&font, &face, false);
+        let (chars, underlined) = if text.flags & DT_NOPREFIX != 0 { (text.text.chars().collect(), Vec::new()) } else { strip_prefixes(text.text) };
+        let max_width = bounds.map_or(f32::MAX, |bounds| bounds.width() as f32);
+        let lines = layout.lines(&chars, &underlined, text.flags & !DT_END_ELLIPSIS, max_width);
+        let width = lines.iter().map(|line| line.width).fold(0.0, f32::max).ceil() as i32;
+        Ok(Point { x: width, y: layout.line_height() as i32 * lines.len() as i32 })
+    }
+
+    /**
+     * Draw text in a rectangle of the canvas, like DrawThemeText.
+     */
+    pub fn draw_text(&self, canvas: &mut RgbaImage, class: &str, part: Option<&str>, state: Option<&str>, text: &ThemeText, rect: Rect) -> Result<(), Error> {
+        let font = self.text_font(class, part, state, text.font.as_ref());
+        let face = load_font(font.weight >= FW_SEMIBOLD)?;
+        let italic = self.bool(class, part, state, "TextItalic").unwrap_or(false);
+        let layout = self.layout(&font, &face, italic);
+
+        // Right to left, text goes in the mirrored rectangle and is aligned from its other side,
+        // but the glyphs themselves are not mirrored.
+        let mut flags = text.flags;
+        let rect = self.device_rect(canvas, rect);
+        if self.mirrored && flags & DT_CENTER == 0 {
+            flags ^= DT_RIGHT;
+        }
+
+        let (chars, underlined) = if flags & DT_NOPREFIX != 0 { (text.text.chars().collect(), Vec::new()) } else { strip_prefixes(text.text) };
+        let lines = layout.lines(&chars, &underlined, flags, rect.width() as f32);
+        let line_height = layout.line_height();
+        let total_height = line_height * lines.len() as f32;
+
+        // Like DrawText, vertical alignment only applies to single lines.
+        let top = if flags & DT_SINGLELINE == 0 {
+            rect.top as f32
+        }
+        else if flags & DT_BOTTOM != 0 {
+            rect.bottom as f32 - total_height
+        }
+        else if flags & DT_VCENTER != 0 {
+            rect.top as f32 + ((rect.height() as f32 - total_height) / 2.0).floor()
+        }
+        else {
+            rect.top as f32
+        };
+
+        // Effects spread out from the text, past its rectangle.
+        let glow_size = self.int(class, part, state, "TextGlowSize").unwrap_or(0).max(0);
+        let glow = glow_size > 0 && self.bool(class, part, state, "TextGlow").unwrap_or(true);
+        let shadow_type = self.enum_value(class, part, state, "TextShadowType").unwrap_or(TST_NONE);
+        let mut shadow_offset = self.position(class, part, state, "TextShadowOffset").unwrap_or(Point { x: 1, y: 1 });
+        if self.mirrored {
+            shadow_offset.x = -shadow_offset.x;
+        }
+        let border_size = self.int(class, part, state, "TextBorderSize").unwrap_or(0).max(0);
+        let spread = [
+            if glow { glow_size * 2 } else { 0 },
+            if shadow_type != TST_NONE { shadow_offset.x.abs().max(shadow_offset.y.abs()) + 1 } else { 0 },
+            border_size,
+        ].into_iter().max().unwrap_or(0);
+        let area = Rect {
+            left: (rect.left - spread).max(0),
+            top: (rect.top - spread).max(0),
+            right: (rect.right + spread).min(canvas.width as i32),
+            bottom: (rect.bottom + spread).min(canvas.height as i32),
+        };
+
+        let mut mask = Mask::new(area);
+        for (index, line) in lines.iter().enumerate() {
+            let x = if flags & DT_RIGHT != 0 {
+                rect.right as f32 - line.width
+            }
+            else if flags & DT_CENTER != 0 {
+                rect.left as f32 + ((rect.width() as f32 - line.width) / 2.0).floor()
+            }
+            else {
+                rect.left as f32
+            };
+            let baseline = (top + line_height * index as f32 + layout.font.ascent()).round();
+            layout.draw_line(&mut mask, line, x.round(), baseline);
+        }
+
+        // Text outside of the rectangle is clipped, as DrawThemeText does without DT_NOCLIP.
+        let mask = mask.clip(rect);
+
+        if glow {
+            let color = self.color(class, part, state, "GlowColor").unwrap_or(Color::new(255, 255, 255));
+            let intensity = self.int(class, part, state, "GlowIntensity").unwrap_or(255).clamp(0, 255) as f32 / 255.0;
+            mask.dilate(glow_size / 2).blur((glow_size + 1) / 2).draw(canvas, color, 2.0 * intensity);
+        }
+
+        if shadow_type != TST_NONE {
+            let color = self.color(class, part, state, "TextShadowColor").unwrap_or(Color::new(0, 0, 0));
+            let shadow = mask.offset(shadow_offset.x, shadow_offset.y);
+            if shadow_type == TST_CONTINUOUS {
+                shadow.blur(1).draw(canvas, color, 1.0);
+            }
+            else {
+                shadow.draw(canvas, color, 1.0);
+            }
+        }
+
+        if border_size > 0 {
+            let color = self.color(class, part, state, "TextBorderColor").unwrap_or(Color::new(0, 0, 0));
+            mask.dilate(border_size).draw(canvas, color, 1.0);
+        }
+
+        let color = self.color(class, part, state, "TextColor").or(text.color).unwrap_or(Color::new(0, 0, 0));
+        mask.draw(canvas, color, 1.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+/*
+ * State transitions.
+ *
+ * Since Vista, controls such as buttons fade from one state to the next with buffered animation
+ * instead of switching at once. How long a fade takes comes from the part's TransitionDurations,
+ * which GetThemeTransitionDuration reads as a table: the first value is the number of states n,
+ * followed by n rows of n durations in milliseconds, one row for each state faded from and one
+ * column for each state faded to. A missing table or a zero duration means no fade.
+ *
+ * The fade itself is a linear cross-fade, like BPAS_LINEAR: each frame blends the old state's
+ * rendering into the new one's in proportion to the time elapsed.
+ *
+ * Animations from the AMAP class (the Animation and TimingFunction properties, with their delays
+ * and cubic bezier timing) are not decoded, and nothing here applies them. Those drive the
+ * animations that Windows plays through the animation manager, not the buffered fades that
+ * controls do on a state change, so a fade rendered here has no delay and linear timing even in
+ * a theme that defines them.
+ */
+
+use anyhow::Error;
+
+use super::text::{ThemeText, DT_CENTER, DT_END_ELLIPSIS, DT_SINGLELINE, DT_VCENTER};
+use super::Renderer;
+use crate::image::{premultiply, unpremultiply, RgbaImage};
+use crate::theme::{parts, PropertyValue, Rect};
+
+/**
+ * The first and last rendering of a fade, and how long it takes.
+ */
+#[derive(Clone, Debug)]
+pub struct Transition {
+    pub from: RgbaImage,
+    pub to: RgbaImage,
+    /**
+     * In milliseconds.
+     */
+    pub duration: u32,
+}
+
+impl Transition {
+    /**
+     * The rendering at a point in time, from 0 (the old state) to the duration (the new state).
+     */
+    pub fn frame(&self, time: u32) -> RgbaImage {
+        let progress = if self.duration == 0 { 1.0 } else { time.min(self.duration) as f32 / self.duration as f32 };
+        let mut frame = RgbaImage::new(self.to.width, self.to.height);
+        for ((result, from), to) in frame.pixels.chunks_exact_mut(4).zip(self.from.pixels.chunks_exact(4)).zip(self.to.pixels.chunks_exact(4)) {
+            let (from, to) = (premultiply([from[0], from[1], from[2], from[3]]), premultiply([to[0], to[1], to[2], to[3]]));
+            let blended: [u8; 4] = std::array::from_fn(|i| (from[i] as f32 + (to[i] as f32 - from[i] as f32) * progress).round() as u8);
+            result.copy_from_slice(&unpremultiply(blended));
+        }
+        frame
+    }
+
+    /**
+     * The frames of the fade at a frame rate, from the old state to the new one, both included.
+     * Frame n shows the fade n frame intervals in, and the last frame shows the new state. Without
+     * a fade, that is just the two states.
+     */
+    pub fn frames(&self, frame_rate: u32) -> Vec<RgbaImage> {
+        let frame_rate = frame_rate.max(1) as u64;
+        let intervals = ((self.duration as u64 * frame_rate).div_ceil(1000)).max(1);
+        (0..=intervals).map(|index| self.frame((index * 1000 / frame_rate).min(self.duration as u64) as u32)).collect()
+    }
+}
+
+impl Renderer<'_> {
+    /**
+     * How long a part fades from one state to another, in milliseconds, like
+     * GetThemeTransitionDuration. None when the theme does not animate the change.
+     */
+    pub fn transition_duration(&self, class: &str, part: Option<&str>, from: Option<&str>, to: Option<&str>) -> Option<u32> {
+        let PropertyValue::IntList(durations) = self.property(class, part, None, "TransitionDurations")? else {
+            return None;
+        };
+        let (from, to) = (parts::state_id(class, part, from)?, parts::state_id(class, part, to)?);
+        let count = *durations.first()?;
+        if from < 1 || to < 1 || from > count || to > count {
+            return None;
+        }
+        let duration = *durations.get(1 + ((from - 1) * count + to - 1) as usize)?;
+        (duration > 0).then_some(duration as u32)
+    }
+
+    /**
+     * Render both ends of a part's change from one state to another, with an optional label
+     * centered in the content rectangle the way a button draws it.
+     */
+    pub fn draw_transition(&self, class: &str, part: Option<&str>, from: Option<&str>, to: Option<&str>, text: Option<&str>, size: (u32, u32)) -> Result<Transition, Error> {
+        let rect = Rect { left: 0, top: 0, right: size.0 as i32, bottom: size.1 as i32 };
+        let draw = |state: Option<&str>| -> Result<RgbaImage, Error> {
+            let mut canvas = RgbaImage::new(size.0, size.1);
+            self.draw_background(&mut canvas, class, part, state, rect)?;
+            if let Some(text) = text {
+                let text = ThemeText { text, flags: DT_CENTER | DT_VCENTER | DT_SINGLELINE | DT_END_ELLIPSIS, font: None, color: None };
+                self.draw_text(&mut canvas, class, part, state, &text, self.content_rect(class, part, state, rect))?;
+            }
+            Ok(canvas)
+        };
+        Ok(Transition { from: draw(from)?, to: draw(to)?, duration: self.transition_duration(class, part, from, to).unwrap_or(0) })
+    }
+}
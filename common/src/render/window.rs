@@ -0,0 +1,241 @@
+/*
+ * Mock windows.
+ *
+ * Draws a top-level window the way the themed non-client area of Windows 7 looks without desktop
+ * composition: the Window class's caption, frame edges and caption buttons around a menu bar and
+ * an empty client area.
+ *
+ * The layout follows the SysMetrics class, with the Windows defaults for metrics that a theme
+ * leaves out. The frame is SizingBorderWidth plus PaddedBorderWidth plus the three pixel edge
+ * that Windows adds, the caption bar is CaptionBarHeight tall below the top frame edge, and the
+ * Caption part covers both. Caption buttons are CaptionBarWidth by CaptionBarHeight less a two
+ * pixel inset, and the title goes inside the Caption's CaptionMargins in the CaptionFont, aligned
+ * by ContentAlignment. Maximized windows have no frame and use MaxCaption.
+ *
+ * Colors that no part covers, the menu bar and client area, come from the theme's system colors
+ * over the Windows Standard scheme.
+ */
+
+use anyhow::Error;
+
+use super::text::{ThemeText, DT_CENTER, DT_END_ELLIPSIS, DT_LEFT, DT_NOPREFIX, DT_RIGHT, DT_SINGLELINE, DT_VCENTER};
+use super::{deflate_rect, Renderer};
+use crate::image::RgbaImage;
+use crate::image::dpi::scale_margins;
+use crate::theme::schema::{HA_CENTER, HA_LEFT, HA_RIGHT};
+use crate::theme::sysmetrics::{scale_px, FontMetric, SizeMetric};
+use crate::theme::{Color, Font, Rect};
+
+/**
+ * The padding on either side of a menu bar item's text, at 96 DPI.
+ */
+const MENU_ITEM_PADDING: i32 = 6;
+
+/**
+ * The edge that Windows adds to SizingBorderWidth for the frame, at 96 DPI.
+ */
+const FRAME_EDGE: i32 = 3;
+
+/**
+ * The space around caption buttons in the caption bar, at 96 DPI.
+ */
+const CAPTION_BUTTON_INSET: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CaptionButton {
+    Minimize,
+    Maximize,
+    Close,
+}
+
+impl CaptionButton {
+    /**
+     * The buttons from left to right.
+     */
+    pub const ALL: [CaptionButton; 3] = [Self::Minimize, Self::Maximize, Self::Close];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Minimize => "Minimize",
+            Self::Maximize => "Maximize",
+            Self::Close => "Close",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|button| button.name().eq_ignore_ascii_case(name))
+    }
+
+    /**
+     * The Window part that draws the button. A maximized window's Maximize button restores it.
+     */
+    pub fn part(self, maximized: bool) -> &'static str {
+        match self {
+            Self::Minimize => "MinButton",
+            Self::Maximize if maximized => "RestoreButton",
+            Self::Maximize => "MaxButton",
+            Self::Close => "CloseButton",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MockWindow {
+    pub title: String,
+    /**
+     * The names of the menu bar items. Without any, the window has no menu bar.
+     */
+    pub menu: Vec<String>,
+    pub active: bool,
+    pub maximized: bool,
+    /**
+     * The state of each caption button, by CaptionButton. Buttons without one are Normal, or
+     * Inactive in an inactive window.
+     */
+    pub button_states: [Option<String>; CaptionButton::ALL.len()],
+}
+
+impl Default for MockWindow {
+    fn default() -> Self {
+        Self {
+            title: "Kanaya".to_owned(),
+            menu: ["File", "Edit", "View", "Help"].into_iter().map(str::to_owned).collect(),
+            active: true,
+            maximized: false,
+            button_states: Default::default(),
+        }
+    }
+}
+
+/**
+ * Where the parts of a mock window go.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WindowLayout {
+    pub caption: Rect,
+    pub frame_left: Rect,
+    pub frame_right: Rect,
+    pub frame_bottom: Rect,
+    /**
+     * The caption buttons that are drawn, with their rectangles.
+     */
+    pub buttons: Vec<(CaptionButton, Rect)>,
+    pub title: Rect,
+    pub menu_bar: Option<Rect>,
+    pub client: Rect,
+}
+
+impl Renderer<'_> {
+    /**
+     * Lay out a mock window in a rectangle.
+     */
+    pub fn window_layout(&self, window: &MockWindow, rect: Rect) -> WindowLayout {
+        let metrics = self.sys_metrics();
+        let size = |metric: SizeMetric| scale_px(metrics.size(metric).unwrap_or_else(|| metric.default_px()), self.dpi);
+
+        let frame = if window.maximized {
+            0
+        }
+        else {
+            size(SizeMetric::SizingBorderWidth) + size(SizeMetric::PaddedBorderWidth) + scale_px(FRAME_EDGE, self.dpi)
+        };
+        let caption_height = size(SizeMetric::CaptionBarHeight);
+        let caption = Rect { bottom: rect.top + frame + caption_height, ..rect };
+        let frame_left = Rect { top: caption.bottom, right: rect.left + frame, ..rect };
+        let frame_right = Rect { top: caption.bottom, left: rect.right - frame, ..rect };
+        let frame_bottom = Rect { top: rect.bottom - frame, left: rect.left + frame, right: rect.right - frame, ..rect };
+
+        // Buttons sit against the right of the caption bar, the close button a little apart.
+        let inset = scale_px(CAPTION_BUTTON_INSET, self.dpi);
+        let (button_width, button_height) = (size(SizeMetric::CaptionBarWidth) - inset, caption_height - inset * 2);
+        let mut buttons = Vec::new();
+        let mut right = rect.right - frame - inset;
+        for button in CaptionButton::ALL.into_iter().rev() {
+            let top = caption.bottom - caption_height + inset;
+            buttons.insert(0, (button, Rect { left: right - button_width, top, right, bottom: top + button_height }));
+            right -= button_width + if button == CaptionButton::Close { inset } else { 0 };
+        }
+
+        let caption_part = if window.maximized { "MaxCaption" } else { "Caption" };
+        let state = if window.active { "Active" } else { "Inactive" };
+        let caption_margins = self.margins("Window", Some(caption_part), Some(state), "CaptionMargins").unwrap_or_default();
+        let title = deflate_rect(Rect { left: rect.left + frame, top: caption.bottom - caption_height, right: right - inset, ..caption }, scale_margins(caption_margins, self.dpi));
+
+        let inner = Rect { left: rect.left + frame, top: caption.bottom, right: rect.right - frame, bottom: rect.bottom - frame };
+        let menu_bar = (!window.menu.is_empty()).then(|| Rect { bottom: inner.top + size(SizeMetric::MenuBarHeight), ..inner });
+        let client = Rect { top: menu_bar.map_or(inner.top, |menu_bar| menu_bar.bottom), ..inner };
+
+        WindowLayout { caption, frame_left, frame_right, frame_bottom, buttons, title, menu_bar, client }
+    }
+
+    /**
+     * Draw a mock window into a rectangle of the canvas.
+     */
+    pub fn draw_window(&self, canvas: &mut RgbaImage, window: &MockWindow, rect: Rect) -> Result<(), Error> {
+        let layout = self.window_layout(window, rect);
+        let metrics = self.sys_metrics();
+        let colors = self.system_colors();
+        let color = |name: &str| colors.get(name).unwrap_or(Color::new(0, 0, 0));
+        let state = if window.active { "Active" } else { "Inactive" };
+
+        self.fill(canvas, layout.client, color("Window"));
+        if !window.maximized {
+            for (part, part_rect) in [("FrameLeft", layout.frame_left), ("FrameRight", layout.frame_right), ("FrameBottom", layout.frame_bottom)] {
+                self.draw_background(canvas, "Window", Some(part), Some(state), part_rect)?;
+            }
+        }
+
+        let caption_part = if window.maximized { "MaxCaption" } else { "Caption" };
+        self.draw_background(canvas, "Window", Some(caption_part), Some(state), layout.caption)?;
+        self.draw_title(canvas, window, &layout, caption_part, state, metrics.font(FontMetric::Caption))?;
+
+        for (button, button_rect) in &layout.buttons {
+            let button_state = window.button_states[*button as usize].as_deref().unwrap_or(if window.active { "Normal" } else { "Inactive" });
+            self.draw_background(canvas, "Window", Some(button.part(window.maximized)), Some(button_state), *button_rect)?;
+        }
+
+        if let Some(menu_bar) = layout.menu_bar {
+            self.draw_menu_bar(canvas, window, menu_bar, metrics.font(FontMetric::Menu))?;
+        }
+        Ok(())
+    }
+
+    fn draw_title(&self, canvas: &mut RgbaImage, window: &MockWindow, layout: &WindowLayout, caption_part: &str, state: &str, font: Option<&Font>) -> Result<(), Error> {
+        let alignment = match self.enum_value("Window", Some(caption_part), Some(state), "ContentAlignment").unwrap_or(HA_LEFT) {
+            HA_CENTER => DT_CENTER,
+            HA_RIGHT => DT_RIGHT,
+            _ => DT_LEFT,
+        };
+        let flags = alignment | DT_VCENTER | DT_SINGLELINE | DT_END_ELLIPSIS | DT_NOPREFIX;
+
+        // The caption text color is the system color unless the caption sets its own.
+        let color = self.system_colors().get(if window.active { "TitleText" } else { "InactiveTitleText" });
+        let text = ThemeText { text: &window.title, flags, font: font.cloned(), color };
+        self.draw_text(canvas, "Window", Some(caption_part), Some(state), &text, layout.title)
+    }
+
+    fn draw_menu_bar(&self, canvas: &mut RgbaImage, window: &MockWindow, rect: Rect, font: Option<&Font>) -> Result<(), Error> {
+        let bar_state = if window.active { "Active" } else { "Inactive" };
+        if self.property("Menu", Some("BarBackground"), Some(bar_state), "BgType").is_some() {
+            self.draw_background(canvas, "Menu", Some("BarBackground"), Some(bar_state), rect)?;
+        }
+        else {
+            self.fill(canvas, rect, self.system_colors().get("MenuBar").unwrap_or(Color::new(240, 240, 240)));
+        }
+
+        let padding = scale_px(MENU_ITEM_PADDING, self.dpi);
+        let color = self.system_colors().get("MenuText");
+        let mut left = rect.left;
+        for item in &window.menu {
+            let text = ThemeText { text: item, flags: DT_CENTER | DT_VCENTER | DT_SINGLELINE, font: font.cloned(), color };
+            let extent = self.text_extent("Menu", Some("BarItem"), Some("Normal"), &text, None)?;
+            let item_rect = Rect { left, right: left + extent.x + padding * 2, ..rect };
+            if item_rect.right > rect.right {
+                break;
+            }
+            self.draw_text(canvas, "Menu", Some("BarItem"), Some("Normal"), &text, item_rect)?;
+            left = item_rect.right;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+/*
+ * Image properties.
+ *
+ * A state can refer to several images: ImageFile is the main image, GlyphImageFile is drawn on
+ * top of it, and ImageFile1 through ImageFile7 are variants that uxtheme picks from by DPI or by
+ * size (see ImageSelectType). Each variant n has a MinDpiN and a MinSizeN property that tell
+ * uxtheme when to use it.
+ */
+
+use super::{PropertyValue, Theme};
+
+pub struct ImagePropertyInfo {
+    pub name: &'static str,
+    /**
+     * The variant number for ImageFile1..7.
+     */
+    pub variant: Option<u8>,
+    pub glyph: bool,
+}
+
+pub const IMAGE_PROPERTIES: &[ImagePropertyInfo] = &[
+    ImagePropertyInfo { name: "ImageFile", variant: None, glyph: false },
+    ImagePropertyInfo { name: "ImageFile1", variant: Some(1), glyph: false },
+    ImagePropertyInfo { name: "ImageFile2", variant: Some(2), glyph: false },
+    ImagePropertyInfo { name: "ImageFile3", variant: Some(3), glyph: false },
+    ImagePropertyInfo { name: "ImageFile4", variant: Some(4), glyph: false },
+    ImagePropertyInfo { name: "ImageFile5", variant: Some(5), glyph: false },
+    ImagePropertyInfo { name: "ImageFile6", variant: Some(6), glyph: false },
+    ImagePropertyInfo { name: "ImageFile7", variant: Some(7), glyph: false },
+    ImagePropertyInfo { name: "GlyphImageFile", variant: None, glyph: true },
+];
+
+pub const VARIANT_COUNT: u8 = 7;
+
+/**
+ * The DPI that each variant is conventionally drawn for: 100%, 125%, 150%, 200%, 250%, 300% and
+ * 400%.
+ */
+pub const VARIANT_DPIS: [u32; VARIANT_COUNT as usize] = [96, 120, 144, 192, 240, 288, 384];
+
+pub fn find_image_property(name: &str) -> Option<&'static ImagePropertyInfo> {
+    IMAGE_PROPERTIES.iter().find(|info| info.name.eq_ignore_ascii_case(name))
+}
+
+pub fn variant_property(variant: u8) -> String {
+    format!("ImageFile{}", variant)
+}
+
+pub fn min_dpi_property(variant: u8) -> String {
+    format!("MinDpi{}", variant)
+}
+
+pub fn min_size_property(variant: u8) -> String {
+    format!("MinSize{}", variant)
+}
+
+/**
+ * A reference from a state to an image.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImageRef {
+    pub class: String,
+    pub part: Option<String>,
+    pub state: Option<String>,
+    pub property: String,
+    /**
+     * The image path, as written in the property.
+     */
+    pub path: String,
+    pub variant: Option<u8>,
+    pub glyph: bool,
+}
+
+impl ImageRef {
+    /**
+     * The DPI that the image is for: its MinDpi property if it has one, the conventional DPI
+     * for its variant otherwise, and 96 for ImageFile and GlyphImageFile.
+     */
+    pub fn dpi(&self, theme: &Theme) -> u32 {
+        let Some(variant) = self.variant else {
+            return VARIANT_DPIS[0];
+        };
+        match theme.resolve_property(&self.class, self.part.as_deref(), self.state.as_deref(), &min_dpi_property(variant)) {
+            Some(PropertyValue::Int(dpi)) if *dpi > 0 => *dpi as u32,
+            _ => VARIANT_DPIS[variant as usize - 1],
+        }
+    }
+}
+
+impl Theme {
+    /**
+     * Every image property in the theme, in theme order.
+     */
+    pub fn image_refs(&self) -> Vec<ImageRef> {
+        let mut refs = Vec::new();
+        for (class, part, state) in self.states() {
+            for property in &state.properties {
+                let (Some(info), PropertyValue::FileName(path)) = (find_image_property(&property.name), &property.value) else {
+                    continue;
+                };
+                refs.push(ImageRef {
+                    class: class.name.clone(),
+                    part: part.name.clone(),
+                    state: state.name.clone(),
+                    property: info.name.to_owned(),
+                    path: path.clone(),
+                    variant: info.variant,
+                    glyph: info.glyph,
+                });
+            }
+        }
+        refs
+    }
+}
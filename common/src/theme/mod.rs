@@ -0,0 +1,415 @@
+/*
+ * Kanaya theme model.
+ *
+ * A theme is a list of classes. Each class has a list of parts and each part has a list of
+ * states, and every one of those levels can hold typed properties. This is the same hierarchy
+ * that uxtheme uses: a property lookup for a state falls back to its part, and then to its class.
+ *
+ * Part 0 and state 0 are the class-wide and part-wide levels respectively, and have no name in
+ * the source formats.
+ */
+
+pub mod schema;
+pub mod parts;
+pub mod system_colors;
+pub mod sysmetrics;
+pub mod image_properties;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use schema::PropertyType;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /**
+     * Convert from a Win32 COLORREF (0x00BBGGRR).
+     */
+    pub fn from_colorref(value: u32) -> Self {
+        Self {
+            r: (value & 0xFF) as u8,
+            g: ((value >> 8) & 0xFF) as u8,
+            b: ((value >> 16) & 0xFF) as u8,
+        }
+    }
+
+    pub fn to_colorref(self) -> u32 {
+        self.r as u32 | (self.g as u32) << 8 | (self.b as u32) << 16
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Margins {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> i32 {
+        self.bottom - self.top
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+/**
+ * A font description as written in theme sources, i.e. "Segoe UI, 9, Bold". Weights other than
+ * normal and bold are written as a number, i.e. "Segoe UI, 9, Weight 600".
+ *
+ * The size is in points, which is how theme sources describe fonts. The binary formats store a
+ * full LOGFONT instead, with the height in pixels at 96 DPI.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Font {
+    pub face: String,
+    pub size: i32,
+    pub weight: i32,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikeout: bool,
+}
+
+pub const FW_NORMAL: i32 = 400;
+pub const FW_SEMIBOLD: i32 = 600;
+pub const FW_BOLD: i32 = 700;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyValue {
+    Enum(i32),
+    String(String),
+    Int(i32),
+    Bool(bool),
+    Color(Color),
+    Margins(Margins),
+    FileName(String),
+    Size(i32),
+    Position(Point),
+    Rect(Rect),
+    Font(Font),
+    IntList(Vec<i32>),
+    Float(f32),
+    FloatList(Vec<f32>),
+}
+
+impl PropertyValue {
+    pub fn property_type(&self) -> PropertyType {
+        match self {
+            PropertyValue::Enum(_) => PropertyType::Enum,
+            PropertyValue::String(_) => PropertyType::String,
+            PropertyValue::Int(_) => PropertyType::Int,
+            PropertyValue::Bool(_) => PropertyType::Bool,
+            PropertyValue::Color(_) => PropertyType::Color,
+            PropertyValue::Margins(_) => PropertyType::Margins,
+            PropertyValue::FileName(_) => PropertyType::FileName,
+            PropertyValue::Size(_) => PropertyType::Size,
+            PropertyValue::Position(_) => PropertyType::Position,
+            PropertyValue::Rect(_) => PropertyType::Rect,
+            PropertyValue::Font(_) => PropertyType::Font,
+            PropertyValue::IntList(_) => PropertyType::IntList,
+            PropertyValue::Float(_) => PropertyType::Float,
+            PropertyValue::FloatList(_) => PropertyType::FloatList,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+    pub name: String,
+    pub value: PropertyValue,
+}
+
+impl Property {
+    pub fn new(name: &str, value: PropertyValue) -> Self {
+        Self { name: name.to_owned(), value }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThemeState {
+    /**
+     * The state name, or None for the part-wide state (state 0).
+     */
+    pub name: Option<String>,
+    pub properties: Vec<Property>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThemePart {
+    /**
+     * The part name, or None for the class-wide part (part 0).
+     */
+    pub name: Option<String>,
+    pub states: Vec<ThemeState>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThemeClass {
+    /**
+     * The class name, including any application prefix (i.e. "Explorer::ListView").
+     */
+    pub name: String,
+    pub parts: Vec<ThemePart>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Theme {
+    pub classes: Vec<ThemeClass>,
+
+    /**
+     * The contents of the image files referenced by FileName properties, keyed by the path as
+     * written in the property value. Images are kept in their original encoding.
+     */
+    pub images: BTreeMap<String, Vec<u8>>,
+}
+
+/**
+ * Normalize an image path for comparison. Paths in theme sources are case insensitive and may
+ * use either kind of slash.
+ */
+pub fn normalize_image_path(path: &str) -> String {
+    path.replace('/', "\\").to_lowercase()
+}
+
+fn name_matches(a: Option<&str>, b: Option<&str>) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        _ => false,
+    }
+}
+
+impl ThemeState {
+    pub fn property(&self, name: &str) -> Option<&PropertyValue> {
+        self.properties.iter()
+            .find(|property| property.name.eq_ignore_ascii_case(name))
+            .map(|property| &property.value)
+    }
+
+    /**
+     * Set a property, replacing the value of an existing property of the same name.
+     */
+    pub fn set_property(&mut self, name: &str, value: PropertyValue) {
+        match self.properties.iter_mut().find(|property| property.name.eq_ignore_ascii_case(name)) {
+            Some(property) => property.value = value,
+            None => self.properties.push(Property::new(name, value)),
+        }
+    }
+
+    pub fn remove_property(&mut self, name: &str) -> Option<PropertyValue> {
+        let index = self.properties.iter().position(|property| property.name.eq_ignore_ascii_case(name))?;
+        Some(self.properties.remove(index).value)
+    }
+}
+
+impl ThemePart {
+    pub fn state(&self, name: Option<&str>) -> Option<&ThemeState> {
+        self.states.iter().find(|state| name_matches(state.name.as_deref(), name))
+    }
+
+    pub fn state_mut(&mut self, name: Option<&str>) -> &mut ThemeState {
+        let index = match self.states.iter().position(|state| name_matches(state.name.as_deref(), name)) {
+            Some(index) => index,
+            None => {
+                self.states.push(ThemeState { name: name.map(str::to_owned), properties: Vec::new() });
+                self.states.len() - 1
+            }
+        };
+        &mut self.states[index]
+    }
+}
+
+impl ThemeClass {
+    pub fn part(&self, name: Option<&str>) -> Option<&ThemePart> {
+        self.parts.iter().find(|part| name_matches(part.name.as_deref(), name))
+    }
+
+    pub fn part_mut(&mut self, name: Option<&str>) -> &mut ThemePart {
+        let index = match self.parts.iter().position(|part| name_matches(part.name.as_deref(), name)) {
+            Some(index) => index,
+            None => {
+                self.parts.push(ThemePart { name: name.map(str::to_owned), states: Vec::new() });
+                self.parts.len() - 1
+            }
+        };
+        &mut self.parts[index]
+    }
+}
+
+impl Theme {
+    pub fn class(&self, name: &str) -> Option<&ThemeClass> {
+        self.classes.iter().find(|class| class.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn class_mut(&mut self, name: &str) -> &mut ThemeClass {
+        let index = match self.classes.iter().position(|class| class.name.eq_ignore_ascii_case(name)) {
+            Some(index) => index,
+            None => {
+                self.classes.push(ThemeClass { name: name.to_owned(), parts: Vec::new() });
+                self.classes.len() - 1
+            }
+        };
+        &mut self.classes[index]
+    }
+
+    pub fn state(&self, class: &str, part: Option<&str>, state: Option<&str>) -> Option<&ThemeState> {
+        self.class(class)?.part(part)?.state(state)
+    }
+
+    /**
+     * Get a state for editing, creating the class, part and state if they do not exist yet.
+     */
+    pub fn state_mut(&mut self, class: &str, part: Option<&str>, state: Option<&str>) -> &mut ThemeState {
+        self.class_mut(class).part_mut(part).state_mut(state)
+    }
+
+    /**
+     * Look up a property the way uxtheme does: the state first, then the part-wide state, and
+     * then the class-wide part.
+     */
+    pub fn resolve_property(&self, class: &str, part: Option<&str>, state: Option<&str>, name: &str) -> Option<&PropertyValue> {
+        let (part, state) = self.property_level(class, part, state, name)?;
+        self.state(class, part, state)?.property(name)
+    }
+
+    /**
+     * Find the level that resolve_property takes a property from, as the part and state that
+     * hold it.
+     */
+    pub fn property_level<'a>(&self, class: &str, part: Option<&'a str>, state: Option<&'a str>, name: &str) -> Option<(Option<&'a str>, Option<&'a str>)> {
+        let class = self.class(class)?;
+        let mut levels = vec![(part, state)];
+        if state.is_some() {
+            levels.push((part, None));
+        }
+        if part.is_some() {
+            levels.push((None, None));
+        }
+
+        levels.into_iter().find(|(part, state)| {
+            class.part(*part).and_then(|part| part.state(*state)).is_some_and(|state| state.property(name).is_some())
+        })
+    }
+
+    /**
+     * Find the contents of an image file referenced by a FileName property.
+     */
+    pub fn image(&self, path: &str) -> Option<&[u8]> {
+        if let Some(data) = self.images.get(path) {
+            return Some(data);
+        }
+        let path = normalize_image_path(path);
+        self.images.iter()
+            .find(|(key, _)| normalize_image_path(key) == path)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    /**
+     * Replace the contents of an image file, keeping the key that it is stored under if there
+     * already is one for the path.
+     */
+    pub fn set_image(&mut self, path: &str, data: Vec<u8>) {
+        let normalized = normalize_image_path(path);
+        let key = self.images.keys()
+            .find(|key| normalize_image_path(key) == normalized)
+            .cloned()
+            .unwrap_or_else(|| path.to_owned());
+        self.images.insert(key, data);
+    }
+
+    /**
+     * The normalized paths of every image that a FileName property refers to.
+     */
+    pub fn referenced_images(&self) -> BTreeSet<String> {
+        self.states()
+            .flat_map(|(_, _, state)| state.properties.iter())
+            .filter_map(|property| match &property.value {
+                PropertyValue::FileName(path) => Some(normalize_image_path(path)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /**
+     * Iterate over every state in the theme, along with its owning class and part.
+     */
+    pub fn states(&self) -> impl Iterator<Item = (&ThemeClass, &ThemePart, &ThemeState)> {
+        self.classes.iter().flat_map(|class| {
+            class.parts.iter().flat_map(move |part| {
+                part.states.iter().map(move |state| (class, part, state))
+            })
+        })
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.r, self.g, self.b)
+    }
+}
+
+impl fmt::Display for Margins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {}, {}, {}", self.left, self.right, self.top, self.bottom)
+    }
+}
+
+impl fmt::Display for Rect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {}, {}, {}", self.left, self.top, self.right, self.bottom)
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {}", self.x, self.y)
+    }
+}
+
+impl fmt::Display for Font {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {}", self.face, self.size)?;
+        match self.weight {
+            FW_NORMAL => {}
+            FW_BOLD => write!(f, ", Bold")?,
+            weight => write!(f, ", Weight {}", weight)?,
+        }
+        if self.italic {
+            write!(f, ", Italic")?;
+        }
+        if self.underline {
+            write!(f, ", Underline")?;
+        }
+        if self.strikeout {
+            write!(f, ", Strikeout")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,539 @@
+/*
+ * Part and state identifiers for the theme classes in vssym32.h.
+ *
+ * Theme sources refer to parts and states by name, but compiled themes number them. These tables
+ * follow the *_PARTS and *_STATES enumerations in vssym32.h. States are numbered from 1 in the
+ * order that they are listed.
+ *
+ * Parts and states that are not listed here can still be written with their numeric identifier
+ * as their name, i.e. [Button.12(3)].
+ */
+
+pub struct PartInfo {
+    pub name: &'static str,
+    pub id: i32,
+    pub states: &'static [&'static str],
+}
+
+pub struct ClassInfo {
+    pub name: &'static str,
+    pub parts: &'static [PartInfo],
+}
+
+const PUSH_STATES: &[&str] = &["Normal", "Hot", "Pressed", "Disabled", "Defaulted", "DefaultedAnimating"];
+const RADIO_STATES: &[&str] = &[
+    "UncheckedNormal", "UncheckedHot", "UncheckedPressed", "UncheckedDisabled",
+    "CheckedNormal", "CheckedHot", "CheckedPressed", "CheckedDisabled",
+];
+const CHECKBOX_STATES: &[&str] = &[
+    "UncheckedNormal", "UncheckedHot", "UncheckedPressed", "UncheckedDisabled",
+    "CheckedNormal", "CheckedHot", "CheckedPressed", "CheckedDisabled",
+    "MixedNormal", "MixedHot", "MixedPressed", "MixedDisabled",
+    "ImplicitNormal", "ImplicitHot", "ImplicitPressed", "ImplicitDisabled",
+    "ExcludedNormal", "ExcludedHot", "ExcludedPressed", "ExcludedDisabled",
+];
+const NORMAL_DISABLED_STATES: &[&str] = &["Normal", "Disabled"];
+const BUTTON_STATES: &[&str] = &["Normal", "Hot", "Pushed", "Disabled"];
+const CAPTION_BUTTON_STATES: &[&str] = &["Normal", "Hot", "Pushed", "Disabled", "Inactive"];
+const PRESSED_STATES: &[&str] = &["Normal", "Hot", "Pressed", "Disabled"];
+const ACTIVE_STATES: &[&str] = &["Active", "Inactive"];
+const CAPTION_STATES: &[&str] = &["Active", "Inactive", "Disabled"];
+const ITEM_STATES: &[&str] = &["Normal", "Hot", "Selected", "Disabled", "SelectedNotFocus", "HotSelected"];
+const HOT_PRESSED_STATES: &[&str] = &["Normal", "Hot", "Pressed"];
+const LINK_STATES: &[&str] = &["Normal", "Hot", "Pressed", "Disabled"];
+const TAB_STATES: &[&str] = &["Normal", "Hot", "Selected", "Disabled", "Focused"];
+const BORDER_STATES: &[&str] = &["Normal", "Focused", "Hot", "Disabled"];
+const DROP_STATES: &[&str] = &["Highlight", "NoHighlight"];
+
+macro_rules! parts {
+    ($( $name:literal = $id:literal $( : $states:expr )? ),* $(,)?) => {
+        &[ $( PartInfo { name: $name, id: $id, states: parts!(@states $( $states )?) }, )* ]
+    };
+    (@states) => { &[] };
+    (@states $states:expr) => { $states };
+}
+
+pub const CLASSES: &[ClassInfo] = &[
+    ClassInfo { name: "Button", parts: parts!(
+        "PushButton" = 1 : PUSH_STATES,
+        "RadioButton" = 2 : RADIO_STATES,
+        "CheckBox" = 3 : CHECKBOX_STATES,
+        "GroupBox" = 4 : NORMAL_DISABLED_STATES,
+        "UserButton" = 5,
+        "CommandLink" = 6 : PUSH_STATES,
+        "CommandLinkGlyph" = 7 : PUSH_STATES,
+        "RadioButton_HCDisabled" = 8,
+        "CheckBox_HCDisabled" = 9,
+        "GroupBox_HCDisabled" = 10,
+        "PushButtonDropDown" = 11 : NORMAL_DISABLED_STATES,
+    ) },
+    ClassInfo { name: "Window", parts: parts!(
+        "Caption" = 1 : CAPTION_STATES,
+        "SmallCaption" = 2 : CAPTION_STATES,
+        "MinCaption" = 3 : CAPTION_STATES,
+        "SmallMinCaption" = 4 : CAPTION_STATES,
+        "MaxCaption" = 5 : CAPTION_STATES,
+        "SmallMaxCaption" = 6 : CAPTION_STATES,
+        "FrameLeft" = 7 : ACTIVE_STATES,
+        "FrameRight" = 8 : ACTIVE_STATES,
+        "FrameBottom" = 9 : ACTIVE_STATES,
+        "SmallFrameLeft" = 10 : ACTIVE_STATES,
+        "SmallFrameRight" = 11 : ACTIVE_STATES,
+        "SmallFrameBottom" = 12 : ACTIVE_STATES,
+        "SysButton" = 13 : CAPTION_BUTTON_STATES,
+        "MdiSysButton" = 14 : BUTTON_STATES,
+        "MinButton" = 15 : CAPTION_BUTTON_STATES,
+        "MdiMinButton" = 16 : BUTTON_STATES,
+        "MaxButton" = 17 : CAPTION_BUTTON_STATES,
+        "CloseButton" = 18 : CAPTION_BUTTON_STATES,
+        "SmallCloseButton" = 19 : CAPTION_BUTTON_STATES,
+        "MdiCloseButton" = 20 : BUTTON_STATES,
+        "RestoreButton" = 21 : CAPTION_BUTTON_STATES,
+        "MdiRestoreButton" = 22 : BUTTON_STATES,
+        "HelpButton" = 23 : CAPTION_BUTTON_STATES,
+        "MdiHelpButton" = 24 : BUTTON_STATES,
+        "HorzScroll" = 25 : BUTTON_STATES,
+        "HorzThumb" = 26 : BUTTON_STATES,
+        "VertScroll" = 27 : BUTTON_STATES,
+        "VertThumb" = 28 : BUTTON_STATES,
+        "Dialog" = 29,
+        "CaptionSizingTemplate" = 30,
+        "SmallCaptionSizingTemplate" = 31,
+        "FrameLeftSizingTemplate" = 32,
+        "SmallFrameLeftSizingTemplate" = 33,
+        "FrameRightSizingTemplate" = 34,
+        "SmallFrameRightSizingTemplate" = 35,
+        "FrameBottomSizingTemplate" = 36,
+        "SmallFrameBottomSizingTemplate" = 37,
+        "Frame" = 38 : ACTIVE_STATES,
+    ) },
+    ClassInfo { name: "Edit", parts: parts!(
+        "EditText" = 1 : &["Normal", "Hot", "Selected", "Disabled", "Focused", "ReadOnly", "Assist", "CueBanner"],
+        "Caret" = 2,
+        "Background" = 3 : &["Normal", "Hot", "Disabled", "Focused", "ReadOnly", "Assist"],
+        "Password" = 4,
+        "BackgroundWithBorder" = 5 : &["Normal", "Hot", "Disabled", "Focused"],
+        "EditBorder_NoScroll" = 6 : &["Normal", "Hot", "Focused", "Disabled"],
+        "EditBorder_HScroll" = 7 : &["Normal", "Hot", "Focused", "Disabled"],
+        "EditBorder_VScroll" = 8 : &["Normal", "Hot", "Focused", "Disabled"],
+        "EditBorder_HVScroll" = 9 : &["Normal", "Hot", "Focused", "Disabled"],
+    ) },
+    ClassInfo { name: "ComboBox", parts: parts!(
+        "DropDownButton" = 1 : PRESSED_STATES,
+        "Background" = 2,
+        "TransparentBackground" = 3 : &["Normal", "Hot", "Disabled", "Focused"],
+        "Border" = 4 : &["Normal", "Hot", "Focused", "Disabled"],
+        "ReadOnly" = 5 : PRESSED_STATES,
+        "DropDownButtonRight" = 6 : PRESSED_STATES,
+        "DropDownButtonLeft" = 7 : PRESSED_STATES,
+        "CueBanner" = 8 : PRESSED_STATES,
+        "DropDownItem" = 9 : &["Normal", "Highlighted"],
+    ) },
+    ClassInfo { name: "ScrollBar", parts: parts!(
+        "ArrowBtn" = 1 : &[
+            "UpNormal", "UpHot", "UpPressed", "UpDisabled",
+            "DownNormal", "DownHot", "DownPressed", "DownDisabled",
+            "LeftNormal", "LeftHot", "LeftPressed", "LeftDisabled",
+            "RightNormal", "RightHot", "RightPressed", "RightDisabled",
+            "UpHover", "DownHover", "LeftHover", "RightHover",
+        ],
+        "ThumbBtnHorz" = 2 : &["Normal", "Hot", "Pressed", "Disabled", "Hover"],
+        "ThumbBtnVert" = 3 : &["Normal", "Hot", "Pressed", "Disabled", "Hover"],
+        "LowerTrackHorz" = 4 : &["Normal", "Hot", "Pressed", "Disabled", "Hover"],
+        "UpperTrackHorz" = 5 : &["Normal", "Hot", "Pressed", "Disabled", "Hover"],
+        "LowerTrackVert" = 6 : &["Normal", "Hot", "Pressed", "Disabled", "Hover"],
+        "UpperTrackVert" = 7 : &["Normal", "Hot", "Pressed", "Disabled", "Hover"],
+        "GripperHorz" = 8 : &["Normal", "Hot", "Pressed", "Disabled", "Hover"],
+        "GripperVert" = 9 : &["Normal", "Hot", "Pressed", "Disabled", "Hover"],
+        "SizeBox" = 10 : &["RightAlign", "LeftAlign", "TopRightAlign", "TopLeftAlign", "HalfBottomRightAlign", "HalfBottomLeftAlign", "HalfTopRightAlign", "HalfTopLeftAlign"],
+        "SizeBoxBkgnd" = 11 : &["Normal", "Hot", "Pressed", "Disabled", "Hover"],
+    ) },
+    ClassInfo { name: "Tab", parts: parts!(
+        "TabItem" = 1 : &["Normal", "Hot", "Selected", "Disabled", "Focused"],
+        "TabItemLeftEdge" = 2 : &["Normal", "Hot", "Selected", "Disabled", "Focused"],
+        "TabItemRightEdge" = 3 : &["Normal", "Hot", "Selected", "Disabled", "Focused"],
+        "TabItemBothEdge" = 4 : &["Normal", "Hot", "Selected", "Disabled", "Focused"],
+        "TopTabItem" = 5 : &["Normal", "Hot", "Selected", "Disabled", "Focused"],
+        "TopTabItemLeftEdge" = 6 : &["Normal", "Hot", "Selected", "Disabled", "Focused"],
+        "TopTabItemRightEdge" = 7 : &["Normal", "Hot", "Selected", "Disabled", "Focused"],
+        "TopTabItemBothEdge" = 8 : &["Normal", "Hot", "Selected", "Disabled", "Focused"],
+        "Pane" = 9,
+        "Body" = 10,
+        "AeroWizardBody" = 11,
+    ) },
+    ClassInfo { name: "Progress", parts: parts!(
+        "Bar" = 1,
+        "BarVert" = 2,
+        "Chunk" = 3,
+        "ChunkVert" = 4,
+        "Fill" = 5 : &["Normal", "Error", "Paused", "Partial"],
+        "FillVert" = 6 : &["Normal", "Error", "Paused", "Partial"],
+        "PulseOverlay" = 7,
+        "MoveOverlay" = 8,
+        "PulseOverlayVert" = 9,
+        "MoveOverlayVert" = 10,
+        "TransparentBar" = 11 : &["Normal", "Partial"],
+        "TransparentBarVert" = 12 : &["Normal", "Partial"],
+    ) },
+    ClassInfo { name: "TrackBar", parts: parts!(
+        "Track" = 1 : &["Normal"],
+        "TrackVert" = 2 : &["Normal"],
+        "Thumb" = 3 : &["Normal", "Hot", "Pressed", "Focused", "Disabled"],
+        "ThumbBottom" = 4 : &["Normal", "Hot", "Pressed", "Focused", "Disabled"],
+        "ThumbTop" = 5 : &["Normal", "Hot", "Pressed", "Focused", "Disabled"],
+        "ThumbVert" = 6 : &["Normal", "Hot", "Pressed", "Focused", "Disabled"],
+        "ThumbLeft" = 7 : &["Normal", "Hot", "Pressed", "Focused", "Disabled"],
+        "ThumbRight" = 8 : &["Normal", "Hot", "Pressed", "Focused", "Disabled"],
+        "Tics" = 9 : &["Normal"],
+        "TicsVert" = 10 : &["Normal"],
+    ) },
+    ClassInfo { name: "Toolbar", parts: parts!(
+        "Button" = 1 : &["Normal", "Hot", "Pressed", "Disabled", "Checked", "HotChecked", "NearHot", "OtherSideHot"],
+        "DropDownButton" = 2 : &["Normal", "Hot", "Pressed", "Disabled", "Checked", "HotChecked", "NearHot", "OtherSideHot"],
+        "SplitButton" = 3 : &["Normal", "Hot", "Pressed", "Disabled", "Checked", "HotChecked", "NearHot", "OtherSideHot"],
+        "SplitButtonDropDown" = 4 : &["Normal", "Hot", "Pressed", "Disabled", "Checked", "HotChecked", "NearHot", "OtherSideHot"],
+        "Separator" = 5,
+        "SeparatorVert" = 6,
+        "DropDownButtonGlyph" = 7 : &["Normal", "Hot", "Pressed", "Disabled"],
+    ) },
+    ClassInfo { name: "Header", parts: parts!(
+        "HeaderItem" = 1 : &[
+            "Normal", "Hot", "Pressed", "SortedNormal", "SortedHot", "SortedPressed",
+            "IconNormal", "IconHot", "IconPressed", "IconSortedNormal", "IconSortedHot", "IconSortedPressed",
+        ],
+        "HeaderItemLeft" = 2 : &["Normal", "Hot", "Pressed"],
+        "HeaderItemRight" = 3 : &["Normal", "Hot", "Pressed"],
+        "HeaderSortArrow" = 4 : &["SortedUp", "SortedDown"],
+        "HeaderDropDown" = 5 : &["Normal", "SoftHot", "Hot"],
+        "HeaderDropDownFilter" = 6 : &["Normal", "SoftHot", "Hot"],
+        "HeaderOverflow" = 7 : &["Normal", "Hot"],
+    ) },
+    ClassInfo { name: "Tooltip", parts: parts!(
+        "Standard" = 1 : &["Normal", "Link"],
+        "StandardTitle" = 2 : &["Normal", "Link"],
+        "Balloon" = 3 : &["Normal", "Link"],
+        "BalloonTitle" = 4,
+        "Close" = 5 : &["Normal", "Hot", "Pressed"],
+        "BalloonStem" = 6 : &[
+            "PointingUpLeftWall", "PointingUpCentered", "PointingUpRightWall",
+            "PointingDownRightWall", "PointingDownCentered", "PointingDownLeftWall",
+        ],
+        "Wrench" = 7 : &["Normal", "Hot", "Pressed"],
+    ) },
+    ClassInfo { name: "ListView", parts: parts!(
+        "ListItem" = 1 : ITEM_STATES,
+        "ListGroup" = 2,
+        "ListDetail" = 3,
+        "ListSortedDetail" = 4,
+        "EmptyText" = 5,
+        "GroupHeader" = 6,
+        "GroupHeaderLine" = 7,
+        "ExpandButton" = 8 : &["Normal", "Hover", "Pushed"],
+        "CollapseButton" = 9 : &["Normal", "Hover", "Pushed"],
+        "ColumnDetail" = 10,
+    ) },
+    ClassInfo { name: "TreeView", parts: parts!(
+        "TreeItem" = 1 : ITEM_STATES,
+        "Glyph" = 2 : &["Closed", "Opened"],
+        "Branch" = 3,
+        "HotGlyph" = 4 : &["Closed", "Opened"],
+    ) },
+    ClassInfo { name: "Menu", parts: parts!(
+        "MenuItem_TmSchema" = 1,
+        "MenuDropDown_TmSchema" = 2,
+        "MenuBarItem_TmSchema" = 3,
+        "MenuBarDropDown_TmSchema" = 4,
+        "Chevron_TmSchema" = 5,
+        "Separator_TmSchema" = 6,
+        "BarBackground" = 7 : ACTIVE_STATES,
+        "BarItem" = 8 : &["Normal", "Hot", "Pushed", "Disabled", "DisabledHot", "DisabledPushed"],
+        "PopupBackground" = 9,
+        "PopupBorders" = 10,
+        "PopupCheck" = 11 : &["CheckmarkNormal", "CheckmarkDisabled", "BulletNormal", "BulletDisabled"],
+        "PopupCheckBackground" = 12 : &["Disabled", "Normal", "Bitmap"],
+        "PopupGutter" = 13,
+        "PopupItem" = 14 : &["Normal", "Hot", "Disabled", "DisabledHot"],
+        "PopupSeparator" = 15,
+        "PopupSubMenu" = 16 : NORMAL_DISABLED_STATES,
+        "SystemClose" = 17 : NORMAL_DISABLED_STATES,
+        "SystemMaximize" = 18 : NORMAL_DISABLED_STATES,
+        "SystemMinimize" = 19 : NORMAL_DISABLED_STATES,
+        "SystemRestore" = 20 : NORMAL_DISABLED_STATES,
+    ) },
+    ClassInfo { name: "Status", parts: parts!(
+        "Pane" = 1,
+        "GripperPane" = 2,
+        "Gripper" = 3,
+    ) },
+    ClassInfo { name: "Rebar", parts: parts!(
+        "Gripper" = 1,
+        "GripperVert" = 2,
+        "Band" = 3,
+        "Chevron" = 4 : &["Normal", "Hot", "Pressed"],
+        "ChevronVert" = 5 : &["Normal", "Hot", "Pressed"],
+        "Background" = 6,
+        "Splitter" = 7 : &["Normal", "Hot", "Pressed"],
+        "SplitterVert" = 8 : &["Normal", "Hot", "Pressed"],
+    ) },
+    ClassInfo { name: "Spin", parts: parts!(
+        "Up" = 1 : PRESSED_STATES,
+        "Down" = 2 : PRESSED_STATES,
+        "UpHorz" = 3 : PRESSED_STATES,
+        "DownHorz" = 4 : PRESSED_STATES,
+    ) },
+    ClassInfo { name: "AeroWizard", parts: parts!(
+        "TitleBar" = 1 : ACTIVE_STATES,
+        "HeaderArea" = 2 : &["NoMargin"],
+        "ContentArea" = 3 : &["NoMargin"],
+        "CommandArea" = 4,
+        "Button" = 5,
+    ) },
+    ClassInfo { name: "Clock", parts: parts!(
+        "Time" = 1 : HOT_PRESSED_STATES,
+    ) },
+    ClassInfo { name: "Communications", parts: parts!(
+        "Tab" = 1 : &["Normal", "Hot", "Selected"],
+    ) },
+    ClassInfo { name: "ControlPanel", parts: parts!(
+        "NavigationPane" = 1,
+        "ContentPane" = 2,
+        "NavigationPaneLabel" = 3,
+        "ContentPaneLabel" = 4,
+        "Title" = 5,
+        "BodyText" = 6,
+        "HelpLink" = 7 : LINK_STATES,
+        "TaskLink" = 8 : &["Normal", "Hot", "Pressed", "Disabled", "Page"],
+        "GroupText" = 9,
+        "ContentLink" = 10 : LINK_STATES,
+        "SectionTitleLink" = 11 : &["Normal", "Hot"],
+        "LargeCommandArea" = 12,
+        "SmallCommandArea" = 13,
+        "Button" = 14,
+        "MessageText" = 15,
+        "NavigationPaneLine" = 16,
+        "ContentPaneLine" = 17,
+        "BannerArea" = 18,
+        "BodyTitle" = 19,
+    ) },
+    ClassInfo { name: "DatePicker", parts: parts!(
+        "DateText" = 1 : &["Normal", "Disabled", "Selected"],
+        "DateBorder" = 2 : &["Normal", "Hot", "Focused", "Disabled"],
+        "ShowCalendarButtonRight" = 3 : LINK_STATES,
+    ) },
+    ClassInfo { name: "DragDrop", parts: parts!(
+        "Copy" = 1 : DROP_STATES,
+        "Move" = 2 : DROP_STATES,
+        "UpdateMetadata" = 3 : DROP_STATES,
+        "CreateLink" = 4 : DROP_STATES,
+        "Warning" = 5 : DROP_STATES,
+        "None" = 6 : DROP_STATES,
+        "ImageBg" = 7,
+        "TextBg" = 8,
+    ) },
+    ClassInfo { name: "EmptyMarkup", parts: parts!(
+        "MarkupText" = 1 : &["NormalText", "LinkText"],
+    ) },
+    ClassInfo { name: "ExplorerBar", parts: parts!(
+        "HeaderBackground" = 1,
+        "HeaderClose" = 2 : HOT_PRESSED_STATES,
+        "HeaderPin" = 3 : &["Normal", "Hot", "Pressed", "SelectedNormal", "SelectedHot", "SelectedPressed"],
+        "IEBarMenu" = 4 : HOT_PRESSED_STATES,
+        "NormalGroupBackground" = 5,
+        "NormalGroupCollapse" = 6 : HOT_PRESSED_STATES,
+        "NormalGroupExpand" = 7 : HOT_PRESSED_STATES,
+        "NormalGroupHead" = 8,
+        "SpecialGroupBackground" = 9,
+        "SpecialGroupCollapse" = 10 : HOT_PRESSED_STATES,
+        "SpecialGroupExpand" = 11 : HOT_PRESSED_STATES,
+        "SpecialGroupHead" = 12,
+    ) },
+    ClassInfo { name: "Flyout", parts: parts!(
+        "Header" = 1,
+        "Body" = 2 : &["Normal", "Emphasized"],
+        "Label" = 3 : &["Normal", "Selected", "Emphasized", "Disabled"],
+        "Link" = 4 : &["Normal", "Hover"],
+        "Divider" = 5,
+        "Window" = 6,
+        "LinkArea" = 7,
+        "LinkHeader" = 8 : &["Normal", "Hover"],
+    ) },
+    ClassInfo { name: "Link", parts: parts!(
+        "Hyperlink" = 1 : &["NormalText", "LinkText"],
+    ) },
+    ClassInfo { name: "ListBox", parts: parts!(
+        "BorderHScroll" = 1 : BORDER_STATES,
+        "BorderHVScroll" = 2 : BORDER_STATES,
+        "BorderNoScroll" = 3 : BORDER_STATES,
+        "BorderVScroll" = 4 : BORDER_STATES,
+        "Item" = 5 : &["Hot", "HotSelected", "Selected", "SelectedNotFocus"],
+    ) },
+    ClassInfo { name: "MenuBand", parts: parts!(
+        "NewAppButton" = 1 : &["Normal", "Hot", "Pressed", "Disabled", "Checked", "HotChecked"],
+        "Seperator" = 2,
+    ) },
+    ClassInfo { name: "MonthCal", parts: parts!(
+        "Background" = 1,
+        "Borders" = 2,
+        "GridBackground" = 3,
+        "ColHeaderSplitter" = 4,
+        "GridCellBackground" = 5 : &["Selected", "Hot", "SelectedHot", "SelectedNotFocused", "Today"],
+        "GridCell" = 6 : &["Hot", "HasState", "HasStateHot", "Today"],
+        "GridCellUpper" = 7 : &["Hot", "HasState", "HasStateHot"],
+        "TrailingGridCell" = 8 : &["Hot", "HasState", "HasStateHot", "Today"],
+        "TrailingGridCellUpper" = 9 : &["Hot", "HasState", "HasStateHot"],
+        "NavNext" = 10 : LINK_STATES,
+        "NavPrev" = 11 : LINK_STATES,
+    ) },
+    ClassInfo { name: "Navigation", parts: parts!(
+        "BackButton" = 1 : LINK_STATES,
+        "ForwardButton" = 2 : LINK_STATES,
+        "MenuButton" = 3 : LINK_STATES,
+    ) },
+    ClassInfo { name: "Page", parts: parts!(
+        "Up" = 1 : PRESSED_STATES,
+        "Down" = 2 : PRESSED_STATES,
+        "UpHorz" = 3 : PRESSED_STATES,
+        "DownHorz" = 4 : PRESSED_STATES,
+    ) },
+    ClassInfo { name: "SearchEditBox", parts: parts!(
+        "SearchEditBoxText" = 1 : &["Formatted"],
+    ) },
+    ClassInfo { name: "StartPanel", parts: parts!(
+        "UserPane" = 1,
+        "MorePrograms" = 2,
+        "MoreProgramsArrow" = 3 : HOT_PRESSED_STATES,
+        "ProgList" = 4,
+        "ProgListSeparator" = 5,
+        "PlacesList" = 6,
+        "PlacesListSeparator" = 7,
+        "LogOff" = 8,
+        "LogOffButtons" = 9 : HOT_PRESSED_STATES,
+        "UserPicture" = 10,
+        "Preview" = 11,
+        "MoreProgramsTab" = 12 : TAB_STATES,
+        "NsCHost" = 13,
+        "SoftwareExplorer" = 14 : TAB_STATES,
+        "OpenBox" = 15 : TAB_STATES,
+        "SearchView" = 16 : HOT_PRESSED_STATES,
+        "MoreProgramsArrowBack" = 17 : HOT_PRESSED_STATES,
+        "TopMatch" = 18,
+        "LogOffSplitButtonDropDown" = 19,
+    ) },
+    ClassInfo { name: "Static", parts: parts!(
+        "Text" = 1,
+    ) },
+    ClassInfo { name: "TaskBand", parts: parts!(
+        "GroupCount" = 1,
+        "FlashButton" = 2,
+        "FlashButtonGroupMenu" = 3,
+    ) },
+    ClassInfo { name: "Taskbar", parts: parts!(
+        "BackgroundBottom" = 1,
+        "BackgroundRight" = 2,
+        "BackgroundTop" = 3,
+        "BackgroundLeft" = 4,
+        "SizingBarBottom" = 5,
+        "SizingBarRight" = 6,
+        "SizingBarTop" = 7,
+        "SizingBarLeft" = 8,
+    ) },
+    ClassInfo { name: "TaskDialog", parts: parts!(
+        "PrimaryPanel" = 1,
+        "MainInstructionPane" = 2,
+        "MainIcon" = 3,
+        "ContentPane" = 4 : &["Standalone"],
+        "ContentIcon" = 5,
+        "ExpandedContent" = 6,
+        "CommandLinkPane" = 7,
+        "SecondaryPanel" = 8,
+        "ControlPane" = 9,
+        "ButtonSection" = 10,
+        "ButtonWrapper" = 11,
+        "ExpandoText" = 12,
+        "ExpandoButton" = 13 : &[
+            "Normal", "Hover", "Pressed", "ExpandedNormal", "ExpandedHover", "ExpandedPressed",
+            "NormalDisabled", "ExpandedDisabled",
+        ],
+        "VerificationText" = 14,
+        "FootnotePane" = 15,
+        "FootnoteArea" = 16,
+        "FootnoteSeparator" = 17,
+        "ExpandedFooterArea" = 18,
+        "ProgressBar" = 19,
+        "ImageAlignment" = 20,
+        "RadioButtonPane" = 21,
+    ) },
+    ClassInfo { name: "TextStyle", parts: parts!(
+        "MainInstruction" = 1,
+        "Instruction" = 2,
+        "BodyTitle" = 3,
+        "BodyText" = 4,
+        "SecondaryText" = 5,
+        "HyperlinkText" = 6 : LINK_STATES,
+        "Expanded" = 7,
+        "Label" = 8,
+        "ControlLabel" = 9 : NORMAL_DISABLED_STATES,
+    ) },
+    ClassInfo { name: "TrayNotify", parts: parts!(
+        "Background" = 1,
+        "AnimBackground" = 2,
+    ) },
+    ClassInfo { name: "UserTile", parts: parts!(
+        "StrokeBackground" = 1,
+        "HoverBackground" = 2 : HOT_PRESSED_STATES,
+    ) },
+];
+
+/**
+ * Strip the application prefix from a class name, i.e. "Explorer::ListView" becomes "ListView".
+ */
+pub fn base_class_name(class: &str) -> &str {
+    class.rfind("::").map(|i| &class[i + 2..]).unwrap_or(class)
+}
+
+pub fn find_class(class: &str) -> Option<&'static ClassInfo> {
+    let class = base_class_name(class);
+    CLASSES.iter().find(|info| info.name.eq_ignore_ascii_case(class))
+}
+
+pub fn find_part(class: &str, part: &str) -> Option<&'static PartInfo> {
+    find_class(class)?.parts.iter().find(|info| info.name.eq_ignore_ascii_case(part))
+}
+
+/**
+ * Resolve a part name to its identifier. Part 0 (no name) is the class-wide part.
+ */
+pub fn part_id(class: &str, part: Option<&str>) -> Option<i32> {
+    match part {
+        None => Some(0),
+        Some(part) => part.parse().ok().or_else(|| find_part(class, part).map(|info| info.id)),
+    }
+}
+
+/**
+ * Resolve a state name to its identifier. State 0 (no name) is the part-wide state.
+ */
+pub fn state_id(class: &str, part: Option<&str>, state: Option<&str>) -> Option<i32> {
+    let Some(state) = state else {
+        return Some(0);
+    };
+    if let Ok(id) = state.parse() {
+        return Some(id);
+    }
+
+    // Class-wide states such as [Window(Active)] are rare, but some themes use them.
+    let states = match part {
+        Some(part) => find_part(class, part)?.states,
+        None => find_class(class)?.parts.first()?.states,
+    };
+    states.iter().position(|name| name.eq_ignore_ascii_case(state)).map(|i| i as i32 + 1)
+}
+
+pub fn part_name(class: &str, id: i32) -> Option<&'static str> {
+    find_class(class)?.parts.iter().find(|info| info.id == id).map(|info| info.name)
+}
+
+pub fn state_name(class: &str, part: i32, id: i32) -> Option<&'static str> {
+    let info = find_class(class)?.parts.iter().find(|info| info.id == part.max(1))?;
+    usize::try_from(id - 1).ok().and_then(|i| info.states.get(i).copied())
+}
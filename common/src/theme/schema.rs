@@ -0,0 +1,436 @@
+/*
+ * Visual style property schema.
+ *
+ * These tables mirror the TMT_* property and enumeration definitions from the Windows SDK's
+ * vssym32.h. They give every known property a stable numeric identifier and a primitive type,
+ * which is what allows the source formats (where properties are named) and the binary formats
+ * (where properties are numbered) to be converted between each other.
+ */
+
+/**
+ * Primitive property types, using the TMT_* type identifiers as discriminants.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PropertyType {
+    Enum = 200,
+    String = 201,
+    Int = 202,
+    Bool = 203,
+    Color = 204,
+    Margins = 205,
+    FileName = 206,
+    Size = 207,
+    Position = 208,
+    Rect = 209,
+    Font = 210,
+    IntList = 211,
+    HBitmap = 212,
+    DiskStream = 213,
+    Stream = 214,
+    BitmapRef = 215,
+    Float = 216,
+    FloatList = 217,
+}
+
+impl PropertyType {
+    pub fn from_id(id: u32) -> Option<Self> {
+        use PropertyType::*;
+        Some(match id {
+            200 => Enum,
+            201 => String,
+            202 => Int,
+            203 => Bool,
+            204 => Color,
+            205 => Margins,
+            206 => FileName,
+            207 => Size,
+            208 => Position,
+            209 => Rect,
+            210 => Font,
+            211 => IntList,
+            212 => HBitmap,
+            213 => DiskStream,
+            214 => Stream,
+            215 => BitmapRef,
+            216 => Float,
+            217 => FloatList,
+            _ => return None,
+        })
+    }
+
+    pub fn id(self) -> u32 {
+        self as u32
+    }
+}
+
+pub struct PropertyInfo {
+    pub name: &'static str,
+    pub id: u32,
+    pub ty: PropertyType,
+}
+
+macro_rules! property_table {
+    ($( $name:literal = $id:literal : $ty:ident ),* $(,)?) => {
+        pub const PROPERTIES: &[PropertyInfo] = &[
+            $( PropertyInfo { name: $name, id: $id, ty: PropertyType::$ty }, )*
+        ];
+    };
+}
+
+property_table!(
+    // Fonts:
+    "Font" = 210 : Font,
+
+    // Documentation:
+    "ColorSchemes" = 401 : String,
+    "Sizes" = 402 : String,
+    "Charset" = 403 : Int,
+    "Name" = 600 : String,
+    "DisplayName" = 601 : String,
+    "Tooltip" = 602 : String,
+    "Company" = 603 : String,
+    "Author" = 604 : String,
+    "Copyright" = 605 : String,
+    "Url" = 606 : String,
+    "Version" = 607 : String,
+    "Description" = 608 : String,
+
+    // SysMetrics fonts:
+    "CaptionFont" = 801 : Font,
+    "SmallCaptionFont" = 802 : Font,
+    "MenuFont" = 803 : Font,
+    "StatusFont" = 804 : Font,
+    "MsgBoxFont" = 805 : Font,
+    "IconTitleFont" = 806 : Font,
+    "Heading1Font" = 807 : Font,
+    "Heading2Font" = 808 : Font,
+    "BodyFont" = 809 : Font,
+
+    // SysMetrics booleans, sizes, integers and strings:
+    "FlatMenus" = 1001 : Bool,
+    "SizingBorderWidth" = 1201 : Size,
+    "ScrollbarWidth" = 1202 : Size,
+    "ScrollbarHeight" = 1203 : Size,
+    "CaptionBarWidth" = 1204 : Size,
+    "CaptionBarHeight" = 1205 : Size,
+    "SmCaptionBarWidth" = 1206 : Size,
+    "SmCaptionBarHeight" = 1207 : Size,
+    "MenuBarWidth" = 1208 : Size,
+    "MenuBarHeight" = 1209 : Size,
+    "PaddedBorderWidth" = 1210 : Size,
+    "MinColorDepth" = 1301 : Int,
+    "CssName" = 1401 : String,
+    "XmlName" = 1402 : String,
+    "LastUpdated" = 1403 : String,
+    "Alias" = 1404 : String,
+
+    // SysMetrics colors:
+    "Scrollbar" = 1601 : Color,
+    "Background" = 1602 : Color,
+    "ActiveCaption" = 1603 : Color,
+    "InactiveCaption" = 1604 : Color,
+    "Menu" = 1605 : Color,
+    "Window" = 1606 : Color,
+    "WindowFrame" = 1607 : Color,
+    "MenuText" = 1608 : Color,
+    "WindowText" = 1609 : Color,
+    "CaptionText" = 1610 : Color,
+    "ActiveBorder" = 1611 : Color,
+    "InactiveBorder" = 1612 : Color,
+    "AppWorkspace" = 1613 : Color,
+    "Highlight" = 1614 : Color,
+    "HighlightText" = 1615 : Color,
+    "BtnFace" = 1616 : Color,
+    "BtnShadow" = 1617 : Color,
+    "GrayText" = 1618 : Color,
+    "BtnText" = 1619 : Color,
+    "InactiveCaptionText" = 1620 : Color,
+    "BtnHighlight" = 1621 : Color,
+    "DkShadow3d" = 1622 : Color,
+    "Light3d" = 1623 : Color,
+    "InfoText" = 1624 : Color,
+    "InfoBk" = 1625 : Color,
+    "ButtonAlternateFace" = 1626 : Color,
+    "HotTracking" = 1627 : Color,
+    "GradientActiveCaption" = 1628 : Color,
+    "GradientInactiveCaption" = 1629 : Color,
+    "MenuHilight" = 1630 : Color,
+    "MenuBar" = 1631 : Color,
+
+    // Color scheme remapping:
+    "FromHue1" = 1801 : Int,
+    "FromHue2" = 1802 : Int,
+    "FromHue3" = 1803 : Int,
+    "FromHue4" = 1804 : Int,
+    "FromHue5" = 1805 : Int,
+    "ToHue1" = 1806 : Int,
+    "ToHue2" = 1807 : Int,
+    "ToHue3" = 1808 : Int,
+    "ToHue4" = 1809 : Int,
+    "ToHue5" = 1810 : Int,
+    "FromColor1" = 2001 : Color,
+    "FromColor2" = 2002 : Color,
+    "FromColor3" = 2003 : Color,
+    "FromColor4" = 2004 : Color,
+    "FromColor5" = 2005 : Color,
+    "ToColor1" = 2006 : Color,
+    "ToColor2" = 2007 : Color,
+    "ToColor3" = 2008 : Color,
+    "ToColor4" = 2009 : Color,
+    "ToColor5" = 2010 : Color,
+
+    // Rendering booleans:
+    "Transparent" = 2201 : Bool,
+    "AutoSize" = 2202 : Bool,
+    "BorderOnly" = 2203 : Bool,
+    "Composited" = 2204 : Bool,
+    "BgFill" = 2205 : Bool,
+    "GlyphTransparent" = 2206 : Bool,
+    "GlyphOnly" = 2207 : Bool,
+    "AlwaysShowSizingBar" = 2208 : Bool,
+    "MirrorImage" = 2209 : Bool,
+    "UniformSizing" = 2210 : Bool,
+    "IntegralSizing" = 2211 : Bool,
+    "SourceGrow" = 2212 : Bool,
+    "SourceShrink" = 2213 : Bool,
+    "DrawBorders" = 2214 : Bool,
+    "NoEtchedEffect" = 2215 : Bool,
+    "TextApplyOverlay" = 2216 : Bool,
+    "TextGlow" = 2217 : Bool,
+    "TextItalic" = 2218 : Bool,
+    "CompositedOpaque" = 2219 : Bool,
+    "LocalizedMirrorImage" = 2220 : Bool,
+
+    // Rendering integers:
+    "ImageCount" = 2401 : Int,
+    "AlphaLevel" = 2402 : Int,
+    "BorderSize" = 2403 : Int,
+    "RoundCornerWidth" = 2404 : Int,
+    "RoundCornerHeight" = 2405 : Int,
+    "GradientRatio1" = 2406 : Int,
+    "GradientRatio2" = 2407 : Int,
+    "GradientRatio3" = 2408 : Int,
+    "GradientRatio4" = 2409 : Int,
+    "GradientRatio5" = 2410 : Int,
+    "ProgressChunkSize" = 2411 : Int,
+    "ProgressSpaceSize" = 2412 : Int,
+    "Saturation" = 2413 : Int,
+    "TextBorderSize" = 2414 : Int,
+    "AlphaThreshold" = 2415 : Int,
+    "Width" = 2416 : Size,
+    "Height" = 2417 : Size,
+    "GlyphIndex" = 2418 : Int,
+    "TrueSizeStretchMark" = 2419 : Int,
+    "MinDpi1" = 2420 : Int,
+    "MinDpi2" = 2421 : Int,
+    "MinDpi3" = 2422 : Int,
+    "MinDpi4" = 2423 : Int,
+    "MinDpi5" = 2424 : Int,
+    "TextGlowSize" = 2425 : Int,
+    "FramesPerSecond" = 2426 : Int,
+    "PixelsPerFrame" = 2427 : Int,
+    "AnimationDelay" = 2428 : Int,
+    "GlowIntensity" = 2429 : Int,
+    "Opacity" = 2430 : Int,
+    "ColorizationColor" = 2431 : Int,
+    "ColorizationOpacity" = 2432 : Int,
+    "MinDpi6" = 2433 : Int,
+    "MinDpi7" = 2434 : Int,
+
+    "GlyphFont" = 2601 : Font,
+
+    // Images:
+    "ImageFile" = 3001 : FileName,
+    "ImageFile1" = 3002 : FileName,
+    "ImageFile2" = 3003 : FileName,
+    "ImageFile3" = 3004 : FileName,
+    "ImageFile4" = 3005 : FileName,
+    "ImageFile5" = 3006 : FileName,
+    "GlyphImageFile" = 3008 : FileName,
+    "ImageFile6" = 3009 : FileName,
+    "ImageFile7" = 3010 : FileName,
+
+    "Text" = 3201 : String,
+    "ClassicValue" = 3202 : String,
+
+    // Positions:
+    "Offset" = 3401 : Position,
+    "TextShadowOffset" = 3402 : Position,
+    "MinSize" = 3403 : Position,
+    "MinSize1" = 3404 : Position,
+    "MinSize2" = 3405 : Position,
+    "MinSize3" = 3406 : Position,
+    "MinSize4" = 3407 : Position,
+    "MinSize5" = 3408 : Position,
+    "NormalSize" = 3409 : Position,
+    "MinSize6" = 3410 : Position,
+    "MinSize7" = 3411 : Position,
+
+    // Margins:
+    "SizingMargins" = 3601 : Margins,
+    "ContentMargins" = 3602 : Margins,
+    "CaptionMargins" = 3603 : Margins,
+
+    // Colors:
+    "BorderColor" = 3801 : Color,
+    "FillColor" = 3802 : Color,
+    "TextColor" = 3803 : Color,
+    "EdgeLightColor" = 3804 : Color,
+    "EdgeHighlightColor" = 3805 : Color,
+    "EdgeShadowColor" = 3806 : Color,
+    "EdgeDkShadowColor" = 3807 : Color,
+    "EdgeFillColor" = 3808 : Color,
+    "TransparentColor" = 3809 : Color,
+    "GradientColor1" = 3810 : Color,
+    "GradientColor2" = 3811 : Color,
+    "GradientColor3" = 3812 : Color,
+    "GradientColor4" = 3813 : Color,
+    "GradientColor5" = 3814 : Color,
+    "ShadowColor" = 3815 : Color,
+    "GlowColor" = 3816 : Color,
+    "TextBorderColor" = 3817 : Color,
+    "TextShadowColor" = 3818 : Color,
+    "GlyphTextColor" = 3819 : Color,
+    "GlyphTransparentColor" = 3820 : Color,
+    "FillColorHint" = 3821 : Color,
+    "BorderColorHint" = 3822 : Color,
+    "AccentColorHint" = 3823 : Color,
+    "Heading1TextColor" = 3824 : Color,
+    "Heading2TextColor" = 3825 : Color,
+    "BodyTextColor" = 3826 : Color,
+
+    // Enumerations:
+    "BgType" = 4001 : Enum,
+    "BorderType" = 4002 : Enum,
+    "FillType" = 4003 : Enum,
+    "SizingType" = 4004 : Enum,
+    "HAlign" = 4005 : Enum,
+    "ContentAlignment" = 4006 : Enum,
+    "VAlign" = 4007 : Enum,
+    "OffsetType" = 4008 : Enum,
+    "IconEffect" = 4009 : Enum,
+    "TextShadowType" = 4010 : Enum,
+    "ImageLayout" = 4011 : Enum,
+    "GlyphType" = 4012 : Enum,
+    "ImageSelectType" = 4013 : Enum,
+    "GlyphFontSizingType" = 4014 : Enum,
+    "TrueSizeScalingType" = 4015 : Enum,
+
+    // Vista and later:
+    "UserPicture" = 5001 : Bool,
+    "DefaultPaneSize" = 5002 : Rect,
+    "BlendColor" = 5003 : Color,
+    "CustomSplitRect" = 5004 : Rect,
+    "AnimationButtonRect" = 5005 : Rect,
+    "AnimationDuration" = 5006 : Int,
+    "TransitionDurations" = 6000 : IntList,
+    "ScaledBackground" = 7001 : Int,
+    "AtlasImage" = 8000 : DiskStream,
+    "AtlasInputImage" = 8001 : String,
+    "AtlasRect" = 8002 : Rect,
+);
+
+/**
+ * Look up a property by its name. Property names are case insensitive.
+ */
+pub fn find_property(name: &str) -> Option<&'static PropertyInfo> {
+    PROPERTIES.iter().find(|info| info.name.eq_ignore_ascii_case(name))
+}
+
+/**
+ * Look up a property by its TMT_* identifier.
+ */
+pub fn find_property_by_id(id: u32) -> Option<&'static PropertyInfo> {
+    PROPERTIES.iter().find(|info| info.id == id)
+}
+
+pub struct EnumInfo {
+    pub property: &'static str,
+    pub values: &'static [&'static str],
+}
+
+/**
+ * Enumeration values, indexed by their numeric value. All of the uxtheme enumerations are
+ * contiguous and start at zero.
+ */
+pub const ENUMS: &[EnumInfo] = &[
+    EnumInfo { property: "BgType", values: &["ImageFile", "BorderFill", "None"] },
+    EnumInfo { property: "BorderType", values: &["Rect", "RoundRect", "Ellipse"] },
+    EnumInfo { property: "FillType", values: &["Solid", "VertGradient", "HorzGradient", "RadialGradient", "TileImage"] },
+    EnumInfo { property: "SizingType", values: &["TrueSize", "Stretch", "Tile"] },
+    EnumInfo { property: "HAlign", values: &["Left", "Center", "Right"] },
+    EnumInfo { property: "ContentAlignment", values: &["Left", "Center", "Right"] },
+    EnumInfo { property: "VAlign", values: &["Top", "Center", "Bottom"] },
+    EnumInfo { property: "OffsetType", values: &[
+        "TopLeft", "TopRight", "TopMiddle", "BottomLeft", "BottomRight", "BottomMiddle", "MiddleLeft",
+        "MiddleRight", "LeftOfCaption", "RightOfCaption", "LeftOfLastButton", "RightOfLastButton",
+        "AboveLastButton", "BelowLastButton",
+    ] },
+    EnumInfo { property: "IconEffect", values: &["None", "Glow", "Shadow", "Pulse", "Alpha"] },
+    EnumInfo { property: "TextShadowType", values: &["None", "Single", "Continuous"] },
+    EnumInfo { property: "ImageLayout", values: &["Vertical", "Horizontal"] },
+    EnumInfo { property: "GlyphType", values: &["None", "ImageGlyph", "FontGlyph"] },
+    EnumInfo { property: "ImageSelectType", values: &["None", "Size", "Dpi"] },
+    EnumInfo { property: "GlyphFontSizingType", values: &["None", "Size", "Dpi"] },
+    EnumInfo { property: "TrueSizeScalingType", values: &["None", "Size", "Dpi"] },
+];
+
+pub fn find_enum(property: &str) -> Option<&'static EnumInfo> {
+    ENUMS.iter().find(|info| info.property.eq_ignore_ascii_case(property))
+}
+
+impl EnumInfo {
+    pub fn value_of(&self, name: &str) -> Option<i32> {
+        self.values.iter().position(|value| value.eq_ignore_ascii_case(name)).map(|i| i as i32)
+    }
+
+    pub fn name_of(&self, value: i32) -> Option<&'static str> {
+        usize::try_from(value).ok().and_then(|i| self.values.get(i).copied())
+    }
+}
+
+// Enumeration values used throughout the renderer and image tooling:
+pub const BT_IMAGEFILE: i32 = 0;
+pub const BT_BORDERFILL: i32 = 1;
+pub const BT_NONE: i32 = 2;
+
+pub const ST_TRUESIZE: i32 = 0;
+pub const ST_STRETCH: i32 = 1;
+pub const ST_TILE: i32 = 2;
+
+pub const IL_VERTICAL: i32 = 0;
+pub const IL_HORIZONTAL: i32 = 1;
+
+pub const IST_NONE: i32 = 0;
+pub const IST_SIZE: i32 = 1;
+pub const IST_DPI: i32 = 2;
+
+pub const HA_LEFT: i32 = 0;
+pub const HA_CENTER: i32 = 1;
+pub const HA_RIGHT: i32 = 2;
+
+pub const VA_TOP: i32 = 0;
+pub const VA_CENTER: i32 = 1;
+pub const VA_BOTTOM: i32 = 2;
+
+pub const GT_NONE: i32 = 0;
+pub const GT_IMAGEGLYPH: i32 = 1;
+pub const GT_FONTGLYPH: i32 = 2;
+
+pub const TSST_NONE: i32 = 0;
+pub const TSST_SIZE: i32 = 1;
+pub const TSST_DPI: i32 = 2;
+
+pub const BT_RECT: i32 = 0;
+pub const BT_ROUNDRECT: i32 = 1;
+pub const BT_ELLIPSE: i32 = 2;
+
+pub const FT_SOLID: i32 = 0;
+pub const FT_VERTGRADIENT: i32 = 1;
+pub const FT_HORZGRADIENT: i32 = 2;
+pub const FT_RADIALGRADIENT: i32 = 3;
+pub const FT_TILEIMAGE: i32 = 4;
+
+pub const TST_NONE: i32 = 0;
+pub const TST_SINGLE: i32 = 1;
+pub const TST_CONTINUOUS: i32 = 2;
@@ -0,0 +1,383 @@
+/*
+ * System fonts and metrics.
+ *
+ * The SysMetrics class of a visual style holds the fonts and non-client sizes that Windows uses
+ * while the style is selected: the caption, menu, message box and status bar fonts, and the
+ * caption, menu, scroll bar and border sizes. Sizes are in pixels at 96 DPI and Windows scales
+ * them, fonts are in points.
+ *
+ * The same values appear in other units elsewhere. The registry (HKCU\Control Panel\Desktop\
+ * WindowMetrics) stores sizes as negative twips, and LOGFONT heights are negative pixels at the
+ * current DPI. The conversion functions here cover all of them.
+ */
+
+use super::{Font, PropertyValue, Theme};
+
+pub const SYSMETRICS_CLASS: &str = "SysMetrics";
+
+pub const BASE_DPI: u32 = 96;
+pub const POINTS_PER_INCH: f64 = 72.0;
+pub const TWIPS_PER_INCH: f64 = 1440.0;
+
+pub fn px_to_pt(px: f64, dpi: u32) -> f64 {
+    px * POINTS_PER_INCH / dpi as f64
+}
+
+pub fn pt_to_px(pt: f64, dpi: u32) -> f64 {
+    pt * dpi as f64 / POINTS_PER_INCH
+}
+
+pub fn px_to_twips(px: f64, dpi: u32) -> f64 {
+    px * TWIPS_PER_INCH / dpi as f64
+}
+
+pub fn twips_to_px(twips: f64, dpi: u32) -> f64 {
+    twips * dpi as f64 / TWIPS_PER_INCH
+}
+
+pub fn pt_to_twips(pt: f64) -> f64 {
+    pt * TWIPS_PER_INCH / POINTS_PER_INCH
+}
+
+pub fn twips_to_pt(twips: f64) -> f64 {
+    twips * POINTS_PER_INCH / TWIPS_PER_INCH
+}
+
+/**
+ * Scale a 96 DPI pixel size to another DPI, rounding like MulDiv does.
+ */
+pub fn scale_px(px: i32, dpi: u32) -> i32 {
+    ((px as i64 * dpi as i64 * 2 + BASE_DPI as i64 * px.signum() as i64) / (BASE_DPI as i64 * 2)) as i32
+}
+
+/**
+ * The LOGFONT height of a font at a DPI: the negative character height in pixels.
+ */
+pub fn logfont_height(font: &Font, dpi: u32) -> i32 {
+    -(pt_to_px(font.size as f64, dpi).round() as i32)
+}
+
+/**
+ * A size as stored under WindowMetrics in the registry, in negative twips.
+ */
+pub fn px_to_registry_metric(px: i32) -> i32 {
+    -(px_to_twips(px as f64, BASE_DPI).round() as i32)
+}
+
+/**
+ * Read a size stored under WindowMetrics. Positive values are pixels, negative values are twips.
+ */
+pub fn registry_metric_to_px(value: i32) -> i32 {
+    if value >= 0 {
+        value
+    }
+    else {
+        twips_to_px(-value as f64, BASE_DPI).round() as i32
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FontMetric {
+    Caption,
+    SmallCaption,
+    Menu,
+    Status,
+    MsgBox,
+    IconTitle,
+    Heading1,
+    Heading2,
+    Body,
+}
+
+impl FontMetric {
+    pub const ALL: [FontMetric; 9] = [
+        Self::Caption, Self::SmallCaption, Self::Menu, Self::Status, Self::MsgBox,
+        Self::IconTitle, Self::Heading1, Self::Heading2, Self::Body,
+    ];
+
+    pub fn property_name(self) -> &'static str {
+        match self {
+            Self::Caption => "CaptionFont",
+            Self::SmallCaption => "SmallCaptionFont",
+            Self::Menu => "MenuFont",
+            Self::Status => "StatusFont",
+            Self::MsgBox => "MsgBoxFont",
+            Self::IconTitle => "IconTitleFont",
+            Self::Heading1 => "Heading1Font",
+            Self::Heading2 => "Heading2Font",
+            Self::Body => "BodyFont",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SizeMetric {
+    SizingBorderWidth,
+    ScrollbarWidth,
+    ScrollbarHeight,
+    CaptionBarWidth,
+    CaptionBarHeight,
+    SmCaptionBarWidth,
+    SmCaptionBarHeight,
+    MenuBarWidth,
+    MenuBarHeight,
+    PaddedBorderWidth,
+}
+
+impl SizeMetric {
+    pub const ALL: [SizeMetric; 10] = [
+        Self::SizingBorderWidth, Self::ScrollbarWidth, Self::ScrollbarHeight,
+        Self::CaptionBarWidth, Self::CaptionBarHeight, Self::SmCaptionBarWidth, Self::SmCaptionBarHeight,
+        Self::MenuBarWidth, Self::MenuBarHeight, Self::PaddedBorderWidth,
+    ];
+
+    pub fn property_name(self) -> &'static str {
+        match self {
+            Self::SizingBorderWidth => "SizingBorderWidth",
+            Self::ScrollbarWidth => "ScrollbarWidth",
+            Self::ScrollbarHeight => "ScrollbarHeight",
+            Self::CaptionBarWidth => "CaptionBarWidth",
+            Self::CaptionBarHeight => "CaptionBarHeight",
+            Self::SmCaptionBarWidth => "SmCaptionBarWidth",
+            Self::SmCaptionBarHeight => "SmCaptionBarHeight",
+            Self::MenuBarWidth => "MenuBarWidth",
+            Self::MenuBarHeight => "MenuBarHeight",
+            Self::PaddedBorderWidth => "PaddedBorderWidth",
+        }
+    }
+
+    /**
+     * The name of the matching value under HKCU\Control Panel\Desktop\WindowMetrics.
+     */
+    pub fn registry_name(self) -> &'static str {
+        match self {
+            Self::SizingBorderWidth => "BorderWidth",
+            Self::ScrollbarWidth => "ScrollWidth",
+            Self::ScrollbarHeight => "ScrollHeight",
+            Self::CaptionBarWidth => "CaptionWidth",
+            Self::CaptionBarHeight => "CaptionHeight",
+            Self::SmCaptionBarWidth => "SmCaptionWidth",
+            Self::SmCaptionBarHeight => "SmCaptionHeight",
+            Self::MenuBarWidth => "MenuWidth",
+            Self::MenuBarHeight => "MenuHeight",
+            Self::PaddedBorderWidth => "PaddedBorderWidth",
+        }
+    }
+
+    /**
+     * The Windows 7 default, in pixels at 96 DPI.
+     */
+    pub fn default_px(self) -> i32 {
+        match self {
+            Self::SizingBorderWidth => 1,
+            Self::ScrollbarWidth | Self::ScrollbarHeight => 17,
+            Self::CaptionBarWidth | Self::CaptionBarHeight => 22,
+            Self::SmCaptionBarWidth | Self::SmCaptionBarHeight => 22,
+            Self::MenuBarWidth | Self::MenuBarHeight => 19,
+            Self::PaddedBorderWidth => 4,
+        }
+    }
+
+    /**
+     * The range that the Window Color and Appearance dialog allows, in pixels at 96 DPI. Values
+     * outside of it are clamped or ignored by Windows.
+     */
+    pub fn range_px(self) -> (i32, i32) {
+        match self {
+            Self::SizingBorderWidth => (1, 50),
+            Self::ScrollbarWidth | Self::ScrollbarHeight => (8, 100),
+            Self::CaptionBarWidth | Self::CaptionBarHeight => (14, 100),
+            Self::SmCaptionBarWidth | Self::SmCaptionBarHeight => (14, 100),
+            Self::MenuBarWidth | Self::MenuBarHeight => (14, 100),
+            Self::PaddedBorderWidth => (0, 50),
+        }
+    }
+}
+
+pub const MIN_FONT_SIZE: i32 = 6;
+pub const MAX_FONT_SIZE: i32 = 24;
+pub const MAX_FACE_NAME_LENGTH: usize = 31;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /**
+     * Windows accepts the value, but the shell is likely to look wrong.
+     */
+    Warning,
+    /**
+     * Windows clamps, ignores or misreads the value.
+     */
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetricIssue {
+    pub property: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/**
+ * The fonts and sizes of the SysMetrics class. Anything that is None is left to Windows.
+ */
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SysMetrics {
+    pub fonts: [Option<Font>; FontMetric::ALL.len()],
+    pub sizes: [Option<i32>; SizeMetric::ALL.len()],
+}
+
+impl SysMetrics {
+    /**
+     * The Windows 7 defaults: Segoe UI 9 for the shell fonts, and the default sizes.
+     */
+    pub fn windows_defaults() -> Self {
+        let mut metrics = Self::default();
+        for metric in [FontMetric::Caption, FontMetric::SmallCaption, FontMetric::Menu, FontMetric::Status, FontMetric::MsgBox, FontMetric::IconTitle] {
+            metrics.set_font(metric, Some(Font { face: "Segoe UI".to_owned(), size: 9, weight: super::FW_NORMAL, ..Default::default() }));
+        }
+        for metric in SizeMetric::ALL {
+            metrics.set_size(metric, Some(metric.default_px()));
+        }
+        metrics
+    }
+
+    pub fn font(&self, metric: FontMetric) -> Option<&Font> {
+        self.fonts[metric as usize].as_ref()
+    }
+
+    pub fn set_font(&mut self, metric: FontMetric, font: Option<Font>) {
+        self.fonts[metric as usize] = font;
+    }
+
+    pub fn size(&self, metric: SizeMetric) -> Option<i32> {
+        self.sizes[metric as usize]
+    }
+
+    pub fn set_size(&mut self, metric: SizeMetric, px: Option<i32>) {
+        self.sizes[metric as usize] = px;
+    }
+
+    /**
+     * Scale every size and font together by a percentage, i.e. 125 to make everything a quarter
+     * larger. Fonts are rounded to whole points and sizes to whole pixels.
+     */
+    pub fn scale(&mut self, percent: u32) {
+        let scale = |value: i32| ((value as i64 * percent as i64 + 50) / 100) as i32;
+        for size in self.sizes.iter_mut().flatten() {
+            *size = scale(*size);
+        }
+        for font in self.fonts.iter_mut().flatten() {
+            font.size = scale(font.size);
+        }
+    }
+
+    pub fn from_theme(theme: &Theme) -> Self {
+        let mut metrics = Self::default();
+        let Some(state) = theme.state(SYSMETRICS_CLASS, None, None) else {
+            return metrics;
+        };
+
+        for metric in FontMetric::ALL {
+            if let Some(PropertyValue::Font(font)) = state.property(metric.property_name()) {
+                metrics.set_font(metric, Some(font.clone()));
+            }
+        }
+        for metric in SizeMetric::ALL {
+            if let Some(PropertyValue::Size(px) | PropertyValue::Int(px)) = state.property(metric.property_name()) {
+                metrics.set_size(metric, Some(*px));
+            }
+        }
+        metrics
+    }
+
+    /**
+     * Write the metrics into a theme's SysMetrics class. Metrics that are None are removed.
+     */
+    pub fn apply_to_theme(&self, theme: &mut Theme) {
+        let state = theme.state_mut(SYSMETRICS_CLASS, None, None);
+        for metric in FontMetric::ALL {
+            match self.font(metric) {
+                Some(font) => state.set_property(metric.property_name(), PropertyValue::Font(font.clone())),
+                None => { state.remove_property(metric.property_name()); }
+            }
+        }
+        for metric in SizeMetric::ALL {
+            match self.size(metric) {
+                Some(px) => state.set_property(metric.property_name(), PropertyValue::Size(px)),
+                None => { state.remove_property(metric.property_name()); }
+            }
+        }
+    }
+
+    /**
+     * Check that the metrics are in the ranges that Windows accepts, and that the text fits into
+     * the bars that it is drawn in at the given DPI. Rounding at fractional scale factors (such
+     * as 120 or 144 DPI) can make text that fits at 96 DPI clip, so it is worth checking every
+     * DPI that the style supports.
+     */
+    pub fn validate(&self, dpi: u32) -> Vec<MetricIssue> {
+        let mut issues = Vec::new();
+        let mut issue = |property: &'static str, severity: Severity, message: String| {
+            issues.push(MetricIssue { property, severity, message });
+        };
+
+        for metric in SizeMetric::ALL {
+            let Some(px) = self.size(metric) else {
+                continue;
+            };
+            let (min, max) = metric.range_px();
+            if px < min || px > max {
+                issue(metric.property_name(), Severity::Error, format!("{} px is outside of the allowed range of {} to {} px.", px, min, max));
+            }
+        }
+
+        for metric in FontMetric::ALL {
+            let Some(font) = self.font(metric) else {
+                continue;
+            };
+            let property = metric.property_name();
+            if font.face.trim().is_empty() {
+                issue(property, Severity::Error, "The font has no face name.".to_owned());
+            }
+            else if font.face.encode_utf16().count() > MAX_FACE_NAME_LENGTH {
+                issue(property, Severity::Error, format!("The face name is longer than {} characters, and will be cut off.", MAX_FACE_NAME_LENGTH));
+            }
+            if font.size < MIN_FONT_SIZE || font.size > MAX_FONT_SIZE {
+                issue(property, Severity::Warning, format!("{} pt is outside of the usual range of {} to {} pt.", font.size, MIN_FONT_SIZE, MAX_FONT_SIZE));
+            }
+            if font.weight < 0 || font.weight > 1000 {
+                issue(property, Severity::Error, format!("Font weight {} is outside of the range 0 to 1000.", font.weight));
+            }
+        }
+
+        // Text needs roughly a third of its height again for the internal leading and the
+        // padding around it.
+        let text_fits = [
+            (FontMetric::Caption, SizeMetric::CaptionBarHeight),
+            (FontMetric::SmallCaption, SizeMetric::SmCaptionBarHeight),
+            (FontMetric::Menu, SizeMetric::MenuBarHeight),
+        ];
+        for (font_metric, size_metric) in text_fits {
+            let (Some(font), Some(px)) = (self.font(font_metric), self.size(size_metric)) else {
+                continue;
+            };
+            let text_height = -logfont_height(font, dpi);
+            let needed = (text_height * 4 + 2) / 3;
+            let available = scale_px(px, dpi);
+            if needed > available {
+                issue(size_metric.property_name(), Severity::Warning, format!(
+                    "At {} DPI, {} ({} pt, {} px) needs about {} px but the bar is {} px, so text will be clipped.",
+                    dpi, font_metric.property_name(), font.size, text_height, needed, available,
+                ));
+            }
+        }
+
+        if let (Some(width), Some(height)) = (self.size(SizeMetric::CaptionBarWidth), self.size(SizeMetric::CaptionBarHeight)) {
+            if width > height * 3 {
+                issue(SizeMetric::CaptionBarWidth.property_name(), Severity::Warning, format!("Caption buttons {} px wide on a {} px caption will crowd out the title.", width, height));
+            }
+        }
+
+        issues
+    }
+}
@@ -0,0 +1,305 @@
+/*
+ * System color schemes.
+ *
+ * Windows has 31 system colors (COLOR_SCROLLBAR through COLOR_MENUBAR). Classic themes set all of
+ * them, and visual styles override them through the SysMetrics class. The same colors have
+ * different names in different places: the .theme file and the registry use the names under
+ * HKCU\Control Panel\Colors (i.e. "ButtonFace", "Hilight"), and visual styles use the property
+ * names from vssym32.h (i.e. "BtnFace", "Highlight"). This module knows both, and lets a scheme
+ * be edited as a set.
+ */
+
+use super::{Color, PropertyValue, Theme};
+use super::sysmetrics::SYSMETRICS_CLASS;
+
+pub struct SystemColorInfo {
+    /**
+     * The COLOR_* index, as passed to GetSysColor.
+     */
+    pub index: usize,
+    /**
+     * The name under HKCU\Control Panel\Colors and in .theme files.
+     */
+    pub registry_name: &'static str,
+    /**
+     * The SysMetrics property name in visual styles.
+     */
+    pub property_name: &'static str,
+    /**
+     * The color in the "Windows Standard" classic scheme.
+     */
+    pub default: Color,
+}
+
+macro_rules! system_colors {
+    ($($index:literal $registry:literal $property:literal ($r:literal $g:literal $b:literal),)*) => {
+        pub const SYSTEM_COLORS: &[SystemColorInfo] = &[
+            $(SystemColorInfo { index: $index, registry_name: $registry, property_name: $property, default: Color::new($r, $g, $b) },)*
+        ];
+    };
+}
+
+system_colors! {
+    0 "Scrollbar" "Scrollbar" (212 208 200),
+    1 "Background" "Background" (58 110 165),
+    2 "ActiveTitle" "ActiveCaption" (10 36 106),
+    3 "InactiveTitle" "InactiveCaption" (128 128 128),
+    4 "Menu" "Menu" (212 208 200),
+    5 "Window" "Window" (255 255 255),
+    6 "WindowFrame" "WindowFrame" (0 0 0),
+    7 "MenuText" "MenuText" (0 0 0),
+    8 "WindowText" "WindowText" (0 0 0),
+    9 "TitleText" "CaptionText" (255 255 255),
+    10 "ActiveBorder" "ActiveBorder" (212 208 200),
+    11 "InactiveBorder" "InactiveBorder" (212 208 200),
+    12 "AppWorkspace" "AppWorkspace" (128 128 128),
+    13 "Hilight" "Highlight" (10 36 106),
+    14 "HilightText" "HighlightText" (255 255 255),
+    15 "ButtonFace" "BtnFace" (212 208 200),
+    16 "ButtonShadow" "BtnShadow" (128 128 128),
+    17 "GrayText" "GrayText" (128 128 128),
+    18 "ButtonText" "BtnText" (0 0 0),
+    19 "InactiveTitleText" "InactiveCaptionText" (212 208 200),
+    20 "ButtonHilight" "BtnHighlight" (255 255 255),
+    21 "ButtonDkShadow" "DkShadow3d" (64 64 64),
+    22 "ButtonLight" "Light3d" (212 208 200),
+    23 "InfoText" "InfoText" (0 0 0),
+    24 "InfoWindow" "InfoBk" (255 255 225),
+    25 "ButtonAlternateFace" "ButtonAlternateFace" (181 181 181),
+    26 "HotTrackingColor" "HotTracking" (0 0 128),
+    27 "GradientActiveTitle" "GradientActiveCaption" (166 202 240),
+    28 "GradientInactiveTitle" "GradientInactiveCaption" (192 192 192),
+    29 "MenuHilight" "MenuHilight" (10 36 106),
+    30 "MenuBar" "MenuBar" (212 208 200),
+}
+
+pub const COLOR_COUNT: usize = 31;
+
+/**
+ * Find a system color by either its registry name or its property name.
+ */
+pub fn find_system_color(name: &str) -> Option<&'static SystemColorInfo> {
+    SYSTEM_COLORS.iter().find(|info| info.registry_name.eq_ignore_ascii_case(name) || info.property_name.eq_ignore_ascii_case(name))
+}
+
+fn to_hsl(color: Color) -> (f64, f64, f64) {
+    let (r, g, b) = (color.r as f64 / 255.0, color.g as f64 / 255.0, color.b as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    if max == min {
+        return (0.0, 0.0, lightness);
+    }
+
+    let delta = max - min;
+    let saturation = if lightness > 0.5 { delta / (2.0 - max - min) } else { delta / (max + min) };
+    let hue = if max == r {
+        (g - b) / delta + if g < b { 6.0 } else { 0.0 }
+    }
+    else if max == g {
+        (b - r) / delta + 2.0
+    }
+    else {
+        (r - g) / delta + 4.0
+    };
+    (hue * 60.0, saturation, lightness)
+}
+
+fn from_hsl(hue: f64, saturation: f64, lightness: f64) -> Color {
+    if saturation == 0.0 {
+        let value = (lightness * 255.0).round() as u8;
+        return Color::new(value, value, value);
+    }
+
+    let q = if lightness < 0.5 { lightness * (1.0 + saturation) } else { lightness + saturation - lightness * saturation };
+    let p = 2.0 * lightness - q;
+    let channel = |t: f64| {
+        let t = t.rem_euclid(1.0);
+        let value = if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        }
+        else if t < 0.5 {
+            q
+        }
+        else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        }
+        else {
+            p
+        };
+        (value * 255.0).round().clamp(0.0, 255.0) as u8
+    };
+
+    let hue = hue / 360.0;
+    Color::new(channel(hue + 1.0 / 3.0), channel(hue), channel(hue - 1.0 / 3.0))
+}
+
+/**
+ * Change the lightness of a color, keeping its hue and saturation, like ColorAdjustLuma with
+ * scaling. The amount is in thousandths: positive amounts move that fraction of the way to white,
+ * negative amounts move that fraction of the way to black.
+ */
+pub fn adjust_luma(color: Color, amount: i32) -> Color {
+    let (hue, saturation, lightness) = to_hsl(color);
+    let amount = amount.clamp(-1000, 1000) as f64 / 1000.0;
+    let lightness = if amount >= 0.0 {
+        lightness + (1.0 - lightness) * amount
+    }
+    else {
+        lightness + lightness * amount
+    };
+    from_hsl(hue, saturation, lightness)
+}
+
+/**
+ * Mix two colors. A weight of 0 gives the first color, 1 gives the second.
+ */
+pub fn blend(a: Color, b: Color, weight: f64) -> Color {
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * weight.clamp(0.0, 1.0)).round() as u8;
+    Color::new(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b))
+}
+
+/**
+ * The 3D shades derived from a face color: white for the highlight, halfway to white for the light
+ * edge, and a third and two thirds of the way to black for the shadows. This is an approximation
+ * for custom faces; the shipped classic schemes were picked by hand, and "Windows Standard", for
+ * one, keeps ButtonLight equal to the face and uses neutral grays for the shadows.
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shades3d {
+    pub highlight: Color,
+    pub light: Color,
+    pub shadow: Color,
+    pub dark_shadow: Color,
+}
+
+pub fn derive_3d_shades(face: Color) -> Shades3d {
+    Shades3d {
+        highlight: adjust_luma(face, 1000),
+        light: adjust_luma(face, 500),
+        shadow: adjust_luma(face, -333),
+        dark_shadow: adjust_luma(face, -666),
+    }
+}
+
+/**
+ * A full or partial set of system colors.
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ColorScheme {
+    pub colors: [Option<Color>; COLOR_COUNT],
+}
+
+impl ColorScheme {
+    /**
+     * The "Windows Standard" classic scheme.
+     */
+    pub fn windows_standard() -> Self {
+        let mut scheme = Self::default();
+        for info in SYSTEM_COLORS {
+            scheme.colors[info.index] = Some(info.default);
+        }
+        scheme
+    }
+
+    pub fn get(&self, name: &str) -> Option<Color> {
+        self.colors[find_system_color(name)?.index]
+    }
+
+    /**
+     * Set a color by either name. Returns false if the name is not a system color.
+     */
+    pub fn set(&mut self, name: &str, color: Color) -> bool {
+        match find_system_color(name) {
+            Some(info) => {
+                self.colors[info.index] = Some(color);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Color> {
+        self.colors[find_system_color(name)?.index].take()
+    }
+
+    /**
+     * Iterate over the colors that are set, in COLOR_* order.
+     */
+    pub fn iter(&self) -> impl Iterator<Item = (&'static SystemColorInfo, Color)> + '_ {
+        SYSTEM_COLORS.iter().filter_map(|info| Some((info, self.colors[info.index]?)))
+    }
+
+    /**
+     * Fill in every color that is not set from another scheme.
+     */
+    pub fn fill_from(&mut self, other: &ColorScheme) {
+        for (color, fallback) in self.colors.iter_mut().zip(other.colors) {
+            if color.is_none() {
+                *color = fallback;
+            }
+        }
+    }
+
+    /**
+     * Set the button face and every color derived from it: the 3D shades, and the colors that
+     * classic schemes keep equal to the face (scroll bar, menu, menu bar and borders). GrayText
+     * is left alone, as it is a text color that schemes pick on its own.
+     */
+    pub fn set_face(&mut self, face: Color) {
+        let shades = derive_3d_shades(face);
+        for name in ["ButtonFace", "Menu", "MenuBar", "ActiveBorder", "InactiveBorder", "Scrollbar"] {
+            self.set(name, face);
+        }
+        self.set("ButtonHilight", shades.highlight);
+        self.set("ButtonLight", shades.light);
+        self.set("ButtonShadow", shades.shadow);
+        self.set("ButtonDkShadow", shades.dark_shadow);
+    }
+
+    /**
+     * Set the active caption color, deriving the other end of the caption gradient from it.
+     */
+    pub fn set_active_caption(&mut self, color: Color) {
+        self.set("ActiveTitle", color);
+        self.set("GradientActiveTitle", adjust_luma(color, 600));
+    }
+
+    pub fn set_inactive_caption(&mut self, color: Color) {
+        self.set("InactiveTitle", color);
+        self.set("GradientInactiveTitle", adjust_luma(color, 600));
+    }
+
+    /**
+     * Set the selection color, which is also used for menu highlights.
+     */
+    pub fn set_highlight(&mut self, color: Color) {
+        self.set("Hilight", color);
+        self.set("MenuHilight", color);
+    }
+
+    /**
+     * Read the colors that a visual style overrides, from its SysMetrics class.
+     */
+    pub fn from_theme(theme: &Theme) -> Self {
+        let mut scheme = Self::default();
+        if let Some(state) = theme.state(SYSMETRICS_CLASS, None, None) {
+            for info in SYSTEM_COLORS {
+                if let Some(PropertyValue::Color(color)) = state.property(info.property_name) {
+                    scheme.colors[info.index] = Some(*color);
+                }
+            }
+        }
+        scheme
+    }
+
+    /**
+     * Write the colors that are set into a visual style's SysMetrics class.
+     */
+    pub fn apply_to_theme(&self, theme: &mut Theme) {
+        let state = theme.state_mut(SYSMETRICS_CLASS, None, None);
+        for (info, color) in self.iter() {
+            state.set_property(info.property_name, PropertyValue::Color(color));
+        }
+    }
+}
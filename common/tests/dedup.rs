@@ -0,0 +1,173 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use kanaya_common::formats::{self, restyle_ini};
+use kanaya_common::image::dedup::{analyze, collect_garbage, deduplicate, DuplicateGroup};
+use kanaya_common::image::{encode, BmpAlpha, ImageEncoding, PngColorType, RgbaImage};
+use kanaya_common::theme::{PropertyValue, Theme};
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+/**
+ * An empty directory for a test to write into.
+ */
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("dedup").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+const PNG: ImageEncoding = ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false };
+
+fn bmp(alpha: BmpAlpha) -> ImageEncoding {
+    ImageEncoding::Bmp { bits: 32, alpha, top_down: false, file_header: true }
+}
+
+fn theme_with_images(images: &[(&str, Vec<u8>)]) -> Theme {
+    let mut source = String::new();
+    for (index, (path, _)) in images.iter().enumerate() {
+        if !path.starts_with("unused") {
+            source.push_str(&format!("[Button.{}]\nImageFile = {}\n\n", index + 1, path));
+        }
+    }
+    let mut theme = restyle_ini::parse(&source).unwrap();
+    for (path, data) in images {
+        theme.set_image(path, data.clone());
+    }
+    theme
+}
+
+fn image_file(state: &str, theme: &Theme) -> String {
+    match theme.state("Button", Some(state), None).unwrap().property("ImageFile") {
+        Some(PropertyValue::FileName(path)) => path.clone(),
+        other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn same_pixels_in_different_formats_are_duplicates() {
+    let image = filled(4, 4, [200, 100, 50, 255]);
+    let png = encode(&image, &PNG).unwrap();
+    let theme = theme_with_images(&[
+        ("a.bmp", encode(&image, &bmp(BmpAlpha::Straight)).unwrap()),
+        ("b.png", png.clone()),
+        ("c.png", png.clone()),
+        ("d.png", encode(&filled(4, 4, [0, 0, 0, 255]), &PNG).unwrap()),
+        ("unused.png", png.clone()),
+    ]);
+
+    let analysis = analyze(&theme);
+    let bmp_size = theme.image("a.bmp").unwrap().len();
+    assert_eq!(analysis.duplicates, [DuplicateGroup {
+        keep: "b.png".to_owned(),
+        duplicates: vec!["c.png".to_owned(), "a.bmp".to_owned()],
+        identical_bytes: false,
+        saved: png.len() + bmp_size,
+    }]);
+    assert_eq!(analysis.unreferenced, [("unused.png".to_owned(), png.len())]);
+    assert_eq!(analysis.saved(), 2 * png.len() + bmp_size);
+}
+
+#[test]
+fn bitmaps_match_by_the_pixels_that_are_drawn() {
+    // uxtheme draws the stored pixels as straight alpha, so a bitmap that an editor saved
+    // premultiplied matches a PNG of its stored pixels, not of the pixels it was made from.
+    let image = filled(4, 4, [60, 60, 60, 128]);
+    let theme = theme_with_images(&[
+        ("a.png", encode(&image, &PNG).unwrap()),
+        ("b.bmp", encode(&image, &bmp(BmpAlpha::Premultiplied)).unwrap()),
+        ("c.png", encode(&filled(4, 4, [30, 30, 30, 128]), &PNG).unwrap()),
+    ]);
+    let analysis = analyze(&theme);
+    assert_eq!(analysis.duplicates.len(), 1);
+    assert_eq!(analysis.duplicates[0].duplicates, ["b.bmp"]);
+    assert_eq!(analysis.duplicates[0].keep, "c.png");
+}
+
+#[test]
+fn undecodable_images_match_by_bytes() {
+    let theme = theme_with_images(&[("a.gif", b"GIF89a".to_vec()), ("b.gif", b"GIF89a".to_vec()), ("c.gif", b"GIF87a".to_vec())]);
+    let analysis = analyze(&theme);
+    assert_eq!(analysis.duplicates.len(), 1);
+    assert_eq!(analysis.duplicates[0].duplicates, ["b.gif"]);
+}
+
+#[test]
+fn deduplicate_repoints_properties_and_removes_copies() {
+    let png = encode(&filled(2, 2, [1, 2, 3, 255]), &PNG).unwrap();
+    let mut theme = theme_with_images(&[("a.png", png.clone()), ("B.PNG", png.clone()), ("unused.png", png)]);
+    theme.state_mut("Button", Some("3"), None).set_property("ImageFile", PropertyValue::FileName("b.png".to_owned()));
+
+    let analysis = analyze(&theme);
+    deduplicate(&mut theme, &analysis);
+    // Among images of the same size, the first path is kept.
+    assert_eq!(theme.images.keys().collect::<Vec<_>>(), ["B.PNG"]);
+    assert_eq!(image_file("1", &theme), "B.PNG");
+    assert_eq!(image_file("2", &theme), "B.PNG");
+    assert_eq!(image_file("3", &theme), "b.png");
+}
+
+#[test]
+fn collect_garbage_keeps_referenced_images_in_any_case() {
+    let mut theme = theme_with_images(&[("Images\\A.png", vec![1]), ("unused.png", vec![1, 2])]);
+    theme.state_mut("Button", Some("1"), None).set_property("ImageFile", PropertyValue::FileName("images/a.png".to_owned()));
+    assert_eq!(collect_garbage(&mut theme), [("unused.png".to_owned(), 2)]);
+    assert!(theme.image("Images\\A.png").is_some());
+    assert!(collect_garbage(&mut theme).is_empty());
+}
+
+#[test]
+fn saving_a_project_deletes_the_merged_files() {
+    let dir = scratch_dir("project");
+    fs::create_dir_all(dir.join("Images")).unwrap();
+    let png = encode(&filled(4, 4, [200, 100, 50, 255]), &PNG).unwrap();
+    fs::write(dir.join("Images/a.png"), &png).unwrap();
+    fs::write(dir.join("Images/b.png"), &png).unwrap();
+    fs::write(dir.join("theme.ini"), "; Buttons\r\n[Button.PushButton]\r\nImageFile = Images\\a.png\r\n\r\n[Button.RadioButton]\r\nImageFile = Images\\b.png\r\n").unwrap();
+
+    let path = dir.join("theme.ini");
+    let mut theme = formats::read_theme(&path).unwrap();
+    let analysis = analyze(&theme);
+    assert_eq!(analysis.saved(), png.len());
+    deduplicate(&mut theme, &analysis);
+    formats::write_theme(&theme, &path, &path).unwrap();
+
+    assert!(dir.join("Images/a.png").exists());
+    assert!(!dir.join("Images/b.png").exists());
+    let source = fs::read_to_string(&path).unwrap();
+    assert!(source.starts_with("; Buttons\r\n"));
+    assert!(source.contains("[Button.RadioButton]\r\nImageFile = Images\\a.png\r\n"), "{}", source);
+
+    let reread = formats::read_theme(&path).unwrap();
+    assert_eq!(image_file("RadioButton", &reread), "Images\\a.png");
+    assert!(analyze(&reread).duplicates.is_empty());
+}
+
+#[test]
+fn saving_into_another_project_deletes_none_of_its_files() {
+    let dir = scratch_dir("other_project");
+    fs::create_dir_all(dir.join("source/Images")).unwrap();
+    fs::create_dir_all(dir.join("other/Images")).unwrap();
+    let png = encode(&filled(4, 4, [200, 100, 50, 255]), &PNG).unwrap();
+    fs::write(dir.join("source/Images/a.png"), &png).unwrap();
+    fs::write(dir.join("source/theme.ini"), "[Button.PushButton]\r\nImageFile = Images\\a.png\r\n").unwrap();
+    fs::write(dir.join("other/Images/b.png"), &png).unwrap();
+    fs::write(dir.join("other/theme.ini"), "[Button.RadioButton]\r\nImageFile = Images\\b.png\r\n").unwrap();
+
+    let source = dir.join("source/theme.ini");
+    let output = dir.join("other/theme.ini");
+    let theme = formats::read_theme(&source).unwrap();
+    formats::write_theme(&theme, &source, &output).unwrap();
+
+    assert!(dir.join("other/Images/a.png").exists());
+    assert!(dir.join("other/Images/b.png").exists());
+    assert_eq!(image_file("PushButton", &formats::read_theme(&output).unwrap()), "Images\\a.png");
+}
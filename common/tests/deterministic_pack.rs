@@ -0,0 +1,140 @@
+use std::path::Path;
+use kanaya_common::formats::msstyles::{self, PackOptions};
+use kanaya_common::formats::pe::{self, ResourceName, ResourceTable};
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::theme::{PropertyValue, Theme};
+use sha2::{Digest, Sha256};
+
+fn load_fixture() -> Theme {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/basic/theme.ini");
+    restyle_ini::read_project(&path).unwrap()
+}
+
+/**
+ * A property record of a VARIANT resource.
+ */
+fn record(property_id: i32, type_id: i32, class_id: i32, part_id: i32, state_id: i32, inline: i32, data: &[u8]) -> Vec<u8> {
+    let mut record = Vec::new();
+    for field in [property_id, type_id, class_id, part_id, state_id, inline, 0, data.len() as i32] {
+        record.extend_from_slice(&field.to_le_bytes());
+    }
+    record.extend_from_slice(data);
+    record.resize(record.len().div_ceil(8) * 8, 0);
+    record
+}
+
+/**
+ * Null-terminated UTF-16 strings, as in a CMAP resource.
+ */
+fn utf16z(names: &[&str]) -> Vec<u8> {
+    names.iter()
+        .flat_map(|name| name.encode_utf16().chain(std::iter::once(0)))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+fn hash(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn same_project_packs_to_same_bytes() {
+    let first = msstyles::pack(&load_fixture(), &PackOptions::default()).unwrap();
+    let second = msstyles::pack(&load_fixture(), &PackOptions::default()).unwrap();
+    assert_eq!(hash(&first), hash(&second));
+}
+
+#[test]
+fn source_order_does_not_change_output() {
+    let theme = load_fixture();
+
+    let mut shuffled = theme.clone();
+    shuffled.classes.reverse();
+    for class in &mut shuffled.classes {
+        class.parts.reverse();
+        for part in &mut class.parts {
+            part.states.reverse();
+            for state in &mut part.states {
+                state.properties.reverse();
+            }
+        }
+    }
+
+    let options = PackOptions::default();
+    assert_eq!(
+        hash(&msstyles::pack(&theme, &options).unwrap()),
+        hash(&msstyles::pack(&shuffled, &options).unwrap()),
+    );
+}
+
+#[test]
+fn timestamp_comes_from_options() {
+    let theme = load_fixture();
+    let packed = msstyles::pack(&theme, &PackOptions { timestamp: 0x12345678 }).unwrap();
+
+    // The COFF header timestamp follows the PE signature and the machine and section count.
+    let pe_offset = u32::from_le_bytes(packed[0x3C..0x40].try_into().unwrap()) as usize;
+    assert_eq!(&packed[pe_offset + 8..pe_offset + 12], &0x12345678_u32.to_le_bytes());
+
+    let default = msstyles::pack(&theme, &PackOptions::default()).unwrap();
+    assert_eq!(&default[pe_offset + 8..pe_offset + 12], &[0, 0, 0, 0]);
+}
+
+#[test]
+fn unpacked_theme_repacks_identically() {
+    let packed = msstyles::pack(&load_fixture(), &PackOptions::default()).unwrap();
+    let unpacked = msstyles::unpack(&packed).unwrap();
+    let repacked = msstyles::pack(&unpacked, &PackOptions::default()).unwrap();
+    assert_eq!(hash(&packed), hash(&repacked));
+}
+
+#[test]
+fn shell_classes_and_numbered_parts_pack() {
+    let theme = restyle_ini::parse("\
+[Taskbar.BackgroundBottom]
+ContentMargins = 1, 1, 1, 1
+
+[StartPanel.MoreProgramsTab(Hot)]
+TextColor = 255 255 255
+
+[Explorer::TaskDialog.ExpandoButton(ExpandedHover)]
+SizingType = TrueSize
+
+[VendorClass.3(2)]
+BorderSize = 2
+").unwrap();
+    let unpacked = msstyles::unpack(&msstyles::pack(&theme, &PackOptions::default()).unwrap()).unwrap();
+    assert!(unpacked.state("Taskbar", Some("BackgroundBottom"), None).is_some());
+    assert!(unpacked.state("StartPanel", Some("MoreProgramsTab"), Some("Hot")).is_some());
+    assert!(unpacked.state("Explorer::TaskDialog", Some("ExpandoButton"), Some("ExpandedHover")).is_some());
+    assert!(unpacked.state("VendorClass", Some("3"), Some("2")).is_some());
+}
+
+#[test]
+fn unknown_part_names_suggest_numbers() {
+    let theme = restyle_ini::parse("[VendorClass.Gadget]\nBorderSize = 2\n").unwrap();
+    let error = msstyles::pack(&theme, &PackOptions::default()).unwrap_err();
+    assert!(error.to_string().contains("[VendorClass.3]"));
+}
+
+/**
+ * A compiled style with one Globals record of type IntList, whose data is given.
+ */
+fn int_list_msstyles(data: &[u8]) -> Vec<u8> {
+    let mut table = ResourceTable::default();
+    table.insert(ResourceName::name("CMAP"), ResourceName::name("CMAP"), 0, utf16z(&["Globals"]));
+    table.insert(ResourceName::name("VARIANT"), ResourceName::name("NORMAL"), 0, record(6000, 211, 0, 0, 0, 0, data));
+    pe::write_image(&table, 0)
+}
+
+#[test]
+fn malformed_list_lengths_are_rejected() {
+    let list = |values: &[i32]| values.iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<u8>>();
+
+    let theme = msstyles::unpack(&int_list_msstyles(&list(&[2, 10, 20]))).unwrap();
+    assert_eq!(theme.state("Globals", None, None).unwrap().property("TransitionDurations"), Some(&PropertyValue::IntList(vec![10, 20])));
+
+    for data in [list(&[-1]), list(&[i32::MIN, 1]), list(&[i32::MAX]), list(&[3, 10, 20]), Vec::new()] {
+        assert!(msstyles::unpack(&int_list_msstyles(&data)).is_err(), "{:?}", data);
+    }
+}
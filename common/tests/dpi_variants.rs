@@ -0,0 +1,153 @@
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::dpi::{generate_variants, VariantOptions};
+use kanaya_common::image::resample::resample_nine_grid;
+use kanaya_common::image::{decode, encode, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use kanaya_common::theme::schema::{IST_DPI, IST_SIZE};
+use kanaya_common::theme::{Margins, Point, PropertyValue, Rect, Theme};
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+/**
+ * A square with a red border of the given width around a blue center.
+ */
+fn framed(size: u32, border: u32) -> RgbaImage {
+    let mut image = filled(size, size, BLUE);
+    for y in 0..size {
+        for x in 0..size {
+            if x < border || y < border || x >= size - border || y >= size - border {
+                image.set_pixel(x, y, RED);
+            }
+        }
+    }
+    image
+}
+
+fn margins(value: i32) -> Margins {
+    Margins { left: value, right: value, top: value, bottom: value }
+}
+
+fn png(image: &RgbaImage) -> Vec<u8> {
+    encode(image, &ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false }).unwrap()
+}
+
+fn button_theme(select_type: &str) -> Theme {
+    let mut theme = restyle_ini::parse(&format!("\
+[Button.PushButton]
+ImageFile = Images\\Button.png
+ImageCount = 2
+ImageLayout = Vertical
+SizingMargins = 2, 2, 2, 2
+{}
+", select_type)).unwrap();
+    let strip = [framed(10, 2), framed(10, 2)].iter().flat_map(|frame| frame.pixels.clone()).collect();
+    theme.set_image("Images\\Button.png", png(&RgbaImage::from_pixels(10, 20, strip).unwrap()));
+    theme
+}
+
+fn property<'a>(theme: &'a Theme, name: &str) -> Option<&'a PropertyValue> {
+    theme.state("Button", Some("PushButton"), None).unwrap().property(name)
+}
+
+#[test]
+fn nine_grid_puts_the_margins_at_the_target_margins() {
+    let scaled = resample_nine_grid(&framed(10, 2), margins(2), 20, 20, margins(4));
+    assert_eq!(scaled, framed(20, 4));
+
+    // Corners keep their size when only the center grows.
+    let stretched = resample_nine_grid(&framed(10, 2), margins(2), 30, 12, margins(2));
+    for (x, y) in [(0, 0), (1, 1), (29, 11), (28, 10), (15, 0), (0, 6)] {
+        assert_eq!(stretched.pixel(x, y), RED, "({}, {})", x, y);
+    }
+    for (x, y) in [(2, 2), (27, 9), (15, 6)] {
+        assert_eq!(stretched.pixel(x, y), BLUE, "({}, {})", x, y);
+    }
+}
+
+#[test]
+fn nine_grid_falls_back_when_the_margins_do_not_fit() {
+    let image = framed(10, 2);
+    let scaled = resample_nine_grid(&image, margins(6), 20, 20, margins(12));
+    assert_eq!((scaled.width, scaled.height), (20, 20));
+    assert_eq!(scaled.pixel(10, 10), BLUE);
+}
+
+#[test]
+fn variants_are_generated_per_frame_and_selected_by_dpi() {
+    let mut theme = button_theme("");
+    let options = VariantOptions { dpis: vec![96, 144, 192] };
+    let generated = generate_variants(&mut theme, &options).unwrap().generated;
+    let summary: Vec<_> = generated.iter().map(|variant| (variant.property.as_str(), variant.path.as_str(), variant.dpi, variant.frame_width)).collect();
+    assert_eq!(summary, [
+        ("ImageFile1", "Images\\Button.png", 96, 10),
+        ("ImageFile3", "Images\\Button_144dpi.png", 144, 15),
+        ("ImageFile4", "Images\\Button_192dpi.png", 192, 20),
+    ]);
+    assert_eq!(generated[2].sizing_margins, Some(margins(4)));
+    assert_eq!(property(&theme, "ImageSelectType"), Some(&PropertyValue::Enum(IST_DPI)));
+    assert_eq!(property(&theme, "MinDpi4"), Some(&PropertyValue::Int(192)));
+    assert_eq!(property(&theme, "MinSize4"), None);
+
+    // Each frame is scaled on its own, so both frames of the strip keep their borders.
+    let variant = decode(theme.image("Images\\Button_192dpi.png").unwrap(), &DecodeOptions::default()).unwrap().image;
+    assert_eq!((variant.width, variant.height), (20, 40));
+    assert_eq!(variant.crop(Rect { left: 0, top: 20, right: 20, bottom: 40 }), framed(20, 4));
+
+    // Variants that exist are left alone.
+    assert!(generate_variants(&mut theme, &options).unwrap().generated.is_empty());
+}
+
+#[test]
+fn size_selected_variants_get_a_min_size() {
+    let mut theme = button_theme("ImageSelectType = Size");
+    generate_variants(&mut theme, &VariantOptions { dpis: vec![120, 192] }).unwrap();
+    assert_eq!(property(&theme, "ImageSelectType"), Some(&PropertyValue::Enum(IST_SIZE)));
+    assert_eq!(property(&theme, "MinSize2"), Some(&PropertyValue::Position(Point { x: 13, y: 13 })));
+    assert_eq!(property(&theme, "MinSize4"), Some(&PropertyValue::Position(Point { x: 20, y: 20 })));
+}
+
+#[test]
+fn variants_come_from_the_highest_resolution_image() {
+    let mut theme = button_theme("ImageFile4 = Images\\Button_big.png\nMinDpi4 = 192");
+    let big = [framed(20, 4), framed(20, 4)].iter().flat_map(|frame| frame.pixels.clone()).collect();
+    theme.set_image("Images\\Button_big.png", png(&RgbaImage::from_pixels(20, 40, big).unwrap()));
+
+    let generated = generate_variants(&mut theme, &VariantOptions { dpis: vec![144, 192] }).unwrap().generated;
+    assert_eq!(generated.len(), 1);
+    assert_eq!((generated[0].dpi, generated[0].source_dpi, generated[0].frame_width), (144, 192, 15));
+}
+
+#[test]
+fn unknown_dpis_are_rejected() {
+    let error = generate_variants(&mut button_theme(""), &VariantOptions { dpis: vec![100] }).unwrap_err();
+    assert!(error.to_string().contains("100 DPI"));
+}
+
+#[test]
+fn images_that_do_not_divide_into_their_frames_are_skipped() {
+    // The check box comes after the push button, so by then the push button has its variants.
+    let mut theme = button_theme("\n[Button.CheckBox]\nImageFile = Images\\CheckBox.png\nImageCount = 3");
+    theme.set_image("Images\\CheckBox.png", png(&framed(10, 2)));
+    let check_box = theme.state("Button", Some("CheckBox"), None).cloned();
+
+    let report = generate_variants(&mut theme, &VariantOptions { dpis: vec![192] }).unwrap();
+    assert_eq!(report.generated.len(), 1);
+    assert_eq!(report.generated[0].part.as_deref(), Some("PushButton"));
+    assert_eq!(property(&theme, "MinDpi4"), Some(&PropertyValue::Int(192)));
+
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].0.path, "Images\\CheckBox.png");
+    assert!(report.failed[0].1.contains("3 frames"), "{}", report.failed[0].1);
+    assert_eq!(theme.state("Button", Some("CheckBox"), None).cloned(), check_box);
+    assert!(theme.image("Images\\CheckBox_192dpi.png").is_none());
+}
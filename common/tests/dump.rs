@@ -0,0 +1,83 @@
+use kanaya_common::formats::dump;
+use kanaya_common::formats::pe::{self, ResourceName, ResourceTable};
+
+/**
+ * A property record of a VARIANT resource.
+ */
+fn record(property_id: i32, type_id: i32, class_id: i32, part_id: i32, state_id: i32, inline: i32, data: &[u8]) -> Vec<u8> {
+    let mut record = Vec::new();
+    for field in [property_id, type_id, class_id, part_id, state_id, inline, 0, data.len() as i32] {
+        record.extend_from_slice(&field.to_le_bytes());
+    }
+    record.extend_from_slice(data);
+    record.resize(record.len().div_ceil(8) * 8, 0);
+    record
+}
+
+/**
+ * Null-terminated UTF-16 strings, as in a CMAP resource.
+ */
+fn utf16z(names: &[&str]) -> Vec<u8> {
+    names.iter()
+        .flat_map(|name| name.encode_utf16().chain(std::iter::once(0)))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/**
+ * A compiled style with a record type that Kanaya does not know, the way newer Windows styles
+ * have, and a second color variant.
+ */
+fn foreign_msstyles() -> Vec<u8> {
+    let mut table = ResourceTable::default();
+    table.insert(ResourceName::name("CMAP"), ResourceName::name("CMAP"), 0, utf16z(&["Globals", "Button"]));
+
+    let mut normal = record(2403, 202, 1, 1, 2, 3, &[]);
+    normal.extend(record(9000, 9999, 1, 1, 0, 0, &[1, 2, 3, 4]));
+    table.insert(ResourceName::name("VARIANT"), ResourceName::name("NORMAL"), 0, normal);
+    table.insert(ResourceName::name("VARIANT"), ResourceName::name("METALLIC"), 0, record(2403, 202, 1, 1, 0, 5, &[]));
+
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    png.extend_from_slice(&[0; 8]);
+    table.insert(ResourceName::name("IMAGE"), ResourceName::Id(1), 0, png);
+    pe::write_image(&table, 0)
+}
+
+#[test]
+fn unreadable_msstyles_dump_raw_resources() {
+    let text = dump::dump_msstyles(&foreign_msstyles());
+    assert!(text.contains("[Error]"));
+    assert!(text.contains("[ClassMap]\n    0 = Globals\n    1 = Button\n"));
+    assert!(text.contains("[Variant NORMAL]\n    [Button.PushButton] #9000 (#9999) = 4 bytes sha256:"));
+    assert!(text.contains("\n    [Button.PushButton(Hot)] BorderSize (int) = inline 3\n"));
+    assert!(text.contains("[Variant METALLIC]\n    [Button.PushButton] BorderSize (int) = inline 5\n"));
+    assert!(text.contains("IMAGE/#1 png 16 bytes sha256:"));
+}
+
+#[test]
+fn raw_dump_does_not_depend_on_record_order() {
+    let mut table = pe::read_image(&foreign_msstyles()).unwrap();
+    let normal = table.find(&ResourceName::name("VARIANT"), &ResourceName::name("NORMAL")).unwrap().to_vec();
+    let (first, second) = normal.split_at(32);
+    table.insert(ResourceName::name("VARIANT"), ResourceName::name("NORMAL"), 0, [second, first].concat());
+    assert_eq!(dump::dump_records(&table), dump::dump_records(&pe::read_image(&foreign_msstyles()).unwrap()));
+}
+
+#[test]
+fn malformed_record_sizes_are_reported() {
+    let mut negative = record(2403, 202, 0, 0, 0, 3, &[]);
+    negative[28..32].copy_from_slice(&(-8_i32).to_le_bytes());
+    let mut truncated = record(2403, 202, 0, 0, 0, 3, &[1, 2, 3, 4]);
+    truncated.truncate(34);
+
+    for (variant, error) in [(negative, "negative size (-8)"), (truncated, "Truncated property data.")] {
+        let mut table = ResourceTable::default();
+        table.insert(ResourceName::name("CMAP"), ResourceName::name("CMAP"), 0, utf16z(&["Globals"]));
+        table.insert(ResourceName::name("VARIANT"), ResourceName::name("NORMAL"), 0, variant);
+
+        let text = dump::dump_msstyles(&pe::write_image(&table, 0));
+        assert!(text.contains("[Error]"), "{}", text);
+        assert!(text.contains("[Variant NORMAL]\n    Error = "), "{}", text);
+        assert!(text.contains(error), "{}", text);
+    }
+}
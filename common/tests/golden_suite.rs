@@ -0,0 +1,155 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::RgbaImage;
+use kanaya_common::render::golden::{self, CaseStatus, GoldenSuite, Tolerance, REPORT_FILE_NAME};
+use kanaya_common::theme::Theme;
+
+/**
+ * Set this to write the renderings as the new golden images instead of comparing with them.
+ */
+const UPDATE_VARIABLE: &str = "KANAYA_UPDATE_GOLDEN";
+
+fn load_fixture(name: &str) -> Theme {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name).join("theme.ini");
+    restyle_ini::read_project(&path).unwrap()
+}
+
+fn golden_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name)
+}
+
+/**
+ * An empty directory for a test to write into.
+ */
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden_suite").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+/**
+ * Run the suite.json of a fixture's golden directory, or accept its renderings with
+ * KANAYA_UPDATE_GOLDEN set.
+ */
+fn check_suite(name: &str) {
+    let theme = load_fixture(name);
+    let dir = golden_dir(name);
+    let suite = GoldenSuite::load(&dir).unwrap();
+    if env::var_os(UPDATE_VARIABLE).is_some() {
+        golden::accept(&theme, &suite, &dir, &[]).unwrap();
+        return;
+    }
+
+    let report = golden::run_suite(&theme, &suite, &dir).unwrap();
+    if !report.passed() {
+        let report_dir = scratch_dir(&format!("{}_report", name));
+        golden::write_report(&report, &report_dir).unwrap();
+        let failures: Vec<&str> = report.failures().map(|result| result.name.as_str()).collect();
+        panic!(
+            "{:?} differ from their golden images. See {}, and run with {}=1 to accept them.",
+            failures, report_dir.join(REPORT_FILE_NAME).display(), UPDATE_VARIABLE,
+        );
+    }
+}
+
+#[test]
+fn basic_theme_matches_goldens() {
+    check_suite("basic");
+}
+
+#[test]
+fn border_fills_match_goldens() {
+    check_suite("borderfill");
+}
+
+#[test]
+fn small_differences_are_within_tolerance() {
+    let expected = filled(10, 10, [100, 150, 200, 255]);
+    let actual = filled(10, 10, [102, 149, 203, 255]);
+    let comparison = golden::compare(&expected, &actual, &Tolerance::default());
+    assert_eq!(comparison.differing, 0);
+    assert!(comparison.passes(&Tolerance::default()));
+}
+
+#[test]
+fn differing_pixels_beyond_the_fraction_fail() {
+    let expected = filled(10, 10, [255, 255, 255, 255]);
+    let mut actual = expected.clone();
+    actual.set_pixel(3, 4, [255, 0, 0, 255]);
+
+    let comparison = golden::compare(&expected, &actual, &Tolerance::default());
+    assert_eq!(comparison.differing, 1);
+    assert!(!comparison.passes(&Tolerance::default()));
+    assert!(comparison.passes(&Tolerance { fraction: 0.01, ..Default::default() }));
+    assert_eq!(comparison.diff.pixel(3, 4)[0..3], [255, 0, 0]);
+}
+
+#[test]
+fn transparency_counts_as_a_difference() {
+    assert_eq!(golden::pixel_distance([0, 0, 0, 0], [255, 255, 255, 0]), 0.0);
+    assert_eq!(golden::pixel_distance([255, 255, 255, 255], [255, 255, 255, 0]), 255.0);
+}
+
+#[test]
+fn accepted_goldens_pass_until_the_theme_changes() {
+    let dir = scratch_dir("accept");
+    let mut theme = load_fixture("basic");
+    let suite = GoldenSuite::from_json(r#"{ "cases": [
+        { "name": "button", "part": "Button.PushButton", "size": "40x20", "text": "OK" },
+        { "name": "window", "window": { "title": "Test" }, "size": "200x120" }
+    ] }"#).unwrap();
+
+    let report = golden::run_suite(&theme, &suite, &dir).unwrap();
+    assert!(report.results.iter().all(|result| result.status == CaseStatus::Missing));
+
+    assert_eq!(golden::accept(&theme, &suite, &dir, &["button".to_owned()]).unwrap(), ["button"]);
+    golden::accept(&theme, &suite, &dir, &[]).unwrap();
+    assert!(golden::run_suite(&theme, &suite, &dir).unwrap().passed());
+
+    let caption = theme.state_mut("Window", Some("Caption"), None);
+    caption.set_property("TextColor", kanaya_common::theme::PropertyValue::Color(kanaya_common::theme::Color::new(255, 0, 0)));
+    let report = golden::run_suite(&theme, &suite, &dir).unwrap();
+    let statuses: Vec<CaseStatus> = report.results.iter().map(|result| result.status).collect();
+    assert_eq!(statuses, [CaseStatus::Passed, CaseStatus::Failed]);
+
+    let report_dir = scratch_dir("accept_report");
+    golden::write_report(&report, &report_dir).unwrap();
+    let html = fs::read_to_string(report_dir.join(REPORT_FILE_NAME)).unwrap();
+    assert!(html.contains("1 of 2 cases passed"));
+    for file in ["window.expected.png", "window.actual.png", "window.diff.png"] {
+        assert!(html.contains(file));
+        assert!(report_dir.join(file).exists());
+    }
+    assert!(!report_dir.join("button.actual.png").exists());
+}
+
+#[test]
+fn size_changes_are_reported() {
+    let dir = scratch_dir("size");
+    let theme = load_fixture("basic");
+    let small = GoldenSuite::from_json(r#"{ "cases": [{ "name": "button", "part": "Button.PushButton", "size": "40x20" }] }"#).unwrap();
+    let large = GoldenSuite::from_json(r#"{ "cases": [{ "name": "button", "part": "Button.PushButton", "size": "50x20" }] }"#).unwrap();
+    golden::accept(&theme, &small, &dir, &[]).unwrap();
+    assert_eq!(golden::run_suite(&theme, &large, &dir).unwrap().results[0].status, CaseStatus::SizeChanged);
+}
+
+#[test]
+fn invalid_suites_are_rejected() {
+    assert!(GoldenSuite::from_json(r#"{ "cases": [{ "name": "button", "part": "Button.PushButton" }] }"#).is_err());
+    assert!(GoldenSuite::from_json(r#"{ "cases": [{ "name": "../button", "gallery": true }] }"#).is_err());
+    assert!(GoldenSuite::from_json(r#"{ "cases": [{ "name": "a", "gallery": true }, { "name": "A", "gallery": true }] }"#).is_err());
+    assert!(GoldenSuite::from_json(r#"{ "cases": [{ "name": "a", "window": { "buttons": { "Help": "Hot" } } }] }"#).is_err());
+}
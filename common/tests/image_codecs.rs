@@ -0,0 +1,217 @@
+use kanaya_common::formats::msstyles::{self, PackOptions};
+use kanaya_common::formats::pe::{self, ResourceName, ResourceTable};
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::atlas::atlas_image;
+use kanaya_common::image::{decode, encode, premultiply, unpremultiply, BmpAlpha, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use kanaya_common::theme::PropertyValue;
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+/**
+ * A property record of a VARIANT resource.
+ */
+fn record(property_id: i32, type_id: i32, class_id: i32, part_id: i32, state_id: i32, inline: i32, data: &[u8]) -> Vec<u8> {
+    let mut record = Vec::new();
+    for field in [property_id, type_id, class_id, part_id, state_id, inline, 0, data.len() as i32] {
+        record.extend_from_slice(&field.to_le_bytes());
+    }
+    record.extend_from_slice(data);
+    record.resize(record.len().div_ceil(8) * 8, 0);
+    record
+}
+
+/**
+ * A 4x2 atlas: red on the left half and green on the right.
+ */
+fn atlas() -> Vec<u8> {
+    let mut image = filled(4, 2, [255, 0, 0, 255]);
+    image.paste(&filled(2, 2, [0, 255, 0, 255]), 2, 0);
+    encode(&image, &ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false }).unwrap()
+}
+
+/**
+ * A 5x3 image with every kind of pixel: opaque, partly transparent, bright and fully transparent.
+ */
+fn translucent() -> RgbaImage {
+    let mut image = RgbaImage::new(5, 3);
+    for y in 0..3 {
+        for x in 0..5 {
+            image.set_pixel(x, y, [(x * 60) as u8, 255 - (y * 80) as u8, 90, [255, 200, 128, 30, 0][x as usize]]);
+        }
+    }
+    image.set_pixel(4, 2, [0, 0, 0, 0]);
+    image
+}
+
+fn bmp(bits: u16, alpha: BmpAlpha, top_down: bool, file_header: bool) -> ImageEncoding {
+    ImageEncoding::Bmp { bits, alpha, top_down, file_header }
+}
+
+fn auto() -> DecodeOptions {
+    DecodeOptions::default()
+}
+
+#[test]
+fn bmp_round_trips_in_every_layout() {
+    let image = translucent();
+    for top_down in [false, true] {
+        for file_header in [false, true] {
+            let encoding = bmp(32, BmpAlpha::Straight, top_down, file_header);
+            let data = encode(&image, &encoding).unwrap();
+            let decoded = decode(&data, &DecodeOptions { bmp_alpha: BmpAlpha::Straight }).unwrap();
+            assert_eq!(decoded.image, image);
+            assert_eq!(decoded.encoding, encoding);
+            assert_eq!(encode(&decoded.image, &decoded.encoding).unwrap(), data);
+        }
+    }
+}
+
+#[test]
+fn opaque_bmps_stay_24_bit() {
+    let image = filled(3, 2, [10, 20, 30, 255]);
+    let data = encode(&image, &bmp(24, BmpAlpha::Ignored, false, true)).unwrap();
+    let decoded = decode(&data, &auto()).unwrap();
+    assert_eq!(decoded.image, image);
+    assert_eq!(decoded.encoding, bmp(24, BmpAlpha::Ignored, false, true));
+}
+
+#[test]
+fn auto_ignores_an_all_zero_alpha_channel() {
+    let data = encode(&filled(2, 2, [10, 20, 30, 255]), &bmp(32, BmpAlpha::Ignored, false, true)).unwrap();
+    let decoded = decode(&data, &auto()).unwrap();
+    assert_eq!(decoded.image.pixel(1, 1), [10, 20, 30, 255]);
+    assert_eq!(decoded.encoding, bmp(32, BmpAlpha::Ignored, false, true));
+    assert_eq!(encode(&decoded.image, &decoded.encoding).unwrap(), data);
+}
+
+#[test]
+fn auto_reads_straight_alpha() {
+    let image = translucent();
+    let data = encode(&image, &bmp(32, BmpAlpha::Straight, false, true)).unwrap();
+    let decoded = decode(&data, &auto()).unwrap();
+    assert_eq!(decoded.encoding, bmp(32, BmpAlpha::Straight, false, true));
+    assert_eq!(decoded.image, image);
+}
+
+#[test]
+fn auto_reads_dark_translucent_pixels_as_straight() {
+    // A shadow like this could also be premultiplied, but uxtheme draws it as straight alpha.
+    let shadow = filled(2, 2, [60, 60, 60, 128]);
+    let data = encode(&shadow, &bmp(32, BmpAlpha::Straight, false, true)).unwrap();
+    let decoded = decode(&data, &auto()).unwrap();
+    assert_eq!(decoded.encoding, bmp(32, BmpAlpha::Straight, false, true));
+    assert_eq!(decoded.image, shadow);
+}
+
+#[test]
+fn premultiplied_alpha_is_read_when_asked_for() {
+    let image = translucent();
+    let data = encode(&image, &bmp(32, BmpAlpha::Premultiplied, false, false)).unwrap();
+    let decoded = decode(&data, &DecodeOptions { bmp_alpha: BmpAlpha::Premultiplied }).unwrap();
+    assert_eq!(decoded.encoding, bmp(32, BmpAlpha::Premultiplied, false, false));
+    for (decoded, original) in decoded.image.pixels.chunks_exact(4).zip(image.pixels.chunks_exact(4)) {
+        assert_eq!(decoded, unpremultiply(premultiply(original.try_into().unwrap())));
+    }
+    assert_eq!(encode(&decoded.image, &decoded.encoding).unwrap(), data);
+}
+
+#[test]
+fn png_round_trips_in_every_color_type() {
+    let gray = filled(4, 4, [85, 85, 85, 255]);
+    let cases = [
+        (translucent(), PngColorType::Rgba, 8),
+        (filled(3, 3, [10, 20, 30, 255]), PngColorType::Rgb, 8),
+        (filled(3, 3, [10, 20, 30, 128]), PngColorType::Rgba, 16),
+        (gray.clone(), PngColorType::Gray, 8),
+        (gray, PngColorType::Gray, 2),
+        (filled(3, 3, [40, 40, 40, 100]), PngColorType::GrayAlpha, 8),
+        (translucent(), PngColorType::Indexed, 4),
+    ];
+    for (image, color_type, bit_depth) in cases {
+        let encoding = ImageEncoding::Png { color_type, bit_depth, interlaced: false };
+        let data = encode(&image, &encoding).unwrap();
+        let decoded = decode(&data, &auto()).unwrap();
+        assert_eq!(decoded.image, image, "{:?}", encoding);
+        assert_eq!(decoded.encoding, encoding);
+    }
+}
+
+#[test]
+fn png_encodings_widen_to_fit_the_image() {
+    let data = encode(&translucent(), &ImageEncoding::Png { color_type: PngColorType::Gray, bit_depth: 8, interlaced: false }).unwrap();
+    let decoded = decode(&data, &auto()).unwrap();
+    assert_eq!(decoded.image, translucent());
+    assert_eq!(decoded.encoding, ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false });
+}
+
+#[test]
+fn unchanged_images_encode_to_their_original_bytes() {
+    let data = encode(&translucent(), &bmp(32, BmpAlpha::Premultiplied, true, true)).unwrap();
+    let mut decoded = decode(&data, &auto()).unwrap();
+    assert!(!decoded.is_modified());
+    assert_eq!(decoded.encode().unwrap(), data);
+
+    decoded.image.set_pixel(0, 0, [1, 2, 3, 255]);
+    assert!(decoded.is_modified());
+    assert_ne!(decoded.encode().unwrap(), data);
+}
+
+#[test]
+fn atlas_streams_are_unpacked_and_cropped() {
+    // A Windows 8 style: the push button draws from STREAM 1, and the hot state takes the right
+    // half. A vendor property carries an image in its own data.
+    let rect: Vec<u8> = [2, 0, 4, 2].iter().flat_map(|value: &i32| value.to_le_bytes()).collect();
+    let mut variant = record(8000, 213, 0, 1, 0, 1, &[]);
+    variant.extend(record(8002, 209, 0, 1, 2, 0, &rect));
+    variant.extend(record(9100, 214, 0, 1, 0, 0, &atlas()));
+    let mut table = ResourceTable::default();
+    let cmap: Vec<u8> = "Button\0".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    table.insert(ResourceName::name("CMAP"), ResourceName::name("CMAP"), 0, cmap);
+    table.insert(ResourceName::name("VARIANT"), ResourceName::name("NORMAL"), 0, variant);
+    table.insert(ResourceName::name("STREAM"), ResourceName::Id(1), 0, atlas());
+
+    let theme = msstyles::unpack(&pe::write_image(&table, 0)).unwrap();
+    let push_button = theme.state("Button", Some("PushButton"), None).unwrap();
+    assert_eq!(push_button.property("AtlasImage"), Some(&PropertyValue::FileName("STREAM\\1.png".to_owned())));
+    assert_eq!(push_button.property("9100"), Some(&PropertyValue::FileName("STREAM\\Embedded1.png".to_owned())));
+    assert_eq!(theme.image("STREAM\\1.png"), Some(atlas().as_slice()));
+    assert_eq!(theme.image("STREAM\\Embedded1.png"), Some(atlas().as_slice()));
+
+    let hot = atlas_image(&theme, "Button", Some("PushButton"), Some("Hot")).unwrap();
+    assert_eq!(hot, Some(filled(2, 2, [0, 255, 0, 255])));
+    assert_eq!(atlas_image(&theme, "Button", Some("CheckBox"), None).unwrap(), None);
+}
+
+#[test]
+fn atlas_images_pack_as_streams() {
+    let mut theme = restyle_ini::parse("\
+[Button.PushButton]
+AtlasImage = Images\\Atlas.png
+
+[Button.PushButton(Hot)]
+AtlasRect = 0, 0, 2, 2
+
+[Button.PushButton(Pressed)]
+AtlasRect = 3, 0, 5, 2
+").unwrap();
+    theme.set_image("Images\\Atlas.png", atlas());
+
+    let table = pe::read_image(&msstyles::pack(&theme, &PackOptions::default()).unwrap()).unwrap();
+    assert_eq!(table.find(&ResourceName::name("STREAM"), &ResourceName::Id(1)), Some(atlas().as_slice()));
+    assert_eq!(table.of_type(&ResourceName::name("IMAGE")).count(), 0);
+    let records = msstyles::read_records(table.find(&ResourceName::name("VARIANT"), &ResourceName::name("NORMAL")).unwrap()).unwrap();
+    assert_eq!(records.iter().find(|record| record.property_id == 8000).map(|record| (record.type_id, record.inline)), Some((213, 1)));
+
+    let unpacked = msstyles::unpack(&pe::write_image(&table, 0)).unwrap();
+    let hot = atlas_image(&unpacked, "Button", Some("PushButton"), Some("Hot")).unwrap();
+    assert_eq!(hot, Some(filled(2, 2, [255, 0, 0, 255])));
+    assert!(atlas_image(&unpacked, "Button", Some("PushButton"), Some("Pressed")).unwrap_err().to_string().contains("4x2 atlas"));
+}
@@ -0,0 +1,201 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::export::{export_images, ExportOptions, INDEX_FILE_NAME};
+use kanaya_common::image::strip::join;
+use kanaya_common::image::{decode, encode, BmpAlpha, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use kanaya_common::theme::{PropertyValue, Theme};
+use serde_json::{json, Value};
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+/**
+ * An empty directory for a test to write into.
+ */
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("image_export").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 128];
+
+fn png(image: &RgbaImage) -> Vec<u8> {
+    encode(image, &ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false }).unwrap()
+}
+
+fn bmp(image: &RgbaImage) -> Vec<u8> {
+    encode(image, &ImageEncoding::Bmp { bits: 32, alpha: BmpAlpha::Straight, top_down: false, file_header: true }).unwrap()
+}
+
+/**
+ * A push button strip of three states with a 120 DPI variant, and a list view item.
+ */
+fn sample_theme() -> Theme {
+    let mut theme = restyle_ini::parse("\
+[Button.PushButton]
+ImageFile = Images\\PushButton.bmp
+ImageFile2 = Images\\PushButton_120.png
+MinDpi2 = 120
+ImageFile3 = Images\\PushButton_144.png
+ImageCount = 3
+ImageLayout = Vertical
+
+[Explorer::ListView.ListItem(Hot)]
+ImageFile = Images\\ListItem.png
+").unwrap();
+    theme.set_image("Images\\PushButton.bmp", bmp(&join(&[filled(4, 2, RED), filled(4, 2, GREEN), filled(4, 2, BLUE)], true).unwrap()));
+    theme.set_image("Images\\PushButton_120.png", png(&filled(5, 9, RED)));
+    theme.set_image("Images\\PushButton_144.png", png(&filled(6, 9, RED)));
+    theme.set_image("Images\\ListItem.png", png(&filled(3, 3, BLUE)));
+    theme
+}
+
+fn read_index(dir: &Path) -> Value {
+    serde_json::from_str(&fs::read_to_string(dir.join(INDEX_FILE_NAME)).unwrap()).unwrap()
+}
+
+fn read_png(dir: &Path, file: &str) -> RgbaImage {
+    decode(&fs::read(dir.join(file)).unwrap(), &DecodeOptions::default()).unwrap().image
+}
+
+#[test]
+fn files_are_named_after_their_property_and_dpi() {
+    let dir = scratch_dir("names");
+    let summary = export_images(&sample_theme(), &dir, &ExportOptions::default()).unwrap();
+
+    let mut files = summary.files.clone();
+    files.sort();
+    assert_eq!(files, [
+        "Button_PushButton_ImageFile2_120dpi.png",
+        "Button_PushButton_ImageFile3_144dpi.png",
+        "Button_PushButton_ImageFile_96dpi.png",
+        "Explorer-ListView_ListItem_Hot_ImageFile_96dpi.png",
+    ]);
+    assert!(summary.missing.is_empty());
+
+    // Bitmaps are converted, and strips are kept whole.
+    let strip = read_png(&dir, "Button_PushButton_ImageFile_96dpi.png");
+    assert_eq!((strip.width, strip.height), (4, 6));
+    assert_eq!((strip.pixel(0, 0), strip.pixel(3, 5)), (RED, BLUE));
+}
+
+#[test]
+fn index_maps_files_back_to_properties() {
+    let dir = scratch_dir("index");
+    let mut theme = sample_theme();
+    theme.state_mut("Button", Some("CheckBox"), None).set_property("ImageFile", PropertyValue::FileName("Images\\Gone.png".to_owned()));
+    let summary = export_images(&theme, &dir, &ExportOptions::default()).unwrap();
+    assert_eq!(summary.missing, ["Images\\Gone.png"]);
+
+    let index = read_index(&dir);
+    let entry = index["images"].as_array().unwrap().iter()
+        .find(|entry| entry["file"] == "Button_PushButton_ImageFile_96dpi.png")
+        .unwrap();
+    assert_eq!(entry, &json!({
+        "file": "Button_PushButton_ImageFile_96dpi.png",
+        "class": "Button",
+        "part": "PushButton",
+        "state": null,
+        "property": "ImageFile",
+        "dpi": 96,
+        "source": "Images\\PushButton.bmp",
+        "width": 4,
+        "height": 6,
+        "frame": null,
+        "frameCount": 3,
+        "layout": "vertical",
+    }));
+    assert_eq!(index["images"].as_array().unwrap().len(), summary.files.len());
+    assert_eq!(index["missing"], json!([
+        { "class": "Button", "part": "CheckBox", "state": null, "property": "ImageFile", "source": "Images\\Gone.png" },
+    ]));
+}
+
+#[test]
+fn split_strips_are_named_after_their_states() {
+    let dir = scratch_dir("split");
+    let summary = export_images(&sample_theme(), &dir, &ExportOptions { split_strips: true }).unwrap();
+
+    for (file, color) in [("Normal", RED), ("Hot", GREEN), ("Pressed", BLUE)] {
+        let file = format!("Button_PushButton_ImageFile_96dpi_{}.png", file);
+        assert!(summary.files.contains(&file), "{:?}", summary.files);
+        assert_eq!(read_png(&dir, &file), filled(4, 2, color));
+    }
+    // Variants are split by the same layout, and images of one frame stay whole.
+    assert!(summary.files.contains(&"Button_PushButton_ImageFile2_120dpi_Pressed.png".to_owned()));
+    assert_eq!(read_png(&dir, "Button_PushButton_ImageFile2_120dpi_Pressed.png"), filled(5, 3, RED));
+    assert!(summary.files.contains(&"Explorer-ListView_ListItem_Hot_ImageFile_96dpi.png".to_owned()));
+
+    let index = read_index(&dir);
+    let frames: Vec<(&str, i64)> = index["images"].as_array().unwrap().iter()
+        .filter(|entry| entry["property"] == "ImageFile" && entry["part"] == "PushButton")
+        .map(|entry| (entry["file"].as_str().unwrap(), entry["frame"].as_i64().unwrap()))
+        .collect();
+    assert_eq!(frames, [
+        ("Button_PushButton_ImageFile_96dpi_Normal.png", 0),
+        ("Button_PushButton_ImageFile_96dpi_Hot.png", 1),
+        ("Button_PushButton_ImageFile_96dpi_Pressed.png", 2),
+    ]);
+}
+
+#[test]
+fn unknown_parts_get_frame_numbers_and_odd_names_are_sanitized() {
+    let dir = scratch_dir("sanitize");
+    let mut theme = restyle_ini::parse("[VendorClass.7]\nImageFile = strip.png\nImageCount = 2\n").unwrap();
+    theme.set_image("strip.png", png(&join(&[filled(2, 2, RED), filled(2, 2, GREEN)], false).unwrap()));
+    let state = theme.state_mut("Odd Class/Name", Some("Part*1"), Some("A:B"));
+    state.set_property("ImageFile", PropertyValue::FileName("strip.png".to_owned()));
+    state.set_property("ImageCount", PropertyValue::Int(2));
+
+    let summary = export_images(&theme, &dir, &ExportOptions { split_strips: true }).unwrap();
+    assert_eq!(summary.files, [
+        "VendorClass_7_ImageFile_96dpi_Frame1.png",
+        "VendorClass_7_ImageFile_96dpi_Frame2.png",
+        "Odd_Class_Name_Part_1_A_B_ImageFile_96dpi_Frame1.png",
+        "Odd_Class_Name_Part_1_A_B_ImageFile_96dpi_Frame2.png",
+    ]);
+}
+
+#[test]
+fn names_that_collide_are_numbered() {
+    let dir = scratch_dir("collisions");
+    let mut theme = restyle_ini::parse("[A::B]\nImageFile = one.png\n\n[A-B]\nImageFile = two.png\n\n[a_b]\nImageFile = three.png\n\n[A.B]\nImageFile = four.png\n").unwrap();
+    for (path, color) in [("one.png", RED), ("two.png", GREEN), ("three.png", BLUE), ("four.png", RED)] {
+        theme.set_image(path, png(&filled(1, 1, color)));
+    }
+
+    let summary = export_images(&theme, &dir, &ExportOptions::default()).unwrap();
+    assert_eq!(summary.files, ["A-B_ImageFile_96dpi.png", "A-B_ImageFile_96dpi_2.png", "a_b_ImageFile_96dpi.png", "A_B_ImageFile_96dpi_2.png"]);
+    assert_eq!(read_png(&dir, "A-B_ImageFile_96dpi_2.png"), filled(1, 1, GREEN));
+    assert_eq!(read_png(&dir, "A_B_ImageFile_96dpi_2.png"), filled(1, 1, RED));
+}
+
+#[test]
+fn undecodable_images_are_skipped_and_listed() {
+    let dir = scratch_dir("failed");
+    let mut theme = sample_theme();
+    theme.set_image("Images\\ListItem.png", b"\x89PNG not a PNG".to_vec());
+    let summary = export_images(&theme, &dir, &ExportOptions::default()).unwrap();
+
+    assert_eq!(summary.files.len(), 3);
+    assert!(!summary.files.iter().any(|file| file.starts_with("Explorer-ListView")));
+    assert_eq!(summary.failed.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>(), ["Images\\ListItem.png"]);
+
+    let index = read_index(&dir);
+    let failed = index["failed"].as_array().unwrap();
+    assert_eq!(failed.len(), 1);
+    assert_eq!((&failed[0]["part"], &failed[0]["state"], &failed[0]["source"]), (&json!("ListItem"), &json!("Hot"), &json!("Images\\ListItem.png")));
+    assert!(failed[0]["error"].is_string());
+}
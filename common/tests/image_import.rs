@@ -0,0 +1,119 @@
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::import::{import_image, ImportOptions};
+use kanaya_common::image::{decode, encode, png, BmpAlpha, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use kanaya_common::theme::{Rect, Theme};
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+const PREMULTIPLIED_BMP: ImageEncoding = ImageEncoding::Bmp { bits: 32, alpha: BmpAlpha::Premultiplied, top_down: false, file_header: true };
+
+fn strip_theme() -> Theme {
+    restyle_ini::parse("\
+[Button.PushButton]
+ImageFile = images\\strip.bmp
+ImageCount = 3
+ImageLayout = Horizontal
+SizingMargins = 1, 1, 1, 1
+").unwrap()
+}
+
+/**
+ * Three 4x4 frames of translucent pixels, the way a premultiplied theme stores them.
+ */
+fn premultiplied_strip() -> Vec<u8> {
+    let mut image = RgbaImage::new(12, 4);
+    for y in 0..4 {
+        for x in 0..12 {
+            image.set_pixel(x, y, [30 + x as u8 * 10, 200, 40 + y as u8 * 30, 60 + x as u8 * 15]);
+        }
+    }
+    encode(&image, &PREMULTIPLIED_BMP).unwrap()
+}
+
+/**
+ * The stored BGRA bytes of a column range of a bottom-up 32-bit BMP with a file header.
+ */
+fn columns(bmp: &[u8], width: usize, range: std::ops::Range<usize>) -> Vec<u8> {
+    let pixels = &bmp[54..];
+    pixels.chunks_exact(width * 4).flat_map(|row| row[range.start * 4..range.end * 4].to_vec()).collect()
+}
+
+fn png_file(image: &RgbaImage) -> Vec<u8> {
+    png::encode(image, &ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false }).unwrap()
+}
+
+#[test]
+fn region_import_keeps_premultiplied_frames_byte_for_byte() {
+    let mut theme = strip_theme();
+    let original = premultiplied_strip();
+    theme.set_image("images\\strip.bmp", original.clone());
+
+    let options = ImportOptions {
+        target: DecodeOptions { bmp_alpha: BmpAlpha::Premultiplied },
+        region: Some(Rect { left: 4, top: 0, right: 8, bottom: 4 }),
+        ..ImportOptions::default()
+    };
+    let new_frame = png_file(&filled(4, 4, [255, 0, 0, 128]));
+    let report = import_image(&mut theme, "Button", Some("PushButton"), None, "ImageFile", &new_frame, &options).unwrap();
+    assert_eq!(report.encoding, PREMULTIPLIED_BMP);
+    assert!(report.warnings.is_empty());
+
+    let stored = theme.image("images\\strip.bmp").unwrap();
+    assert_eq!(columns(stored, 12, 0..4), columns(&original, 12, 0..4));
+    assert_eq!(columns(stored, 12, 8..12), columns(&original, 12, 8..12));
+    // The new frame is stored premultiplied too.
+    assert_eq!(&columns(stored, 12, 4..5)[..4], &[0, 0, 128, 128]);
+}
+
+#[test]
+fn explicit_bmp_encoding_also_reads_the_target() {
+    let mut theme = strip_theme();
+    let original = premultiplied_strip();
+    theme.set_image("images\\strip.bmp", original.clone());
+
+    let options = ImportOptions {
+        encoding: Some(PREMULTIPLIED_BMP),
+        region: Some(Rect { left: 0, top: 0, right: 4, bottom: 4 }),
+        ..ImportOptions::default()
+    };
+    let new_frame = png_file(&filled(4, 4, [0, 0, 255, 255]));
+    import_image(&mut theme, "Button", Some("PushButton"), None, "ImageFile", &new_frame, &options).unwrap();
+    let stored = theme.image("images\\strip.bmp").unwrap();
+    assert_eq!(columns(stored, 12, 4..12), columns(&original, 12, 4..12));
+}
+
+#[test]
+fn whole_image_import_converts_to_the_target_encoding() {
+    let mut theme = strip_theme();
+    theme.set_image("images\\strip.bmp", premultiplied_strip());
+
+    let replacement = filled(9, 3, [100, 50, 0, 255]);
+    let report = import_image(&mut theme, "Button", Some("PushButton"), None, "ImageFile", &png_file(&replacement), &ImportOptions::default()).unwrap();
+    assert_eq!((report.width, report.height), (9, 3));
+    let decoded = decode(theme.image("images\\strip.bmp").unwrap(), &DecodeOptions::default()).unwrap();
+    assert_eq!(decoded.image, replacement);
+    assert!(matches!(decoded.encoding, ImageEncoding::Bmp { file_header: true, .. }));
+}
+
+#[test]
+fn mismatched_sizes_are_reported() {
+    let mut theme = strip_theme();
+    theme.set_image("images\\strip.bmp", premultiplied_strip());
+
+    let report = import_image(&mut theme, "Button", Some("PushButton"), None, "ImageFile", &png_file(&filled(10, 1, [0, 0, 0, 255])), &ImportOptions::default()).unwrap();
+    let messages: Vec<&str> = report.warnings.iter().map(|warning| warning.message.as_str()).collect();
+    assert_eq!(messages.len(), 2, "{:?}", messages);
+    assert!(messages[0].contains("ImageCount = 3"));
+    assert!(messages[1].contains("SizingMargins"));
+
+    let region = ImportOptions { region: Some(Rect { left: 10, top: 0, right: 14, bottom: 4 }), ..ImportOptions::default() };
+    assert!(import_image(&mut theme, "Button", Some("PushButton"), None, "ImageFile", &png_file(&filled(4, 4, [0, 0, 0, 255])), &region).is_err());
+}
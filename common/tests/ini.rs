@@ -0,0 +1,99 @@
+use kanaya_common::formats::ini::{Document, Encoding};
+
+fn round_trip(bytes: &[u8]) -> Document {
+    let document = Document::decode(bytes).unwrap();
+    assert_eq!(document.encode(), bytes);
+    document
+}
+
+fn utf16le(text: &str) -> Vec<u8> {
+    [0xFF, 0xFE].into_iter().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect()
+}
+
+const SOURCE: &str = "\
+; Written by hand
+[Theme]
+DisplayName = Kanaya   ; trailing comment
+  Unknown=Key
+
+[Control Panel\\Colors]
+ActiveTitle=0 84 227
+";
+
+#[test]
+fn utf8_round_trips_byte_for_byte() {
+    let document = round_trip(SOURCE.as_bytes());
+    assert_eq!(document.encoding, Encoding::Utf8);
+    assert_eq!(document.line_ending, "\n");
+    assert_eq!(document.get("theme", "displayname"), Some("Kanaya   ; trailing comment"));
+    assert_eq!(document.get("Theme", "Unknown"), Some("Key"));
+
+    let bom = [&[0xEF, 0xBB, 0xBF][..], SOURCE.replace('\n', "\r\n").as_bytes()].concat();
+    assert_eq!(round_trip(&bom).encoding, Encoding::Utf8Bom);
+}
+
+#[test]
+fn utf16le_with_bom_round_trips_byte_for_byte() {
+    let document = round_trip(&utf16le(&SOURCE.replace('\n', "\r\n")));
+    assert_eq!(document.encoding, Encoding::Utf16Le);
+    assert_eq!(document.line_ending, "\r\n");
+    assert_eq!(document.get("Control Panel\\Colors", "ActiveTitle"), Some("0 84 227"));
+}
+
+#[test]
+fn ansi_round_trips_byte_for_byte() {
+    let document = round_trip(b"[Theme]\r\nDisplayName=Caf\xE9\r\n");
+    assert_eq!(document.encoding, Encoding::Ansi);
+    assert_eq!(document.get("Theme", "DisplayName"), Some("Caf\u{E9}"));
+}
+
+#[test]
+fn mixed_line_endings_are_kept_per_line() {
+    let source = "; comment\r\n[Theme]\nA=1\r\nB=2\n\r\n[Other]\r\nC=3\n";
+    let mut document = round_trip(source.as_bytes());
+    assert_eq!(document.line_ending, "\r\n");
+
+    document.set("Theme", "B", "20");
+    document.set("Theme", "D", "4");
+    document.set("Other", "C", "30");
+    assert_eq!(document.to_text(), "; comment\r\n[Theme]\nA=1\r\nB=20\nD=4\r\n\r\n[Other]\r\nC=30\n");
+}
+
+#[test]
+fn missing_trailing_newline_is_kept() {
+    let mut document = round_trip(b"[Theme]\nDisplayName=Kanaya");
+    assert!(!document.trailing_newline);
+
+    document.set("Theme", "DisplayName", "Other");
+    assert_eq!(document.to_text(), "[Theme]\nDisplayName=Other");
+    document.set("Theme", "Added", "1");
+    assert_eq!(document.to_text(), "[Theme]\nDisplayName=Other\nAdded=1");
+    document.set("New", "Key", "2");
+    assert_eq!(document.to_text(), "[Theme]\nDisplayName=Other\nAdded=1\n\n[New]\nKey=2");
+}
+
+#[test]
+fn empty_and_single_line_documents_round_trip() {
+    round_trip(b"");
+    round_trip(b"[Theme]");
+    round_trip(b"\n");
+    assert_eq!(Document::parse("[Theme]").line_ending, "\r\n");
+}
+
+#[test]
+fn odd_length_utf16_is_an_error() {
+    let mut bytes = utf16le("[Theme]\r\n");
+    bytes.push(b'A');
+    assert!(Document::decode(&bytes).unwrap_err().to_string().contains("odd number of bytes"));
+
+    let mut bytes = vec![0xFE, 0xFF, 0, b'['];
+    bytes.push(0);
+    assert!(Document::decode(&bytes).is_err());
+}
+
+#[test]
+fn removing_keys_keeps_the_other_lines() {
+    let mut document = Document::parse(SOURCE);
+    assert_eq!(document.section_mut("Theme").remove("unknown"), Some("Key".to_owned()));
+    assert_eq!(document.to_text(), SOURCE.replace("  Unknown=Key\n", ""));
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::margins::{self, MarginOptions};
+use kanaya_common::image::RgbaImage;
+use kanaya_common::theme::{Margins, PropertyValue, Theme};
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+fn load_fixture() -> Theme {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/basic/theme.ini");
+    restyle_ini::read_project(&path).unwrap()
+}
+
+/**
+ * A 12x10 image with a two pixel border around a uniform fill, and a highlight along the top and
+ * left edges.
+ */
+fn nine_grid() -> RgbaImage {
+    let mut image = filled(12, 10, [200, 220, 240, 255]);
+    for y in 0..10 {
+        for x in 0..12 {
+            if !(2..10).contains(&x) || !(2..8).contains(&y) {
+                image.set_pixel(x, y, [0, 40, 120, 255]);
+            }
+            if x == 0 || y == 0 {
+                image.set_pixel(x, y, [255, 255, 255, 255]);
+            }
+        }
+    }
+    image
+}
+
+#[test]
+fn nine_grid_margins_go_around_the_fill() {
+    let proposal = margins::detect_margins(&[nine_grid()], &MarginOptions::default()).unwrap();
+    let expected = Margins { left: 2, right: 2, top: 2, bottom: 2 };
+    assert_eq!(proposal.sizing, expected);
+    assert_eq!(proposal.content, expected);
+    assert_eq!(proposal.confidence, 1.0);
+}
+
+#[test]
+fn strips_share_their_margins() {
+    let mut hot = nine_grid();
+    hot.set_pixel(4, 1, [255, 0, 0, 255]);
+    let proposal = margins::detect_margins(&[nine_grid(), hot], &MarginOptions::default()).unwrap();
+    assert_eq!(proposal.sizing, Margins { left: 5, right: 2, top: 2, bottom: 2 });
+}
+
+#[test]
+fn gradients_have_low_confidence() {
+    let mut image = RgbaImage::new(16, 16);
+    for y in 0..16 {
+        for x in 0..16 {
+            image.set_pixel(x, y, [x as u8 * 16, 0, 0, 255]);
+        }
+    }
+    let proposal = margins::detect_margins(&[image], &MarginOptions::default()).unwrap();
+    assert!(proposal.confidence < 0.5, "confidence {}", proposal.confidence);
+}
+
+#[test]
+fn mismatched_frames_are_rejected() {
+    assert!(margins::detect_margins(&[], &MarginOptions::default()).is_err());
+    assert!(margins::detect_margins(&[filled(4, 4, [0; 4]), filled(4, 5, [0; 4])], &MarginOptions::default()).is_err());
+}
+
+#[test]
+fn missing_images_do_not_stop_the_others() {
+    let mut theme = load_fixture();
+    theme.state_mut("Window", Some("FrameBottom"), None).set_property("ImageFile", PropertyValue::FileName("Images\\Missing.png".to_owned()));
+    let report = margins::propose_theme_margins(&theme, &MarginOptions::default());
+
+    let failed: Vec<&str> = report.failed.iter().map(|(image_ref, _)| image_ref.path.as_str()).collect();
+    assert_eq!(failed, ["Images\\Missing.png"]);
+    assert!(report.failed[0].1.contains("missing"));
+    let paths: Vec<&str> = report.states.iter().map(|state| state.image_ref.path.as_str()).collect();
+    assert!(paths.contains(&"Images\\PushButton.png"), "{:?}", paths);
+    assert!(!paths.contains(&"Images\\CheckBox.png"));
+}
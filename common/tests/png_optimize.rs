@@ -0,0 +1,71 @@
+use std::path::Path;
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::png_optimize::{self, OptimizedImage};
+use kanaya_common::image::{decode, encode, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use kanaya_common::theme::Theme;
+
+fn load_fixture() -> Theme {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/basic/theme.ini");
+    restyle_ini::read_project(&path).unwrap()
+}
+
+fn rgba_png(image: &RgbaImage) -> Vec<u8> {
+    encode(image, &ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false }).unwrap()
+}
+
+/**
+ * Optimize a PNG, and check that it got smaller and still holds the same pixels. Returns the
+ * encoding of the result.
+ */
+fn optimize(image: &RgbaImage) -> ImageEncoding {
+    let original = rgba_png(image);
+    let optimized = png_optimize::optimize_png(&original).unwrap().expect("The image did not get smaller.");
+    assert!(optimized.len() < original.len());
+    let decoded = decode(&optimized, &DecodeOptions::default()).unwrap();
+    assert!(decoded.image == *image);
+    decoded.encoding
+}
+
+#[test]
+fn few_colors_become_a_palette() {
+    let mut image = RgbaImage::new(32, 32);
+    let mut seed = 1u32;
+    for y in 0..32 {
+        for x in 0..32 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let pixel = match (seed >> 16) % 3 {
+                0 => [255, 0, 0, 255],
+                1 => [0, 128, 255, 128],
+                _ => [0, 0, 0, 0],
+            };
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    let encoding = optimize(&image);
+    assert!(matches!(encoding, ImageEncoding::Png { color_type: PngColorType::Indexed, bit_depth: 2, .. }), "{:?}", encoding);
+}
+
+#[test]
+fn opaque_grays_become_grayscale() {
+    let mut image = RgbaImage::new(300, 8);
+    for y in 0..8 {
+        for x in 0..300 {
+            let gray = ((x * 7 + y * 13) % 256) as u8;
+            image.set_pixel(x, y, [gray, gray, gray, 255]);
+        }
+    }
+    assert!(matches!(optimize(&image), ImageEncoding::Png { color_type: PngColorType::Gray, bit_depth: 8, .. }));
+}
+
+#[test]
+fn optimized_images_are_not_optimized_again() {
+    let mut theme = load_fixture();
+    let before = theme.images.clone();
+    let optimized = png_optimize::optimize_theme(&mut theme).unwrap();
+    for OptimizedImage { path, before: size, after } in &optimized {
+        assert!(after < size);
+        let original = decode(&before[path], &DecodeOptions::default()).unwrap().image;
+        assert!(decode(&theme.images[path], &DecodeOptions::default()).unwrap().image == original);
+    }
+    assert!(png_optimize::optimize_theme(&mut theme).unwrap().is_empty());
+}
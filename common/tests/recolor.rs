@@ -0,0 +1,123 @@
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::recolor::{curve, recolor, ColorTransform, RecolorOptions};
+use kanaya_common::image::{decode, encode, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use kanaya_common::theme::{Color, PropertyValue};
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+fn hsl(hue: f32, saturation: f32, lightness: f32) -> ColorTransform {
+    ColorTransform::Hsl { hue, saturation, lightness }
+}
+
+#[test]
+fn curves_interpolate_between_points() {
+    let table = curve(&[(255, 255), (0, 0), (128, 96)]);
+    assert_eq!(table[0], 0);
+    assert_eq!(table[64], 48);
+    assert_eq!(table[128], 96);
+    assert_eq!(table[192], 176);
+    assert_eq!(table[255], 255);
+
+    // Outside of the points, values are kept.
+    let table = curve(&[(100, 50), (200, 150)]);
+    assert_eq!((table[50], table[100], table[150], table[200], table[250]), (50, 50, 100, 150, 250));
+
+    assert_eq!(curve(&[]), std::array::from_fn(|value| value as u8));
+}
+
+#[test]
+fn hsl_round_trips_every_color() {
+    let identity = hsl(0.0, 0.0, 0.0);
+    let full_turn = hsl(360.0, 0.0, 0.0);
+    for r in (0..=255).step_by(15) {
+        for g in (0..=255).step_by(17) {
+            for b in (0..=255).step_by(51) {
+                let color = Color::new(r as u8, g as u8, b as u8);
+                assert_eq!(identity.apply(color), color);
+                assert_eq!(full_turn.apply(color), color);
+            }
+        }
+    }
+}
+
+#[test]
+fn hsl_adjustments_move_hue_saturation_and_lightness() {
+    assert_eq!(hsl(120.0, 0.0, 0.0).apply(Color::new(255, 0, 0)), Color::new(0, 255, 0));
+    assert_eq!(hsl(-120.0, 0.0, 0.0).apply(Color::new(255, 0, 0)), Color::new(0, 0, 255));
+    assert_eq!(hsl(0.0, -1.0, 0.0).apply(Color::new(255, 0, 0)), Color::new(128, 128, 128));
+    assert_eq!(hsl(0.0, 0.0, 1.0).apply(Color::new(0, 84, 227)), Color::new(255, 255, 255));
+    assert_eq!(hsl(0.0, 0.0, -1.0).apply(Color::new(0, 84, 227)), Color::new(0, 0, 0));
+}
+
+#[test]
+fn neutrals_can_be_kept() {
+    let options = RecolorOptions { keep_neutrals: true, ..RecolorOptions::new(hsl(0.0, 0.0, -0.5)) };
+    assert_eq!(options.apply(Color::new(128, 128, 128)), Color::new(128, 128, 128));
+    assert_ne!(options.apply(Color::new(0, 84, 227)), Color::new(0, 84, 227));
+}
+
+#[test]
+fn shared_images_are_copied_and_color_keys_are_kept() {
+    let mut theme = restyle_ini::parse("\
+[Button.PushButton]
+ImageFile = Shared.png
+TextColor = 255 0 0
+Transparent = True
+TransparentColor = 255 0 0
+
+[Edit]
+ImageFile = Shared.png
+TextColor = 255 0 0
+").unwrap();
+    let png = ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false };
+    let mut image = filled(2, 1, [255, 0, 0, 255]);
+    image.set_pixel(1, 0, [0, 0, 255, 255]);
+    theme.set_image("Shared.png", encode(&image, &png).unwrap());
+
+    let options = RecolorOptions { include_classes: vec!["Button".to_owned()], ..RecolorOptions::new(hsl(120.0, 0.0, 0.0)) };
+    let report = recolor(&mut theme, &options);
+    assert_eq!(report.properties, 1);
+    assert_eq!(report.copied, [("Shared.png".to_owned(), "Shared_Recolored.png".to_owned())]);
+
+    let button = theme.state("Button", Some("PushButton"), None).unwrap();
+    assert_eq!(button.property("TextColor"), Some(&PropertyValue::Color(Color::new(0, 255, 0))));
+    assert_eq!(button.property("TransparentColor"), Some(&PropertyValue::Color(Color::new(255, 0, 0))));
+    assert_eq!(button.property("ImageFile"), Some(&PropertyValue::FileName("Shared_Recolored.png".to_owned())));
+    let edit = theme.state("Edit", None, None).unwrap();
+    assert_eq!(edit.property("TextColor"), Some(&PropertyValue::Color(Color::new(255, 0, 0))));
+    assert_eq!(edit.property("ImageFile"), Some(&PropertyValue::FileName("Shared.png".to_owned())));
+
+    let recolored = decode(theme.image("Shared_Recolored.png").unwrap(), &DecodeOptions::default()).unwrap().image;
+    assert_eq!(recolored.pixel(0, 0), [255, 0, 0, 255]);
+    assert_eq!(recolored.pixel(1, 0), [255, 0, 0, 255]);
+    assert_eq!(theme.image("Shared.png"), Some(encode(&image, &png).unwrap().as_slice()));
+}
+
+#[test]
+fn undecodable_images_are_skipped_and_the_rest_recolored() {
+    let mut theme = restyle_ini::parse("\
+[Button.CheckBox]
+ImageFile = Broken.png
+
+[Button.PushButton]
+ImageFile = Button.png
+").unwrap();
+    let png = ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false };
+    theme.set_image("Broken.png", b"\x89PNG not a PNG".to_vec());
+    theme.set_image("Button.png", encode(&filled(1, 1, [255, 0, 0, 255]), &png).unwrap());
+
+    let report = recolor(&mut theme, &RecolorOptions::new(hsl(120.0, 0.0, 0.0)));
+    assert_eq!(report.images, ["Button.png"]);
+    assert_eq!(report.skipped.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>(), ["Broken.png"]);
+    assert_eq!(theme.image("Broken.png"), Some(&b"\x89PNG not a PNG"[..]));
+    let recolored = decode(theme.image("Button.png").unwrap(), &DecodeOptions::default()).unwrap().image;
+    assert_eq!(recolored.pixel(0, 0), [0, 255, 0, 255]);
+}
@@ -0,0 +1,152 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::{decode, DecodeOptions, RgbaImage};
+use kanaya_common::render::{self, Renderer};
+use kanaya_common::theme::{PropertyValue, Rect, Theme};
+
+/**
+ * Set this to write the renderings as the new golden images instead of comparing with them.
+ */
+const UPDATE_VARIABLE: &str = "KANAYA_UPDATE_GOLDEN";
+
+fn load_fixture() -> Theme {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/borderfill/theme.ini");
+    restyle_ini::read_project(&path).unwrap()
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/borderfill").join(format!("{}.png", name))
+}
+
+fn render(theme: &Theme, class: &str, part: &str, width: u32, height: u32) -> RgbaImage {
+    let mut canvas = RgbaImage::new(width, height);
+    let rect = Rect { left: 0, top: 0, right: width as i32, bottom: height as i32 };
+    Renderer::new(theme).draw_background(&mut canvas, class, Some(part), None, rect).unwrap();
+    canvas
+}
+
+fn check_golden(name: &str, image: &RgbaImage) {
+    let path = golden_path(name);
+    if env::var_os(UPDATE_VARIABLE).is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, render::to_png(image).unwrap()).unwrap();
+        return;
+    }
+
+    let data = fs::read(&path).unwrap_or_else(|_| panic!("{} is missing. Run with {}=1 to create it.", path.display(), UPDATE_VARIABLE));
+    let expected = decode(&data, &DecodeOptions::default()).unwrap().image;
+    assert!(
+        *image == expected,
+        "The rendering of {} differs from {}. Run with {}=1 to accept it.",
+        name, path.display(), UPDATE_VARIABLE,
+    );
+}
+
+#[test]
+fn solid_fill_with_border() {
+    check_golden("solid", &render(&load_fixture(), "Progress", "Bar", 24, 12));
+}
+
+#[test]
+fn horizontal_gradient_follows_ratios() {
+    check_golden("horizontal_gradient", &render(&load_fixture(), "Progress", "Chunk", 64, 8));
+}
+
+#[test]
+fn vertical_gradient_spreads_colors_without_ratios() {
+    check_golden("vertical_gradient", &render(&load_fixture(), "Progress", "Fill", 8, 64));
+}
+
+#[test]
+fn two_color_vertical_gradient() {
+    check_golden("frame_gradient", &render(&load_fixture(), "Window", "FrameLeft", 8, 32));
+}
+
+#[test]
+fn radial_gradient() {
+    check_golden("radial_gradient", &render(&load_fixture(), "Header", "HeaderItem", 32, 20));
+}
+
+#[test]
+fn tiled_image_fill() {
+    check_golden("tile_image", &render(&load_fixture(), "Rebar", "Background", 20, 14));
+}
+
+#[test]
+fn round_rect_border() {
+    check_golden("round_rect", &render(&load_fixture(), "Tooltip", "Balloon", 40, 20));
+}
+
+#[test]
+fn ellipse_with_gradient() {
+    check_golden("ellipse", &render(&load_fixture(), "TrackBar", "Thumb", 16, 24));
+}
+
+#[test]
+fn border_only_leaves_the_inside_alone() {
+    let image = render(&load_fixture(), "Status", "Pane", 16, 10);
+    check_golden("border_only", &image);
+    assert_eq!(image.pixel(8, 5), [0, 0, 0, 0]);
+}
+
+#[test]
+fn gradient_ratios_are_running_totals() {
+    let theme = load_fixture();
+    let stops = Renderer::new(&theme).gradient("Progress", Some("Chunk"), None).stops;
+    let positions: Vec<u32> = stops.iter().map(|(position, _)| (position * 255.0).round() as u32).collect();
+    assert_eq!(positions, [64, 192, 255]);
+}
+
+fn render_mirrored(theme: &Theme, state: Option<&str>, canvas_width: u32, rect: Rect) -> RgbaImage {
+    let mut canvas = RgbaImage::new(canvas_width, rect.bottom as u32);
+    let mut renderer = Renderer::new(theme);
+    renderer.mirrored = true;
+    renderer.draw_background(&mut canvas, "Progress", Some("Chunk"), state, rect).unwrap();
+    canvas
+}
+
+#[test]
+fn right_to_left_flips_backgrounds() {
+    let theme = load_fixture();
+    let normal = render(&theme, "Progress", "Chunk", 64, 8);
+    let mirrored = render_mirrored(&theme, None, 64, Rect { left: 0, top: 0, right: 64, bottom: 8 });
+    for y in 0..8 {
+        for x in 0..64 {
+            assert_eq!(mirrored.pixel(x, y), normal.pixel(63 - x, y));
+        }
+    }
+}
+
+#[test]
+fn right_to_left_keeps_images_that_are_not_mirrored() {
+    let theme = load_fixture();
+    let normal = render(&theme, "Progress", "Chunk", 64, 8);
+    let mirrored = render_mirrored(&theme, Some("Paused"), 64, Rect { left: 0, top: 0, right: 64, bottom: 8 });
+    assert!(mirrored == normal);
+}
+
+#[test]
+fn right_to_left_measures_from_the_right() {
+    let theme = load_fixture();
+    let mirrored = render_mirrored(&theme, None, 100, Rect { left: 10, top: 0, right: 30, bottom: 8 });
+    assert_eq!(mirrored.pixel(65, 4)[3], 0);
+    assert_eq!(mirrored.pixel(80, 4)[3], 255);
+    assert_eq!(mirrored.pixel(95, 4)[3], 0);
+}
+
+#[test]
+fn true_size_images_are_clipped_to_their_rectangle() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/basic/theme.ini");
+    let mut theme = restyle_ini::read_project(&path).unwrap();
+    theme.state_mut("Button", Some("CheckBox"), None).set_property("TrueSizeStretchMark", PropertyValue::Int(500));
+    let mut canvas = RgbaImage::new(20, 20);
+    let rect = Rect { left: 7, top: 0, right: 13, bottom: 20 };
+    Renderer::new(&theme).draw_background(&mut canvas, "Button", Some("CheckBox"), None, rect).unwrap();
+    for y in 0..20 {
+        for x in 0..20 {
+            assert_eq!(canvas.pixel(x, y)[3] != 0, (7..13).contains(&x) && (3..16).contains(&y), "pixel {}, {}", x, y);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::{decode, encode, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use kanaya_common::render::golden::{self, Tolerance};
+use kanaya_common::render::text::{ThemeText, DT_END_ELLIPSIS, DT_NOPREFIX, DT_SINGLELINE, DT_VCENTER, DT_WORDBREAK};
+use kanaya_common::render::Renderer;
+use kanaya_common::theme::{Rect, Theme};
+
+fn load_fixture() -> Theme {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/text/theme.ini");
+    restyle_ini::read_project(&path).unwrap()
+}
+
+/**
+ * Set this to write the renderings as the new golden images instead of comparing with them.
+ */
+const UPDATE_VARIABLE: &str = "KANAYA_UPDATE_GOLDEN";
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/text")
+}
+
+/**
+ * Draw text on the background of a TextStyle part of the text fixture.
+ */
+fn render(theme: &Theme, part: &str, text: &str, flags: u32, width: u32, height: u32) -> RgbaImage {
+    let mut canvas = RgbaImage::new(width, height);
+    let renderer = Renderer::new(theme);
+    let rect = Rect { left: 0, top: 0, right: width as i32, bottom: height as i32 };
+    renderer.draw_background(&mut canvas, "TextStyle", Some(part), None, rect).unwrap();
+    let text = ThemeText { text, flags, ..Default::default() };
+    // An inset, so that effects have room around the text.
+    let inset = Rect { left: 8, top: 4, right: width as i32 - 8, bottom: height as i32 - 4 };
+    renderer.draw_text(&mut canvas, "TextStyle", Some(part), None, &text, inset).unwrap();
+    canvas
+}
+
+/**
+ * Compare a rendering with its golden image, or save it as the golden image with
+ * KANAYA_UPDATE_GOLDEN set.
+ */
+fn check_golden(name: &str, actual: &RgbaImage) {
+    let path = golden_dir().join(format!("{}.png", name));
+    if env::var_os(UPDATE_VARIABLE).is_some() {
+        let encoding = ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false };
+        fs::write(&path, encode(actual, &encoding).unwrap()).unwrap();
+    }
+
+    let data = fs::read(&path).unwrap_or_else(|_| panic!("{} is missing. Run with {}=1 to create it.", path.display(), UPDATE_VARIABLE));
+    let expected = decode(&data, &DecodeOptions::default()).unwrap().image;
+    let tolerance = Tolerance::default();
+    let comparison = golden::compare(&expected, actual, &tolerance);
+    assert!(
+        (expected.width, expected.height) == (actual.width, actual.height) && comparison.passes(&tolerance),
+        "The rendering of {} differs from {} in {} pixels. Run with {}=1 to accept it.",
+        name, path.display(), comparison.differing, UPDATE_VARIABLE,
+    );
+}
+
+/**
+ * The bounding box of the pixels that match a test, as left, top, right and bottom (inclusive).
+ */
+fn bounds(image: &RgbaImage, test: impl Fn([u8; 4]) -> bool) -> Option<(u32, u32, u32, u32)> {
+    let mut result: Option<(u32, u32, u32, u32)> = None;
+    for y in 0..image.height {
+        for x in 0..image.width {
+            if test(image.pixel(x, y)) {
+                result = Some(match result {
+                    Some((left, top, right, bottom)) => (left.min(x), top.min(y), right.max(x), bottom.max(y)),
+                    None => (x, y, x, y),
+                });
+            }
+        }
+    }
+    result
+}
+
+fn is_text(pixel: [u8; 4]) -> bool {
+    pixel[0] < 40 && pixel[1] < 40 && pixel[2] < 40
+}
+
+fn is_ink(pixel: [u8; 4]) -> bool {
+    pixel[..3] != [255, 255, 255]
+}
+
+fn is_red(pixel: [u8; 4]) -> bool {
+    pixel[0] > 200 && pixel[1] < 60 && pixel[2] < 60
+}
+
+fn is_tinted_red(pixel: [u8; 4]) -> bool {
+    let [r, g, b, _] = pixel.map(i32::from);
+    r > g + 10 && r > b + 10
+}
+
+fn is_tinted_blue(pixel: [u8; 4]) -> bool {
+    let [r, g, b, _] = pixel.map(i32::from);
+    b > r + 10 && b > g + 10
+}
+
+fn count(image: &RgbaImage, test: impl Fn([u8; 4]) -> bool) -> usize {
+    image.pixels.chunks_exact(4).filter(|pixel| test((*pixel).try_into().unwrap())).count()
+}
+
+const LINE: u32 = DT_SINGLELINE | DT_VCENTER;
+
+#[test]
+fn single_shadow_is_offset_behind_the_text() {
+    let theme = load_fixture();
+    let image = render(&theme, "MainInstruction", "Shadow", LINE, 90, 32);
+    check_golden("shadow_single", &image);
+
+    let (left, top, right, bottom) = bounds(&image, is_text).unwrap();
+    let (shadow_left, shadow_top, shadow_right, shadow_bottom) = bounds(&image, is_red).unwrap();
+    assert_eq!((shadow_right, shadow_bottom), (right + 2, bottom + 2));
+    assert!(shadow_left > left && shadow_top > top);
+}
+
+#[test]
+fn continuous_shadow_is_blurred() {
+    let theme = load_fixture();
+    let single = render(&theme, "MainInstruction", "Shadow", LINE, 90, 32);
+    let continuous = render(&theme, "Instruction", "Shadow", LINE, 90, 32);
+    check_golden("shadow_continuous", &continuous);
+
+    // Blurring spreads the shadow past the single one, but leaves none of it at full strength.
+    let (_, _, single_right, single_bottom) = bounds(&single, is_tinted_red).unwrap();
+    let (_, _, right, bottom) = bounds(&continuous, is_tinted_red).unwrap();
+    assert!(right > single_right && bottom > single_bottom);
+    assert!(count(&continuous, is_red) < count(&single, is_red));
+}
+
+#[test]
+fn glow_spreads_around_the_text() {
+    let theme = load_fixture();
+    let image = render(&theme, "BodyTitle", "Glow", LINE, 80, 40);
+    check_golden("glow", &image);
+
+    let (left, top, right, bottom) = bounds(&image, is_text).unwrap();
+    let (glow_left, glow_top, glow_right, glow_bottom) = bounds(&image, is_tinted_blue).unwrap();
+    assert!(glow_left + 3 <= left && glow_top + 3 <= top, "{:?}", (glow_left, glow_top, left, top));
+    assert!(glow_right >= right + 3 && glow_bottom >= bottom + 3, "{:?}", (glow_right, glow_bottom, right, bottom));
+
+    // Without TextGlowSize, the same text has no glow.
+    let plain = render(&theme, "SecondaryText", "Glow", LINE, 80, 40);
+    assert_eq!(count(&plain, is_tinted_blue), 0);
+}
+
+#[test]
+fn border_outlines_the_text() {
+    let theme = load_fixture();
+    let image = render(&theme, "BodyText", "Border", LINE, 80, 32);
+    check_golden("border", &image);
+
+    // The border is the text spread out by TextBorderSize.
+    let (left, top, right, bottom) = bounds(&render(&theme, "SecondaryText", "Border", LINE, 80, 32), is_ink).unwrap();
+    assert_eq!(bounds(&image, is_ink), Some((left - 1, top - 1, right + 1, bottom + 1)));
+    assert!(count(&image, is_red) > 0);
+}
+
+#[test]
+fn word_break_wraps_lines_at_spaces() {
+    let theme = load_fixture();
+    let renderer = Renderer::new(&theme);
+    let text = ThemeText { text: "Wrapped words here", flags: DT_WORDBREAK, ..Default::default() };
+    let one_line = renderer.text_extent("TextStyle", Some("ControlLabel"), None, &ThemeText { flags: 0, ..text.clone() }, None).unwrap();
+    let narrow = Rect { left: 0, top: 0, right: one_line.x * 2 / 3, bottom: 100 };
+    let wrapped = renderer.text_extent("TextStyle", Some("ControlLabel"), None, &text, Some(narrow)).unwrap();
+    assert_eq!(wrapped.y, one_line.y * 2);
+    assert!(wrapped.x <= narrow.right);
+
+    let image = render(&theme, "ControlLabel", text.text, DT_WORDBREAK, narrow.right as u32 + 16, 48);
+    check_golden("word_break", &image);
+    let rows: Vec<bool> = (0..image.height).map(|y| (0..image.width).any(|x| is_ink(image.pixel(x, y)))).collect();
+    let bands = rows.windows(2).filter(|pair| !pair[0] && pair[1]).count() + rows[0] as usize;
+    assert_eq!(bands, 2);
+}
+
+#[test]
+fn end_ellipsis_shortens_lines_that_do_not_fit() {
+    let theme = load_fixture();
+    let text = "A caption that is too long";
+    let image = render(&theme, "ControlLabel", text, LINE | DT_END_ELLIPSIS, 80, 20);
+    check_golden("end_ellipsis", &image);
+
+    // The line is the longest start of the text that fits with "..." after it.
+    let renderer = Renderer::new(&theme);
+    let fits = |candidate: &str| {
+        let extent = renderer.text_extent("TextStyle", Some("ControlLabel"), None, &ThemeText { text: candidate, ..Default::default() }, None).unwrap();
+        extent.x <= 80 - 16
+    };
+    let shortened = (0..text.len()).rev().map(|end| format!("{}...", &text[..end])).find(|candidate| fits(candidate)).unwrap();
+    assert!(shortened.len() < text.len());
+    assert_eq!(image, render(&theme, "ControlLabel", &shortened, LINE, 80, 20));
+    assert_ne!(image, render(&theme, "ControlLabel", text, LINE, 80, 20));
+}
+
+#[test]
+fn ampersands_underline_the_next_character() {
+    let theme = load_fixture();
+    let prefixed = render(&theme, "ControlLabel", "&File", LINE, 60, 28);
+    check_golden("prefix_underline", &prefixed);
+
+    // The only difference from the plain text is one row under the F.
+    let plain = render(&theme, "ControlLabel", "File", LINE, 60, 28);
+    let differences: Vec<(u32, u32)> = (0..plain.height)
+        .flat_map(|y| (0..plain.width).map(move |x| (x, y)))
+        .filter(|(x, y)| prefixed.pixel(*x, *y) != plain.pixel(*x, *y))
+        .collect();
+    assert!(differences.len() >= 4, "{:?}", differences);
+    assert!(differences.iter().all(|(_, y)| *y == differences[0].1), "{:?}", differences);
+    let f = Renderer::new(&theme).text_extent("TextStyle", Some("ControlLabel"), None, &ThemeText { text: "F", ..Default::default() }, None).unwrap();
+    let columns: Vec<u32> = differences.iter().map(|(x, _)| *x).collect();
+    assert_eq!(columns.first(), Some(&8));
+    assert!(*columns.last().unwrap() <= 8 + f.x as u32, "{:?} {}", columns, f.x);
+
+    // && is a literal ampersand, and DT_NOPREFIX draws the text as it is.
+    assert_eq!(render(&theme, "ControlLabel", "a&&b", LINE, 60, 28), render(&theme, "ControlLabel", "a&b", LINE | DT_NOPREFIX, 60, 28));
+    assert_ne!(render(&theme, "ControlLabel", "&File", LINE | DT_NOPREFIX, 60, 28), plain);
+}
@@ -0,0 +1,66 @@
+use std::path::Path;
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::{decode, png, DecodeOptions, RgbaImage};
+use kanaya_common::render::Renderer;
+use kanaya_common::render::transition::Transition;
+use kanaya_common::theme::Theme;
+
+fn load_fixture() -> Theme {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/basic/theme.ini");
+    restyle_ini::read_project(&path).unwrap()
+}
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+#[test]
+fn durations_are_looked_up_by_state_pair() {
+    let theme = load_fixture();
+    let renderer = Renderer::new(&theme);
+    let duration = |from: &str, to: &str| renderer.transition_duration("Button", Some("PushButton"), Some(from), Some(to));
+    assert_eq!(duration("Normal", "Hot"), Some(250));
+    assert_eq!(duration("Hot", "Normal"), Some(500));
+    assert_eq!(duration("Hot", "Pressed"), Some(100));
+    assert_eq!(duration("Normal", "Pressed"), None);
+    assert_eq!(renderer.transition_duration("Button", Some("CheckBox"), Some("UncheckedNormal"), Some("UncheckedHot")), None);
+}
+
+#[test]
+fn frames_cross_fade_at_the_frame_rate() {
+    let transition = Transition { from: filled(2, 2, [0, 0, 0, 255]), to: filled(2, 2, [200, 100, 0, 255]), duration: 100 };
+    let frames = transition.frames(20);
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].pixel(0, 0), [0, 0, 0, 255]);
+    assert_eq!(frames[1].pixel(0, 0), [100, 50, 0, 255]);
+    assert_eq!(frames[2].pixel(1, 1), [200, 100, 0, 255]);
+}
+
+#[test]
+fn fading_from_transparent_keeps_the_color() {
+    let transition = Transition { from: RgbaImage::new(1, 1), to: filled(1, 1, [255, 0, 0, 255]), duration: 10 };
+    assert_eq!(transition.frame(5).pixel(0, 0), [255, 0, 0, 128]);
+}
+
+#[test]
+fn changes_without_a_fade_have_two_frames() {
+    let theme = load_fixture();
+    let transition = Renderer::new(&theme).draw_transition("Button", Some("PushButton"), Some("Normal"), Some("Pressed"), None, (75, 23)).unwrap();
+    assert_eq!(transition.duration, 0);
+    let frames = transition.frames(30);
+    assert_eq!(frames.len(), 2);
+    assert!(frames[1] == transition.to);
+}
+
+#[test]
+fn animated_pngs_show_the_first_frame_as_a_still() {
+    let frames = [filled(3, 2, [10, 20, 30, 255]), filled(3, 2, [40, 50, 60, 128])];
+    let data = png::encode_animated(&frames, 1, 30).unwrap();
+    assert!(decode(&data, &DecodeOptions::default()).unwrap().image == frames[0]);
+}
@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use kanaya_common::formats::{self, restyle_ini};
+use kanaya_common::theme::{Color, Font, Margins, PropertyValue, FW_BOLD, FW_NORMAL, FW_SEMIBOLD};
+
+fn fixture_path(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(path)
+}
+
+/**
+ * An empty directory for a test to write into.
+ */
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("restyle_ini").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+const SOURCE: &str = "\
+; A comment, which is not kept.
+[Globals]
+TextColor = 16 32 48
+
+[Button.PushButton(Hot)]
+SizingMargins = 1, 2, 3, 4
+ImageFile = Images\\PushButton.png
+SizingType = Stretch
+X-Kanaya-Note = keep me, as written
+
+[Rebar.Band]
+";
+
+#[test]
+fn read_write_read_round_trip() {
+    let theme = restyle_ini::parse(SOURCE).unwrap();
+    let written = restyle_ini::write(&theme);
+    let reread = restyle_ini::parse(&written).unwrap();
+    assert_eq!(reread, theme);
+    assert_eq!(restyle_ini::write(&reread), written);
+
+    let state = theme.state("Button", Some("PushButton"), Some("Hot")).unwrap();
+    assert_eq!(state.property("SizingMargins"), Some(&PropertyValue::Margins(Margins { left: 1, right: 2, top: 3, bottom: 4 })));
+    assert_eq!(theme.state("Globals", None, None).unwrap().property("TextColor"), Some(&PropertyValue::Color(Color::new(16, 32, 48))));
+    assert!(theme.state("Rebar", Some("Band"), None).is_some());
+}
+
+#[test]
+fn unknown_properties_are_kept_as_strings() {
+    let theme = restyle_ini::parse(SOURCE).unwrap();
+    let state = theme.state("Button", Some("PushButton"), Some("Hot")).unwrap();
+    assert_eq!(state.property("X-Kanaya-Note"), Some(&PropertyValue::String("keep me, as written".to_owned())));
+    assert!(restyle_ini::write(&theme).contains("X-Kanaya-Note = keep me, as written\n"));
+}
+
+#[test]
+fn fixture_project_round_trips() {
+    let source = restyle_ini::decode(&std::fs::read(fixture_path("basic/theme.ini")).unwrap()).unwrap();
+    let theme = restyle_ini::parse(&source).unwrap();
+    assert_eq!(restyle_ini::parse(&restyle_ini::write(&theme)).unwrap(), theme);
+}
+
+#[test]
+fn sources_decode_like_the_files_that_update_project_edits() {
+    let utf16: Vec<u8> = [0xFF, 0xFE].into_iter()
+        .chain("[Globals]\r\nTextColor = 1 2 3\r\n".encode_utf16().flat_map(u16::to_le_bytes))
+        .collect();
+    let theme = restyle_ini::parse(&restyle_ini::decode(&utf16).unwrap()).unwrap();
+    assert_eq!(theme.state("Globals", None, None).unwrap().property("TextColor"), Some(&PropertyValue::Color(Color::new(1, 2, 3))));
+
+    // A trailing odd byte is an error, not silently dropped.
+    let mut odd = utf16.clone();
+    odd.push(b'x');
+    assert!(restyle_ini::decode(&odd).unwrap_err().to_string().contains("odd number of bytes"));
+    let path = scratch_dir("odd_utf16").join("theme.ini");
+    fs::write(&path, &odd).unwrap();
+    assert!(restyle_ini::read_file(&path).is_err());
+    assert!(restyle_ini::update_project(&theme, &path).is_err());
+}
+
+#[test]
+fn bad_values_name_the_line() {
+    let error = restyle_ini::parse("[Button.PushButton]\nSizingMargins = 1, 2\n").unwrap_err();
+    assert!(format!("{:#}", error).contains("Line 2"));
+}
+
+#[test]
+fn updating_a_project_keeps_its_comments_and_layout() {
+    let dir = scratch_dir("update");
+    let source = "\
+; Kanaya, by hand\r
+[Globals]\r
+TextColor = 16 32 48   \r
+\r
+; Buttons\r
+[button.pushbutton(hot)]\r
+ImageFile=Images\\PushButton.png\r
+SizingType = Stretch\r
+\r
+[Rebar.Band]\r
+BorderSize = 1\r
+";
+    fs::create_dir_all(dir.join("Images")).unwrap();
+    fs::write(dir.join("Images/PushButton.png"), b"old").unwrap();
+    fs::write(dir.join("theme.ini"), source).unwrap();
+
+    let path = dir.join("theme.ini");
+    let mut theme = restyle_ini::read_project(&path).unwrap();
+    formats::write_theme(&theme, &path, &path).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), source);
+
+    let hot = theme.state_mut("Button", Some("PushButton"), Some("Hot"));
+    hot.set_property("SizingType", PropertyValue::Enum(2));
+    hot.set_property("ContentMargins", PropertyValue::Margins(Margins { left: 1, right: 1, top: 2, bottom: 2 }));
+    theme.state_mut("Globals", None, None).remove_property("TextColor");
+    theme.classes.retain(|class| class.name != "Rebar");
+    theme.state_mut("Edit", None, None).set_property("BorderSize", PropertyValue::Int(2));
+    theme.set_image("Images\\PushButton.png", b"new".to_vec());
+    formats::write_theme(&theme, &path, &path).unwrap();
+
+    assert_eq!(fs::read_to_string(&path).unwrap(), "\
+; Kanaya, by hand\r
+[Globals]\r
+\r
+; Buttons\r
+[button.pushbutton(hot)]\r
+ImageFile=Images\\PushButton.png\r
+SizingType = Tile\r
+ContentMargins = 1, 1, 2, 2\r
+\r
+[Edit]\r
+BorderSize = 2\r
+");
+    assert_eq!(fs::read(dir.join("Images/PushButton.png")).unwrap(), b"new");
+    assert_eq!(restyle_ini::read_project(&path).unwrap(), theme);
+}
+
+#[test]
+fn font_weights_round_trip() {
+    for (weight, text) in [(FW_NORMAL, "Segoe UI, 9"), (FW_SEMIBOLD, "Segoe UI, 9, Weight 600, Italic"), (FW_BOLD, "Segoe UI, 9, Bold, Italic"), (900, "Segoe UI, 9, Weight 900, Italic")] {
+        let font = Font { face: "Segoe UI".to_owned(), size: 9, weight, italic: weight != FW_NORMAL, ..Default::default() };
+        assert_eq!(font.to_string(), text);
+        assert_eq!(restyle_ini::parse_value("Font", text).unwrap(), PropertyValue::Font(font));
+    }
+    assert!(restyle_ini::parse_value("Font", "Segoe UI, 9, Weight heavy").is_err());
+}
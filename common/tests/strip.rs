@@ -0,0 +1,142 @@
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::strip::{edit_strip, join, replace_state_frame, split, StripLayout};
+use kanaya_common::image::{decode, encode, BmpAlpha, DecodeOptions, ImageEncoding, PngColorType, RgbaImage};
+use kanaya_common::theme::schema::IL_VERTICAL;
+use kanaya_common::theme::{PropertyValue, Theme};
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+fn png(image: &RgbaImage) -> Vec<u8> {
+    encode(image, &ImageEncoding::Png { color_type: PngColorType::Rgba, bit_depth: 8, interlaced: false }).unwrap()
+}
+
+/**
+ * A vertical strip of 4x2 frames, one per color.
+ */
+fn strip(colors: &[[u8; 4]]) -> RgbaImage {
+    join(&colors.iter().map(|color| filled(4, 2, *color)).collect::<Vec<_>>(), true).unwrap()
+}
+
+/**
+ * The colors of the frames of a stored image, with the layout given by the theme.
+ */
+fn frame_colors(theme: &Theme, path: &str) -> Vec<[u8; 4]> {
+    let image = decode(theme.image(path).unwrap(), &DecodeOptions::default()).unwrap().image;
+    let layout = StripLayout::of(theme, "Button", Some("PushButton"), None);
+    split(&image, layout).unwrap().iter().map(|frame| frame.pixel(0, 0)).collect()
+}
+
+fn button_theme(extra: &str) -> Theme {
+    let mut theme = restyle_ini::parse(&format!("\
+[Button.PushButton]
+ImageFile = Images\\Button.png
+ImageCount = 3
+ImageLayout = Vertical
+{}
+", extra)).unwrap();
+    theme.set_image("Images\\Button.png", png(&strip(&[RED, GREEN, BLUE])));
+    theme
+}
+
+fn property<'a>(theme: &'a Theme, name: &str) -> Option<&'a PropertyValue> {
+    theme.state("Button", Some("PushButton"), None).unwrap().property(name)
+}
+
+#[test]
+fn split_and_join_are_inverse() {
+    let image = join(&[filled(3, 5, RED), filled(3, 5, GREEN)], false).unwrap();
+    assert_eq!((image.width, image.height), (6, 5));
+    assert_eq!((image.pixel(2, 4), image.pixel(3, 0)), (RED, GREEN));
+
+    let frames = split(&image, StripLayout { count: 2, vertical: false }).unwrap();
+    assert_eq!(frames, [filled(3, 5, RED), filled(3, 5, GREEN)]);
+    assert_eq!(join(&frames, false).unwrap(), image);
+
+    assert!(split(&image, StripLayout { count: 4, vertical: false }).is_err());
+    assert!(join(&[filled(3, 5, RED), filled(3, 4, GREEN)], false).is_err());
+}
+
+#[test]
+fn reordering_rewrites_the_image_and_its_count() {
+    let mut theme = button_theme("");
+    edit_strip(&mut theme, "Button", Some("PushButton"), None, "ImageFile", &DecodeOptions::default(), |strip| strip.reorder(&[2, 0])).unwrap();
+
+    assert_eq!(property(&theme, "ImageCount"), Some(&PropertyValue::Int(2)));
+    assert_eq!(property(&theme, "ImageLayout"), Some(&PropertyValue::Enum(IL_VERTICAL)));
+    assert_eq!(frame_colors(&theme, "Images\\Button.png"), [BLUE, RED]);
+}
+
+#[test]
+fn replacing_a_state_frame_leaves_the_other_frames_and_variants() {
+    let mut theme = button_theme("ImageFile1 = Images\\Button.png\nImageFile2 = Images\\Button_120dpi.png\nMinDpi2 = 120");
+    let variant = png(&join(&[filled(5, 3, RED), filled(5, 3, GREEN), filled(5, 3, BLUE)], true).unwrap());
+    theme.set_image("Images\\Button_120dpi.png", variant.clone());
+
+    replace_state_frame(&mut theme, "Button", Some("PushButton"), "Hot", "ImageFile", &DecodeOptions::default(), filled(4, 2, WHITE)).unwrap();
+    assert_eq!(frame_colors(&theme, "Images\\Button.png"), [RED, WHITE, BLUE]);
+    assert_eq!(property(&theme, "ImageCount"), Some(&PropertyValue::Int(3)));
+    assert_eq!(theme.image("Images\\Button_120dpi.png"), Some(variant.as_slice()));
+
+    // Pressed is state 3, the last frame; Disabled (state 4) only borrows it.
+    assert!(replace_state_frame(&mut theme, "Button", Some("PushButton"), "Disabled", "ImageFile", &DecodeOptions::default(), filled(4, 2, WHITE)).is_err());
+    assert!(replace_state_frame(&mut theme, "Button", Some("PushButton"), "Pressed", "ImageFile", &DecodeOptions::default(), filled(2, 2, WHITE)).is_err());
+}
+
+#[test]
+fn count_changes_that_reach_a_dpi_variant_are_rejected() {
+    let mut theme = button_theme("ImageFile2 = Images\\Button_120dpi.png\nMinDpi2 = 120");
+    theme.set_image("Images\\Button_120dpi.png", png(&join(&[filled(5, 3, RED), filled(5, 3, GREEN), filled(5, 3, BLUE)], true).unwrap()));
+    let original = theme.clone();
+
+    let error = edit_strip(&mut theme, "Button", Some("PushButton"), None, "ImageFile", &DecodeOptions::default(), |strip| strip.reorder(&[0, 1])).unwrap_err();
+    assert!(error.to_string().contains("ImageFile2"), "{}", error);
+    assert_eq!(theme, original);
+}
+
+#[test]
+fn state_images_keep_their_own_layout() {
+    // The Pressed state has its own image, so changing it must not change the count of the part.
+    let mut theme = button_theme("\n[Button.PushButton(Pressed)]\nImageFile = Images\\Pressed.png");
+    theme.set_image("Images\\Pressed.png", png(&strip(&[RED, GREEN, BLUE])));
+
+    edit_strip(&mut theme, "Button", Some("PushButton"), Some("Pressed"), "ImageFile", &DecodeOptions::default(), |strip| strip.reorder(&[1])).unwrap();
+    let pressed = theme.state("Button", Some("PushButton"), Some("Pressed")).unwrap();
+    assert_eq!(pressed.property("ImageCount"), Some(&PropertyValue::Int(1)));
+    assert_eq!(property(&theme, "ImageCount"), Some(&PropertyValue::Int(3)));
+    assert_eq!(frame_colors(&theme, "Images\\Button.png"), [RED, GREEN, BLUE]);
+
+    let image = decode(theme.image("Images\\Pressed.png").unwrap(), &DecodeOptions::default()).unwrap().image;
+    assert_eq!(image, filled(4, 2, GREEN));
+}
+
+#[test]
+fn premultiplied_strips_stay_premultiplied() {
+    let mut theme = restyle_ini::parse("\
+[Button.PushButton]
+ImageFile = Images\\Button.bmp
+ImageCount = 2
+ImageLayout = Vertical
+").unwrap();
+    let premultiplied = ImageEncoding::Bmp { bits: 32, alpha: BmpAlpha::Premultiplied, top_down: false, file_header: true };
+    let options = DecodeOptions { bmp_alpha: BmpAlpha::Premultiplied };
+    let translucent = [255, 125, 0, 51];
+    theme.set_image("Images\\Button.bmp", encode(&strip(&[translucent, RED]), &premultiplied).unwrap());
+
+    edit_strip(&mut theme, "Button", Some("PushButton"), None, "ImageFile", &options, |strip| strip.reorder(&[1, 0])).unwrap();
+    let data = theme.image("Images\\Button.bmp").unwrap();
+    assert_eq!(decode(data, &options).unwrap().image, strip(&[RED, translucent]));
+    assert_eq!(decode(data, &DecodeOptions { bmp_alpha: BmpAlpha::Straight }).unwrap().image.pixel(0, 2), [51, 25, 0, 51]);
+}
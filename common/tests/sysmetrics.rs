@@ -0,0 +1,71 @@
+use kanaya_common::theme::sysmetrics::{self, FontMetric, Severity, SizeMetric, SysMetrics};
+use kanaya_common::theme::{Font, Theme, FW_NORMAL};
+
+fn font(face: &str, size: i32) -> Font {
+    Font { face: face.to_owned(), size, weight: FW_NORMAL, ..Default::default() }
+}
+
+#[test]
+fn unit_conversions() {
+    assert_eq!(sysmetrics::pt_to_px(9.0, 96), 12.0);
+    assert_eq!(sysmetrics::px_to_pt(12.0, 96), 9.0);
+    assert_eq!(sysmetrics::pt_to_px(9.0, 144), 18.0);
+    assert_eq!(sysmetrics::px_to_twips(1.0, 96), 15.0);
+    assert_eq!(sysmetrics::twips_to_px(15.0, 96), 1.0);
+    assert_eq!(sysmetrics::pt_to_twips(1.0), 20.0);
+    assert_eq!(sysmetrics::twips_to_pt(180.0), 9.0);
+}
+
+#[test]
+fn pixel_scaling_rounds_like_muldiv() {
+    assert_eq!(sysmetrics::scale_px(22, 96), 22);
+    assert_eq!(sysmetrics::scale_px(22, 120), 28);
+    assert_eq!(sysmetrics::scale_px(1, 144), 2);
+    assert_eq!(sysmetrics::scale_px(-3, 120), -4);
+    assert_eq!(sysmetrics::logfont_height(&font("Segoe UI", 9), 120), -15);
+}
+
+#[test]
+fn registry_metrics_are_negative_twips() {
+    assert_eq!(sysmetrics::px_to_registry_metric(22), -330);
+    assert_eq!(sysmetrics::registry_metric_to_px(-330), 22);
+    assert_eq!(sysmetrics::registry_metric_to_px(17), 17);
+}
+
+#[test]
+fn defaults_are_valid_at_common_dpis() {
+    let metrics = SysMetrics::windows_defaults();
+    for dpi in [96, 120, 144, 192] {
+        assert_eq!(metrics.validate(dpi), [], "at {} DPI", dpi);
+    }
+}
+
+#[test]
+fn validation_reports_bad_values() {
+    let mut metrics = SysMetrics::default();
+    metrics.set_size(SizeMetric::ScrollbarWidth, Some(4));
+    metrics.set_font(FontMetric::Caption, Some(font("An Extremely Long Font Face Name Here", 9)));
+    metrics.set_font(FontMetric::Menu, Some(font("Segoe UI", 14)));
+    metrics.set_size(SizeMetric::MenuBarHeight, Some(19));
+
+    let issues = metrics.validate(96);
+    let find = |property: &str| issues.iter().find(|issue| issue.property == property).map(|issue| issue.severity);
+    assert_eq!(find("ScrollbarWidth"), Some(Severity::Error));
+    assert_eq!(find("CaptionFont"), Some(Severity::Error));
+    assert_eq!(find("MenuBarHeight"), Some(Severity::Warning));
+}
+
+#[test]
+fn theme_round_trip_and_scaling() {
+    let mut metrics = SysMetrics::windows_defaults();
+    let mut theme = Theme::default();
+    metrics.apply_to_theme(&mut theme);
+    assert_eq!(SysMetrics::from_theme(&theme), metrics);
+
+    metrics.scale(125);
+    assert_eq!(metrics.size(SizeMetric::CaptionBarHeight), Some(28));
+    assert_eq!(metrics.font(FontMetric::Caption).unwrap().size, 11);
+    metrics.set_size(SizeMetric::CaptionBarHeight, None);
+    metrics.apply_to_theme(&mut theme);
+    assert_eq!(SysMetrics::from_theme(&theme).size(SizeMetric::CaptionBarHeight), None);
+}
@@ -0,0 +1,85 @@
+use kanaya_common::formats::reg;
+use kanaya_common::formats::theme_file::ThemeFile;
+use kanaya_common::theme::system_colors::{self, ColorScheme, COLOR_COUNT, SYSTEM_COLORS};
+use kanaya_common::theme::{Color, Theme};
+
+fn custom_scheme() -> ColorScheme {
+    let mut scheme = ColorScheme::default();
+    scheme.set_face(Color::new(236, 233, 216));
+    scheme.set_active_caption(Color::new(0, 84, 227));
+    scheme.set_highlight(Color::new(49, 106, 197));
+    scheme
+}
+
+#[test]
+fn colors_are_found_by_either_name() {
+    assert_eq!(SYSTEM_COLORS.len(), COLOR_COUNT);
+    let by_registry = system_colors::find_system_color("ButtonFace").unwrap();
+    let by_property = system_colors::find_system_color("btnface").unwrap();
+    assert_eq!(by_registry.index, 15);
+    assert_eq!(by_property.index, 15);
+    assert!(system_colors::find_system_color("NotAColor").is_none());
+}
+
+#[test]
+fn luma_adjustments_reach_white_and_black() {
+    let color = Color::new(10, 120, 200);
+    assert_eq!(system_colors::adjust_luma(color, 1000), Color::new(255, 255, 255));
+    assert_eq!(system_colors::adjust_luma(color, -1000), Color::new(0, 0, 0));
+    assert_eq!(system_colors::adjust_luma(color, 0), color);
+}
+
+#[test]
+fn face_sets_the_derived_shades() {
+    let face = Color::new(212, 208, 200);
+    let shades = system_colors::derive_3d_shades(face);
+    let scheme = custom_scheme();
+    assert_eq!(shades.highlight, Color::new(255, 255, 255));
+    assert!(shades.dark_shadow.r < shades.shadow.r && shades.shadow.r < face.r && face.r < shades.light.r);
+    assert_eq!(scheme.get("MenuBar"), scheme.get("ButtonFace"));
+    assert_eq!(scheme.get("GrayText"), None);
+
+    let mut standard = ColorScheme::windows_standard();
+    standard.set_face(Color::new(236, 233, 216));
+    assert_eq!(standard.get("GrayText"), Some(Color::new(128, 128, 128)));
+    assert_eq!(scheme.get("MenuHilight"), Some(Color::new(49, 106, 197)));
+}
+
+#[test]
+fn registry_exports_round_trip() {
+    let scheme = custom_scheme();
+    let bytes = reg::write_color_scheme(&scheme);
+    assert_eq!(&bytes[..2], &[0xFF, 0xFE]);
+    assert_eq!(reg::read_color_scheme(&bytes).unwrap(), scheme);
+}
+
+#[test]
+fn regedit4_exports_are_read() {
+    let text = "REGEDIT4\r\n\r\n[HKEY_CURRENT_USER\\Control Panel\\Colors]\r\n\"ButtonFace\"=\"1 2 3\"\r\n\"Unknown\"=\"4 5 6\"\r\n";
+    let scheme = reg::read_color_scheme(text.as_bytes()).unwrap();
+    assert_eq!(scheme.get("BtnFace"), Some(Color::new(1, 2, 3)));
+    assert_eq!(scheme.iter().count(), 1);
+    assert!(reg::read_color_scheme(b"[Colors]\r\n").is_err());
+}
+
+#[test]
+fn theme_files_and_visual_styles_round_trip() {
+    let scheme = custom_scheme();
+
+    let mut theme_file = ThemeFile::parse("[Theme]\r\nDisplayName=Test\r\n");
+    theme_file.set_color_scheme(&scheme);
+    assert_eq!(ThemeFile::decode(&theme_file.encode()).unwrap().color_scheme(), scheme);
+
+    let mut theme = Theme::default();
+    scheme.apply_to_theme(&mut theme);
+    assert_eq!(ColorScheme::from_theme(&theme), scheme);
+}
+
+#[test]
+fn missing_colors_fall_back() {
+    let mut scheme = custom_scheme();
+    scheme.fill_from(&ColorScheme::windows_standard());
+    assert!(scheme.colors.iter().all(Option::is_some));
+    assert_eq!(scheme.get("InfoWindow"), Some(Color::new(255, 255, 225)));
+    assert_eq!(scheme.get("ButtonFace"), Some(Color::new(236, 233, 216)));
+}
@@ -0,0 +1,39 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use kanaya_common::formats::ini::Encoding;
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::formats::theme_file::{write_matching_theme_file, ThemeFile};
+
+/**
+ * An empty directory for a test to write into.
+ */
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("theme_file").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn matching_theme_file_is_created_for_a_new_visual_style() {
+    let dir = scratch_dir("new");
+    let theme = restyle_ini::parse("[Documentation]\nDisplayName = Kanaya Blue\n").unwrap();
+    let path = write_matching_theme_file(&theme, &dir.join("Kanaya.msstyles"), None).unwrap();
+    assert_eq!(path, dir.join("Kanaya.theme"));
+
+    let file = ThemeFile::read_file(&path).unwrap();
+    assert_eq!(file.document.encoding, Encoding::Utf16Le);
+    assert_eq!(file.display_name(), Some("Kanaya Blue"));
+    assert_eq!(file.visual_style().path.as_deref(), Some("%ResourceDir%\\Themes\\Kanaya\\Kanaya.msstyles"));
+}
+
+#[test]
+fn matching_theme_file_updates_an_existing_one_in_place() {
+    let dir = scratch_dir("existing");
+    let existing = "; Kanaya\n[Theme]\nDisplayName=Mine\n\n[VisualStyles]\nPath=old.msstyles\nColorStyle=Metallic\n";
+    fs::write(dir.join("Kanaya.theme"), existing).unwrap();
+
+    let path = write_matching_theme_file(&restyle_ini::parse("").unwrap(), &dir.join("Kanaya.msstyles"), Some("C:\\Kanaya.msstyles")).unwrap();
+    let written = fs::read_to_string(path).unwrap();
+    assert_eq!(written, existing.replace("Path=old.msstyles", "Path=C:\\Kanaya.msstyles") + "\n[MasterThemeSelector]\nMTSM=DABJDKT\n");
+}
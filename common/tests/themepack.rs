@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use kanaya_common::formats::cab::{Cabinet, CabinetFile, Compression};
+use kanaya_common::formats::theme_file::SECTION_DESKTOP;
+use kanaya_common::formats::themepack::{is_absolute_windows_path, PackOptions, ThemePack};
+
+/**
+ * An empty directory for a test to write into.
+ */
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("themepack").join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/**
+ * Bytes that do not compress well, so that MSZIP blocks stay large.
+ */
+fn noise(length: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed;
+    (0..length).map(|_| {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        (state >> 16) as u8
+    }).collect()
+}
+
+fn sample_cabinet() -> Cabinet {
+    Cabinet {
+        files: vec![
+            CabinetFile::new("Kanaya.theme", b"[Theme]\r\nDisplayName=Kanaya\r\n".to_vec(), 0),
+            // Straddles the 32 KiB block boundary, and the next file starts in the second block.
+            CabinetFile::new("DesktopBackground/img0.jpg", noise(0x8000 + 1234, 1), 1_700_000_000),
+            CabinetFile::new("Sounds\\Ding.wav", vec![0; 50_000], 1_700_000_000),
+            CabinetFile::new("Cursors\\Flèche.cur", noise(10, 2), 0),
+            CabinetFile::new("Empty.txt", Vec::new(), 0),
+        ],
+    }
+}
+
+#[test]
+fn cabinets_round_trip_across_block_boundaries() {
+    let cabinet = sample_cabinet();
+    for compression in [Compression::MsZip, Compression::None] {
+        let data = cabinet.write(compression).unwrap();
+        let read = Cabinet::read(&data).unwrap();
+        assert_eq!(read, Cabinet::read(&cabinet.write(compression).unwrap()).unwrap());
+        assert_eq!(read.files.len(), cabinet.files.len());
+        for (read, written) in read.files.iter().zip(&cabinet.files) {
+            assert_eq!(read.name, written.name);
+            assert_eq!(read.data, written.data, "{}", written.name);
+            assert_eq!((read.date, read.time), (written.date, written.time));
+        }
+        assert_eq!(read.file("desktopbackground/IMG0.JPG").unwrap().data.len(), 0x8000 + 1234);
+        assert_eq!(cabinet.write(compression).unwrap(), data);
+    }
+}
+
+#[test]
+fn mszip_compresses_and_corruption_is_reported() {
+    let cabinet = sample_cabinet();
+    let compressed = cabinet.write(Compression::MsZip).unwrap();
+    assert!(compressed.len() < cabinet.write(Compression::None).unwrap().len());
+
+    let mut corrupt = compressed.clone();
+    let last = corrupt.len() - 20;
+    corrupt[last] ^= 0xFF;
+    assert!(Cabinet::read(&corrupt).is_err());
+    assert!(Cabinet::read(&compressed[..compressed.len() - 100]).is_err());
+}
+
+#[test]
+fn absolute_windows_paths_are_recognized() {
+    for path in ["C:\\Windows\\Web\\Wallpaper\\img0.jpg", "d:/pictures/a.jpg", "\\\\server\\share\\a.jpg", "\\Windows\\a.jpg"] {
+        assert!(is_absolute_windows_path(path), "{}", path);
+    }
+    for path in ["img0.jpg", "Wallpaper\\img0.jpg", "..\\Sounds\\a.wav", "%SystemRoot%\\a.wav"] {
+        assert!(!is_absolute_windows_path(path), "{}", path);
+    }
+}
+
+#[test]
+fn theme_packs_bundle_relative_files_and_leave_windows_files_alone() {
+    let dir = scratch_dir("bundle");
+    fs::create_dir_all(dir.join("Wallpaper")).unwrap();
+    fs::write(dir.join("Wallpaper/blue.jpg"), b"jpeg").unwrap();
+    fs::write(dir.join("Kanaya.theme"), "\
+[Control Panel\\Desktop]\r
+Wallpaper=Wallpaper\\blue.jpg\r
+\r
+[Control Panel\\Cursors]\r
+Arrow=C:\\Windows\\Cursors\\aero_arrow.cur\r
+Wait=%SystemRoot%\\Cursors\\aero_busy.ani\r
+").unwrap();
+
+    let variables = BTreeMap::from([("SystemRoot".to_owned(), "C:\\Windows".to_owned())]);
+    let pack = ThemePack::from_theme_file(&dir.join("Kanaya.theme"), &variables).unwrap();
+    assert_eq!(pack.files.keys().collect::<Vec<_>>(), ["DesktopBackground\\blue.jpg"]);
+    assert_eq!(
+        pack.theme_file.document.get(SECTION_DESKTOP, "Wallpaper"),
+        Some("%USERPROFILE%\\AppData\\Local\\Microsoft\\Windows\\Themes\\Kanaya\\DesktopBackground\\blue.jpg"),
+    );
+    let cursors = pack.theme_file.cursors();
+    assert_eq!(cursors["Arrow"], "C:\\Windows\\Cursors\\aero_arrow.cur");
+    assert_eq!(cursors["Wait"], "%SystemRoot%\\Cursors\\aero_busy.ani");
+
+    let read = ThemePack::read(&pack.write(&PackOptions::default()).unwrap()).unwrap();
+    assert_eq!(read.files, pack.files);
+    assert_eq!(read.theme_file.encode(), pack.theme_file.encode());
+}
+
+#[test]
+fn other_absolute_paths_are_reported_off_windows() {
+    if cfg!(windows) {
+        return;
+    }
+    let dir = scratch_dir("absolute");
+    fs::write(dir.join("Kanaya.theme"), "[Control Panel\\Desktop]\r\nWallpaper=D:\\Pictures\\mine.jpg\r\n").unwrap();
+    let error = ThemePack::from_theme_file(&dir.join("Kanaya.theme"), &BTreeMap::new()).unwrap_err();
+    assert!(error.to_string().contains("D:\\Pictures\\mine.jpg"), "{}", error);
+}
+
+#[test]
+fn hostile_paths_are_not_extracted() {
+    let dir = scratch_dir("hostile");
+    let target = dir.join("target");
+    let theme = CabinetFile::new("Kanaya.theme", b"[Theme]\r\nDisplayName=Kanaya\r\n".to_vec(), 0);
+    for name in ["../evil.theme", "../x", "a/../../x", "a\\..\\..\\x", "/etc/x", "\\x", "C:x", "c:\\x", "./x", "a//x"] {
+        // Set the name directly, as CabinetFile::new would turn slashes into backslashes.
+        let evil = CabinetFile { name: name.to_owned(), ..CabinetFile::new("evil", b"evil".to_vec(), 0) };
+        let cabinet = Cabinet { files: vec![theme.clone(), evil] };
+        let error = ThemePack::read(&cabinet.write(Compression::None).unwrap()).unwrap_err();
+        assert!(error.to_string().contains(name), "{}: {}", name, error);
+
+        // Packs that are built in memory are checked when they are extracted, before anything
+        // is written.
+        let mut pack = ThemePack::read(&Cabinet { files: vec![theme.clone()] }.write(Compression::None).unwrap()).unwrap();
+        pack.files.insert("DesktopBackground\\img0.jpg".to_owned(), b"jpeg".to_vec());
+        pack.files.insert(name.to_owned(), b"evil".to_vec());
+        assert!(pack.extract(&target).is_err(), "{}", name);
+        assert!(!target.exists(), "{}", name);
+    }
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+    let pack = ThemePack { name: "../Kanaya".to_owned(), ..ThemePack::default() };
+    assert!(pack.extract(&target).is_err());
+    assert!(!dir.join("Kanaya.theme").exists());
+}
@@ -0,0 +1,165 @@
+use kanaya_common::formats::restyle_ini;
+use kanaya_common::image::transparency::{convert_color_keys, key_to_alpha, TransparencyOptions};
+use kanaya_common::image::{decode, encode, BmpAlpha, DecodeOptions, ImageEncoding, RgbaImage};
+use kanaya_common::theme::{Color, PropertyValue, Theme};
+
+fn filled(width: u32, height: u32, pixel: [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image.set_pixel(x, y, pixel);
+        }
+    }
+    image
+}
+
+const MAGENTA: [u8; 4] = [255, 0, 255, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const GRAY: [u8; 4] = [100, 100, 100, 255];
+
+const BMP_24: ImageEncoding = ImageEncoding::Bmp { bits: 24, alpha: BmpAlpha::Ignored, top_down: false, file_header: true };
+
+/**
+ * A 3x3 gray image with its center pixel in a key color.
+ */
+fn keyed_center(key: [u8; 4]) -> RgbaImage {
+    let mut image = filled(3, 3, GRAY);
+    image.set_pixel(1, 1, key);
+    image
+}
+
+fn decoded(theme: &Theme, path: &str) -> (RgbaImage, ImageEncoding) {
+    let decoded = decode(theme.image(path).unwrap(), &DecodeOptions::default()).unwrap();
+    (decoded.image, decoded.encoding)
+}
+
+fn property<'a>(theme: &'a Theme, part: &str, state: Option<&str>, name: &str) -> Option<&'a PropertyValue> {
+    theme.state("Button", Some(part), state).and_then(|state| state.property(name))
+}
+
+#[test]
+fn key_pixels_become_transparent() {
+    let mut image = keyed_center(MAGENTA);
+    key_to_alpha(&mut image, Color::new(255, 0, 255), false);
+    assert_eq!(image.pixel(1, 1), [0, 0, 0, 0]);
+    assert_eq!(image.pixel(0, 0), GRAY);
+    assert_eq!(image.pixel(1, 0), GRAY);
+}
+
+#[test]
+fn antialiasing_gives_edges_partial_alpha() {
+    let mut image = keyed_center(MAGENTA);
+    key_to_alpha(&mut image, Color::new(255, 0, 255), true);
+    assert_eq!(image.pixel(1, 1), [0, 0, 0, 0]);
+    // Corners only touch the key diagonally, which weighs less than a side: 15/16 and 14/16
+    // of full alpha.
+    assert_eq!(image.pixel(0, 0), [100, 100, 100, 239]);
+    assert_eq!(image.pixel(1, 0), [100, 100, 100, 223]);
+
+    // The edges of the image are not softened.
+    let mut opaque = filled(3, 3, GRAY);
+    key_to_alpha(&mut opaque, Color::new(255, 0, 255), true);
+    assert_eq!(opaque, filled(3, 3, GRAY));
+}
+
+#[test]
+fn bitmaps_become_32_bit_straight_alpha() {
+    let mut theme = restyle_ini::parse("\
+[Button.PushButton]
+ImageFile = Images\\Button.bmp
+Transparent = True
+").unwrap();
+    theme.set_image("Images\\Button.bmp", encode(&keyed_center(MAGENTA), &BMP_24).unwrap());
+
+    let report = convert_color_keys(&mut theme, &TransparencyOptions::default());
+    assert_eq!(report.converted, ["Images\\Button.bmp"]);
+    assert!(report.skipped.is_empty());
+
+    let (image, encoding) = decoded(&theme, "Images\\Button.bmp");
+    assert_eq!(encoding, ImageEncoding::Bmp { bits: 32, alpha: BmpAlpha::Straight, top_down: false, file_header: true });
+    assert_eq!((image.pixel(1, 1), image.pixel(0, 0)), ([0, 0, 0, 0], GRAY));
+    assert_eq!(property(&theme, "PushButton", None, "Transparent"), None);
+}
+
+#[test]
+fn images_with_disagreeing_keys_are_skipped() {
+    let mut theme = restyle_ini::parse("\
+[Button.PushButton]
+ImageFile = Images\\Shared.bmp
+Transparent = True
+
+[Button.CheckBox]
+ImageFile = Images\\Shared.bmp
+Transparent = True
+TransparentColor = 0 255 0
+").unwrap();
+    let original = encode(&keyed_center(MAGENTA), &BMP_24).unwrap();
+    theme.set_image("Images\\Shared.bmp", original.clone());
+
+    let report = convert_color_keys(&mut theme, &TransparencyOptions::default());
+    assert!(report.converted.is_empty());
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].0, "Images\\Shared.bmp");
+    assert_eq!(theme.image("Images\\Shared.bmp"), Some(original.as_slice()));
+    assert_eq!(property(&theme, "PushButton", None, "Transparent"), Some(&PropertyValue::Bool(true)));
+    assert_eq!(property(&theme, "CheckBox", None, "TransparentColor"), Some(&PropertyValue::Color(Color::new(0, 255, 0))));
+}
+
+#[test]
+fn inherited_keys_are_restored_for_images_that_were_not_converted() {
+    // Hot inherits the part's key for an image that the check box keys differently, so only the
+    // part's own image is converted. Turning the key off at the part must not take it from Hot.
+    let mut theme = restyle_ini::parse("\
+[Button.PushButton]
+ImageFile = Images\\Normal.bmp
+Transparent = True
+TransparentColor = 0 255 0
+
+[Button.PushButton(Hot)]
+ImageFile = Images\\Hot.bmp
+
+[Button.CheckBox]
+ImageFile = Images\\Hot.bmp
+Transparent = True
+").unwrap();
+    theme.set_image("Images\\Normal.bmp", encode(&keyed_center(GREEN), &BMP_24).unwrap());
+    theme.set_image("Images\\Hot.bmp", encode(&keyed_center(GREEN), &BMP_24).unwrap());
+
+    let report = convert_color_keys(&mut theme, &TransparencyOptions { antialias: true });
+    assert_eq!(report.converted, ["Images\\Normal.bmp"]);
+    assert_eq!(report.skipped.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>(), ["Images\\Hot.bmp"]);
+
+    assert_eq!(property(&theme, "PushButton", None, "Transparent"), None);
+    assert_eq!(property(&theme, "PushButton", None, "TransparentColor"), None);
+    assert_eq!(property(&theme, "PushButton", Some("Hot"), "Transparent"), Some(&PropertyValue::Bool(true)));
+    assert_eq!(property(&theme, "PushButton", Some("Hot"), "TransparentColor"), Some(&PropertyValue::Color(Color::new(0, 255, 0))));
+    assert_eq!(property(&theme, "CheckBox", None, "Transparent"), Some(&PropertyValue::Bool(true)));
+
+    let (normal, _) = decoded(&theme, "Images\\Normal.bmp");
+    assert_eq!(normal.pixel(1, 1), [0, 0, 0, 0]);
+    assert!(normal.pixel(0, 0)[3] < 255);
+    let (hot, _) = decoded(&theme, "Images\\Hot.bmp");
+    assert_eq!(hot, keyed_center(GREEN));
+}
+
+#[test]
+fn undecodable_images_are_skipped_and_the_rest_converted() {
+    let mut theme = restyle_ini::parse("\
+[Button.CheckBox]
+ImageFile = Images\\Broken.bmp
+Transparent = True
+
+[Button.PushButton]
+ImageFile = Images\\Button.bmp
+Transparent = True
+").unwrap();
+    theme.set_image("Images\\Broken.bmp", b"BM not a bitmap".to_vec());
+    theme.set_image("Images\\Button.bmp", encode(&keyed_center(MAGENTA), &BMP_24).unwrap());
+
+    let report = convert_color_keys(&mut theme, &TransparencyOptions::default());
+    assert_eq!(report.converted, ["Images\\Button.bmp"]);
+    assert_eq!(report.skipped.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>(), ["Images\\Broken.bmp"]);
+    assert_eq!(theme.image("Images\\Broken.bmp"), Some(&b"BM not a bitmap"[..]));
+    assert_eq!(property(&theme, "CheckBox", None, "Transparent"), Some(&PropertyValue::Bool(true)));
+    assert_eq!(property(&theme, "PushButton", None, "Transparent"), None);
+}